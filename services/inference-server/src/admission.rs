@@ -0,0 +1,529 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+use crate::config::{AdaptiveTimeoutConfig, LoadSheddingConfig, Settings, WarmupConfig};
+use crate::error::ApiError;
+
+/// Concurrency limits for completions, with optional load shedding once the
+/// queue of requests waiting for a slot gets too deep.
+///
+/// Slots are taken in a fixed order (global, then provider, then model) so two
+/// requests never wait on each other's permits; they are released in reverse.
+/// With `limits.concurrency.warmup`, the global and provider caps start small
+/// after startup and grow to their configured size.
+#[derive(Debug)]
+pub struct AdmissionControl {
+    /// `None` when `max_concurrent_requests` is unset: everything is admitted at once
+    global: Option<Limiter>,
+    per_provider: HashMap<String, Limiter>,
+    /// `limits.concurrency.per_model` patterns, most specific first
+    model_limits: Vec<(String, usize)>,
+    /// One limiter per model that matched a pattern, created on first use
+    per_model: Mutex<BTreeMap<String, Arc<Limiter>>>,
+    shedding: Option<LoadSheddingConfig>,
+    adaptive_timeout: Option<AdaptiveTimeoutConfig>,
+    /// `max_concurrent_streams_per_client`, with the streams each client has open
+    client_streams: Option<ClientStreams>,
+}
+
+/// Open streams per client, capped at `max`
+#[derive(Debug)]
+struct ClientStreams {
+    max: usize,
+    open: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+/// One open stream counted against a client; the count drops with it,
+/// whether the stream finished or the client went away
+#[derive(Debug)]
+pub struct StreamSlot {
+    client: String,
+    open: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = open.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                open.remove(&self.client);
+            }
+        }
+    }
+}
+
+/// A pool of completion slots and the requests queued for one
+#[derive(Debug)]
+struct Limiter {
+    max: usize,
+    slots: Arc<Semaphore>,
+    queued: AtomicUsize,
+    /// Startup warmup; the semaphore starts smaller and grows to `max`
+    ramp: Option<Ramp>,
+}
+
+/// Linear growth of a limiter from `initial` slots to its full size over `window`
+#[derive(Debug)]
+struct Ramp {
+    started: Instant,
+    initial: usize,
+    window: Duration,
+    /// Slots handed to the semaphore so far
+    granted: AtomicUsize,
+}
+
+impl Ramp {
+    fn new(config: &WarmupConfig, max: usize) -> Self {
+        let initial = config.initial.max(1).min(max);
+        Self {
+            started: Instant::now(),
+            initial,
+            window: Duration::from_secs(config.window_secs),
+            granted: AtomicUsize::new(initial),
+        }
+    }
+
+    /// Slots that should be available by now
+    fn target(&self, max: usize) -> usize {
+        let elapsed = self.started.elapsed();
+        if elapsed >= self.window {
+            return max;
+        }
+        let progress = elapsed.as_secs_f64() / self.window.as_secs_f64();
+        let grown = ((max - self.initial) as f64 * progress) as usize;
+        (self.initial + grown).min(max)
+    }
+
+    /// How long until the next slot is due, while the ramp is still running
+    fn next_step(&self, max: usize) -> Option<Duration> {
+        let remaining = self
+            .window
+            .checked_sub(self.started.elapsed())
+            .filter(|remaining| !remaining.is_zero())?;
+        let step = self.window / (max - self.initial).max(1) as u32;
+        Some(step.min(remaining).max(Duration::from_millis(1)))
+    }
+}
+
+/// Decrements the queue depth when a waiting request gets its slot or goes away
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Provider and model slots held for one completion, plus the client's stream
+/// slot for a stream. Fields drop in order, so the model slot is released
+/// before the provider slot.
+#[derive(Debug, Default)]
+pub struct ScopedPermits {
+    model: Option<OwnedSemaphorePermit>,
+    provider: Option<OwnedSemaphorePermit>,
+    stream: Option<StreamSlot>,
+}
+
+impl ScopedPermits {
+    /// Keep the client's stream slot along with the other permits
+    pub fn with_stream(mut self, stream: Option<StreamSlot>) -> Self {
+        self.stream = stream;
+        self
+    }
+}
+
+impl Limiter {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            slots: Arc::new(Semaphore::new(max)),
+            queued: AtomicUsize::new(0),
+            ramp: None,
+        }
+    }
+
+    /// A limiter that starts with `warmup.initial` slots, if warmup is configured
+    fn warming(max: usize, warmup: Option<&WarmupConfig>) -> Self {
+        let Some(warmup) = warmup else {
+            return Self::new(max);
+        };
+        let ramp = Ramp::new(warmup, max);
+        Self {
+            max,
+            slots: Arc::new(Semaphore::new(ramp.initial)),
+            queued: AtomicUsize::new(0),
+            ramp: Some(ramp),
+        }
+    }
+
+    /// Slots currently usable: the full size once any warmup is over
+    fn limit(&self) -> usize {
+        self.ramp
+            .as_ref()
+            .map_or(self.max, |ramp| ramp.granted.load(Ordering::SeqCst))
+    }
+
+    /// Hand the semaphore the slots the warmup has reached by now
+    fn catch_up(&self) {
+        let Some(ref ramp) = self.ramp else {
+            return;
+        };
+        let target = ramp.target(self.max);
+        let granted = ramp.granted.load(Ordering::SeqCst);
+        if target > granted
+            && ramp
+                .granted
+                .compare_exchange(granted, target, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        {
+            self.slots.add_permits(target - granted);
+        }
+    }
+
+    fn in_flight(&self) -> usize {
+        self.limit() - self.slots.available_permits()
+    }
+
+    fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    fn is_saturated(&self, shedding: Option<&LoadSheddingConfig>) -> bool {
+        shedding.is_some_and(|shedding| self.queue_depth() >= shedding.queue_depth_threshold)
+    }
+
+    /// Take a slot, waiting in the queue unless it is already too deep
+    async fn acquire(
+        &self,
+        shedding: Option<&LoadSheddingConfig>,
+    ) -> Result<OwnedSemaphorePermit, ApiError> {
+        self.catch_up();
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let depth = self.queued.fetch_add(1, Ordering::SeqCst);
+        let _slot = QueueSlot(&self.queued);
+        if let Some(shedding) = shedding
+            && depth >= shedding.queue_depth_threshold
+        {
+            return Err(ApiError::Overloaded {
+                retry_after_secs: retry_after_secs(depth, shedding),
+            });
+        }
+
+        // While warming up, wake at each ramp step in case a slot was added
+        loop {
+            self.catch_up();
+            let acquire = self.slots.clone().acquire_owned();
+            let permit = match self.ramp.as_ref().and_then(|ramp| ramp.next_step(self.max)) {
+                Some(step) => match tokio::time::timeout(step, acquire).await {
+                    Ok(permit) => permit,
+                    Err(_) => continue,
+                },
+                None => acquire.await,
+            };
+            return Ok(permit.expect("admission semaphore is never closed"));
+        }
+    }
+}
+
+impl AdmissionControl {
+    pub fn new(settings: &Settings) -> Self {
+        let concurrency = &settings.limits.concurrency;
+        let warmup = concurrency.warmup.as_ref();
+        let mut model_limits: Vec<_> = concurrency
+            .per_model
+            .iter()
+            .map(|(pattern, max)| (pattern.clone(), *max))
+            .collect();
+        model_limits.sort_by_key(|(pattern, _)| {
+            (pattern.contains('*'), Reverse(pattern.len()), pattern.clone())
+        });
+
+        Self {
+            global: settings
+                .server
+                .max_concurrent_requests
+                .map(|max| Limiter::warming(max, warmup)),
+            per_provider: concurrency
+                .per_provider
+                .iter()
+                .map(|(name, max)| (name.clone(), Limiter::warming(*max, warmup)))
+                .collect(),
+            model_limits,
+            per_model: Mutex::new(BTreeMap::new()),
+            shedding: settings.server.load_shedding.clone(),
+            adaptive_timeout: concurrency.adaptive_timeout.clone(),
+            client_streams: settings
+                .server
+                .max_concurrent_streams_per_client
+                .map(|max| ClientStreams {
+                    max,
+                    open: Arc::new(Mutex::new(HashMap::new())),
+                }),
+        }
+    }
+
+    /// Whether the next request to queue for a global slot would be shed
+    pub fn is_saturated(&self) -> bool {
+        self.global
+            .as_ref()
+            .is_some_and(|global| global.is_saturated(self.shedding.as_ref()))
+    }
+
+    /// Wait for a global completion slot. The permit must be held until the
+    /// response is fully sent; `None` means no limit is configured.
+    pub async fn admit(&self) -> Result<Option<OwnedSemaphorePermit>, ApiError> {
+        match self.global {
+            Some(ref global) => global.acquire(self.shedding.as_ref()).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Count a new stream against `client`, or reject it with `TooManyStreams` when
+    /// the client already has its limit open. `None` means no limit is configured.
+    pub fn admit_stream(&self, client: &str) -> Result<Option<StreamSlot>, ApiError> {
+        let Some(ref streams) = self.client_streams else {
+            return Ok(None);
+        };
+        let mut open = streams.open.lock().unwrap_or_else(|e| e.into_inner());
+        let count = open.entry(client.to_string()).or_insert(0);
+        if *count >= streams.max {
+            return Err(ApiError::TooManyStreams { max: streams.max });
+        }
+        *count += 1;
+        Ok(Some(StreamSlot {
+            client: client.to_string(),
+            open: streams.open.clone(),
+        }))
+    }
+
+    /// Wait for the provider and model slots of a request that already holds
+    /// its global slot. Both must be held until the response is fully sent.
+    pub async fn admit_scoped(
+        &self,
+        provider: &str,
+        model: &str,
+    ) -> Result<ScopedPermits, ApiError> {
+        let mut permits = ScopedPermits::default();
+        if let Some(limiter) = self.per_provider.get(provider) {
+            permits.provider = Some(limiter.acquire(self.shedding.as_ref()).await?);
+        }
+        if let Some(limiter) = self.model_limiter(model) {
+            permits.model = Some(limiter.acquire(self.shedding.as_ref()).await?);
+        }
+        Ok(permits)
+    }
+
+    /// How far a completion on `provider` that holds its slots has its timeouts
+    /// stretched per `adaptive_timeout` by the other completions in flight there
+    pub fn timeout_factor(&self, provider: &str) -> f64 {
+        match (&self.adaptive_timeout, self.per_provider.get(provider)) {
+            (Some(adaptive), Some(limiter)) => adaptive.factor(limiter.in_flight()),
+            _ => 1.0,
+        }
+    }
+
+    /// Completions in flight for each model with a limit that has been used
+    pub fn model_in_flight(&self) -> BTreeMap<String, usize> {
+        let per_model = self.per_model.lock().unwrap_or_else(|e| e.into_inner());
+        per_model
+            .iter()
+            .map(|(model, limiter)| (model.clone(), limiter.in_flight()))
+            .collect()
+    }
+
+    fn model_limiter(&self, model: &str) -> Option<Arc<Limiter>> {
+        let mut per_model = self.per_model.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(limiter) = per_model.get(model) {
+            return Some(limiter.clone());
+        }
+        let (_, max) = self
+            .model_limits
+            .iter()
+            .find(|(pattern, _)| matches_pattern(pattern, model))?;
+        let limiter = Arc::new(Limiter::new(*max));
+        per_model.insert(model.to_string(), limiter.clone());
+        Some(limiter)
+    }
+}
+
+/// Match `model` against a pattern where `*` stands for any run of characters
+pub fn matches_pattern(pattern: &str, model: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = model.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<_> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Expected wait for `depth` queued requests, rounded up to whole seconds
+fn retry_after_secs(depth: usize, shedding: &LoadSheddingConfig) -> u64 {
+    let wait_ms = depth as u64 * shedding.estimated_wait_per_request_ms;
+    wait_ms.div_ceil(1000).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shedding(threshold: usize, wait_ms: u64) -> LoadSheddingConfig {
+        LoadSheddingConfig {
+            queue_depth_threshold: threshold,
+            estimated_wait_per_request_ms: wait_ms,
+        }
+    }
+
+    /// Default settings; each test sets the limits it exercises
+    fn base_settings() -> Settings {
+        let provider = crate::config::InferenceProvider::Mock {
+            responses_dir: Default::default(),
+            record: false,
+            record_upstream: None,
+        };
+        Settings::for_tests(provider, "x", "default-model")
+    }
+
+    fn admission(global: Option<usize>, per_model: &[(&str, usize)]) -> AdmissionControl {
+        let mut settings = base_settings();
+        settings.server.max_concurrent_requests = global;
+        settings.server.load_shedding = Some(shedding(1, 1000));
+        settings.limits.concurrency.per_model = per_model
+            .iter()
+            .map(|(pattern, max)| (pattern.to_string(), *max))
+            .collect();
+        AdmissionControl::new(&settings)
+    }
+
+    #[test]
+    fn test_retry_after_scales_with_depth() {
+        assert_eq!(retry_after_secs(0, &shedding(0, 500)), 1);
+        assert_eq!(retry_after_secs(3, &shedding(3, 500)), 2);
+        assert_eq!(retry_after_secs(4, &shedding(4, 2000)), 8);
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("llama-70b", "llama-70b"));
+        assert!(!matches_pattern("llama-70b", "llama-70b-instruct"));
+        assert!(matches_pattern("*-70b*", "llama-70b-instruct"));
+        assert!(matches_pattern("qwen*coder", "qwen2.5-coder"));
+        assert!(!matches_pattern("qwen*coder", "qwen2.5-coder-7b"));
+        assert!(matches_pattern("*", "anything"));
+    }
+
+    #[tokio::test]
+    async fn test_most_specific_model_limit_applies() {
+        let admission = admission(None, &[("*", 5), ("llama-*", 2), ("llama-70b", 1)]);
+
+        let _held = admission.admit_scoped("mock", "llama-70b").await.unwrap();
+        let _other = admission.admit_scoped("mock", "llama-8b").await.unwrap();
+
+        assert_eq!(admission.model_limiter("llama-70b").unwrap().max, 1);
+        assert_eq!(admission.model_limiter("llama-8b").unwrap().max, 2);
+        assert_eq!(admission.model_limiter("gpt-4o").unwrap().max, 5);
+        assert_eq!(
+            admission.model_in_flight(),
+            BTreeMap::from([
+                ("gpt-4o".to_string(), 0),
+                ("llama-70b".to_string(), 1),
+                ("llama-8b".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_stream_slots_are_counted_per_client() {
+        let mut settings = base_settings();
+        settings.server.max_concurrent_streams_per_client = Some(2);
+        let admission = AdmissionControl::new(&settings);
+
+        let first = admission.admit_stream("team-a").unwrap();
+        let _second = admission.admit_stream("team-a").unwrap();
+        assert!(matches!(
+            admission.admit_stream("team-a"),
+            Err(ApiError::TooManyStreams { max: 2 })
+        ));
+        // Other clients have their own count
+        assert!(admission.admit_stream("team-b").unwrap().is_some());
+
+        drop(first);
+        assert!(admission.admit_stream("team-a").unwrap().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warmup_raises_the_limit_over_the_window() {
+        let warmup = WarmupConfig {
+            initial: 2,
+            window_secs: 8,
+        };
+        let limiter = Limiter::warming(10, Some(&warmup));
+        assert_eq!(limiter.limit(), 2);
+
+        tokio::time::advance(Duration::from_secs(4)).await;
+        limiter.catch_up();
+        assert_eq!(limiter.limit(), 6);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+        limiter.catch_up();
+        assert_eq!(limiter.limit(), 10);
+        assert_eq!(limiter.slots.available_permits(), 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_warmup_admits_waiters_as_slots_are_added() {
+        let warmup = WarmupConfig {
+            initial: 1,
+            window_secs: 10,
+        };
+        let limiter = Limiter::warming(2, Some(&warmup));
+        let _held = limiter.acquire(None).await.unwrap();
+
+        let started = Instant::now();
+        let _second = limiter.acquire(None).await.unwrap();
+
+        // Nothing was released; the ramp itself made room
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        assert_eq!(limiter.in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiters_leave_the_queue() {
+        let admission = admission(Some(1), &[]);
+        let held = admission.admit().await.unwrap();
+        assert!(held.is_some());
+
+        let waiting = tokio::time::timeout(
+            std::time::Duration::from_millis(20),
+            admission.admit(),
+        );
+        let (timed_out, shed) = tokio::join!(waiting, async {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            assert!(admission.is_saturated());
+            admission.admit().await
+        });
+
+        assert!(timed_out.is_err());
+        assert!(matches!(shed, Err(ApiError::Overloaded { retry_after_secs: 1 })));
+        assert_eq!(admission.global.as_ref().unwrap().queue_depth(), 0);
+        assert!(!admission.is_saturated());
+    }
+}
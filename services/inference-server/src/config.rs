@@ -1,13 +1,25 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::models::FormatType;
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Settings {
     pub server: ServerConfig,
     pub inference: InferenceConfig,
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub startup: StartupConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -16,20 +28,186 @@ pub struct ServerConfig {
     pub host: String,
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Directory of `<locale>.yaml` error message translations picked via `Accept-Language`
+    #[serde(default)]
+    pub locales_dir: Option<PathBuf>,
+    /// Name the configured provider in the `GET /` response
+    #[serde(default = "default_root_includes_provider")]
+    pub root_includes_provider: bool,
+    /// Completions run at once; further requests wait in a queue. Unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    /// Reject requests outright once the completion queue is too deep
+    #[serde(default)]
+    pub load_shedding: Option<LoadSheddingConfig>,
+    /// Largest non-streaming completion body sent to clients, in bytes. Unlimited when unset.
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// What to do with a completion over `max_response_bytes`
+    #[serde(default)]
+    pub oversized_response: OversizedResponsePolicy,
+    /// Bearer keys clients must send; requests are not authenticated when empty
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// Streams one client may hold open at once, counted per API key when `api_keys` is
+    /// set and per IP address otherwise. Unlimited when unset.
+    #[serde(default)]
+    pub max_concurrent_streams_per_client: Option<usize>,
+    /// How long requests still in flight at shutdown, streams included, may take to finish
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+    /// Largest request body the API routes accept, in bytes; larger ones get a 413
+    #[serde(default = "default_max_request_bytes")]
+    pub max_request_bytes: usize,
+}
+
+/// Handling of completions larger than `server.max_response_bytes`
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizedResponsePolicy {
+    /// Drop the logprobs and add a `logprobs_dropped` warning
+    #[default]
+    Degrade,
+    /// Fail with a 413 `response_too_large` error
+    Reject,
+}
+
+/// Queue-depth-based load shedding, on top of `max_concurrent_requests`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LoadSheddingConfig {
+    /// Requests arriving while this many are already queued get a 503
+    pub queue_depth_threshold: usize,
+    /// Expected time each queued request adds, used for the `Retry-After` hint
+    #[serde(default = "default_estimated_wait_per_request_ms")]
+    pub estimated_wait_per_request_ms: u64,
+}
+
+/// Limits applied on top of the `server` ones
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct LimitsConfig {
+    #[serde(default)]
+    pub concurrency: ConcurrencyLimits,
+}
+
+/// In-flight completion caps below `max_concurrent_requests`, so one busy model
+/// can't take every slot. Requests waiting on these queue and shed like the global cap.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct ConcurrencyLimits {
+    /// Cap per model, keyed by name or `*` pattern; an exact name wins, then the
+    /// longest matching pattern. Each matching model gets its own cap.
+    #[serde(default)]
+    pub per_model: HashMap<String, usize>,
+    /// Cap per provider, keyed by provider name (`openai`, `lmstudio`, `mock`)
+    #[serde(default)]
+    pub per_provider: HashMap<String, usize>,
+    /// Start the global and provider caps low after startup and grow them
+    #[serde(default)]
+    pub warmup: Option<WarmupConfig>,
+    /// Give completions more time while their provider is busy
+    #[serde(default)]
+    pub adaptive_timeout: Option<AdaptiveTimeoutConfig>,
+}
+
+/// Completion deadline that stretches with the provider's load. Only providers
+/// with a `per_provider` cap are scaled, since that cap is what counts their load.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdaptiveTimeoutConfig {
+    /// Share of `inference.timeout_secs` added for each other completion in
+    /// flight on the same provider
+    pub per_in_flight: f64,
+    /// Largest multiple of `inference.timeout_secs` a completion may get
+    pub max_factor: f64,
+}
+
+impl AdaptiveTimeoutConfig {
+    /// Multiple of the base timeout for a completion sharing its provider with
+    /// `in_flight - 1` others
+    pub fn factor(&self, in_flight: usize) -> f64 {
+        let others = in_flight.saturating_sub(1) as f64;
+        (1.0 + self.per_in_flight * others).min(self.max_factor).max(1.0)
+    }
+}
+
+/// Startup concurrency ramp, so a cold upstream isn't hit at full concurrency
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WarmupConfig {
+    /// Slots available right after startup
+    pub initial: usize,
+    /// Seconds over which the caps grow linearly to their configured size
+    pub window_secs: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct InferenceConfig {
-    // Common fields all providers need
+    // Common fields all providers need; `router` backends set their own instead
+    #[serde(default)]
     pub base_url: String,
+    // Several `lmstudio` instances to spread requests over round-robin, used instead of base_url
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub base_urls: Vec<String>,
     #[serde(default = "default_model")]
     pub default_model: String,
+    // Default model per provider name, used instead of `default_model` for that provider
+    #[serde(default)]
+    pub provider_default_models: HashMap<String, String>,
     #[serde(default)]
     pub allowed_models: Option<HashSet<String>>, // Optional list of allowed models
     #[serde(default = "default_timeout_secs")]
     pub timeout_secs: u64,
     #[serde(default)]
     pub http: Option<HttpConfigSchema>,
+    // Reject two consecutive user/assistant messages with the same role
+    #[serde(default)]
+    pub enforce_alternating_roles: bool,
+    // Appended verbatim to every assistant message, e.g. a compliance disclaimer
+    #[serde(default)]
+    pub response_footer: Option<String>,
+    // Remove control characters other than newline and tab from response text and stream deltas
+    #[serde(default)]
+    pub strip_control_chars: bool,
+    // Keep finish_reason on one chunk per choice and drop empty chunks after it in streams
+    #[serde(default)]
+    pub normalize_stream_finish: bool,
+    // System prompt per resolved model, used when the client sent no system message
+    #[serde(default)]
+    pub model_system_prompts: HashMap<String, String>,
+    // End a stream with `stream_stalled` when no chunk arrives for this long; unset waits forever
+    #[serde(default)]
+    pub stream_stall_timeout_secs: Option<u64>,
+    // Pass stream chunks through a channel holding at most this many, so a slow client
+    // pauses the upstream read; unset streams straight through
+    #[serde(default)]
+    pub stream_buffer_chunks: Option<usize>,
+    // Merged into every upstream request body for fields the API doesn't model; client values win
+    #[serde(default)]
+    pub extra_request_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    // What to do with bytes in an upstream stream that are not valid UTF-8
+    #[serde(default)]
+    pub stream_invalid_utf8: InvalidUtf8,
+    // Request a seeded OpenAI or LM Studio stream again when it drops, skipping what was sent
+    #[serde(default)]
+    pub stream_reconnect: bool,
+    // Narrow the `response_format` types clients may request; all known types when unset
+    #[serde(default)]
+    pub allowed_response_formats: Option<Vec<FormatType>>,
+    // `router` backend that also gets a sample of completions, compared in the logs
+    #[serde(default)]
+    pub shadow_provider: Option<String>,
+    // Share of completions mirrored to `shadow_provider`, from 0.0 to 1.0
+    #[serde(default = "default_shadow_sample_rate")]
+    pub shadow_sample_rate: f64,
+    // Largest `max_tokens` a request may ask for; 128000 when unset
+    #[serde(default)]
+    pub max_tokens_limit: Option<u32>,
+    // Honor `extensions.echo_resolved_request` in extended mode; off by default
+    #[serde(default)]
+    pub allow_resolved_request_echo: bool,
+    // Add the normalized request to every extended-mode response as `effective_request`
+    #[serde(default)]
+    pub extended_includes_request: bool,
+    // Include `warnings` in standard-mode responses too; extended mode always has them
+    #[serde(default)]
+    pub expose_warnings: bool,
     // Provider-specific configuration
     #[serde(flatten)]
     pub provider: InferenceProvider,
@@ -41,8 +219,15 @@ pub enum InferenceProvider {
     #[serde(rename = "lmstudio")]
     LMStudio, // No extra fields needed
 
+    #[serde(rename = "ollama")]
+    Ollama, // No extra fields needed
+
     #[serde(rename = "triton")]
-    Triton { model_version: String },
+    Triton {
+        // Empty lets Triton choose the version by the model's version policy
+        #[serde(default)]
+        model_version: String,
+    },
 
     #[serde(rename = "openai")]
     OpenAI {
@@ -51,11 +236,95 @@ pub enum InferenceProvider {
         organization_id: Option<String>,
     },
 
+    #[serde(rename = "anthropic")]
+    Anthropic {
+        api_key: String,
+        // `anthropic-version` header; the provider's default when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+    },
+
+    /// Any server speaking the OpenAI chat API (vLLM, llama.cpp, LocalAI, ...)
+    #[serde(rename = "openai-compatible")]
+    OpenAICompatible {
+        // Relative to `base_url`
+        #[serde(default = "default_chat_path")]
+        chat_path: String,
+        #[serde(default = "default_models_path")]
+        models_path: String,
+        // Sent as a bearer token when set
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key: Option<String>,
+        // Extension parameters passed through to the server
+        #[serde(default)]
+        extensions_allowlist: Vec<String>,
+    },
+
+    /// vLLM's OpenAI server, with its sampling and guided decoding parameters
+    #[serde(rename = "vllm")]
+    Vllm {
+        // vLLM's `--api-key`, sent as a bearer token when set
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        api_key: Option<String>,
+    },
+
     #[serde(rename = "mock")]
     Mock {
         #[serde(default = "default_mock_responses_dir")]
         responses_dir: PathBuf,
+        // Answer missing scenarios from `record_upstream` and save them as new files
+        #[serde(default)]
+        record: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        record_upstream: Option<RecordUpstream>,
     },
+
+    /// Several named providers, picked per request by model name
+    #[serde(rename = "router")]
+    Router {
+        backends: HashMap<String, RouterBackend>,
+        // Checked in order; the first matching route wins
+        #[serde(default)]
+        routes: Vec<ModelRoute>,
+    },
+}
+
+/// OpenAI-compatible server a recording `mock` provider forwards to
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RecordUpstream {
+    pub base_url: String,
+    /// Sent as a bearer token when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Model requested upstream; the scenario name when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// One provider behind `router`, configured like a top-level provider
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RouterBackend {
+    pub base_url: String,
+    /// Replaces `inference.http` for this backend
+    #[serde(default)]
+    pub http: Option<HttpConfigSchema>,
+    /// Model requested from this backend when a route fails over to it; the
+    /// client's model is passed on unchanged when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    #[serde(flatten)]
+    pub provider: InferenceProvider,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ModelRoute {
+    /// Model name or pattern where `*` matches any run of characters, e.g. `gpt-4*`
+    pub model: String,
+    /// Name of the backend in `backends`
+    pub backend: String,
+    /// Backends tried in order when the previous one can't be reached or times out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -67,6 +336,16 @@ pub struct LoggingConfig {
     #[serde(default = "default_log_output")]
     pub output: LogOutput,
     pub file: Option<FileLoggingConfig>,
+    /// Allow trusted callers to raise the log level for a single request via `X-Log-Level`
+    #[serde(default)]
+    pub allow_level_override: bool,
+    /// Truncate logged request/response bodies to this many characters
+    #[serde(default)]
+    pub max_logged_body_chars: Option<usize>,
+    /// Salt for the message content hashes in the access log and `/admin/usage`;
+    /// content hashing is off when unset
+    #[serde(default)]
+    pub content_hash_salt: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -85,6 +364,131 @@ pub enum LogOutput {
     Both,
 }
 
+/// Operator endpoints under `/admin`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AdminConfig {
+    /// Bearer token required by `/admin/*`; the admin routes are not registered when unset
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// How many request summaries `GET /admin/requests/recent` keeps
+    #[serde(default = "default_recent_requests")]
+    pub recent_requests: usize,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            recent_requests: default_recent_requests(),
+        }
+    }
+}
+
+/// Checks run once before the listener binds
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StartupConfig {
+    /// Run the provider health check before serving traffic
+    #[serde(default)]
+    pub probe_upstream: bool,
+    /// Abort startup when the probe fails instead of logging a warning
+    #[serde(default)]
+    pub probe_required: bool,
+    #[serde(default = "default_probe_timeout_secs")]
+    pub probe_timeout_secs: u64,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self {
+            probe_upstream: false,
+            probe_required: false,
+            probe_timeout_secs: default_probe_timeout_secs(),
+        }
+    }
+}
+
+/// Metrics served at `GET /metrics`
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MetricsConfig {
+    /// How often the background task checks provider health for `provider_healthy`; 0 disables it
+    #[serde(default = "default_health_poll_interval_secs")]
+    pub health_poll_interval_secs: u64,
+    /// Record connect, first-byte and total time of upstream calls in `upstream_phase_seconds`
+    #[serde(default)]
+    pub upstream_phases: bool,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            health_poll_interval_secs: default_health_poll_interval_secs(),
+            upstream_phases: false,
+        }
+    }
+}
+
+/// Wire-format details of the OpenAI-compatible API. The defaults produce
+/// exactly what OpenAI clients expect.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RuntimeConfig {
+    /// End every SSE stream with `data: [DONE]`
+    #[serde(default = "default_stream_done_marker")]
+    pub stream_done_marker: bool,
+    /// Seconds between SSE keep-alive comments on idle streams; 0 disables them
+    #[serde(default = "default_stream_keep_alive_secs")]
+    pub stream_keep_alive_secs: u64,
+    /// Seconds between empty-delta chunks sent while a stream waits for its
+    /// first token, for proxies that need data rather than comments; 0 disables them
+    #[serde(default)]
+    pub first_token_keep_alive_secs: u64,
+    /// How an error that happens mid-stream is sent
+    #[serde(default)]
+    pub stream_errors: StreamErrorFormat,
+    /// `owned_by` of `/v1/models` entries, keyed by provider name; "local" when unlisted
+    #[serde(default)]
+    pub owned_by: HashMap<String, String>,
+    /// Report the provider as accepting `tools`
+    #[serde(default)]
+    pub supports_tools: bool,
+    /// Answer `stream: true` with buffered JSON when the client's `Accept`
+    /// header allows `application/json` but not `text/event-stream`
+    #[serde(default = "default_negotiate_accept")]
+    pub negotiate_accept: bool,
+    /// Warn about deprecated request features and send `Deprecation`/`Sunset` headers
+    #[serde(default = "default_deprecation_notices")]
+    pub deprecation_notices: bool,
+    /// Warning codes of deprecations to stop reporting, e.g. `deprecated_functions`
+    #[serde(default)]
+    pub silenced_deprecations: Vec<String>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            stream_done_marker: default_stream_done_marker(),
+            stream_keep_alive_secs: default_stream_keep_alive_secs(),
+            first_token_keep_alive_secs: 0,
+            stream_errors: StreamErrorFormat::default(),
+            owned_by: HashMap::new(),
+            supports_tools: false,
+            negotiate_accept: default_negotiate_accept(),
+            deprecation_notices: default_deprecation_notices(),
+            silenced_deprecations: Vec::new(),
+        }
+    }
+}
+
+/// SSE framing of errors that end a stream
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamErrorFormat {
+    /// A plain `data:` event holding an OpenAI error object
+    #[default]
+    Data,
+    /// The same payload as a named `event: error`
+    Event,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct FileLoggingConfig {
     #[serde(default = "default_log_directory")]
@@ -99,6 +503,19 @@ pub struct FileLoggingConfig {
     pub rotation_policy: RotationPolicy,
 }
 
+/// Handling of invalid UTF-8 in upstream streams. Characters split across
+/// network reads are always reassembled; this only covers bytes that can
+/// never form a character.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidUtf8 {
+    /// Substitute U+FFFD and keep streaming
+    #[default]
+    Replace,
+    /// End the stream with a `stream_error`
+    Error,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum RotationPolicy {
@@ -118,10 +535,56 @@ pub struct HttpConfigSchema {
     pub max_retries: u32,
     #[serde(default = "default_retry_backoff_ms")]
     pub retry_backoff_ms: u64,
+    // Random extra wait of up to this much added to each backoff, so requests
+    // that failed together don't retry in lockstep
+    #[serde(default)]
+    pub retry_jitter_ms: u64,
+    // Also retry error responses whose body contains one of these substrings,
+    // whatever the status (for upstreams that report transient errors as 400s)
+    #[serde(default)]
+    pub retry_on_messages: Option<Vec<String>>,
+    // Longest `Retry-After` on a 429 that is waited out; a longer one, or one past
+    // the request deadline, returns the 429 at once
+    #[serde(default = "default_max_retry_after_secs")]
+    pub max_retry_after_secs: u64,
     #[serde(default = "default_keep_alive_secs")]
     pub keep_alive_secs: Option<u64>,
     #[serde(default = "default_max_idle_connections")]
     pub max_idle_connections: Option<usize>,
+    #[serde(default)]
+    pub hedging: Option<HedgingConfig>,
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+/// Hedged requests: send a duplicate non-streaming request if the first is slow
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HedgingConfig {
+    pub delay_ms: u64,
+    #[serde(default = "default_max_hedges")]
+    pub max_hedges: u32,
+    // Only hedge requests expected to be reproducible (seed set or temperature 0)
+    #[serde(default = "default_hedge_deterministic_only")]
+    pub deterministic_only: bool,
+}
+
+/// Stop calling an upstream that keeps failing, for a while
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct CircuitBreakerConfig {
+    // Consecutive failed requests that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    // How long an open circuit fails fast before letting a trial request through
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_cooldown_secs() -> u64 {
+    30
 }
 
 fn default_connect_timeout_secs() -> u64 {
@@ -136,6 +599,10 @@ fn default_retry_backoff_ms() -> u64 {
     250
 }
 
+fn default_max_retry_after_secs() -> u64 {
+    10
+}
+
 fn default_keep_alive_secs() -> Option<u64> {
     Some(60)
 }
@@ -144,6 +611,54 @@ fn default_max_idle_connections() -> Option<usize> {
     Some(10)
 }
 
+fn default_health_poll_interval_secs() -> u64 {
+    30
+}
+
+fn default_probe_timeout_secs() -> u64 {
+    5
+}
+
+fn default_max_hedges() -> u32 {
+    1
+}
+
+fn default_hedge_deterministic_only() -> bool {
+    true
+}
+
+fn default_root_includes_provider() -> bool {
+    true
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    30
+}
+
+fn default_max_request_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_stream_done_marker() -> bool {
+    true
+}
+
+fn default_stream_keep_alive_secs() -> u64 {
+    15 // axum's default keep-alive interval
+}
+
+fn default_negotiate_accept() -> bool {
+    true
+}
+
+fn default_deprecation_notices() -> bool {
+    true
+}
+
+fn default_estimated_wait_per_request_ms() -> u64 {
+    1000
+}
+
 fn default_model() -> String {
     "gpt-oss-20b".to_string()
 }
@@ -160,6 +675,18 @@ fn default_timeout_secs() -> u64 {
     60
 }
 
+fn default_shadow_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_chat_path() -> String {
+    "v1/chat/completions".to_string()
+}
+
+fn default_models_path() -> String {
+    "v1/models".to_string()
+}
+
 fn default_mock_responses_dir() -> PathBuf {
     PathBuf::from("./mocks")
 }
@@ -196,6 +723,10 @@ fn default_rotation_policy() -> RotationPolicy {
     RotationPolicy::Daily
 }
 
+fn default_recent_requests() -> usize {
+    100
+}
+
 impl HttpConfigSchema {
     pub fn timeout(&self) -> Duration {
         Duration::from_secs(self.timeout_secs)
@@ -208,6 +739,31 @@ impl HttpConfigSchema {
     pub fn keep_alive(&self) -> Option<Duration> {
         self.keep_alive_secs.map(Duration::from_secs)
     }
+
+    /// Wait before retry number `retry` (from 1): the exponential backoff
+    /// plus a random share of `retry_jitter_ms`
+    pub fn retry_backoff(&self, retry: u32) -> Duration {
+        use rand::Rng;
+
+        let backoff_ms = self.retry_backoff_ms * 2u64.pow(retry.saturating_sub(1));
+        let jitter_ms = match self.retry_jitter_ms {
+            0 => 0,
+            max => rand::rng().random_range(0..=max),
+        };
+        Duration::from_millis(backoff_ms + jitter_ms)
+    }
+}
+
+impl HedgingConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+impl CircuitBreakerConfig {
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
 }
 
 impl InferenceConfig {
@@ -216,18 +772,82 @@ impl InferenceConfig {
     pub fn provider_name(&self) -> &str {
         match &self.provider {
             InferenceProvider::LMStudio => "lmstudio",
+            InferenceProvider::Ollama => "ollama",
             InferenceProvider::Triton { .. } => "triton",
             InferenceProvider::OpenAI { .. } => "openai",
+            InferenceProvider::Anthropic { .. } => "anthropic",
+            InferenceProvider::OpenAICompatible { .. } => "openai-compatible",
+            InferenceProvider::Vllm { .. } => "vllm",
             InferenceProvider::Mock { .. } => "mock",
+            InferenceProvider::Router { .. } => "router",
         }
     }
+    /// Model used when a request to `provider` omits `model`
+    pub fn default_model_for(&self, provider: &str) -> &str {
+        self.provider_default_models
+            .get(provider)
+            .unwrap_or(&self.default_model)
+    }
+
     #[allow(dead_code)]
     pub fn requires_auth(&self) -> bool {
-        matches!(self.provider, InferenceProvider::OpenAI { .. })
+        matches!(
+            self.provider,
+            InferenceProvider::OpenAI { .. }
+                | InferenceProvider::Anthropic { .. }
+                | InferenceProvider::OpenAICompatible { api_key: Some(_), .. }
+                | InferenceProvider::Vllm { api_key: Some(_) }
+        )
     }
 }
 
 impl Settings {
+    /// Settings for tests: the defaults of a minimal config file, with the given
+    /// provider, upstream and default model. The HTTP client gets short but real
+    /// timeouts, which `HttpConfigSchema::default()` leaves at 0.
+    #[cfg(test)]
+    pub fn for_tests(provider: InferenceProvider, base_url: &str, default_model: &str) -> Settings {
+        let mut settings: Settings = serde_yml::from_str(
+            "server: {host: localhost, port: 3000}\ninference: {provider: mock}\nlogging: {}\n",
+        )
+        .unwrap();
+        settings.inference.provider = provider;
+        settings.inference.base_url = base_url.to_string();
+        settings.inference.default_model = default_model.to_string();
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 5,
+            connect_timeout_secs: 1,
+            ..Default::default()
+        });
+        settings
+    }
+
+    /// Settings for one `router` backend: its URL, HTTP options and provider
+    /// in place of the router's, everything else shared
+    pub fn for_backend(&self, backend: &RouterBackend) -> Settings {
+        let mut settings = self.clone();
+        settings.inference.base_url = backend.base_url.clone();
+        settings.inference.provider = backend.provider.clone();
+        if backend.http.is_some() {
+            settings.inference.http = backend.http.clone();
+        }
+        settings
+    }
+
+    /// Settings for the server a recording `mock` provider forwards to, spoken
+    /// to as an OpenAI-compatible provider, everything else shared
+    pub fn for_record_upstream(&self, upstream: &RecordUpstream) -> Settings {
+        let mut settings = self.clone();
+        settings.inference.base_url = upstream.base_url.clone();
+        settings.inference.provider = InferenceProvider::OpenAICompatible {
+            chat_path: default_chat_path(),
+            models_path: default_models_path(),
+            api_key: upstream.api_key.clone(),
+            extensions_allowlist: Vec::new(),
+        };
+        settings
+    }
+
     pub fn new() -> Result<Self, config::ConfigError> {
         let config = config::Config::builder()
             // Try both .yaml and .yml extensions
@@ -258,12 +878,72 @@ impl Settings {
     /// Validate configuration settings
     pub fn validate(&self) -> Result<(), config::ConfigError> {
         // Validate base_url is a valid URL
-        if let Err(e) = url::Url::parse(&self.inference.base_url) {
+        if let InferenceProvider::Router { ref backends, ref routes } = self.inference.provider {
+            validate_router(backends, routes, self.inference.shadow_provider.as_deref())?;
+        } else if self.inference.shadow_provider.is_some() {
+            return Err(config::ConfigError::Message(
+                "shadow_provider names a router backend and requires provider: router".to_string()
+            ));
+        } else if !self.inference.base_urls.is_empty() {
+            if !matches!(self.inference.provider, InferenceProvider::LMStudio) {
+                return Err(config::ConfigError::Message(
+                    "base_urls is only supported by the lmstudio provider".to_string()
+                ));
+            }
+            for base_url in &self.inference.base_urls {
+                if let Err(e) = url::Url::parse(base_url) {
+                    return Err(config::ConfigError::Message(
+                        format!("Invalid base_urls entry '{}': {}", base_url, e)
+                    ));
+                }
+            }
+        } else if let Err(e) = url::Url::parse(&self.inference.base_url) {
             return Err(config::ConfigError::Message(
                 format!("Invalid base_url '{}': {}", self.inference.base_url, e)
             ));
         }
 
+        if let InferenceProvider::Mock { record, ref record_upstream, .. } = self.inference.provider {
+            match record_upstream {
+                None if record => {
+                    return Err(config::ConfigError::Message(
+                        "mock record: true requires record_upstream".to_string()
+                    ));
+                }
+                Some(upstream) => {
+                    if let Err(e) = url::Url::parse(&upstream.base_url) {
+                        return Err(config::ConfigError::Message(
+                            format!("Invalid record_upstream.base_url '{}': {}", upstream.base_url, e)
+                        ));
+                    }
+                }
+                None => {}
+            }
+        }
+
+        if let Some(ref http) = self.inference.http
+            && http.circuit_breaker.as_ref().is_some_and(|breaker| breaker.failure_threshold == 0)
+        {
+            return Err(config::ConfigError::Message(
+                "http.circuit_breaker.failure_threshold must be at least 1".to_string()
+            ));
+        }
+
+        if let Some(ref adaptive) = self.limits.concurrency.adaptive_timeout
+            && (adaptive.per_in_flight < 0.0 || adaptive.max_factor < 1.0)
+        {
+            return Err(config::ConfigError::Message(
+                "limits.concurrency.adaptive_timeout needs per_in_flight >= 0 and max_factor >= 1"
+                    .to_string(),
+            ));
+        }
+
+        if self.server.api_keys.iter().any(|key| key.trim().is_empty()) {
+            return Err(config::ConfigError::Message(
+                "server.api_keys must not contain empty keys".to_string()
+            ));
+        }
+
         // Validate model names don't contain path separators
         if let Some(ref allowed) = self.inference.allowed_models {
             for model in allowed {
@@ -282,6 +962,24 @@ impl Settings {
             ));
         }
 
+        if self.inference.stream_buffer_chunks == Some(0) {
+            return Err(config::ConfigError::Message(
+                "stream_buffer_chunks must be at least 1".to_string()
+            ));
+        }
+
+        if self.inference.max_tokens_limit == Some(0) {
+            return Err(config::ConfigError::Message(
+                "max_tokens_limit must be at least 1".to_string()
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.inference.shadow_sample_rate) {
+            return Err(config::ConfigError::Message(
+                "shadow_sample_rate must be between 0.0 and 1.0".to_string()
+            ));
+        }
+
         // Validate timeout is reasonable (max 1 hour)
         if self.inference.timeout_secs > 3600 {
             return Err(config::ConfigError::Message(
@@ -311,6 +1009,23 @@ impl Settings {
                     "retry_backoff_ms cannot exceed 30000".to_string()
                 ));
             }
+            if http_config.retry_jitter_ms > 30000 {
+                return Err(config::ConfigError::Message(
+                    "retry_jitter_ms cannot exceed 30000".to_string()
+                ));
+            }
+            if let Some(ref hedging) = http_config.hedging {
+                if hedging.delay_ms == 0 {
+                    return Err(config::ConfigError::Message(
+                        "hedging.delay_ms must be greater than 0".to_string()
+                    ));
+                }
+                if hedging.max_hedges == 0 || hedging.max_hedges > 3 {
+                    return Err(config::ConfigError::Message(
+                        "hedging.max_hedges must be between 1 and 3".to_string()
+                    ));
+                }
+            }
         }
 
         // Validate LogOutput::File and LogOutput::Both require file config
@@ -336,7 +1051,60 @@ impl Settings {
                 )
             ));
         }
+        if let Some(ref allowed) = self.inference.allowed_models
+            && let Some((provider, model)) = self
+                .inference
+                .provider_default_models
+                .iter()
+                .find(|(_, model)| !allowed.contains(*model))
+        {
+            return Err(config::ConfigError::Message(format!(
+                "provider_default_models.{provider} '{model}' must be in allowed_models list"
+            )));
+        }
 
         Ok(())
     }
 }
+
+fn validate_router(
+    backends: &HashMap<String, RouterBackend>,
+    routes: &[ModelRoute],
+    shadow: Option<&str>,
+) -> Result<(), config::ConfigError> {
+    if backends.is_empty() {
+        return Err(config::ConfigError::Message(
+            "router needs at least one backend".to_string(),
+        ));
+    }
+    for (name, backend) in backends {
+        if let Err(e) = url::Url::parse(&backend.base_url) {
+            return Err(config::ConfigError::Message(format!(
+                "Invalid base_url '{}' for backend '{name}': {e}",
+                backend.base_url
+            )));
+        }
+        if matches!(backend.provider, InferenceProvider::Router { .. }) {
+            return Err(config::ConfigError::Message(format!(
+                "Backend '{name}' cannot itself be a router"
+            )));
+        }
+    }
+    for route in routes {
+        if let Some(backend) = std::iter::once(&route.backend)
+            .chain(&route.fallbacks)
+            .find(|name| !backends.contains_key(*name))
+        {
+            return Err(config::ConfigError::Message(format!(
+                "Route for '{}' names unknown backend '{backend}'",
+                route.model
+            )));
+        }
+    }
+    if let Some(shadow) = shadow.filter(|name| !backends.contains_key(*name)) {
+        return Err(config::ConfigError::Message(format!(
+            "shadow_provider '{shadow}' is not a router backend"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use serde::Serialize;
+
+use crate::models::Message;
+
+/// Context string for deriving the hashing key, so the salt is never used directly
+const KEY_CONTEXT: &str = "inference-server message content hash v1";
+
+/// Salted fingerprints of a request's messages. Holds no message content,
+/// so it can go anywhere request metadata goes.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentHashes {
+    /// Hash over every message hash in order; identical conversations share it
+    pub conversation: String,
+    pub messages: Vec<MessageHash>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageHash {
+    pub role: &'static str,
+    pub hash: String,
+    /// Content length in characters, to tell apart collisions in truncated displays
+    pub chars: usize,
+}
+
+/// Keyed BLAKE3 hasher for message content. Without the salt the hashes can't be
+/// reproduced, so short prompts can't be recovered by hashing guesses.
+pub struct ContentHasher {
+    key: [u8; 32],
+}
+
+impl ContentHasher {
+    pub fn new(salt: &str) -> Self {
+        Self {
+            key: blake3::derive_key(KEY_CONTEXT, salt.as_bytes()),
+        }
+    }
+
+    pub fn hash_messages(&self, messages: &[Message]) -> ContentHashes {
+        let mut conversation = blake3::Hasher::new_keyed(&self.key);
+        let messages = messages
+            .iter()
+            .map(|message| {
+                let hash = self.hash_message(message);
+                conversation.update(hash.as_bytes());
+                MessageHash {
+                    role: message.role.as_str(),
+                    hash: hash.to_hex().to_string(),
+                    chars: message.content.as_deref().map_or(0, |c| c.chars().count()),
+                }
+            })
+            .collect();
+
+        ContentHashes {
+            conversation: conversation.finalize().to_hex().to_string(),
+            messages,
+        }
+    }
+
+    /// Hash of the role, text and tool-call payload of one message. Each field is
+    /// length-prefixed so different splits of the same bytes never collide.
+    /// Message content is plain text in this API; content parts, once accepted,
+    /// need to be folded in here in order.
+    fn hash_message(&self, message: &Message) -> blake3::Hash {
+        let tool_calls = message
+            .tool_calls
+            .as_ref()
+            .and_then(|calls| serde_json::to_string(calls).ok());
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        for field in [
+            Some(message.role.as_str()),
+            message.content.as_deref(),
+            tool_calls.as_deref(),
+            message.tool_call_id.as_deref(),
+        ] {
+            match field {
+                Some(text) => {
+                    hasher.update(&[1]);
+                    hasher.update(&(text.len() as u64).to_le_bytes());
+                    hasher.update(text.as_bytes());
+                }
+                None => {
+                    hasher.update(&[0]);
+                }
+            }
+        }
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Role;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::new(Role::System, "Be brief"),
+            Message::new(Role::User, "What is my account number?"),
+        ]
+    }
+
+    #[test]
+    fn test_identical_conversations_hash_identically() {
+        let hasher = ContentHasher::new("salt-a");
+
+        let first = hasher.hash_messages(&conversation());
+        let second = hasher.hash_messages(&conversation());
+
+        assert_eq!(first.conversation, second.conversation);
+        assert_eq!(first.messages[1].hash, second.messages[1].hash);
+        assert_eq!(first.messages[1].role, "user");
+        assert_eq!(first.messages[1].chars, 26);
+
+        let mut changed = conversation();
+        changed[1] = Message::new(Role::User, "What is my account number!");
+        assert_ne!(hasher.hash_messages(&changed).conversation, first.conversation);
+        // Moving text between messages changes the hash too
+        let merged = vec![Message::new(Role::User, "Be briefWhat is my account number?")];
+        assert_ne!(hasher.hash_messages(&merged).conversation, first.conversation);
+    }
+
+    #[test]
+    fn test_different_salts_hash_differently_and_hide_content() {
+        let a = ContentHasher::new("salt-a").hash_messages(&conversation());
+        let b = ContentHasher::new("salt-b").hash_messages(&conversation());
+
+        assert_ne!(a.conversation, b.conversation);
+        assert_ne!(a.messages[0].hash, b.messages[0].hash);
+
+        let serialized = serde_json::to_string(&a).unwrap();
+        assert!(!serialized.contains("account number"));
+        assert!(!serialized.contains("Be brief"));
+    }
+}
@@ -0,0 +1,143 @@
+//! Request features we plan to remove, and how clients are told about them.
+//!
+//! A request using a registered feature gets a warning with the feature's stable
+//! code, a `Deprecation` header (RFC 9745) and, once a removal date is set, a
+//! `Sunset` header (RFC 8594). Operators can silence entries through `runtime`.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use axum::http::{HeaderMap, HeaderValue};
+
+use crate::config::RuntimeConfig;
+use crate::models::{CompletionRequest, Role, Warning};
+
+#[derive(Debug)]
+pub struct Deprecation {
+    /// Stable warning code clients can match on
+    pub code: &'static str,
+    pub param: &'static str,
+    pub message: &'static str,
+    /// Unix time the feature was deprecated, sent as `Deprecation: @<seconds>`
+    pub since: u64,
+    /// Unix time after which the feature may stop working, sent as an HTTP-date
+    pub sunset: Option<u64>,
+    /// Must stay cheap: it runs on every request while the entry is active
+    used_by: fn(&CompletionRequest) -> bool,
+}
+
+pub static REGISTRY: &[Deprecation] = &[
+    Deprecation {
+        code: "deprecated_functions",
+        param: "functions",
+        message: "functions and function_call are deprecated; use tools and tool_choice",
+        since: 1_735_689_600, // 2025-01-01
+        sunset: Some(1_798_761_600), // 2027-01-01
+        used_by: |request| request.functions.is_some() || request.function_call.is_some(),
+    },
+    Deprecation {
+        code: "deprecated_function_messages",
+        param: "messages",
+        message: "function messages and function_call in history are deprecated; use tool messages",
+        since: 1_735_689_600, // 2025-01-01
+        sunset: Some(1_798_761_600), // 2027-01-01
+        used_by: |request| {
+            request
+                .messages
+                .iter()
+                .any(|m| m.role == Role::Function || m.function_call.is_some())
+        },
+    },
+];
+
+/// Registry entries to check, after the operator's `runtime` settings
+pub fn active(config: &RuntimeConfig) -> Vec<&'static Deprecation> {
+    if !config.deprecation_notices {
+        return Vec::new();
+    }
+    REGISTRY
+        .iter()
+        .filter(|deprecation| {
+            !config
+                .silenced_deprecations
+                .iter()
+                .any(|c| c == deprecation.code)
+        })
+        .collect()
+}
+
+/// Entries of `active` that `request` uses
+pub fn used_by(
+    active: &[&'static Deprecation],
+    request: &CompletionRequest,
+) -> Vec<&'static Deprecation> {
+    active
+        .iter()
+        .copied()
+        .filter(|deprecation| (deprecation.used_by)(request))
+        .collect()
+}
+
+impl Deprecation {
+    pub fn warning(&self) -> Warning {
+        let message = match self.sunset {
+            Some(sunset) => format!("{} (removal after {})", self.message, http_date(sunset)),
+            None => self.message.to_string(),
+        };
+        Warning::new(self.code, message, Some(self.param))
+    }
+}
+
+/// Set `Deprecation` to the earliest deprecation date among `used` and `Sunset`
+/// to the earliest removal date
+pub fn insert_headers(used: &[&Deprecation], headers: &mut HeaderMap) {
+    let Some(since) = used.iter().map(|d| d.since).min() else {
+        return;
+    };
+    if let Ok(value) = HeaderValue::from_str(&format!("@{since}")) {
+        headers.insert("deprecation", value);
+    }
+    if let Some(sunset) = used.iter().filter_map(|d| d.sunset).min()
+        && let Ok(value) = HeaderValue::from_str(&http_date(sunset))
+    {
+        headers.insert("sunset", value);
+    }
+}
+
+fn http_date(unix_secs: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(unix_secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_codes_are_unique() {
+        let mut codes: Vec<_> = REGISTRY.iter().map(|d| d.code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), REGISTRY.len());
+    }
+
+    #[test]
+    fn test_operators_can_silence_notices() {
+        let mut config = RuntimeConfig::default();
+        assert_eq!(active(&config).len(), REGISTRY.len());
+
+        config.silenced_deprecations = vec!["deprecated_functions".to_string()];
+        let codes: Vec<_> = active(&config).iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["deprecated_function_messages"]);
+
+        config.deprecation_notices = false;
+        assert!(active(&config).is_empty());
+    }
+
+    #[test]
+    fn test_headers_use_earliest_dates() {
+        let mut headers = HeaderMap::new();
+        insert_headers(&REGISTRY.iter().collect::<Vec<_>>(), &mut headers);
+
+        assert_eq!(headers["deprecation"], "@1735689600");
+        assert_eq!(headers["sunset"], "Fri, 01 Jan 2027 00:00:00 GMT");
+    }
+}
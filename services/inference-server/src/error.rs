@@ -1,6 +1,9 @@
-use axum::http::StatusCode;
+use axum::http::{Method, StatusCode, header};
 use axum::response::{IntoResponse, Response, Json};
 
+use crate::history::ErrorCategory;
+use crate::i18n::{LocalizableError, MessageKey};
+use crate::metrics::ValidationRejection;
 use crate::models::{OpenAIError, OpenAIErrorResponse};
 use crate::providers::ProviderError;
 use crate::validations::ValidationError;
@@ -9,6 +12,24 @@ use crate::validations::ValidationError;
 pub enum ApiError {
     Validation(ValidationError),
     Provider(ProviderError),
+    /// Missing or wrong bearer token for a protected route
+    Unauthorized,
+    /// No route matches the request path
+    NotFound { method: Method, path: String },
+    /// The route exists but does not accept this method
+    MethodNotAllowed {
+        method: Method,
+        path: String,
+        allowed: Vec<String>,
+    },
+    /// Load shedding: the completion queue is too deep to wait in
+    Overloaded { retry_after_secs: u64 },
+    /// The client already holds `server.max_concurrent_streams_per_client` streams
+    TooManyStreams { max: usize },
+    /// The serialized completion is over `server.max_response_bytes`
+    ResponseTooLarge { size: usize, max_bytes: usize },
+    /// The request body is over `server.max_request_bytes`
+    RequestTooLarge { max_bytes: usize },
 }
 
 impl std::fmt::Display for ApiError {
@@ -16,6 +37,37 @@ impl std::fmt::Display for ApiError {
         match self {
             ApiError::Validation(e) => write!(f, "{e}"),
             ApiError::Provider(e) => write!(f, "{e}"),
+            ApiError::Unauthorized => write!(f, "Invalid or missing API key"),
+            ApiError::NotFound { method, path } => write!(f, "Invalid URL ({method} {path})"),
+            ApiError::MethodNotAllowed {
+                method,
+                path,
+                allowed,
+            } => {
+                write!(f, "Method {method} is not allowed for {path}")?;
+                if !allowed.is_empty() {
+                    write!(f, "; allowed methods: {}", allowed.join(", "))?;
+                }
+                Ok(())
+            }
+            ApiError::Overloaded { retry_after_secs } => write!(
+                f,
+                "The server is overloaded; retry after {retry_after_secs} seconds"
+            ),
+            ApiError::TooManyStreams { max } => write!(
+                f,
+                "Too many concurrent streams; at most {max} may be open per client"
+            ),
+            ApiError::ResponseTooLarge { size, max_bytes } => write!(
+                f,
+                "The completion is {size} bytes, over the {max_bytes}-byte response limit; \
+                 request fewer logprobs or tokens"
+            ),
+            ApiError::RequestTooLarge { max_bytes } => write!(
+                f,
+                "The request body is over the {max_bytes}-byte limit; \
+                 shorten the messages or send fewer inputs"
+            ),
         }
     }
 }
@@ -25,6 +77,13 @@ impl std::error::Error for ApiError {
         match self {
             ApiError::Validation(e) => Some(e),
             ApiError::Provider(e) => Some(e),
+            ApiError::Unauthorized
+            | ApiError::NotFound { .. }
+            | ApiError::MethodNotAllowed { .. }
+            | ApiError::Overloaded { .. }
+            | ApiError::TooManyStreams { .. }
+            | ApiError::ResponseTooLarge { .. }
+            | ApiError::RequestTooLarge { .. } => None,
         }
     }
 }
@@ -41,18 +100,135 @@ impl From<ValidationError> for ApiError {
     }
 }
 
+impl ApiError {
+    /// Catalog key and arguments used to localize the message
+    pub fn message_key(&self) -> MessageKey {
+        match self {
+            ApiError::Validation(e) => e.message_key(),
+            ApiError::Provider(e) => e.message_key(),
+            ApiError::Unauthorized => MessageKey::new("api.unauthorized"),
+            ApiError::NotFound { method, path } => MessageKey::new("api.not_found")
+                .arg("method", method)
+                .arg("path", path),
+            ApiError::MethodNotAllowed {
+                method,
+                path,
+                allowed,
+            } => MessageKey::new("api.method_not_allowed")
+                .arg("method", method)
+                .arg("path", path)
+                .arg("allowed", allowed.join(", ")),
+            ApiError::Overloaded { retry_after_secs } => {
+                MessageKey::new("api.overloaded").arg("retry_after_secs", retry_after_secs)
+            }
+            ApiError::TooManyStreams { max } => {
+                MessageKey::new("api.too_many_streams").arg("max", max)
+            }
+            ApiError::ResponseTooLarge { size, max_bytes } => {
+                MessageKey::new("api.response_too_large")
+                    .arg("size", size)
+                    .arg("max_bytes", max_bytes)
+            }
+            ApiError::RequestTooLarge { max_bytes } => {
+                MessageKey::new("api.request_too_large").arg("max_bytes", max_bytes)
+            }
+        }
+    }
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, openai_error) = match self {
+        let (status, openai_error) = match &self {
             ApiError::Validation(e) => (e.status_code(), e.to_openai_error()),
             ApiError::Provider(e) => (e.status_code(), e.to_openai_error()),
+            ApiError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "authentication_error".to_string(),
+                    param: None,
+                    code: Some("invalid_api_key".to_string()),
+                },
+            ),
+            ApiError::NotFound { .. } => (
+                StatusCode::NOT_FOUND,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "invalid_request_error".to_string(),
+                    param: None,
+                    code: Some("not_found".to_string()),
+                },
+            ),
+            ApiError::MethodNotAllowed { .. } => (
+                StatusCode::METHOD_NOT_ALLOWED,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "invalid_request_error".to_string(),
+                    param: None,
+                    code: Some("method_not_allowed".to_string()),
+                },
+            ),
+            ApiError::Overloaded { .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "server_error".to_string(),
+                    param: None,
+                    code: Some("server_overloaded".to_string()),
+                },
+            ),
+            ApiError::TooManyStreams { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "rate_limit_error".to_string(),
+                    param: None,
+                    code: Some("too_many_streams".to_string()),
+                },
+            ),
+            ApiError::ResponseTooLarge { .. } => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "invalid_request_error".to_string(),
+                    param: None,
+                    code: Some("response_too_large".to_string()),
+                },
+            ),
+            ApiError::RequestTooLarge { .. } => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                OpenAIError {
+                    message: self.to_string(),
+                    error_type: "invalid_request_error".to_string(),
+                    param: None,
+                    code: Some("request_too_large".to_string()),
+                },
+            ),
         };
 
+        let category = ErrorCategory(openai_error.error_type.clone());
+        let localizable = LocalizableError {
+            error: openai_error.clone(),
+            message: self.message_key(),
+        };
         let error_response = OpenAIErrorResponse {
             error: openai_error,
         };
 
-        (status, Json(error_response)).into_response()
+        let mut response = (status, Json(error_response)).into_response();
+        if let ApiError::Overloaded { retry_after_secs } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, retry_after_secs.into());
+        }
+        if let ApiError::Validation(ref e) = self {
+            response
+                .extensions_mut()
+                .insert(ValidationRejection(e.variant_name()));
+        }
+        response.extensions_mut().insert(category);
+        response.extensions_mut().insert(localizable);
+        response
     }
 }
 
@@ -66,7 +242,15 @@ impl ProviderError {
                 StatusCode::from_u16(*status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
             }
             ProviderError::ModelNotAvailable { .. } => StatusCode::BAD_REQUEST,
-            ProviderError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            // Keep the upstream's 413/414: the caller must shrink the request
+            ProviderError::PayloadTooLarge { status, .. } => {
+                StatusCode::from_u16(*status).unwrap_or(StatusCode::PAYLOAD_TOO_LARGE)
+            }
+            // Upstream unreachable in time: retryable, like any gateway timeout
+            ProviderError::ConnectTimeout => StatusCode::GATEWAY_TIMEOUT,
+            // The request itself took too long: the caller should ask for less
+            ProviderError::DeadlineExceeded { .. } => StatusCode::REQUEST_TIMEOUT,
+            ProviderError::StreamStalled { .. } => StatusCode::BAD_GATEWAY,
             ProviderError::Configuration(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ProviderError::StreamingNotSupported => StatusCode::BAD_REQUEST,
             ProviderError::StreamError(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -115,11 +299,41 @@ impl ProviderError {
                     code: Some("model_not_found".to_string()),
                 }
             }
-            ProviderError::Timeout => OpenAIError {
-                message: "Request to inference provider timed out".to_string(),
+            ProviderError::PayloadTooLarge { status, .. } => OpenAIError {
+                message: format!(
+                    "The inference provider rejected the request as too large (HTTP {}). Shorten the prompt or send fewer messages.",
+                    status
+                ),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("messages".to_string()),
+                code: Some("request_too_large".to_string()),
+            },
+            ProviderError::ConnectTimeout => OpenAIError {
+                message: "Timed out connecting to inference provider; retry later".to_string(),
+                error_type: "timeout_error".to_string(),
+                param: None,
+                code: Some("connect_timeout".to_string()),
+            },
+            ProviderError::DeadlineExceeded {
+                elapsed_ms,
+                budget_ms,
+            } => OpenAIError {
+                message: format!(
+                    "Request to inference provider exceeded its {}ms deadline ({}ms elapsed); shorten the prompt or lower max_tokens",
+                    budget_ms, elapsed_ms
+                ),
                 error_type: "timeout_error".to_string(),
                 param: None,
-                code: Some("provider_timeout".to_string()),
+                code: Some("deadline_exceeded".to_string()),
+            },
+            ProviderError::StreamStalled { after_chunks } => OpenAIError {
+                message: format!(
+                    "Inference provider stopped streaming after {} chunks",
+                    after_chunks
+                ),
+                error_type: "api_error".to_string(),
+                param: None,
+                code: Some("stream_stalled".to_string()),
             },
             ProviderError::Configuration(msg) => OpenAIError {
                 message: format!("Provider configuration error: {}", msg),
@@ -147,6 +361,56 @@ impl ProviderError {
             },
         }
     }
+
+    /// Catalog key and arguments used to localize the message
+    pub fn message_key(&self) -> MessageKey {
+        match self {
+            ProviderError::ConnectionFailed(msg) => {
+                MessageKey::new("provider.connection_failed").arg("detail", msg)
+            }
+            ProviderError::InvalidResponse(msg) => {
+                MessageKey::new("provider.invalid_response").arg("detail", msg)
+            }
+            ProviderError::RequestFailed { status, message } => {
+                MessageKey::new("provider.request_failed")
+                    .arg("status", status)
+                    .arg("detail", message)
+            }
+            ProviderError::ModelNotAvailable {
+                requested,
+                available,
+            } => MessageKey::new("provider.model_not_available")
+                .arg("model", requested)
+                .arg("available", available.join(", ")),
+            ProviderError::PayloadTooLarge { status, .. } => {
+                MessageKey::new("provider.payload_too_large").arg("status", status)
+            }
+            ProviderError::ConnectTimeout => MessageKey::new("provider.connect_timeout"),
+            ProviderError::DeadlineExceeded {
+                elapsed_ms,
+                budget_ms,
+            } => MessageKey::new("provider.deadline_exceeded")
+                .arg("elapsed_ms", elapsed_ms)
+                .arg("budget_ms", budget_ms),
+            ProviderError::StreamStalled { after_chunks } => {
+                MessageKey::new("provider.stream_stalled").arg("after_chunks", after_chunks)
+            }
+            ProviderError::Configuration(msg) => {
+                MessageKey::new("provider.configuration").arg("detail", msg)
+            }
+            ProviderError::StreamingNotSupported => {
+                MessageKey::new("provider.streaming_not_supported")
+            }
+            ProviderError::StreamError(msg) => {
+                MessageKey::new("provider.stream_error").arg("detail", msg)
+            }
+            ProviderError::InvalidExtension { param, reason } => {
+                MessageKey::new("provider.invalid_extension")
+                    .arg("param", param)
+                    .arg("reason", reason)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -165,13 +429,25 @@ mod tests {
     }
 
     #[test]
-    fn test_provider_timeout_error() {
-        let error = ProviderError::Timeout;
-        let openai_error = error.to_openai_error();
+    fn test_provider_timeout_errors() {
+        let openai_error = ProviderError::ConnectTimeout.to_openai_error();
+        assert_eq!(openai_error.error_type, "timeout_error");
+        assert_eq!(openai_error.code, Some("connect_timeout".to_string()));
+        assert!(openai_error.message.contains("Timed out connecting"));
 
+        let openai_error = ProviderError::DeadlineExceeded {
+            elapsed_ms: 30_012,
+            budget_ms: 30_000,
+        }
+        .to_openai_error();
         assert_eq!(openai_error.error_type, "timeout_error");
-        assert_eq!(openai_error.code, Some("provider_timeout".to_string()));
-        assert!(openai_error.message.contains("timed out"));
+        assert_eq!(openai_error.code, Some("deadline_exceeded".to_string()));
+        assert!(openai_error.message.contains("30000ms deadline"));
+
+        let openai_error = ProviderError::StreamStalled { after_chunks: 7 }.to_openai_error();
+        assert_eq!(openai_error.error_type, "api_error");
+        assert_eq!(openai_error.code, Some("stream_stalled".to_string()));
+        assert!(openai_error.message.contains("after 7 chunks"));
     }
 
     #[test]
@@ -258,9 +534,21 @@ mod tests {
             StatusCode::BAD_GATEWAY
         );
         assert_eq!(
-            ProviderError::Timeout.status_code(),
+            ProviderError::ConnectTimeout.status_code(),
             StatusCode::GATEWAY_TIMEOUT
         );
+        assert_eq!(
+            ProviderError::DeadlineExceeded {
+                elapsed_ms: 1,
+                budget_ms: 1
+            }
+            .status_code(),
+            StatusCode::REQUEST_TIMEOUT
+        );
+        assert_eq!(
+            ProviderError::StreamStalled { after_chunks: 0 }.status_code(),
+            StatusCode::BAD_GATEWAY
+        );
         assert_eq!(
             ProviderError::RequestFailed {
                 status: 401,
@@ -279,6 +567,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_payload_too_large_is_client_error() {
+        let error = ProviderError::PayloadTooLarge {
+            status: 413,
+            message: "Request Entity Too Large".to_string(),
+        };
+
+        assert_eq!(error.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        let openai_error = error.to_openai_error();
+        assert_eq!(openai_error.error_type, "invalid_request_error");
+        assert_eq!(openai_error.code, Some("request_too_large".to_string()));
+        assert!(openai_error.message.contains("Shorten the prompt"));
+    }
+
     #[test]
     fn test_configuration_error() {
         let error = ProviderError::Configuration("Invalid config".to_string());
@@ -298,4 +600,19 @@ mod tests {
         assert_eq!(openai_error.code, Some("stream_error".to_string()));
         assert!(openai_error.message.contains("Connection lost"));
     }
+
+    #[test]
+    fn test_method_not_allowed_names_allowed_methods() {
+        let error = ApiError::MethodNotAllowed {
+            method: Method::POST,
+            path: "/v1/models".to_string(),
+            allowed: vec!["GET".to_string(), "HEAD".to_string()],
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "Method POST is not allowed for /v1/models; allowed methods: GET, HEAD"
+        );
+        assert_eq!(error.into_response().status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
 }
@@ -0,0 +1,391 @@
+//! Golden-response regression harness.
+//!
+//! Each `tests/golden/*.yaml` case is a fixed, seeded, temperature-0 request sent
+//! through the full router in-process. Against the mock provider the case's
+//! `scenario` is served and the result must match `expected` exactly (after
+//! whitespace and finish-reason normalization); this runs in normal CI as a guard
+//! on the pipeline. With `--features live-regression` the same requests go to the
+//! configured provider and are compared to `live_expected` by token overlap.
+//!
+//! Set `GOLDEN_REGENERATE=1` to overwrite the stored expectations with what was observed.
+
+use axum::{Router, body::Body, http::Request};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use crate::config::Settings;
+use crate::providers::mock::MockResponseFile;
+use crate::{AppState, build_router, create_provider};
+
+const GOLDEN_DIR: &str = "tests/golden";
+const REGENERATE_ENV: &str = "GOLDEN_REGENERATE";
+/// Seed applied to every case that doesn't set its own
+const GOLDEN_SEED: u64 = 42;
+/// Token overlap required against live providers unless a case overrides it
+#[cfg(feature = "live-regression")]
+const DEFAULT_LIVE_THRESHOLD: f64 = 0.6;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct GoldenCase {
+    name: String,
+    /// Body posted to `/v1/chat/completions`
+    request: serde_json::Value,
+    /// Mock response file served for the request's `mock-*` model
+    scenario: serde_yml::Value,
+    expected: Observed,
+    /// Also run against live providers
+    #[serde(default = "default_live")]
+    live: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    live_expected: Option<Observed>,
+}
+
+fn default_live() -> bool {
+    true
+}
+
+/// What a case produced, in the shape it is stored
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct Observed {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    finish_reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error_type: Option<String>,
+    /// Sorted `provider_extensions.data` keys, for extended-mode cases
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    extension_keys: Vec<String>,
+}
+
+/// How closely response text has to match
+#[derive(Debug, Clone, Copy)]
+enum Similarity {
+    Exact,
+    TokenOverlap(f64),
+}
+
+fn load_cases() -> Vec<(PathBuf, GoldenCase)> {
+    let mut paths: Vec<_> = std::fs::read_dir(GOLDEN_DIR)
+        .expect("golden directory")
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "yaml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let raw = std::fs::read_to_string(&path).unwrap();
+            let case: GoldenCase = serde_yml::from_str(&raw)
+                .unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+            serde_yml::from_value::<MockResponseFile>(case.scenario.clone())
+                .unwrap_or_else(|e| panic!("{}: invalid scenario: {e}", path.display()));
+            (path, case)
+        })
+        .collect()
+}
+
+/// Pin sampling so repeated runs are comparable
+fn seeded_request(case: &GoldenCase) -> serde_json::Value {
+    let mut request = case.request.clone();
+    let body = request.as_object_mut().expect("request must be an object");
+    body.entry("seed").or_insert(serde_json::json!(GOLDEN_SEED));
+    body.entry("temperature").or_insert(serde_json::json!(0));
+    request
+}
+
+async fn observe(app: Router, request: serde_json::Value) -> Observed {
+    let response = app
+        .oneshot(
+            Request::post("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    let is_stream = response
+        .headers()
+        .get("content-type")
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    let mut observed = Observed {
+        status,
+        ..Default::default()
+    };
+    if is_stream {
+        reassemble_stream(&String::from_utf8_lossy(&bytes), &mut observed);
+        return observed;
+    }
+
+    let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+    if let Some(error) = body.get("error") {
+        observed.error_type = error["type"].as_str().map(str::to_string);
+        return observed;
+    }
+    let choice = &body["choices"][0];
+    observed.text = choice["message"]["content"].as_str().map(str::to_string);
+    observed.finish_reason = choice["finish_reason"].as_str().map(str::to_string);
+    if let Some(data) = body["provider_extensions"]["data"].as_object() {
+        observed.extension_keys = data.keys().cloned().collect();
+        observed.extension_keys.sort();
+    }
+    observed
+}
+
+/// Join the content deltas of an SSE body back into one message
+fn reassemble_stream(body: &str, observed: &mut Observed) {
+    let mut text = String::new();
+    for data in body.lines().filter_map(|line| line.strip_prefix("data: ")) {
+        if data == "[DONE]" {
+            break;
+        }
+        let Ok(chunk) = serde_json::from_str::<serde_json::Value>(data) else {
+            continue;
+        };
+        if let Some(error) = chunk.get("error") {
+            observed.error_type = error["type"].as_str().map(str::to_string);
+            continue;
+        }
+        let choice = &chunk["choices"][0];
+        if let Some(content) = choice["delta"]["content"].as_str() {
+            text.push_str(content);
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            observed.finish_reason = Some(reason.to_string());
+        }
+    }
+    observed.text = Some(text);
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Upstreams spell the same stop condition differently
+fn normalize_finish_reason(reason: &str) -> String {
+    match reason.to_ascii_lowercase().as_str() {
+        "eos" | "end_turn" | "stop_sequence" | "stop" => "stop".to_string(),
+        "max_tokens" | "length" => "length".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Dice coefficient over lowercase word tokens, counting repeats
+fn token_overlap(expected: &str, actual: &str) -> f64 {
+    fn tokens(text: &str) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for token in text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+        {
+            *counts.entry(token.to_lowercase()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    let (expected, actual) = (tokens(expected), tokens(actual));
+    let total: usize = expected.values().sum::<usize>() + actual.values().sum::<usize>();
+    if total == 0 {
+        return 1.0;
+    }
+    let shared: usize = expected
+        .iter()
+        .map(|(token, count)| (*count).min(actual.get(token).copied().unwrap_or(0)))
+        .sum();
+    2.0 * shared as f64 / total as f64
+}
+
+/// Every way `actual` differs from `expected`; empty when the case passes
+fn compare(expected: &Observed, actual: &Observed, similarity: Similarity) -> Vec<String> {
+    let mut problems = Vec::new();
+    if expected.status != actual.status {
+        problems.push(format!("status {} != {}", actual.status, expected.status));
+    }
+    if expected.error_type != actual.error_type {
+        problems.push(format!(
+            "error type {:?} != {:?}",
+            actual.error_type, expected.error_type
+        ));
+    }
+
+    let finish = |reason: &Option<String>| reason.as_deref().map(normalize_finish_reason);
+    if finish(&expected.finish_reason) != finish(&actual.finish_reason) {
+        problems.push(format!(
+            "finish_reason {:?} != {:?}",
+            actual.finish_reason, expected.finish_reason
+        ));
+    }
+
+    match (&expected.text, &actual.text, similarity) {
+        (Some(want), Some(got), Similarity::Exact) => {
+            if normalize_whitespace(want) != normalize_whitespace(got) {
+                problems.push(format!("text {got:?} != {want:?}"));
+            }
+        }
+        (Some(want), Some(got), Similarity::TokenOverlap(threshold)) => {
+            let score = token_overlap(want, got);
+            if score < threshold {
+                problems.push(format!("text overlap {score:.2} < {threshold:.2}: {got:?}"));
+            }
+        }
+        (None, None, _) => {}
+        (want, got, _) => problems.push(format!("text {got:?} != {want:?}")),
+    }
+
+    if expected.extension_keys != actual.extension_keys {
+        problems.push(format!(
+            "extension keys {:?} != {:?}",
+            actual.extension_keys, expected.extension_keys
+        ));
+    }
+    problems
+}
+
+/// Report every case and fail listing the ones that failed
+fn finish_suite(label: &str, results: Vec<(String, Vec<String>)>) {
+    let failed: Vec<_> = results
+        .iter()
+        .filter(|(_, problems)| !problems.is_empty())
+        .collect();
+    for (name, problems) in &results {
+        match problems.as_slice() {
+            [] => println!("[{label}] PASS {name}"),
+            _ => println!("[{label}] FAIL {name}: {}", problems.join("; ")),
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "{} of {} {label} golden cases failed: {}",
+        failed.len(),
+        results.len(),
+        failed
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+}
+
+fn regenerate(path: &Path, case: &GoldenCase) {
+    let yaml = serde_yml::to_string(case).unwrap();
+    std::fs::write(path, yaml).unwrap();
+    println!("regenerated {}", path.display());
+}
+
+fn should_regenerate() -> bool {
+    std::env::var(REGENERATE_ENV).is_ok_and(|v| v == "1")
+}
+
+fn mock_router(responses_dir: &Path, case: &GoldenCase) -> Router {
+    let model = case.request["model"]
+        .as_str()
+        .and_then(|m| m.strip_prefix("mock-"))
+        .expect("golden request model must be mock-<scenario>");
+    std::fs::write(
+        responses_dir.join(format!("{model}.yaml")),
+        serde_yml::to_string(&case.scenario).unwrap(),
+    )
+    .unwrap();
+
+    let settings: Settings = serde_yml::from_str(&format!(
+        r#"
+server: {{}}
+inference:
+  provider: mock
+  base_url: "http://localhost:1234"
+  default_model: mock-default
+  responses_dir: {responses_dir:?}
+logging: {{}}
+"#
+    ))
+    .unwrap();
+    let settings = Arc::new(settings);
+    build_router(AppState::new(create_provider(&settings).unwrap(), settings))
+}
+
+#[tokio::test]
+async fn test_golden_cases_against_mock() {
+    let cases = load_cases();
+    assert!(cases.len() >= 5, "expected at least five golden cases");
+
+    let mut results = Vec::new();
+    for (path, mut case) in cases {
+        let responses_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(responses_dir.path(), &case);
+        let observed = observe(app, seeded_request(&case)).await;
+
+        let mut problems = compare(&case.expected, &observed, Similarity::Exact);
+        if should_regenerate() && !problems.is_empty() {
+            case.expected = observed;
+            regenerate(&path, &case);
+            problems.clear();
+        }
+        results.push((case.name.clone(), problems));
+    }
+    finish_suite("mock", results);
+}
+
+#[cfg(feature = "live-regression")]
+#[tokio::test]
+async fn test_golden_cases_against_live_provider() {
+    let settings = Arc::new(Settings::new().expect("live regression needs a provider config"));
+    let app = build_router(AppState::new(create_provider(&settings).unwrap(), settings));
+
+    let mut results = Vec::new();
+    for (path, mut case) in load_cases().into_iter().filter(|(_, case)| case.live) {
+        // Let the configured default model serve the request
+        let mut request = seeded_request(&case);
+        request.as_object_mut().unwrap().remove("model");
+        let observed = observe(app.clone(), request).await;
+
+        if should_regenerate() {
+            case.live_expected = Some(observed.clone());
+            regenerate(&path, &case);
+        }
+        let problems = match case.live_expected {
+            Some(ref expected) => {
+                let threshold = case.threshold.unwrap_or(DEFAULT_LIVE_THRESHOLD);
+                compare(expected, &observed, Similarity::TokenOverlap(threshold))
+            }
+            None => vec![format!("no live_expected; run with {REGENERATE_ENV}=1")],
+        };
+        results.push((case.name.clone(), problems));
+    }
+    finish_suite("live", results);
+}
+
+#[test]
+fn test_comparison_normalizes_whitespace_and_finish_reason() {
+    let expected = Observed {
+        status: 200,
+        text: Some("The capital of France is Paris.".to_string()),
+        finish_reason: Some("stop".to_string()),
+        ..Default::default()
+    };
+    let mut actual = expected.clone();
+    actual.text = Some("The capital  of France\nis Paris. ".to_string());
+    actual.finish_reason = Some("eos".to_string());
+    assert!(compare(&expected, &actual, Similarity::Exact).is_empty());
+
+    actual.text = Some("Paris is the capital of France.".to_string());
+    assert_eq!(compare(&expected, &actual, Similarity::Exact).len(), 1);
+    assert!(compare(&expected, &actual, Similarity::TokenOverlap(0.8)).is_empty());
+
+    actual.text = Some("I don't know.".to_string());
+    actual.finish_reason = Some("length".to_string());
+    let problems = compare(&expected, &actual, Similarity::TokenOverlap(0.6));
+    assert_eq!(problems.len(), 2);
+    assert!(problems[1].starts_with("text overlap"));
+}
@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::content_hash::{ContentHashes, MessageHash};
+use crate::models::{CompletionResponse, Warning};
+
+/// Usage bucket for requests made without an API key
+pub const ANONYMOUS_KEY_LABEL: &str = "anonymous";
+
+/// Distinct prompt hashes tracked per key; later new hashes are not counted
+const MAX_TRACKED_PROMPTS_PER_KEY: usize = 10_000;
+
+/// One access-log record. Never holds message content, only request metadata.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestSummary {
+    pub id: String,
+    pub timestamp: u64,
+    pub method: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub provider: String,
+    pub status: u16,
+    pub latency_ms: u64,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finish_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_hashes: Option<Vec<MessageHash>>,
+    /// Codes of the warnings raised while serving the request
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Response extension set by the completion handler so the access log can
+/// describe the request without re-parsing the body
+#[derive(Debug, Clone, Default)]
+pub struct RequestOutcome {
+    pub id: Option<String>,
+    pub model: Option<String>,
+    pub stream: bool,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub total_tokens: Option<u32>,
+    pub finish_reason: Option<String>,
+    pub content_hashes: Option<ContentHashes>,
+    /// Raised by pipeline stages, in order
+    pub warnings: Vec<Warning>,
+}
+
+impl RequestOutcome {
+    pub fn new(model: Option<&str>, stream: bool) -> Self {
+        Self {
+            model: model.map(str::to_string),
+            stream,
+            ..Default::default()
+        }
+    }
+
+    /// Copy usage and finish reason from a non-streaming response
+    pub fn record_response(&mut self, response: &CompletionResponse) {
+        self.id = Some(response.id.clone());
+        self.model = Some(response.model.clone());
+        if let Some(ref usage) = response.usage {
+            self.prompt_tokens = usage.prompt_tokens;
+            self.completion_tokens = usage.completion_tokens;
+            self.total_tokens = usage.total_tokens;
+        }
+        self.finish_reason = response
+            .choices
+            .first()
+            .and_then(|choice| choice.finish_reason.as_ref())
+            .and_then(|reason| serde_json::to_value(reason).ok())
+            .and_then(|value| value.as_str().map(str::to_string));
+    }
+}
+
+/// Response extension set by `ApiError` carrying its OpenAI error type
+#[derive(Debug, Clone)]
+pub struct ErrorCategory(pub String);
+
+/// Request and response extension naming the API key a request was made with.
+/// Holds a short hash of the key, never the key itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyLabel(pub String);
+
+impl KeyLabel {
+    pub fn for_key(key: &str) -> Self {
+        let hash = blake3::hash(key.as_bytes()).to_hex();
+        Self(format!("key-{}", &hash[..12]))
+    }
+}
+
+/// Filters for `GET /admin/requests/recent`
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    pub status: Option<u16>,
+    pub model: Option<String>,
+    pub key_label: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, summary: &RequestSummary) -> bool {
+        self.status.is_none_or(|status| summary.status == status)
+            && self
+                .model
+                .as_deref()
+                .is_none_or(|model| summary.model.as_deref() == Some(model))
+            && self
+                .key_label
+                .as_deref()
+                .is_none_or(|label| summary.key_label.as_deref() == Some(label))
+    }
+}
+
+/// Bounded ring buffer of the most recent request summaries
+pub struct RequestHistory {
+    capacity: usize,
+    entries: Mutex<VecDeque<RequestSummary>>,
+}
+
+impl RequestHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Append a summary, evicting the oldest once the buffer is full
+    pub fn record(&self, summary: RequestSummary) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(summary);
+    }
+
+    /// Matching summaries, newest first
+    pub fn recent(&self, query: &HistoryQuery) -> Vec<RequestSummary> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .iter()
+            .rev()
+            .filter(|summary| query.matches(summary))
+            .take(query.limit.unwrap_or(self.capacity))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Per-key counts of repeated conversations, by salted conversation hash
+#[derive(Default)]
+pub struct PromptUsage {
+    keys: Mutex<HashMap<String, KeyPrompts>>,
+}
+
+#[derive(Default)]
+struct KeyPrompts {
+    requests: u64,
+    prompts: HashMap<String, u64>,
+}
+
+/// One entry of `GET /admin/usage`
+#[derive(Debug, Serialize)]
+pub struct KeyUsage {
+    pub key_label: String,
+    pub requests: u64,
+    pub top_prompts: Vec<PromptCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PromptCount {
+    pub conversation_hash: String,
+    pub count: u64,
+}
+
+impl PromptUsage {
+    pub fn record(&self, key_label: Option<&str>, conversation_hash: &str) {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let key = keys
+            .entry(key_label.unwrap_or(ANONYMOUS_KEY_LABEL).to_string())
+            .or_default();
+        key.requests += 1;
+        if let Some(count) = key.prompts.get_mut(conversation_hash) {
+            *count += 1;
+        } else if key.prompts.len() < MAX_TRACKED_PROMPTS_PER_KEY {
+            key.prompts.insert(conversation_hash.to_string(), 1);
+        }
+    }
+
+    /// The `top` most repeated conversations for every key, busiest keys first
+    pub fn top(&self, top: usize) -> Vec<KeyUsage> {
+        let keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        let mut usage: Vec<_> = keys
+            .iter()
+            .map(|(label, key)| {
+                let mut prompts: Vec<_> = key.prompts.iter().collect();
+                prompts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                KeyUsage {
+                    key_label: label.clone(),
+                    requests: key.requests,
+                    top_prompts: prompts
+                        .into_iter()
+                        .take(top)
+                        .map(|(hash, count)| PromptCount {
+                            conversation_hash: hash.clone(),
+                            count: *count,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        usage.sort_by(|a, b| {
+            b.requests
+                .cmp(&a.requests)
+                .then_with(|| a.key_label.cmp(&b.key_label))
+        });
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(id: &str, status: u16, model: &str) -> RequestSummary {
+        RequestSummary {
+            id: id.to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            path: "/v1/chat/completions".to_string(),
+            key_label: None,
+            model: Some(model.to_string()),
+            provider: "mock".to_string(),
+            status,
+            latency_ms: 1,
+            stream: false,
+            prompt_tokens: None,
+            completion_tokens: None,
+            total_tokens: None,
+            finish_reason: None,
+            error_category: None,
+            conversation_hash: None,
+            message_hashes: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_history_evicts_oldest_and_returns_newest_first() {
+        let history = RequestHistory::new(2);
+        history.record(summary("a", 200, "m"));
+        history.record(summary("b", 200, "m"));
+        history.record(summary("c", 200, "m"));
+
+        let ids: Vec<_> = history
+            .recent(&HistoryQuery::default())
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, vec!["c", "b"]);
+    }
+
+    #[test]
+    fn test_history_filters_and_limits() {
+        let history = RequestHistory::new(10);
+        history.record(summary("a", 200, "mock-one"));
+        history.record(summary("b", 400, "mock-one"));
+        history.record(summary("c", 200, "mock-two"));
+        history.record(summary("d", 200, "mock-one"));
+
+        let query = HistoryQuery {
+            status: Some(200),
+            model: Some("mock-one".to_string()),
+            ..Default::default()
+        };
+        let ids: Vec<_> = history.recent(&query).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, vec!["d", "a"]);
+
+        let limited = HistoryQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(history.recent(&limited)[0].id, "d");
+    }
+
+    #[test]
+    fn test_prompt_usage_ranks_repeated_conversations_per_key() {
+        let usage = PromptUsage::default();
+        usage.record(Some("team-a"), "h1");
+        usage.record(Some("team-a"), "h2");
+        usage.record(Some("team-a"), "h2");
+        usage.record(None, "h1");
+
+        let top = usage.top(1);
+
+        assert_eq!(top[0].key_label, "team-a");
+        assert_eq!(top[0].requests, 3);
+        assert_eq!(top[0].top_prompts.len(), 1);
+        assert_eq!(top[0].top_prompts[0].conversation_hash, "h2");
+        assert_eq!(top[0].top_prompts[0].count, 2);
+        assert_eq!(top[1].key_label, ANONYMOUS_KEY_LABEL);
+    }
+}
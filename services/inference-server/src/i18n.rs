@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::models::OpenAIError;
+
+/// Locale of the messages built into the error types
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Catalog key and named arguments for one client-facing error message
+#[derive(Debug, Clone)]
+pub struct MessageKey {
+    pub key: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl MessageKey {
+    pub fn new(key: &'static str) -> Self {
+        Self {
+            key,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.args.push((name, value.to_string()));
+        self
+    }
+}
+
+/// Response extension set by `ApiError` so the message can be translated once
+/// the caller's `Accept-Language` is known. Only `message` is ever replaced.
+#[derive(Debug, Clone)]
+pub struct LocalizableError {
+    pub error: OpenAIError,
+    pub message: MessageKey,
+}
+
+/// Translations loaded from `<locale>.yaml` files, each a flat map of key to template.
+/// Templates reference arguments as `{name}`.
+#[derive(Debug, Default)]
+pub struct MessageCatalog {
+    locales: HashMap<String, HashMap<String, String>>,
+}
+
+impl MessageCatalog {
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Failed to read locales directory {dir:?}: {e}"))?;
+
+        let mut locales = HashMap::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Failed to read locales directory {dir:?}: {e}"))?
+                .path();
+            if !matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml")
+            ) {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read locale file {path:?}: {e}"))?;
+            let messages: HashMap<String, String> = serde_yml::from_str(&contents)
+                .map_err(|e| format!("Failed to parse locale file {path:?}: {e}"))?;
+            locales.insert(locale.to_ascii_lowercase(), messages);
+        }
+
+        Ok(Self { locales })
+    }
+
+    /// Pick the best loaded locale for an `Accept-Language` header value.
+    /// Returns `None` when the default locale should be used.
+    pub fn negotiate(&self, accept_language: &str) -> Option<&str> {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        // Stable sort keeps header order among equal weights
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        for (tag, _) in ranges {
+            let tag = tag.to_ascii_lowercase();
+            let primary = tag.split('-').next().unwrap_or(&tag);
+            if tag == "*" || primary == DEFAULT_LOCALE {
+                return None;
+            }
+            for candidate in [tag.as_str(), primary] {
+                if let Some((locale, _)) = self.locales.get_key_value(candidate) {
+                    return Some(locale);
+                }
+            }
+        }
+        None
+    }
+
+    /// Translated message, or `None` if the locale has no entry for this key
+    pub fn translate(&self, locale: &str, message: &MessageKey) -> Option<String> {
+        let template = self.locales.get(locale)?.get(message.key)?;
+        let translated = message
+            .args
+            .iter()
+            .fold(template.clone(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            });
+        Some(translated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_catalog() -> (TempDir, MessageCatalog) {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("fr.yaml"),
+            "validation.invalid_temperature: \"La température doit être comprise entre 0.0 et 2.0, reçu {value}\"\n",
+        )
+        .unwrap();
+        std::fs::write(temp_dir.path().join("README.md"), "not a locale").unwrap();
+        let catalog = MessageCatalog::load_dir(temp_dir.path()).unwrap();
+        (temp_dir, catalog)
+    }
+
+    #[test]
+    fn test_negotiate_prefers_highest_quality_loaded_locale() {
+        let (_dir, catalog) = test_catalog();
+
+        assert_eq!(catalog.negotiate("fr-CH, fr;q=0.9, en;q=0.8"), Some("fr"));
+        assert_eq!(catalog.negotiate("de, fr;q=0.5"), Some("fr"));
+        assert_eq!(catalog.negotiate("en-US, fr;q=0.9"), None);
+        assert_eq!(catalog.negotiate("de"), None);
+        assert_eq!(catalog.negotiate("fr;q=0"), None);
+    }
+
+    #[test]
+    fn test_translate_substitutes_args_and_falls_back_per_key() {
+        let (_dir, catalog) = test_catalog();
+        let message = MessageKey::new("validation.invalid_temperature").arg("value", 3.5);
+
+        assert_eq!(
+            catalog.translate("fr", &message).as_deref(),
+            Some("La température doit être comprise entre 0.0 et 2.0, reçu 3.5")
+        );
+        assert_eq!(
+            catalog.translate("fr", &MessageKey::new("validation.empty_messages")),
+            None
+        );
+    }
+}
@@ -0,0 +1,812 @@
+//! End-to-end tests driving the real router from `build_router`.
+//!
+//! Provider and pipeline tests call their own code directly; these go through
+//! routing, middleware, response conversion and SSE framing the way a client
+//! does. `TestApp` serves the mock provider from a temporary fixture directory,
+//! and `SseReader` reads event streams frame by frame as they arrive.
+
+use axum::body::{Body, BodyDataStream};
+use axum::http::{Request, StatusCode, header};
+use axum::response::Response;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+use crate::config::{Settings, StreamErrorFormat};
+use crate::{AppState, build_router, create_provider};
+
+/// Scenario served for the `mock-test` model
+const REPLY_FIXTURE: &str = r#"
+responses:
+  - text: "The quick brown fox"
+    model_used: "mock-test"
+    prompt_tokens: 5
+    completion_tokens: 4
+    total_tokens: 9
+settings:
+  mode: first
+  chunk_delay_ms: 0
+"#;
+
+/// The router over a mock provider reading fixtures from a temporary directory
+struct TestApp {
+    fixtures: TempDir,
+    settings: Arc<Settings>,
+}
+
+impl TestApp {
+    fn new() -> Self {
+        let fixtures = TempDir::new().unwrap();
+        let settings: Settings = serde_yml::from_str(&format!(
+            r#"
+server:
+  host: 127.0.0.1
+  port: 0
+inference:
+  provider: mock
+  base_url: "http://localhost"
+  default_model: mock-test
+  timeout_secs: 30
+  responses_dir: {:?}
+logging: {{}}
+"#,
+            fixtures.path()
+        ))
+        .unwrap();
+        let app = Self {
+            fixtures,
+            settings: Arc::new(settings),
+        };
+        app.fixture("test", REPLY_FIXTURE)
+    }
+
+    /// Add a `mock-<name>` scenario
+    fn fixture(self, name: &str, yaml: &str) -> Self {
+        std::fs::write(self.fixtures.path().join(format!("{name}.yaml")), yaml).unwrap();
+        self
+    }
+
+    /// Adjust the settings every later request is served with
+    fn configure(mut self, configure: impl FnOnce(&mut Settings)) -> Self {
+        configure(Arc::make_mut(&mut self.settings));
+        self
+    }
+
+    fn router(&self) -> axum::Router {
+        let provider = create_provider(&self.settings).unwrap();
+        build_router(AppState::new(provider, self.settings.clone()))
+    }
+
+    async fn get(&self, uri: &str) -> Response {
+        self.router()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn post_completion(&self, body: serde_json::Value) -> Response {
+        self.send_completion(body, None).await
+    }
+
+    /// Post a completion with an explicit `Accept` header
+    async fn post_completion_accepting(&self, body: serde_json::Value, accept: &str) -> Response {
+        self.send_completion(body, Some(accept)).await
+    }
+
+    async fn send_completion(&self, body: serde_json::Value, accept: Option<&str>) -> Response {
+        let mut request =
+            Request::post("/v1/chat/completions").header(header::CONTENT_TYPE, "application/json");
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        self.router()
+            .oneshot(request.body(Body::from(body.to_string())).unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// Post a Responses API request to `/v1/responses`
+    async fn post_response(&self, body: serde_json::Value) -> Response {
+        let request = Request::post("/v1/responses")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.router().oneshot(request).await.unwrap()
+    }
+
+    async fn post_embeddings(&self, body: serde_json::Value) -> Response {
+        let request = Request::post("/v1/embeddings")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.router().oneshot(request).await.unwrap()
+    }
+
+    /// Post a completion with an `Authorization` header
+    async fn post_completion_authorized(
+        &self,
+        body: serde_json::Value,
+        authorization: &str,
+    ) -> Response {
+        let request = Request::post("/v1/chat/completions")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, authorization)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        self.router().oneshot(request).await.unwrap()
+    }
+}
+
+fn content_type(response: &Response) -> &str {
+    response.headers()[header::CONTENT_TYPE].to_str().unwrap()
+}
+
+async fn json_body(response: Response) -> serde_json::Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+/// One server-sent event as framed on the wire
+#[derive(Debug, PartialEq)]
+enum SseEvent {
+    Data(String),
+    /// `:` comment lines, which keep-alives are made of
+    Comment(String),
+}
+
+/// Minimal SSE client: splits the body into events on blank lines, reading
+/// more of the body only when no complete event is buffered
+struct SseReader {
+    body: BodyDataStream,
+    buffer: String,
+}
+
+impl SseReader {
+    fn new(response: Response) -> Self {
+        Self {
+            body: response.into_body().into_data_stream(),
+            buffer: String::new(),
+        }
+    }
+
+    async fn next_event(&mut self) -> Option<SseEvent> {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let frame: String = self.buffer.drain(..end + 2).collect();
+                return Some(Self::parse(frame.trim_end_matches('\n')));
+            }
+            let bytes = self.body.next().await?.unwrap();
+            self.buffer.push_str(std::str::from_utf8(&bytes).unwrap());
+        }
+    }
+
+    fn parse(frame: &str) -> SseEvent {
+        let mut data = Vec::new();
+        for line in frame.lines() {
+            if let Some(comment) = line.strip_prefix(':') {
+                return SseEvent::Comment(comment.trim().to_string());
+            }
+            let value = line
+                .strip_prefix("data:")
+                .unwrap_or_else(|| panic!("unexpected SSE line {line:?}"));
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+        SseEvent::Data(data.join("\n"))
+    }
+
+    /// Every data payload up to `[DONE]`, parsed as JSON; panics if the stream
+    /// ends without it
+    async fn chunks(&mut self) -> Vec<serde_json::Value> {
+        let mut chunks = Vec::new();
+        while let Some(event) = self.next_event().await {
+            match event {
+                SseEvent::Data(data) if data == "[DONE]" => return chunks,
+                SseEvent::Data(data) => chunks.push(serde_json::from_str(&data).unwrap()),
+                SseEvent::Comment(_) => {}
+            }
+        }
+        panic!("stream ended without [DONE]");
+    }
+}
+
+/// Concatenate the content deltas of a chat completion stream
+fn reassemble(chunks: &[serde_json::Value]) -> String {
+    chunks
+        .iter()
+        .filter_map(|chunk| chunk["choices"][0]["delta"]["content"].as_str())
+        .collect()
+}
+
+fn user_request(stream: bool) -> serde_json::Value {
+    serde_json::json!({
+        "model": "mock-test",
+        "stream": stream,
+        "messages": [{"role": "user", "content": "Hello"}]
+    })
+}
+
+#[tokio::test]
+async fn test_completion_returns_openai_json() {
+    let app = TestApp::new();
+
+    let response = app.post_completion(user_request(false)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "application/json");
+    let body = json_body(response).await;
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["model"], "mock-test");
+    assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(body["choices"][0]["message"]["content"], "The quick brown fox");
+    assert_eq!(body["choices"][0]["finish_reason"], "stop");
+    assert_eq!(body["usage"]["total_tokens"], 9);
+}
+
+#[tokio::test]
+async fn test_stream_reassembles_to_completion_text() {
+    let app = TestApp::new();
+
+    let response = app.post_completion(user_request(true)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "text/event-stream");
+    let chunks = SseReader::new(response).chunks().await;
+    assert!(
+        chunks
+            .iter()
+            .all(|chunk| chunk["object"] == "chat.completion.chunk")
+    );
+    assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+    assert_eq!(
+        chunks.last().unwrap()["choices"][0]["finish_reason"],
+        "stop"
+    );
+    let ids: Vec<_> = chunks.iter().map(|chunk| &chunk["id"]).collect();
+    assert!(ids.windows(2).all(|pair| pair[0] == pair[1]));
+
+    let streamed = reassemble(&chunks);
+    let completed = json_body(app.post_completion(user_request(false)).await).await;
+    assert_eq!(
+        streamed.trim_end(),
+        completed["choices"][0]["message"]["content"]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_slow_stream_sends_keep_alive_comments() {
+    let app = TestApp::new().fixture(
+        "slow",
+        r#"
+responses:
+  - text: "Eventually"
+settings:
+  chunk_delay_ms: 40000
+"#,
+    );
+    let mut request = user_request(true);
+    request["model"] = serde_json::json!("mock-slow");
+
+    let mut events = SseReader::new(app.post_completion(request).await);
+
+    let first = events.next_event().await.unwrap();
+    assert!(matches!(first, SseEvent::Data(ref data) if data.contains("\"role\"")));
+    // Axum's default keep-alive fires every 15s while the next token is 40s away
+    assert_eq!(
+        events.next_event().await.unwrap(),
+        SseEvent::Comment(String::new())
+    );
+    assert_eq!(
+        events.next_event().await.unwrap(),
+        SseEvent::Comment(String::new())
+    );
+    let chunks = events.chunks().await;
+    assert_eq!(reassemble(&chunks).trim_end(), "Eventually");
+}
+
+/// Streams one chunk of text, then stalls until `stream_stall_timeout_secs` ends it
+const HUNG_FIXTURE: &str = r#"
+responses:
+  - text: "Stuck here"
+settings:
+  chunk_delay_ms: 0
+  hang_after_chunks: 1
+"#;
+
+async fn stalled_stream_body(app: TestApp) -> String {
+    let app = app
+        .fixture("hung", HUNG_FIXTURE)
+        .configure(|settings| settings.inference.stream_stall_timeout_secs = Some(20));
+    let mut request = user_request(true);
+    request["model"] = serde_json::json!("mock-hung");
+
+    let response = app.post_completion(request).await;
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_default_stream_framing_is_openai_style() {
+    let body = stalled_stream_body(TestApp::new()).await;
+
+    // A keep-alive during the stall, the error as a plain data event, then [DONE]
+    assert!(body.contains("\n\n:\n\n"), "{body}");
+    assert!(body.contains("\n\ndata: {\"error\":{"), "{body}");
+    assert!(body.ends_with("\n\ndata: [DONE]\n\n"), "{body}");
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_runtime_options_change_stream_framing() {
+    let app = TestApp::new().configure(|settings| {
+        settings.runtime.stream_done_marker = false;
+        settings.runtime.stream_keep_alive_secs = 0;
+        settings.runtime.stream_errors = StreamErrorFormat::Event;
+    });
+
+    let body = stalled_stream_body(app).await;
+
+    assert!(!body.contains("\n:"), "{body}");
+    assert!(body.contains("\n\nevent: error\ndata: {\"error\":{"), "{body}");
+    assert!(body.contains("stream_stalled"), "{body}");
+    assert!(!body.contains("[DONE]"), "{body}");
+}
+
+#[tokio::test]
+async fn test_stream_is_buffered_for_json_only_clients() {
+    let app = TestApp::new();
+
+    let response = app
+        .post_completion_accepting(user_request(true), "application/json")
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(content_type(&response), "application/json");
+    let body = json_body(response).await;
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(
+        body["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap()
+            .trim_end(),
+        "The quick brown fox"
+    );
+    assert_eq!(body["choices"][0]["finish_reason"], "stop");
+}
+
+#[tokio::test]
+async fn test_stream_stays_sse_unless_only_json_is_accepted() {
+    for accept in ["application/json, text/event-stream", "application/json, */*;q=0.1"] {
+        let response = TestApp::new()
+            .post_completion_accepting(user_request(true), accept)
+            .await;
+        assert!(content_type(&response).starts_with("text/event-stream"), "{accept}");
+    }
+
+    let app = TestApp::new().configure(|settings| settings.runtime.negotiate_accept = false);
+    let response = app
+        .post_completion_accepting(user_request(true), "application/json")
+        .await;
+    assert!(content_type(&response).starts_with("text/event-stream"));
+}
+
+#[tokio::test]
+async fn test_deprecated_features_are_announced() {
+    let app = TestApp::new();
+    let request = serde_json::json!({
+        "model": "mock-test",
+        "response_mode": "extended",
+        "messages": [
+            {"role": "user", "content": "Weather?"},
+            {"role": "function", "name": "get_weather", "content": "{\"temp\":18}"}
+        ],
+        "functions": [{"name": "get_weather"}]
+    });
+
+    let response = app.post_completion(request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "@1735689600");
+    assert_eq!(response.headers()["sunset"], "Fri, 01 Jan 2027 00:00:00 GMT");
+    let body = json_body(response).await;
+    let codes: Vec<_> = body["warnings"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|warning| warning["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["deprecated_functions", "deprecated_function_messages"]);
+
+    let mut clean = user_request(false);
+    clean["response_mode"] = serde_json::json!("extended");
+    let response = app.post_completion(clean).await;
+    assert!(response.headers().get("deprecation").is_none());
+    assert!(response.headers().get("sunset").is_none());
+    assert!(json_body(response).await.get("warnings").is_none());
+}
+
+#[tokio::test]
+async fn test_validation_error_envelope() {
+    let app = TestApp::new();
+    let mut request = user_request(false);
+    request["temperature"] = serde_json::json!(3.5);
+
+    let response = app.post_completion(request).await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(content_type(&response), "application/json");
+    let body = json_body(response).await;
+    let error = body["error"].as_object().unwrap();
+    let mut fields: Vec<_> = error.keys().map(String::as_str).collect();
+    fields.sort();
+    // `code` is left out when there is none
+    assert_eq!(fields, ["message", "param", "type"]);
+    assert_eq!(error["type"], "invalid_request_error");
+    assert_eq!(error["param"], "temperature");
+}
+
+#[tokio::test]
+async fn test_provider_error_envelope() {
+    let app = TestApp::new();
+    let mut request = user_request(false);
+    // No fixture and no default.yaml to fall back to
+    request["model"] = serde_json::json!("mock-missing");
+
+    for stream in [false, true] {
+        request["stream"] = serde_json::json!(stream);
+        let response = app.post_completion(request.clone()).await;
+
+        // Errors before the stream starts are plain JSON, not SSE
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(content_type(&response), "application/json");
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["type"], "api_error");
+        assert_eq!(body["error"]["code"], "configuration_error");
+        assert!(body["error"]["message"].as_str().unwrap().contains("missing"));
+    }
+}
+
+#[tokio::test]
+async fn test_slow_mock_response_exceeds_deadline() {
+    let app = TestApp::new()
+        .fixture("slow", "responses:\n  - text: \"too late\"\n    delay_ms: 5000\n")
+        .configure(|settings| settings.inference.timeout_secs = 1);
+    let mut request = user_request(false);
+    request["model"] = serde_json::json!("mock-slow");
+
+    let response = app.post_completion(request).await;
+
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    let body = json_body(response).await;
+    assert_eq!(body["error"]["type"], "timeout_error");
+    assert_eq!(body["error"]["code"], "deadline_exceeded");
+}
+
+#[tokio::test]
+async fn test_responses_endpoint_reshapes_completion() {
+    let app = TestApp::new();
+
+    let response = app
+        .post_response(serde_json::json!({
+            "model": "mock-test",
+            "instructions": "Be brief.",
+            "input": [{"role": "user", "content": [{"type": "input_text", "text": "Hello"}]}]
+        }))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["object"], "response");
+    assert_eq!(body["status"], "completed");
+    assert_eq!(body["model"], "mock-test");
+    assert_eq!(body["output"][0]["type"], "message");
+    assert_eq!(body["output"][0]["content"][0]["text"], "The quick brown fox");
+    assert_eq!(body["usage"]["input_tokens"], 5);
+    assert_eq!(body["usage"]["output_tokens"], 4);
+    assert!(body.get("choices").is_none());
+}
+
+#[tokio::test]
+async fn test_responses_endpoint_rejects_streaming() {
+    let response = TestApp::new()
+        .post_response(serde_json::json!({"input": "Hello", "stream": true}))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert_eq!(body["error"]["param"], "stream");
+    assert_eq!(body["error"]["code"], "unsupported_parameter");
+}
+
+/// Server with two client keys configured
+fn keyed_app() -> TestApp {
+    TestApp::new().configure(|settings| {
+        settings.server.api_keys = vec!["key-a".to_string(), "key-b".to_string()];
+    })
+}
+
+#[tokio::test]
+async fn test_valid_api_key_is_accepted() {
+    let app = keyed_app();
+
+    for key in ["key-a", "key-b"] {
+        let response = app
+            .post_completion_authorized(user_request(false), &format!("Bearer {key}"))
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_invalid_api_key_is_rejected() {
+    let app = keyed_app();
+
+    for authorization in ["Bearer key-c", "key-a", "Basic a2V5LWE="] {
+        let response = app
+            .post_completion_authorized(user_request(false), authorization)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["type"], "authentication_error");
+        assert_eq!(body["error"]["code"], "invalid_api_key");
+    }
+}
+
+#[tokio::test]
+async fn test_missing_api_key_is_rejected_except_for_probes() {
+    let app = keyed_app();
+
+    let response = app.post_completion(user_request(false)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(content_type(&response), "application/json");
+    let body = json_body(response).await;
+    assert_eq!(body["error"]["message"], "Invalid or missing API key");
+    assert_eq!(app.get("/v1/models").await.status(), StatusCode::UNAUTHORIZED);
+
+    assert_eq!(app.get("/").await.status(), StatusCode::OK);
+    assert_eq!(app.get("/health").await.status(), StatusCode::OK);
+}
+
+/// A streamed completion request sent with `key`
+fn stream_request(key: &str) -> Request<Body> {
+    Request::post("/v1/chat/completions")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {key}"))
+        .body(Body::from(user_request(true).to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_concurrent_streams_are_capped_per_client() {
+    let app = keyed_app().configure(|settings| {
+        settings.server.max_concurrent_streams_per_client = Some(2);
+    });
+    // One router, so every request shares the same stream counts
+    let router = app.router();
+    let open = |key: &'static str| router.clone().oneshot(stream_request(key));
+
+    let first = open("key-a").await.unwrap();
+    let second = open("key-a").await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_eq!(second.status(), StatusCode::OK);
+
+    let rejected = open("key-a").await.unwrap();
+    assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+    let body = json_body(rejected).await;
+    assert_eq!(body["error"]["type"], "rate_limit_error");
+    assert_eq!(body["error"]["code"], "too_many_streams");
+    // Another key has slots of its own
+    assert_eq!(open("key-b").await.unwrap().status(), StatusCode::OK);
+
+    // A client disconnecting frees its slot, and so does a stream running to the end
+    drop(first);
+    let third = open("key-a").await.unwrap();
+    assert_eq!(third.status(), StatusCode::OK);
+    SseReader::new(second).chunks().await;
+    assert_eq!(open("key-a").await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_no_api_keys_leaves_requests_open() {
+    let response = TestApp::new().post_completion(user_request(false)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_models_lists_fixtures() {
+    let app = TestApp::new().fixture("other", REPLY_FIXTURE);
+
+    let response = app.get("/v1/models").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["object"], "list");
+    let ids: Vec<_> = body["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|model| model["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(ids, ["mock-other", "mock-test"]);
+    assert_eq!(body["data"][0]["object"], "model");
+    assert_eq!(body["data"][0]["owned_by"], "local");
+    // Unpaged requests keep the plain list envelope
+    assert!(body.get("has_more").is_none());
+}
+
+#[tokio::test]
+async fn test_models_owned_by_follows_provider_setting() {
+    let app = TestApp::new().configure(|settings| {
+        settings
+            .runtime
+            .owned_by
+            .insert("mock".to_string(), "acme-labs".to_string());
+    });
+
+    let body = json_body(app.get("/v1/models").await).await;
+
+    assert_eq!(body["data"][0]["owned_by"], "acme-labs");
+}
+
+#[tokio::test]
+async fn test_models_pages_with_cursor() {
+    let app = TestApp::new()
+        .fixture("other", REPLY_FIXTURE)
+        .fixture("third", REPLY_FIXTURE);
+
+    let first = json_body(app.get("/v1/models?limit=2").await).await;
+    assert_eq!(first["first_id"], "mock-other");
+    assert_eq!(first["last_id"], "mock-test");
+    assert_eq!(first["has_more"], true);
+
+    let second = json_body(app.get("/v1/models?limit=2&after=mock-test").await).await;
+    assert_eq!(second["data"][0]["id"], "mock-third");
+    assert_eq!(second["has_more"], false);
+
+    let response = app.get("/v1/models?limit=2&after=mock-gone").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(json_body(response).await["error"]["param"], "after");
+}
+
+#[tokio::test]
+async fn test_health_reports_mock_provider() {
+    let app = TestApp::new();
+
+    let response = app.get("/health").await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["status"], "healthy");
+    assert_eq!(body["provider"], "mock");
+}
+
+#[tokio::test]
+async fn test_legacy_function_calling_round_trip() {
+    let app = TestApp::new().fixture(
+        "weather",
+        r#"
+responses:
+  - text: ""
+    finish_reason: tool_calls
+    tool_calls:
+      - id: call_abc
+        type: function
+        function:
+          name: get_weather
+          arguments: '{"city":"Paris"}'
+"#,
+    );
+    let request = serde_json::json!({
+        "model": "mock-weather",
+        "response_mode": "extended",
+        "messages": [
+            {"role": "user", "content": "Weather in Paris?"},
+            {"role": "assistant", "content": null,
+             "function_call": {"name": "get_weather", "arguments": "{\"city\":\"Lyon\"}"}},
+            {"role": "function", "name": "get_weather", "content": "{\"temp\":18}"},
+            {"role": "user", "content": "And Paris?"}
+        ],
+        "functions": [{
+            "name": "get_weather",
+            "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+        }],
+        "function_call": "auto"
+    });
+
+    let response = app.post_completion(request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    let choice = &body["choices"][0];
+    assert_eq!(choice["finish_reason"], "function_call");
+    assert!(choice["message"].get("tool_calls").is_none());
+    assert_eq!(choice["message"]["function_call"]["name"], "get_weather");
+    assert_eq!(
+        choice["message"]["function_call"]["arguments"],
+        "{\"city\":\"Paris\"}"
+    );
+    assert_eq!(body["warnings"][0]["code"], "deprecated_functions");
+
+    // The same fixture answers a tools request in the tools shape
+    let mut modern = user_request(false);
+    modern["model"] = serde_json::json!("mock-weather");
+    let body = json_body(app.post_completion(modern).await).await;
+    assert_eq!(body["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(body["choices"][0]["message"]["tool_calls"][0]["id"], "call_abc");
+    assert!(body.get("warnings").is_none());
+}
+
+#[tokio::test]
+async fn test_embeddings_endpoint_returns_one_vector_per_input() {
+    let app = TestApp::new();
+
+    let response = app
+        .post_embeddings(serde_json::json!({"model": "mock-test", "input": ["Hello", "World"]}))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = json_body(response).await;
+    assert_eq!(body["object"], "list");
+    assert_eq!(body["model"], "mock-test");
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[1]["object"], "embedding");
+    assert_eq!(data[1]["index"], 1);
+    assert!(!data[0]["embedding"].as_array().unwrap().is_empty());
+    assert!(body["usage"]["prompt_tokens"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_embeddings_endpoint_rejects_empty_input() {
+    let app = TestApp::new();
+
+    let response = app
+        .post_embeddings(serde_json::json!({"model": "mock-test", "input": []}))
+        .await;
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = json_body(response).await;
+    assert_eq!(body["error"]["param"], "input");
+}
+
+#[tokio::test]
+async fn test_oversized_request_body_is_rejected_with_413() {
+    let app = TestApp::new().configure(|settings| settings.server.max_request_bytes = 1024);
+    let mut completion = user_request(false);
+    completion["messages"][0]["content"] = serde_json::json!("x".repeat(2048));
+    let embeddings = serde_json::json!({"model": "mock-test", "input": ["x".repeat(2048)]});
+
+    // Without a `Content-Length`, the limit trips while the body is read
+    let responses = [
+        app.post_completion(completion.clone()).await,
+        app.post_embeddings(embeddings).await,
+    ];
+    for response in responses {
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(content_type(&response), "application/json");
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "request_too_large");
+        assert!(body["error"]["message"].as_str().unwrap().contains("1024-byte"));
+    }
+
+    // With one, the body is refused before it is read
+    let body = completion.to_string();
+    let request = Request::post("/v1/chat/completions")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.router().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(json_body(response).await["error"]["code"], "request_too_large");
+
+    // Bodies under the limit, and routes outside the API, are unaffected
+    assert_eq!(app.post_completion(user_request(false)).await.status(), StatusCode::OK);
+    assert_eq!(app.get("/health").await.status(), StatusCode::OK);
+}
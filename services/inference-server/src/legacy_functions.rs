@@ -0,0 +1,180 @@
+//! Compatibility shim for the deprecated `functions`/`function_call` fields.
+//!
+//! Legacy requests are rewritten into `tools`/`tool_choice` before validation,
+//! and their responses are turned back into the legacy shape: the first tool
+//! call becomes a top-level `function_call` with `finish_reason: "function_call"`,
+//! the way OpenAI answers legacy requests.
+
+use futures_util::StreamExt;
+
+use crate::models::{
+    CompletionRequest, CompletionResponse, FinishReason, FunctionCallOption, Role, Tool,
+    ToolCall, ToolChoice, ToolFunction,
+};
+use crate::providers::ProviderStream;
+
+/// Whether the request uses any legacy function-calling field, in its
+/// parameters or in the conversation history
+pub fn is_legacy(request: &CompletionRequest) -> bool {
+    request.functions.is_some()
+        || request.function_call.is_some()
+        || request
+            .messages
+            .iter()
+            .any(|m| m.function_call.is_some() || m.role == Role::Function)
+}
+
+/// Rewrite legacy fields into their tool equivalents. Assistant `function_call`
+/// messages get a generated call id, which the `function` result message that
+/// follows answers.
+pub fn translate_request(request: &mut CompletionRequest) {
+    if let Some(functions) = request.functions.take() {
+        let tools = request.tools.get_or_insert_with(Vec::new);
+        tools.extend(functions.into_iter().map(|function| Tool {
+            tool_type: "function".to_string(),
+            function,
+        }));
+    }
+
+    if let Some(function_call) = request.function_call.take()
+        && request.tool_choice.is_none()
+    {
+        request.tool_choice = Some(match function_call {
+            FunctionCallOption::String(mode) => ToolChoice::String(mode),
+            FunctionCallOption::Object { name } => ToolChoice::Object {
+                choice_type: "function".to_string(),
+                function: ToolFunction { name },
+            },
+        });
+    }
+
+    let mut last_call_id = None;
+    for (index, message) in request.messages.iter_mut().enumerate() {
+        if let Some(function) = message.function_call.take() {
+            let id = format!("call_{index}");
+            message.tool_calls = Some(vec![ToolCall {
+                id: id.clone(),
+                tool_type: "function".to_string(),
+                function,
+            }]);
+            last_call_id = Some(id);
+        } else if message.role == Role::Function {
+            message.role = Role::Tool;
+            message.tool_call_id = last_call_id.take();
+            message.name = None;
+        }
+    }
+}
+
+/// Move each choice's first tool call into `function_call`
+pub fn convert_response(response: &mut CompletionResponse) {
+    for choice in &mut response.choices {
+        let Some(ref mut message) = choice.message else {
+            continue;
+        };
+        let Some(calls) = message.tool_calls.take() else {
+            continue;
+        };
+        message.function_call = calls.into_iter().next().map(|call| call.function);
+        if choice.finish_reason == Some(FinishReason::ToolCalls) {
+            choice.finish_reason = Some(FinishReason::FunctionCall);
+        }
+    }
+}
+
+/// Streaming counterpart of [`convert_response`]. Tool call deltas carry no
+/// index here, so every delta is taken as part of the first call.
+pub fn convert_stream(stream: ProviderStream) -> ProviderStream {
+    Box::pin(stream.map(|item| {
+        let mut chunk = item?;
+        for choice in &mut chunk.choices {
+            if let Some(calls) = choice.delta.tool_calls.take() {
+                choice.delta.function_call =
+                    calls.into_iter().next().and_then(|call| call.function);
+            }
+            if choice.finish_reason == Some(FinishReason::ToolCalls) {
+                choice.finish_reason = Some(FinishReason::FunctionCall);
+            }
+        }
+        Ok(chunk)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FunctionCallDelta, StreamChunk, ToolCallDelta};
+    use crate::providers::create_final_chunk;
+
+    fn legacy_request() -> CompletionRequest {
+        serde_json::from_value(serde_json::json!({
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "content": null,
+                 "function_call": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}},
+                {"role": "function", "name": "get_weather", "content": "{\"temp\":18}"}
+            ],
+            "functions": [{"name": "get_weather", "parameters": {"type": "object"}}],
+            "function_call": {"name": "get_weather"}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_translate_request_to_tools() {
+        let mut request = legacy_request();
+        assert!(is_legacy(&request));
+
+        translate_request(&mut request);
+
+        assert!(request.functions.is_none() && request.function_call.is_none());
+        let tools = request.tools.as_ref().unwrap();
+        assert_eq!(tools[0].tool_type, "function");
+        assert_eq!(tools[0].function.name, "get_weather");
+        assert!(matches!(
+            request.tool_choice,
+            Some(ToolChoice::Object { ref function, .. }) if function.name == "get_weather"
+        ));
+
+        let call = &request.messages[1].tool_calls.as_ref().unwrap()[0];
+        assert_eq!(call.id, "call_1");
+        assert_eq!(call.function.arguments, "{\"city\":\"Paris\"}");
+        assert!(request.messages[1].function_call.is_none());
+        let result = &request.messages[2];
+        assert_eq!(result.role, Role::Tool);
+        assert_eq!(result.tool_call_id.as_deref(), Some("call_1"));
+        assert!(result.name.is_none());
+        assert!(!is_legacy(&request));
+    }
+
+    #[tokio::test]
+    async fn test_stream_tool_call_deltas_become_function_call() {
+        let mut call = create_final_chunk("id", "m", FinishReason::ToolCalls, None);
+        call.choices[0].finish_reason = None;
+        call.choices[0].delta.tool_calls = Some(vec![ToolCallDelta {
+            id: Some("call_0".to_string()),
+            tool_type: Some("function".to_string()),
+            function: Some(FunctionCallDelta {
+                name: Some("get_weather".to_string()),
+                arguments: Some("{}".to_string()),
+            }),
+        }]);
+        let done = create_final_chunk("id", "m", FinishReason::ToolCalls, None);
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter([Ok(call), Ok(done)]));
+
+        let chunks: Vec<StreamChunk> = convert_stream(stream)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        let delta = &chunks[0].choices[0].delta;
+        assert!(delta.tool_calls.is_none());
+        let function = delta.function_call.as_ref().unwrap();
+        assert_eq!(function.name.as_deref(), Some("get_weather"));
+        assert_eq!(function.arguments.as_deref(), Some("{}"));
+        assert_eq!(
+            chunks[1].choices[0].finish_reason,
+            Some(FinishReason::FunctionCall)
+        );
+    }
+}
@@ -1,69 +1,156 @@
+mod admission;
 mod config;
+mod content_hash;
+mod deprecations;
 mod error;
+#[cfg(test)]
+mod golden;
+mod history;
+mod i18n;
+#[cfg(test)]
+mod integration;
+mod legacy_functions;
+mod metrics;
+mod model_list;
 mod models;
+mod pipeline;
 mod providers; // Must be before config since config uses it
+mod responses_api;
+mod runtime;
+mod shadow;
+mod startup;
 mod telemetry;
 mod validations;
 
 use axum::{
     Json, Router,
-    extract::State,
-    response::{
-        IntoResponse, Response,
-        sse::{Event, KeepAlive, Sse},
-    },
-    routing::{get, post},
+    body::Body,
+    extract::{ConnectInfo, DefaultBodyLimit, Extension, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{MethodRouter, get, post},
 };
-use futures_util::{Stream, StreamExt};
-use serde::Serialize;
-use std::convert::Infallible;
-use std::pin::Pin;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::TcpListener;
 use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::timeout::TimeoutLayer;
-use tracing::{debug, info, instrument};
+use tracing::{Instrument, info, info_span, instrument, warn};
+use tracing_subscriber::filter::LevelFilter;
 
-use providers::InferenceProvider;
-use validations::{
-    determine_model, validate_completion_request, validate_model_allowed,
-    validate_provider_capabilities,
-};
+use pipeline::RequestContext;
+use providers::circuit_breaker::CircuitHealth;
+use providers::{EndpointHealth, ExtensionSpec, InferenceProvider};
+use providers::mock::{MOCK_VARIANT_EXTENSION, MockControl, ScenarioPosition};
 
+use admission::AdmissionControl;
 use config::Settings;
 use error::ApiError;
-use models::{CompletionRequest, CompletionResponse};
+use history::{
+    ErrorCategory, HistoryQuery, KeyLabel, KeyUsage, PromptUsage, RequestHistory,
+    RequestOutcome, RequestSummary,
+};
+use i18n::{LocalizableError, MessageCatalog};
+use metrics::{Metrics, ValidationRejection};
+use models::{CompletionRequest, EmbeddingRequest, EmbeddingResponse};
+use runtime::RuntimeOptions;
+use shadow::Shadow;
+use startup::StartupReport;
 
 // Hold the http client and provider settings
 #[derive(Clone)]
 struct AppState {
     provider: Arc<dyn InferenceProvider>,
     settings: Arc<Settings>,
+    history: Arc<RequestHistory>,
+    usage: Arc<PromptUsage>,
+    catalog: Arc<MessageCatalog>,
+    startup_report: Arc<StartupReport>,
+    metrics: Arc<Metrics>,
+    admission: Arc<AdmissionControl>,
+    runtime: Arc<RuntimeOptions>,
+    /// Provider mirroring a sample of completions, when `shadow_provider` is set
+    shadow: Option<Arc<Shadow>>,
+    started: Instant,
 }
 
-// Type alias for complex SSE stream type
-type SseStream = Sse<Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>>;
+impl AppState {
+    fn new(provider: Arc<dyn InferenceProvider>, settings: Arc<Settings>) -> Self {
+        let history = Arc::new(RequestHistory::new(settings.admin.recent_requests));
+        let admission = Arc::new(AdmissionControl::new(&settings));
+        let runtime = Arc::new(RuntimeOptions::new(&settings));
+        let startup_report = Arc::new(StartupReport::new(
+            &settings,
+            provider.as_ref(),
+            startup::ProbeOutcome::Skipped,
+        ));
+        Self {
+            provider,
+            settings,
+            history,
+            usage: Arc::new(PromptUsage::default()),
+            catalog: Arc::new(MessageCatalog::default()),
+            startup_report,
+            metrics: Arc::new(Metrics::default()),
+            admission,
+            runtime,
+            shadow: None,
+            started: Instant::now(),
+        }
+    }
+
+    fn with_shadow(mut self, shadow: Shadow) -> Self {
+        self.shadow = Some(Arc::new(shadow));
+        self
+    }
 
-// Enum that can return either JSON response or SSE stream
-enum CompletionOrStream {
-    Json(Json<CompletionResponse>),
-    Stream(SseStream),
-}
+    fn with_startup_report(mut self, report: StartupReport) -> Self {
+        self.startup_report = Arc::new(report);
+        self
+    }
+
+    fn with_catalog(mut self, catalog: MessageCatalog) -> Self {
+        self.catalog = Arc::new(catalog);
+        self
+    }
 
-impl IntoResponse for CompletionOrStream {
-    fn into_response(self) -> Response {
-        match self {
-            CompletionOrStream::Json(json) => json.into_response(),
-            CompletionOrStream::Stream(sse) => sse.into_response(),
+    fn request_context(&self, request: CompletionRequest) -> RequestContext {
+        let ctx = RequestContext::new(request, self.provider.clone(), self.settings.clone())
+            .with_metrics(self.metrics.clone())
+            .with_admission(self.admission.clone())
+            .with_runtime(self.runtime.clone());
+        match self.shadow {
+            Some(ref shadow) => ctx.with_shadow(shadow.clone()),
+            None => ctx,
         }
     }
 }
 
+/// Header trusted callers can set to raise the log level for their request
+const LOG_LEVEL_HEADER: &str = "x-log-level";
+
+/// Extra time the router-wide timeout allows beyond the completion deadline
+const DEADLINE_GRACE: Duration = Duration::from_secs(1);
+
+/// Header selecting a mock response variant (see `providers::mock`)
+const MOCK_VARIANT_HEADER: &str = "x-mock-variant";
+
+/// Header choosing the response mode for clients that can't set `response_mode` in the body
+const RESPONSE_MODE_HEADER: &str = "x-response-mode";
+
 // Root response to health check
 #[derive(Serialize)]
 struct RootResponse {
     message: String,
+    service: &'static str,
+    version: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    provider: Option<String>,
 }
 
 #[tokio::main]
@@ -74,216 +161,906 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let settings = Arc::new(settings);
     let provider = create_provider(&settings).expect("Failed to create inference provider");
-    let app_state = AppState {
-        provider,
-        settings: settings.clone(),
+    let shadow = create_shadow(&settings).expect("Failed to create shadow provider");
+    let catalog = match settings.server.locales_dir {
+        Some(ref dir) => MessageCatalog::load_dir(dir).expect("Failed to load error message locales"),
+        None => MessageCatalog::default(),
     };
 
-    let app: Router = Router::new()
-        .route("/", get(root))
-        .route("/v1/chat/completions", post(generate_completion))
-        .route("/v1/models", get(list_models))
-        .route("/health", get(health_check))
-        .with_state(app_state)
-        .layer(RequestBodyLimitLayer::new(10 * 1024 * 1024)) // 10MB max body
-        .layer(TimeoutLayer::new(Duration::from_secs(settings.inference.timeout_secs)));
+    let probe = match startup::probe_provider(&settings.startup, provider.as_ref()).await {
+        Ok(probe) => probe,
+        Err(e) => {
+            tracing::error!(error = %e, "Aborting startup");
+            telemetry::shutdown_logging(logger_provider);
+            return Err(e.into());
+        }
+    };
+    let report = StartupReport::new(&settings, provider.as_ref(), probe);
+    info!(
+        target: "startup",
+        report = %serde_json::to_string(&report).unwrap_or_default(),
+        "Startup summary"
+    );
+
+    let mut app_state = AppState::new(provider, settings.clone())
+        .with_catalog(catalog)
+        .with_startup_report(report);
+    if let Some(shadow) = shadow {
+        app_state = app_state.with_shadow(shadow);
+    }
+
+    let health_poll_secs = settings.metrics.health_poll_interval_secs;
+    if health_poll_secs > 0 {
+        metrics::spawn_health_poller(
+            app_state.metrics.clone(),
+            app_state.provider.clone(),
+            Duration::from_secs(health_poll_secs),
+        );
+    }
+
+    let mut app = build_router(app_state);
+
+    // Only trusted deployments should let callers raise their own log level
+    if settings.logging.allow_level_override {
+        app = app.layer(middleware::from_fn(log_level_override));
+    }
 
     let addr = format!("{}:{}", settings.server.host, settings.server.port);
     let listener = TcpListener::bind(&addr).await?;
 
     info!("Server listening on {}", addr);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Once signalled, stop accepting connections and give the ones still
+    // open, streams included, the grace period to finish
+    let (signalled_tx, signalled_rx) = tokio::sync::oneshot::channel();
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        shutdown_signal().await;
+        let _ = signalled_tx.send(());
+    });
+    let grace = Duration::from_secs(settings.server.shutdown_grace_secs);
+    let grace_elapsed = async move {
+        match signalled_rx.await {
+            Ok(()) => tokio::time::sleep(grace).await,
+            Err(_) => std::future::pending().await,
+        }
+    };
+    let served = tokio::select! {
+        served = server => served,
+        () = grace_elapsed => {
+            warn!(
+                grace_secs = grace.as_secs(),
+                "Grace period elapsed with requests still in flight, shutting down"
+            );
+            Ok(())
+        }
+    };
 
     telemetry::shutdown_logging(logger_provider);
+    served?;
     Ok(())
 }
 
+/// Assemble the API routes. `get` routes also answer HEAD with the same headers and no body.
+/// The fallbacks are registered last so every route above inherits them.
+fn build_router(app_state: AppState) -> Router {
+    // Backstop only: completions enforce the same budget themselves and report
+    // `deadline_exceeded`, so give them a moment to do that first
+    let mut timeout = Duration::from_secs(app_state.settings.inference.timeout_secs);
+    if let Some(ref adaptive) = app_state.settings.limits.concurrency.adaptive_timeout {
+        timeout = timeout.mul_f64(adaptive.max_factor.max(1.0));
+    }
+    let timeout = timeout + DEADLINE_GRACE;
+    let max_request_bytes = app_state.settings.server.max_request_bytes;
+
+    let mut routes = Router::new()
+        .route("/", get(root))
+        .route(
+            "/v1/chat/completions",
+            limit_request_body(
+                post(generate_completion).route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    admit_completion,
+                )),
+                max_request_bytes,
+            ),
+        )
+        .route(
+            "/v1/chat/completions/dry-run",
+            limit_request_body(post(dry_run_completion), max_request_bytes),
+        )
+        .route(
+            "/v1/responses",
+            limit_request_body(
+                post(create_response).route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    admit_completion,
+                )),
+                max_request_bytes,
+            ),
+        )
+        .route(
+            "/v1/embeddings",
+            limit_request_body(
+                post(create_embeddings).route_layer(middleware::from_fn_with_state(
+                    app_state.clone(),
+                    admit_completion,
+                )),
+                max_request_bytes,
+            ),
+        )
+        .route("/v1/models", get(list_models))
+        .route("/v1/extensions", get(list_extensions))
+        .route("/health", get(health_check))
+        .route("/metrics", get(render_metrics));
+
+    if app_state.settings.admin.api_key.is_some() {
+        routes = routes.nest("/admin", admin_routes(&app_state));
+    }
+
+    let routes = routes
+        .fallback(not_found)
+        .method_not_allowed_fallback(method_not_allowed)
+        .with_state(app_state.clone());
+
+    // Router layers wrap individual handlers, which run before axum fills in `Allow`,
+    // so the 405 renderer has to wrap the routed service as a whole
+    Router::new()
+        .fallback_service(routes)
+        .layer(middleware::map_response(describe_method_not_allowed))
+        .layer(middleware::from_fn_with_state(app_state.clone(), require_api_key))
+        .layer(middleware::from_fn_with_state(app_state.clone(), localize_errors))
+        .layer(middleware::from_fn_with_state(app_state, access_log))
+        // Backstop for every other route; the API routes carry their own limit
+        .layer(RequestBodyLimitLayer::new(max_request_bytes.max(10 * 1024 * 1024)))
+        .layer(TimeoutLayer::new(timeout))
+}
+
+/// Hold a route's request bodies to `server.max_request_bytes`. Oversized bodies are
+/// refused before the JSON is parsed: by their `Content-Length` up front, or once
+/// reading passes the limit.
+fn limit_request_body(route: MethodRouter<AppState>, max_bytes: usize) -> MethodRouter<AppState> {
+    route
+        // Replaces axum's own 2 MB cap on the `Json` extractor
+        .route_layer(DefaultBodyLimit::disable())
+        .route_layer(RequestBodyLimitLayer::new(max_bytes))
+        .route_layer(middleware::map_response_with_state(
+            max_bytes,
+            describe_request_too_large,
+        ))
+}
+
+/// Render the plain-text 413s of the body limit as OpenAI errors. JSON 413s are
+/// already rendered errors and pass through.
+async fn describe_request_too_large(
+    State(max_bytes): State<usize>,
+    response: Response,
+) -> Response {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if response.status() != StatusCode::PAYLOAD_TOO_LARGE || is_json {
+        return response;
+    }
+    ApiError::RequestTooLarge { max_bytes }.into_response()
+}
+
+/// Operator endpoints, all behind the admin bearer token.
+/// The `/mock` routes only exist when the provider is the mock.
+fn admin_routes(app_state: &AppState) -> Router<AppState> {
+    let mut routes = Router::new()
+        .route("/requests/recent", get(recent_requests))
+        .route("/startup-report", get(startup_report))
+        .route("/diagnostics", get(diagnostics))
+        .route("/usage", get(prompt_usage));
+    if let Some(control) = app_state.provider.mock_control() {
+        routes = routes.nest("/mock", mock_admin_routes(control));
+    }
+    routes
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_admin_key,
+        ))
+}
+
+/// Inspect and reset the mock provider's scenario positions, so tests can
+/// start from a known point without restarting the server
+fn mock_admin_routes(control: MockControl) -> Router<AppState> {
+    let state_control = control.clone();
+    Router::new()
+        .route(
+            "/reset",
+            post(move || async move {
+                control.reset();
+                StatusCode::NO_CONTENT
+            }),
+        )
+        .route(
+            "/state",
+            get(move || async move {
+                Json(MockStateResponse {
+                    scenarios: state_control.state(),
+                })
+            }),
+        )
+}
+
+/// Check the client's bearer key against `server.api_keys`, when any are set.
+/// `/` and `/health` stay open for probes, and `/admin` has its own key.
+/// The matched key's label rides on the request and the response for the access log.
+async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let keys = &state.settings.server.api_keys;
+    let path = request.uri().path();
+    if keys.is_empty() || matches!(path, "/" | "/health") || path.starts_with("/admin/") {
+        return Ok(next.run(request).await);
+    }
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let label = match provided {
+        Some(provided) if keys.iter().any(|key| key == provided) => KeyLabel::for_key(provided),
+        _ => return Err(ApiError::Unauthorized),
+    };
+    request.extensions_mut().insert(label.clone());
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(label);
+    Ok(response)
+}
+
+async fn require_admin_key(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match (provided, state.settings.admin.api_key.as_deref()) {
+        (Some(provided), Some(expected)) if provided == expected => Ok(next.run(request).await),
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Hold a completion slot for the whole response, streams included, or shed the
+/// request with a `Retry-After` hint when the queue is too deep
+async fn admit_completion(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let path = request.uri().path().to_string();
+    let permit = match state.admission.admit().await {
+        Ok(permit) => permit,
+        Err(e) => {
+            state.metrics.requests_shed_total.inc(&[("path", &path)]);
+            return Err(e);
+        }
+    };
+
+    let response = next.run(request).await;
+    let streaming = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"));
+    match permit {
+        // A stream outlives the handler, so the slot is released when the body ends
+        Some(permit) if streaming => Ok(response.map(|body| {
+            Body::from_stream(body.into_data_stream().map(move |chunk| {
+                let _slot = &permit;
+                chunk
+            }))
+        })),
+        _ => Ok(response),
+    }
+}
+
+/// Log one summary line per `/v1` request and keep it for `/admin/requests/recent`.
+/// Streamed requests are recorded when the response starts, so they carry no usage.
+async fn access_log(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+    if !path.starts_with("/v1/") {
+        return response;
+    }
+    if let Some(rejection) = response.extensions().get::<ValidationRejection>() {
+        state
+            .metrics
+            .validation_rejections_total
+            .inc(&[("rule", rejection.0)]);
+    }
+
+    let outcome = response
+        .extensions()
+        .get::<RequestOutcome>()
+        .cloned()
+        .unwrap_or_default();
+    let (conversation_hash, message_hashes) = match outcome.content_hashes {
+        Some(hashes) => (Some(hashes.conversation), Some(hashes.messages)),
+        None => (None, None),
+    };
+    let summary = RequestSummary {
+        id: outcome
+            .id
+            .unwrap_or_else(|| uuid::Uuid::now_v7().to_string()),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+        method,
+        path,
+        key_label: response
+            .extensions()
+            .get::<KeyLabel>()
+            .map(|label| label.0.clone()),
+        model: outcome.model,
+        provider: state.provider.name().to_string(),
+        status: response.status().as_u16(),
+        latency_ms: started.elapsed().as_millis() as u64,
+        stream: outcome.stream,
+        prompt_tokens: outcome.prompt_tokens,
+        completion_tokens: outcome.completion_tokens,
+        total_tokens: outcome.total_tokens,
+        finish_reason: outcome.finish_reason,
+        error_category: response
+            .extensions()
+            .get::<ErrorCategory>()
+            .map(|category| category.0.clone()),
+        conversation_hash,
+        message_hashes,
+        warnings: outcome.warnings.into_iter().map(|w| w.code).collect(),
+    };
+
+    info!(
+        target: "access_log",
+        id = %summary.id,
+        method = %summary.method,
+        path = %summary.path,
+        status = summary.status,
+        latency_ms = summary.latency_ms,
+        model = ?summary.model,
+        provider = %summary.provider,
+        stream = summary.stream,
+        total_tokens = ?summary.total_tokens,
+        finish_reason = ?summary.finish_reason,
+        error_category = ?summary.error_category,
+        conversation_hash = ?summary.conversation_hash,
+        warnings = ?summary.warnings,
+        "Request completed"
+    );
+    if let Some(ref hash) = summary.conversation_hash {
+        state.usage.record(summary.key_label.as_deref(), hash);
+    }
+    state.history.record(summary);
+
+    response
+}
+
+/// Translate error messages for the caller's `Accept-Language`.
+/// Only `message` changes; `type`, `code`, and `param` stay stable for clients.
+async fn localize_errors(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| state.catalog.negotiate(value))
+        .map(str::to_string);
+
+    let response = next.run(request).await;
+    let Some(locale) = locale else {
+        return response;
+    };
+    let Some(localizable) = response.extensions().get::<LocalizableError>().cloned() else {
+        return response;
+    };
+    let Some(message) = state.catalog.translate(&locale, &localizable.message) else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    let body = Json(models::OpenAIErrorResponse {
+        error: models::OpenAIError {
+            message,
+            ..localizable.error
+        },
+    })
+    .into_response()
+    .into_body();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    if let Ok(value) = header::HeaderValue::from_str(&locale) {
+        parts.headers.insert(header::CONTENT_LANGUAGE, value);
+    }
+    Response::from_parts(parts, body)
+}
+
+async fn not_found(method: Method, uri: Uri) -> ApiError {
+    ApiError::NotFound {
+        method,
+        path: uri.path().to_string(),
+    }
+}
+
+/// Marker left on 405 responses so the body can be rendered once axum has set `Allow`
+#[derive(Clone)]
+struct MethodNotAllowed {
+    method: Method,
+    path: String,
+}
+
+async fn method_not_allowed(method: Method, uri: Uri) -> Response {
+    // OPTIONS on a known route is a capability probe, not an error; axum adds `Allow`
+    if method == Method::OPTIONS {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+
+    let mut response = StatusCode::METHOD_NOT_ALLOWED.into_response();
+    response.extensions_mut().insert(MethodNotAllowed {
+        method,
+        path: uri.path().to_string(),
+    });
+    response
+}
+
+/// Render 405s from `method_not_allowed` as OpenAI errors naming the allowed methods
+async fn describe_method_not_allowed(response: Response) -> Response {
+    let Some(MethodNotAllowed { method, path }) =
+        response.extensions().get::<MethodNotAllowed>().cloned()
+    else {
+        return response;
+    };
+
+    let allow = response.headers().get(header::ALLOW).cloned();
+    let allowed = allow
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(',').map(|m| m.trim().to_string()).collect())
+        .unwrap_or_default();
+
+    let mut rendered = ApiError::MethodNotAllowed {
+        method,
+        path,
+        allowed,
+    }
+    .into_response();
+    if let Some(allow) = allow {
+        rendered.headers_mut().insert(header::ALLOW, allow);
+    }
+    rendered
+}
+
+/// Run the request inside a span carrying the `X-Log-Level` override, if present
+async fn log_level_override(request: Request, next: Next) -> Response {
+    let level = request
+        .headers()
+        .get(LOG_LEVEL_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<LevelFilter>().ok());
+
+    match level {
+        Some(level) => {
+            let span = info_span!("request", log_level_override = %level);
+            next.run(request).instrument(span).await
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// Resolve on SIGINT (Ctrl-C) or, on Unix, SIGTERM, the stop signal container runtimes send
 async fn shutdown_signal() {
-    tokio::signal::ctrl_c()
-        .await
-        .expect("Failed to install CTRL+C signal handler");
-    info!("Shutdown signal received, starting graceful shutdown");
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install CTRL+C signal handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM signal handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = interrupt => info!("SIGINT received, starting graceful shutdown"),
+        () = terminate => info!("SIGTERM received, starting graceful shutdown"),
+    }
 }
 
-// Factory function to create the right provider
+// Factory function to create the right provider, behind a circuit breaker when
+// `http.circuit_breaker` is set and the provider calls an upstream
 fn create_provider(
     settings: &Arc<Settings>,
 ) -> Result<Arc<dyn InferenceProvider>, Box<dyn std::error::Error>> {
     use config::InferenceProvider as ConfigProvider;
+    use providers::circuit_breaker::CircuitBreakerProvider;
+
+    let provider = build_provider(settings)?;
+    let breaker = settings
+        .inference
+        .http
+        .as_ref()
+        .and_then(|http| http.circuit_breaker.clone());
+    match breaker {
+        Some(config)
+            if !matches!(
+                settings.inference.provider,
+                ConfigProvider::Mock { .. } | ConfigProvider::Router { .. }
+            ) =>
+        {
+            Ok(Arc::new(CircuitBreakerProvider::new(provider, config)))
+        }
+        _ => Ok(provider),
+    }
+}
+
+fn build_provider(
+    settings: &Arc<Settings>,
+) -> Result<Arc<dyn InferenceProvider>, Box<dyn std::error::Error>> {
+    use config::InferenceProvider as ConfigProvider;
+    use providers::anthropic::AnthropicProvider;
     use providers::lmstudio::LMStudioProvider;
     use providers::mock::MockProvider;
+    use providers::ollama::OllamaProvider;
     use providers::openai::OpenAIProvider;
+    use providers::openai_compatible::GenericOpenAIProvider;
+    use providers::routing::RoutingProvider;
+    use providers::triton::TritonProvider;
 
     match &settings.inference.provider {
         ConfigProvider::LMStudio => Ok(Arc::new(
             LMStudioProvider::new(settings.clone())
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
         )),
+        ConfigProvider::Anthropic { .. } => Ok(Arc::new(
+            AnthropicProvider::new(settings.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
+        )),
         ConfigProvider::Mock { .. } => Ok(Arc::new(
             MockProvider::new(settings.clone())
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
         )),
+        ConfigProvider::Ollama => Ok(Arc::new(
+            OllamaProvider::new(settings.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
+        )),
         ConfigProvider::OpenAI { .. } => Ok(Arc::new(
             OpenAIProvider::new(settings.clone())
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
         )),
-        ConfigProvider::Triton { .. } => Err("Triton provider not yet implemented".into()),
+        ConfigProvider::OpenAICompatible { .. } | ConfigProvider::Vllm { .. } => Ok(Arc::new(
+            GenericOpenAIProvider::new(settings.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
+        )),
+        ConfigProvider::Triton { .. } => Ok(Arc::new(
+            TritonProvider::new(settings.clone())
+                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
+        )),
+        ConfigProvider::Router { backends, routes } => {
+            let fallback_models = backends
+                .iter()
+                .filter_map(|(name, backend)| Some((name.clone(), backend.default_model.clone()?)))
+                .collect();
+            let backends = backends
+                .iter()
+                .map(|(name, backend)| {
+                    let provider = create_provider(&Arc::new(settings.for_backend(backend)))?;
+                    Ok((name.clone(), provider))
+                })
+                .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+            Ok(Arc::new(
+                RoutingProvider::new(backends, routes.clone(), &fallback_models)
+                    .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?,
+            ))
+        }
     }
 }
-// Update the generate_completion function in main.rs:
 
-#[instrument(skip(state), fields(
+/// Shadow for `inference.shadow_provider`, a second instance of that router backend
+fn create_shadow(settings: &Arc<Settings>) -> Result<Option<Shadow>, Box<dyn std::error::Error>> {
+    let config::InferenceProvider::Router { ref backends, .. } = settings.inference.provider else {
+        return Ok(None);
+    };
+    let Some(backend) = settings
+        .inference
+        .shadow_provider
+        .as_ref()
+        .and_then(|name| backends.get(name))
+    else {
+        return Ok(None);
+    };
+    let provider = create_provider(&Arc::new(settings.for_backend(backend)))?;
+    Ok(Some(Shadow::new(
+        provider,
+        settings.inference.shadow_sample_rate,
+        settings.logging.max_logged_body_chars,
+    )))
+}
+
+/// Handle `POST /v1/chat/completions` by running the completion pipeline
+#[instrument(skip(state, headers), fields(
     message_count = request.messages.len(),
     model = request.model.as_deref().unwrap_or("default"),
     stream = request.stream.unwrap_or(false),
     user = request.user.as_deref(),
+    upstream_attempts = tracing::field::Empty,
 ))]
 async fn generate_completion(
     State(state): State<AppState>,
-    Json(request): Json<CompletionRequest>,
-) -> Result<CompletionOrStream, ApiError> {
-    // Validate the incoming request structure
-    validate_completion_request(&request)?;
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    Json(mut request): Json<CompletionRequest>,
+) -> Response {
+    apply_mock_variant_header(&mut request, &headers, state.provider.as_ref());
+    apply_response_mode_header(&mut request, &headers);
+    let peer = connect_info.map(|Extension(ConnectInfo(addr))| addr);
+    let mut ctx = state.request_context(request);
+    ctx.client = Some(client_id(&state.settings, &headers, peer));
+    ctx.buffer_stream = state.runtime.negotiate_accept && accepts_only_json(&headers);
+    pipeline::run(ctx).await
+}
+
+/// Who a request counts against for per-client limits: its API key's label when
+/// `server.api_keys` is set, otherwise the peer's IP address. Requests without
+/// either share one `unknown` client.
+fn client_id(settings: &Settings, headers: &HeaderMap, peer: Option<SocketAddr>) -> String {
+    let key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|key| settings.server.api_keys.iter().any(|known| known == key));
+    match (key, peer) {
+        (Some(key), _) => format!("key:{}", KeyLabel::for_key(key).0),
+        (None, Some(peer)) => format!("ip:{}", peer.ip()),
+        (None, None) => "unknown".to_string(),
+    }
+}
 
-    // Determine which model to use (applies defaults if needed)
-    let model = determine_model(
+/// Handle `POST /v1/responses` by running the completion pipeline on the
+/// translated request and answering in the Responses API shape
+#[instrument(skip_all, fields(upstream_attempts = tracing::field::Empty))]
+async fn create_response(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<responses_api::ResponsesRequest>,
+) -> Response {
+    let mut request = match responses_api::to_completion_request(request) {
+        Ok(request) => request,
+        Err(e) => return ApiError::Validation(e).into_response(),
+    };
+    apply_mock_variant_header(&mut request, &headers, state.provider.as_ref());
+    let mut ctx = state.request_context(request);
+    ctx.responses_api = true;
+    // Providers that insist on streaming are read to the end
+    ctx.buffer_stream = true;
+    pipeline::run(ctx).await
+}
+
+/// Embed `input` with the model's backend. Providers without embeddings
+/// answer with a configuration error.
+async fn create_embeddings(
+    State(state): State<AppState>,
+    Json(request): Json<EmbeddingRequest>,
+) -> Result<Json<EmbeddingResponse>, ApiError> {
+    let inference = &state.settings.inference;
+    validations::validate_embedding_request(&request)?;
+    let model = validations::determine_model(
         request.model.as_deref(),
-        &state.settings.inference.default_model,
-        state.settings.inference.allowed_models.as_ref(),
+        inference.default_model_for(state.provider.name()),
+        inference.allowed_models.as_ref(),
     )?;
+    validations::validate_model_allowed(model, inference.allowed_models.as_ref())?;
 
-    // Validate the model is allowed (if restrictions are configured)
-    validate_model_allowed(model, state.settings.inference.allowed_models.as_ref())?;
+    let provider = match state.provider.route(model).await? {
+        Some(backend) => backend,
+        None => state.provider.clone(),
+    };
+    Ok(Json(provider.embed(&request, model).await?))
+}
 
-    // Validate provider capabilities
-    validate_provider_capabilities(
-        &request,
-        state.provider.supports_streaming(),
-        false, // tools not yet supported
-    )?;
+/// Whether `Accept` allows `application/json` but not an event stream.
+/// Parameters such as `q` are ignored; a missing header accepts anything.
+fn accepts_only_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return false;
+    };
+    let mut json = false;
+    for media_type in accept.split(',') {
+        let media_type = media_type.split(';').next().unwrap_or_default().trim();
+        match media_type.to_ascii_lowercase().as_str() {
+            "application/json" | "application/*" => json = true,
+            "text/event-stream" | "text/*" | "*/*" => return false,
+            _ => {}
+        }
+    }
+    json
+}
 
-    debug!("Using model: {}", model);
+/// Copy `X-Mock-Variant` into the `mock_variant` extension for providers that accept it.
+/// An explicit extension in the body wins over the header.
+fn apply_mock_variant_header(
+    request: &mut CompletionRequest,
+    headers: &HeaderMap,
+    provider: &dyn InferenceProvider,
+) {
+    let Some(variant) = headers
+        .get(MOCK_VARIANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return;
+    };
+    if !provider
+        .supported_extensions()
+        .contains(&MOCK_VARIANT_EXTENSION)
+    {
+        return;
+    }
+    request
+        .extensions
+        .get_or_insert_default()
+        .entry(MOCK_VARIANT_EXTENSION.to_string())
+        .or_insert_with(|| serde_json::json!(variant));
+}
 
-    // Check if streaming is requested
-    if request.stream == Some(true) {
-        // Get stream from provider
-        let provider_stream = state
-            .provider
-            .stream(&request, model)
-            .await
-            .map_err(ApiError::Provider)?;
+/// Let `X-Response-Mode` (`standard` or `extended`) override the body's `response_mode`.
+/// Other values are ignored.
+fn apply_response_mode_header(request: &mut CompletionRequest, headers: &HeaderMap) {
+    let mode = headers
+        .get(RESPONSE_MODE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            serde_json::from_value(serde_json::json!(value.trim().to_ascii_lowercase())).ok()
+        });
+    if let Some(mode) = mode {
+        request.response_mode = Some(mode);
+    }
+}
 
-        // Convert to SSE events
-        let sse_stream = provider_stream
-            .map(|chunk_result| {
-                match chunk_result {
-                    Ok(chunk) => {
-                        // Format as SSE: "data: {json}\n\n"
-                        match serde_json::to_string(&chunk) {
-                            Ok(json) => Ok(Event::default().data(json)),
-                            Err(e) => {
-                                tracing::error!("Failed to serialize chunk: {}", e);
-                                // Send OpenAI-compatible error event
-                                let error_response = models::OpenAIErrorResponse {
-                                    error: models::OpenAIError {
-                                        message: format!("Serialization error: {}", e),
-                                        error_type: "api_error".to_string(),
-                                        param: None,
-                                        code: Some("stream_serialization_error".to_string()),
-                                    }
-                                };
-                                let error_json = serde_json::to_string(&error_response)
-                                    .unwrap_or_else(|_| r#"{"error":{"message":"Stream error","type":"api_error"}}"#.to_string());
-                                Ok(Event::default().data(error_json))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // Send OpenAI-compatible error in stream
-                        let error_response = models::OpenAIErrorResponse {
-                            error: models::OpenAIError {
-                                message: e.to_string(),
-                                error_type: "api_error".to_string(),
-                                param: None,
-                                code: Some("stream_error".to_string()),
-                            }
-                        };
-                        let error_json = serde_json::to_string(&error_response)
-                            .unwrap_or_else(|_| r#"{"error":{"message":"Stream error","type":"api_error"}}"#.to_string());
-                        Ok(Event::default().data(error_json))
-                    }
-                }
-            })
-            .chain(futures_util::stream::once(async {
-                // Send [DONE] marker
-                Ok(Event::default().data("[DONE]"))
-            }));
+/// Validate and route a request, returning what would be sent upstream without running inference
+#[instrument(skip(state, request), fields(message_count = request.messages.len()))]
+async fn dry_run_completion(
+    State(state): State<AppState>,
+    Json(request): Json<CompletionRequest>,
+) -> Result<Json<DryRunReport>, ApiError> {
+    build_dry_run_report(&state, &request).await.map(Json)
+}
 
-        info!(model = model, stream = true, "Streaming completion started");
+async fn build_dry_run_report(
+    state: &AppState,
+    request: &CompletionRequest,
+) -> Result<DryRunReport, ApiError> {
+    let mut ctx = state.request_context(request.clone());
+    pipeline::resolve_model(&mut ctx)?;
+    pipeline::route(&mut ctx).await?;
+    pipeline::preprocess(&mut ctx);
+    pipeline::apply_policies(&ctx)?;
+    let model = ctx.model.as_str();
+    // Preview what would actually be sent, after preprocessing
+    let request = &ctx.request;
+    let mut warnings = Vec::new();
 
-        return Ok(CompletionOrStream::Stream(
-            Sse::new(Box::pin(sse_stream)
-                as Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>)
-            .keep_alive(KeepAlive::default()),
-        ));
+    if request.model.is_none() {
+        warnings.push(format!("No model specified; default model '{model}' will be used"));
     }
 
-    // Non-streaming: Use the provider to generate completion
-    let response = state
+    if let Some(ref extensions) = request.extensions {
+        ctx.provider
+            .validate_extensions(extensions)
+            .map_err(ApiError::Provider)?;
+    }
+
+    let normalized_request = ctx
         .provider
-        .generate(&request, model)
-        .await
+        .build_inference_request(request, model)
+        .map_err(ApiError::Provider)?;
+    let upstream_body = ctx
+        .provider
+        .preview_request_body(request, model)
         .map_err(ApiError::Provider)?;
 
-    // Log only if we have usage information
-    if let Some(ref usage) = response.usage {
-        info!(
-            model = model,
-            choices_count = response.choices.len(),
-            total_tokens = ?usage.total_tokens,
-            prompt_tokens = ?usage.prompt_tokens,
-            completion_tokens = ?usage.completion_tokens,
-            stream = false,
-            "Completion successful"
-        );
-    } else {
-        info!(
-            model = model,
-            choices_count = response.choices.len(),
-            stream = false,
-            "Completion successful (no usage data)"
-        );
-    }
+    Ok(DryRunReport {
+        object: "chat.completion.dry_run".to_string(),
+        provider: ctx.provider.name().to_string(),
+        model: model.to_string(),
+        stream: request.stream.unwrap_or(false),
+        estimated_prompt_tokens: providers::estimate_prompt_tokens(&request.messages),
+        normalized_request,
+        upstream_body,
+        warnings,
+    })
+}
 
-    Ok(CompletionOrStream::Json(Json(response)))
+#[derive(Deserialize)]
+struct ModelsQuery {
+    limit: Option<usize>,
+    after: Option<String>,
 }
 
-async fn list_models(State(state): State<AppState>) -> Result<Json<ModelsResponse>, ApiError> {
-    let models = state
+/// Models sorted by id. With `limit` the list is paged after the `after` id
+/// and the envelope carries `has_more`, `first_id` and `last_id`.
+async fn list_models(
+    State(state): State<AppState>,
+    Query(query): Query<ModelsQuery>,
+) -> Result<Json<ModelsResponse>, ApiError> {
+    let listed = state
         .provider
-        .list_models()
+        .list_models_by_provider()
         .await
         .map_err(ApiError::Provider)?;
 
-    let model_list = models
+    let models = model_list::aggregate(listed);
+    let page = model_list::paginate(models, query.limit, query.after.as_deref())
+        .map_err(ApiError::Validation)?;
+
+    let model_list: Vec<_> = page
+        .models
         .into_iter()
-        .map(|id| ModelInfo {
-            id,
+        .map(|model| ModelInfo {
+            owned_by: state.runtime.owned_by(&model.provider).to_string(),
+            id: model.id,
             object: "model".to_string(),
-            owned_by: "local".to_string(),
         })
         .collect();
 
+    let paged = query.limit.is_some();
     Ok(Json(ModelsResponse {
         object: "list".to_string(),
+        first_id: model_list.first().filter(|_| paged).map(|m| m.id.clone()),
+        last_id: model_list.last().filter(|_| paged).map(|m| m.id.clone()),
+        has_more: paged.then_some(page.has_more),
         data: model_list,
     }))
 }
 
-async fn health_check(State(state): State<AppState>) -> Result<Json<HealthResponse>, ApiError> {
+/// Extensions each serving provider accepts under `extensions`, with their
+/// types and bounds
+async fn list_extensions(State(state): State<AppState>) -> Json<ExtensionsResponse> {
+    let data = state
+        .provider
+        .extension_specs_by_provider()
+        .into_iter()
+        .map(|(provider, specs)| ProviderExtensionsInfo {
+            provider,
+            extensions: specs
+                .iter()
+                .map(|spec| ExtensionInfo {
+                    spec,
+                    constraint: spec.constraint(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    Json(ExtensionsResponse {
+        object: "list".to_string(),
+        data,
+    })
+}
+
+/// Report provider health. While load shedding is active the status is
+/// `saturated` with a 503, so load balancers route traffic elsewhere.
+async fn health_check(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<HealthResponse>), ApiError> {
     // Check if provider is healthy
     state
         .provider
@@ -298,24 +1075,116 @@ async fn health_check(State(state): State<AppState>) -> Result<Json<HealthRespon
         max_retries: config.max_retries,
     });
 
-    Ok(Json(HealthResponse {
-        status: "healthy".to_string(),
+    let (status, label) = if state.admission.is_saturated() {
+        (StatusCode::SERVICE_UNAVAILABLE, "saturated")
+    } else {
+        (StatusCode::OK, "healthy")
+    };
+    Ok((
+        status,
+        Json(HealthResponse {
+            status: label.to_string(),
+            provider: state.provider.name().to_string(),
+            http_config,
+            model_in_flight: state.admission.model_in_flight(),
+            endpoints: state.provider.endpoint_health(),
+            circuits: state.provider.circuit_health(),
+        }),
+    ))
+}
+
+async fn render_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    for (model, in_flight) in state.admission.model_in_flight() {
+        state
+            .metrics
+            .model_in_flight
+            .set(&[("model", &model)], in_flight as f64);
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    )
+}
+
+async fn startup_report(State(state): State<AppState>) -> Json<StartupReport> {
+    Json(state.startup_report.as_ref().clone())
+}
+
+/// Build and runtime facts for support, gathered in one place
+async fn diagnostics(State(state): State<AppState>) -> Json<DiagnosticsResponse> {
+    Json(DiagnosticsResponse {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: option_env!("GIT_COMMIT"),
+        uptime_secs: state.started.elapsed().as_secs(),
         provider: state.provider.name().to_string(),
-        http_config,
-    }))
+        tokio_workers: tokio::runtime::Handle::current().metrics().num_workers(),
+        in_flight_requests: state.metrics.completions_in_flight.get(&[]).max(0.0) as u64,
+    })
+}
+
+async fn recent_requests(
+    State(state): State<AppState>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<RecentRequestsResponse> {
+    Json(RecentRequestsResponse {
+        object: "list".to_string(),
+        data: state.history.recent(&query),
+    })
 }
 
-async fn root() -> Json<RootResponse> {
+#[derive(Deserialize)]
+struct UsageQuery {
+    top: Option<usize>,
+}
+
+async fn prompt_usage(
+    State(state): State<AppState>,
+    Query(query): Query<UsageQuery>,
+) -> Json<UsageResponse> {
+    Json(UsageResponse {
+        object: "list".to_string(),
+        data: state.usage.top(query.top.unwrap_or(10)),
+    })
+}
+
+async fn root(State(state): State<AppState>) -> Json<RootResponse> {
+    let provider = state
+        .settings
+        .server
+        .root_includes_provider
+        .then(|| state.provider.name().to_string());
     Json(RootResponse {
         message: "Ok".to_string(),
+        service: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        provider,
     })
 }
 
 // Response types for the API endpoints
+#[derive(Serialize)]
+struct DryRunReport {
+    object: String,
+    provider: String,
+    model: String,
+    stream: bool,
+    estimated_prompt_tokens: u32,
+    normalized_request: providers::InferenceRequest,
+    upstream_body: serde_json::Value,
+    warnings: Vec<String>,
+}
+
 #[derive(Serialize)]
 struct ModelsResponse {
     object: String,
     data: Vec<ModelInfo>,
+    // Only set on paged requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has_more: Option<bool>,
 }
 
 #[derive(Serialize)]
@@ -325,11 +1194,69 @@ struct ModelInfo {
     owned_by: String,
 }
 
+#[derive(Serialize)]
+struct ExtensionsResponse {
+    object: String,
+    data: Vec<ProviderExtensionsInfo>,
+}
+
+#[derive(Serialize)]
+struct ProviderExtensionsInfo {
+    provider: String,
+    extensions: Vec<ExtensionInfo>,
+}
+
+#[derive(Serialize)]
+struct ExtensionInfo {
+    #[serde(flatten)]
+    spec: &'static ExtensionSpec,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    constraint: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RecentRequestsResponse {
+    object: String,
+    data: Vec<RequestSummary>,
+}
+
+#[derive(Serialize)]
+struct UsageResponse {
+    object: String,
+    data: Vec<KeyUsage>,
+}
+
+#[derive(Serialize)]
+struct DiagnosticsResponse {
+    version: &'static str,
+    /// `GIT_COMMIT` at build time, if it was set
+    git_commit: Option<&'static str>,
+    uptime_secs: u64,
+    provider: String,
+    tokio_workers: usize,
+    /// Chat completions being handled, including streams still being sent
+    in_flight_requests: u64,
+}
+
+#[derive(Serialize)]
+struct MockStateResponse {
+    scenarios: BTreeMap<String, ScenarioPosition>,
+}
+
 #[derive(Serialize)]
 struct HealthResponse {
     status: String,
     provider: String,
     http_config: Option<HttpConfigInfo>,
+    /// Completions in flight per model with a `limits.concurrency` cap
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    model_in_flight: BTreeMap<String, usize>,
+    /// Per-endpoint state when the provider spreads requests over several upstreams
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    endpoints: Vec<EndpointHealth>,
+    /// Circuit breaker state per provider when `http.circuit_breaker` is set
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    circuits: Vec<CircuitHealth>,
 }
 
 #[derive(Serialize)]
@@ -338,3 +1265,1450 @@ struct HttpConfigInfo {
     connect_timeout_secs: u64,
     max_retries: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CompletionResponse, Message, Role};
+    use crate::telemetry::CaptureWriter;
+    use crate::providers::{BoxFuture, InferenceRequest, InferenceResponse, ProviderError};
+    use axum::body::Body;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+    use tracing::debug;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn test_settings(provider: config::InferenceProvider) -> Arc<Settings> {
+        Arc::new(Settings::for_tests(provider, "http://127.0.0.1:9", "default-model"))
+    }
+
+    /// Provider that counts executions so tests can assert inference never ran
+    struct CountingProvider {
+        executions: Arc<AtomicUsize>,
+    }
+
+    impl InferenceProvider for CountingProvider {
+        fn execute(
+            &self,
+            request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            self.executions.fetch_add(1, Ordering::SeqCst);
+            let model = request.model.clone();
+            Box::pin(async move {
+                Ok(InferenceResponse {
+                    text: "executed".to_string(),
+                    model_used: model,
+                    finish_reason: None,
+                    total_tokens: None,
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    latency_ms: None,
+                    provider_request_id: None,
+                    system_fingerprint: None,
+                    tool_calls: None,
+                    logprobs: None,
+                    provider_data: None,
+                })
+            })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            providers::standard_completion_response(response, original_request, self.name())
+        }
+
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+            Box::pin(async { Ok(vec!["counting-model".to_string()]) })
+        }
+    }
+
+    fn counting_router() -> Router {
+        build_router(AppState::new(
+            Arc::new(CountingProvider {
+                executions: Arc::new(AtomicUsize::new(0)),
+            }),
+            test_settings(config::InferenceProvider::LMStudio),
+        ))
+    }
+
+    async fn send(app: Router, method: Method, uri: &str) -> Response {
+        app.oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn json_body(response: Response) -> serde_json::Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    async fn text_body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_head_returns_get_headers_without_body() {
+        for uri in ["/", "/health", "/v1/models"] {
+            let response = send(counting_router(), Method::HEAD, uri).await;
+
+            assert_eq!(response.status(), StatusCode::OK, "HEAD {uri}");
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "application/json",
+                "HEAD {uri}"
+            );
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(bytes.is_empty(), "HEAD {uri} returned a body");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wrong_method_returns_openai_405() {
+        let response = send(counting_router(), Method::POST, "/v1/models").await;
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::ALLOW], "GET,HEAD");
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "method_not_allowed");
+        assert_eq!(
+            body["error"]["message"],
+            "Method POST is not allowed for /v1/models; allowed methods: GET, HEAD"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_options_lists_allowed_methods() {
+        let response = send(counting_router(), Method::OPTIONS, "/v1/chat/completions").await;
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[header::ALLOW], "POST");
+    }
+
+    const ADMIN_KEY: &str = "admin-secret";
+
+    /// Router over a mock provider with one `mock-test` scenario and admin routes enabled
+    fn mock_router(responses_dir: &std::path::Path) -> Router {
+        mock_router_with(responses_dir, |_| {})
+    }
+
+    /// Mock-backed router with the admin key set and further settings tweaked by `configure`
+    fn mock_router_with(
+        responses_dir: &std::path::Path,
+        configure: impl FnOnce(&mut Settings),
+    ) -> Router {
+        std::fs::write(
+            responses_dir.join("test.yaml"),
+            r#"
+responses:
+  - text: "Mock reply"
+    model_used: "mock-test"
+    prompt_tokens: 5
+    completion_tokens: 3
+    total_tokens: 8
+settings:
+  mode: first
+"#,
+        )
+        .unwrap();
+
+        let mut settings = (*test_settings(config::InferenceProvider::Mock {
+            responses_dir: responses_dir.to_path_buf(),
+            record: false,
+            record_upstream: None,
+        }))
+        .clone();
+        settings.admin.api_key = Some(ADMIN_KEY.to_string());
+        configure(&mut settings);
+        let settings = Arc::new(settings);
+        build_router(AppState::new(create_provider(&settings).unwrap(), settings))
+    }
+
+    #[tokio::test]
+    async fn test_upstream_attempts_reported_in_extended_mode() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "cmpl-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "qwen2.5-7b",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        let mut settings = (*test_settings(config::InferenceProvider::OpenAICompatible {
+            chat_path: "v1/chat/completions".to_string(),
+            models_path: "v1/models".to_string(),
+            api_key: None,
+            extensions_allowlist: Vec::new(),
+        }))
+        .clone();
+        settings.inference.base_url = server.uri();
+        settings.inference.http = Some(config::HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            ..Default::default()
+        });
+        let settings = Arc::new(settings);
+        let app = build_router(AppState::new(create_provider(&settings).unwrap(), settings));
+
+        let response = send_completion(
+            app,
+            serde_json::json!({
+                "model": "qwen2.5-7b",
+                "response_mode": "extended",
+                "messages": [{"role": "user", "content": "Hello"}]
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(body["provider_extensions"]["provider"], "openai-compatible");
+        assert_eq!(body["provider_extensions"]["data"]["upstream_attempts"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_router_sends_requests_to_backend_by_model() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            let backend = |base_url: &str, provider| config::RouterBackend {
+                base_url: base_url.to_string(),
+                http: None,
+                default_model: None,
+                provider,
+            };
+            let route = |model: &str, backend: &str| config::ModelRoute {
+                model: model.to_string(),
+                backend: backend.to_string(),
+                fallbacks: Vec::new(),
+            };
+            let mock = settings.inference.provider.clone();
+            settings.inference.provider = config::InferenceProvider::Router {
+                backends: HashMap::from([
+                    ("local".to_string(), backend("http://localhost", mock)),
+                    (
+                        "studio".to_string(),
+                        backend("http://127.0.0.1:9", config::InferenceProvider::LMStudio),
+                    ),
+                ]),
+                routes: vec![route("llama*", "studio"), route("mock-*", "local")],
+            };
+            // Models are credited to the backend's provider, not the router
+            settings
+                .runtime
+                .owned_by
+                .insert("mock".to_string(), "mock-lab".to_string());
+            settings.validate().unwrap();
+        });
+        let completion = |model: &str| {
+            serde_json::json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "Hello"}]
+            })
+        };
+
+        let response = send_completion(app.clone(), completion("mock-test")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(body["choices"][0]["message"]["content"], "Mock reply");
+
+        // The unreachable backend's models are left out of the union
+        let response = send_completion(app.clone(), completion("gpt-4o")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = json_body(response).await;
+        assert!(body["error"]["message"].as_str().unwrap().contains("mock-test"));
+
+        let body = json_body(send(app, Method::GET, "/v1/models").await).await;
+        assert_eq!(body["data"][0]["id"], "mock-test");
+        assert_eq!(body["data"][0]["owned_by"], "mock-lab");
+    }
+
+    #[tokio::test]
+    async fn test_router_fails_over_to_fallback_backend() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            let mock = settings.inference.provider.clone();
+            settings.inference.provider = config::InferenceProvider::Router {
+                backends: HashMap::from([
+                    (
+                        "studio".to_string(),
+                        config::RouterBackend {
+                            base_url: "http://127.0.0.1:9".to_string(),
+                            http: Some(config::HttpConfigSchema {
+                                timeout_secs: 5,
+                                max_retries: 0,
+                                ..Default::default()
+                            }),
+                            default_model: None,
+                            provider: config::InferenceProvider::LMStudio,
+                        },
+                    ),
+                    (
+                        "local".to_string(),
+                        config::RouterBackend {
+                            base_url: "http://localhost".to_string(),
+                            http: None,
+                            default_model: Some("mock-test".to_string()),
+                            provider: mock,
+                        },
+                    ),
+                ]),
+                routes: vec![config::ModelRoute {
+                    model: "*".to_string(),
+                    backend: "studio".to_string(),
+                    fallbacks: vec!["local".to_string()],
+                }],
+            };
+            settings.validate().unwrap();
+        });
+
+        let response = send_completion(
+            app,
+            serde_json::json!({
+                "model": "llama3.1-8b",
+                "response_mode": "extended",
+                "messages": [{"role": "user", "content": "Hello"}]
+            }),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = json_body(response).await;
+        assert_eq!(body["choices"][0]["message"]["content"], "Mock reply");
+        assert_eq!(body["provider_extensions"]["data"]["backend"], "local");
+    }
+
+    #[tokio::test]
+    async fn test_shadow_gets_a_copy_without_changing_the_response() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let executions = Arc::new(AtomicUsize::new(0));
+        let shadow = Shadow::new(
+            Arc::new(CountingProvider {
+                executions: executions.clone(),
+            }),
+            1.0,
+            None,
+        );
+        let primary = mock_router(temp_dir.path());
+        let settings = test_settings(config::InferenceProvider::Mock {
+            responses_dir: temp_dir.path().to_path_buf(),
+            record: false,
+            record_upstream: None,
+        });
+        let app = build_router(
+            AppState::new(create_provider(&settings).unwrap(), settings).with_shadow(shadow),
+        );
+        let body = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+
+        let expected = json_body(send_completion(primary, body.clone()).await).await;
+        let response = send_completion(app, body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let actual = json_body(response).await;
+        assert_eq!(actual["choices"], expected["choices"]);
+        assert_eq!(actual["choices"][0]["message"]["content"], "Mock reply");
+
+        // The shadow request runs in the background
+        for _ in 0..100 {
+            if executions.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(executions.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_extensions_endpoint_lists_provider_specs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |_| {});
+
+        let body = json_body(send(app, Method::GET, "/v1/extensions").await).await;
+
+        assert_eq!(body["object"], "list");
+        assert_eq!(body["data"][0]["provider"], "mock");
+        let extension = &body["data"][0]["extensions"][0];
+        assert_eq!(extension["name"], MOCK_VARIANT_EXTENSION);
+        assert_eq!(extension["type"], "string");
+        assert!(extension["description"].is_string());
+        assert!(extension.get("constraint").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_extensions_endpoint_lists_every_router_backend() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            let backend = |base_url: &str, provider| config::RouterBackend {
+                base_url: base_url.to_string(),
+                http: None,
+                default_model: None,
+                provider,
+            };
+            let mock = settings.inference.provider.clone();
+            settings.inference.provider = config::InferenceProvider::Router {
+                backends: HashMap::from([
+                    ("local".to_string(), backend("http://localhost", mock)),
+                    (
+                        "studio".to_string(),
+                        backend("http://127.0.0.1:9", config::InferenceProvider::LMStudio),
+                    ),
+                ]),
+                routes: vec![config::ModelRoute {
+                    model: "*".to_string(),
+                    backend: "local".to_string(),
+                    fallbacks: Vec::new(),
+                }],
+            };
+            settings.validate().unwrap();
+        });
+
+        let body = json_body(send(app, Method::GET, "/v1/extensions").await).await;
+
+        let providers: Vec<_> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["provider"].as_str().unwrap())
+            .collect();
+        assert_eq!(providers, ["mock", "lmstudio"]);
+        assert_eq!(body["data"][0]["extensions"][0]["name"], MOCK_VARIANT_EXTENSION);
+        let studio: Vec<_> = body["data"][1]["extensions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|extension| extension["name"].as_str().unwrap())
+            .collect();
+        assert!(studio.contains(&"top_k"));
+    }
+
+    #[tokio::test]
+    async fn test_root_reports_version_and_optional_provider() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        let app = mock_router_with(temp_dir.path(), |_| {});
+        let body = json_body(send(app, Method::GET, "/").await).await;
+        assert_eq!(body["message"], "Ok");
+        assert_eq!(body["service"], env!("CARGO_PKG_NAME"));
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["provider"], "mock");
+
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.root_includes_provider = false;
+        });
+        let body = json_body(send(app, Method::GET, "/").await).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body.get("provider").is_none());
+    }
+
+    async fn post_completion(app: Router, body: serde_json::Value) -> StatusCode {
+        send_completion(app, body).await.status()
+    }
+
+    async fn send_completion(app: Router, body: serde_json::Value) -> Response {
+        app.oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn get_recent(app: Router, query: &str) -> serde_json::Value {
+        get_admin(app, &format!("/admin/requests/recent{query}")).await
+    }
+
+    async fn get_admin(app: Router, uri: &str) -> serde_json::Value {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header(header::AUTHORIZATION, format!("Bearer {ADMIN_KEY}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        json_body(response).await
+    }
+
+    #[tokio::test]
+    async fn test_recent_requests_records_and_filters() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+        let user_message = serde_json::json!([{"role": "user", "content": "secret prompt"}]);
+
+        let ok = serde_json::json!({"model": "mock-test", "messages": user_message});
+        assert_eq!(post_completion(app.clone(), ok).await, StatusCode::OK);
+        let failed = serde_json::json!({"model": "mock-test", "messages": []});
+        assert_eq!(post_completion(app.clone(), failed).await, StatusCode::BAD_REQUEST);
+        let streamed =
+            serde_json::json!({"model": "mock-test", "messages": user_message, "stream": true});
+        assert_eq!(post_completion(app.clone(), streamed).await, StatusCode::OK);
+
+        let all = get_recent(app.clone(), "").await;
+        let entries = all["data"].as_array().unwrap();
+        assert_eq!(entries.len(), 3);
+        // Newest first
+        assert_eq!(entries[0]["stream"], true);
+        assert_eq!(entries[1]["status"], 400);
+        assert_eq!(entries[1]["error_category"], "invalid_request_error");
+        assert_eq!(entries[2]["status"], 200);
+        assert_eq!(entries[2]["total_tokens"], 8);
+        assert_eq!(entries[2]["finish_reason"], "stop");
+        assert_eq!(entries[2]["provider"], "mock");
+        assert!(!all.to_string().contains("secret prompt"));
+
+        let failures = get_recent(app.clone(), "?status=400").await;
+        assert_eq!(failures["data"].as_array().unwrap().len(), 1);
+        let by_model = get_recent(app.clone(), "?model=mock-test&limit=2").await;
+        assert_eq!(by_model["data"].as_array().unwrap().len(), 2);
+        let other_model = get_recent(app, "?model=mock-other").await;
+        assert!(other_model["data"].as_array().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_content_hashes_reach_history_and_usage_without_content() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.logging.content_hash_salt = Some("test-salt".to_string());
+        });
+        let request = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "secret prompt"}]
+        });
+
+        for _ in 0..2 {
+            assert_eq!(post_completion(app.clone(), request.clone()).await, StatusCode::OK);
+        }
+
+        let recent = get_recent(app.clone(), "").await;
+        let entries = recent["data"].as_array().unwrap();
+        let hash = entries[0]["conversation_hash"].as_str().unwrap();
+        assert_eq!(entries[1]["conversation_hash"], hash);
+        assert_eq!(entries[0]["message_hashes"][0]["role"], "user");
+        assert_eq!(entries[0]["message_hashes"][0]["chars"], 13);
+
+        let usage = get_admin(app, "/admin/usage?top=5").await;
+        assert_eq!(usage["data"][0]["key_label"], "anonymous");
+        assert_eq!(usage["data"][0]["top_prompts"][0]["conversation_hash"], hash);
+        assert_eq!(usage["data"][0]["top_prompts"][0]["count"], 2);
+
+        assert!(!recent.to_string().contains("secret prompt"));
+        assert!(!usage.to_string().contains("secret prompt"));
+    }
+
+    /// Post `body` as a completion request authorized with `key`
+    async fn post_completion_with_key(app: Router, body: &serde_json::Value, key: &str) {
+        let response = app
+            .oneshot(
+                Request::post("/v1/chat/completions")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header(header::AUTHORIZATION, format!("Bearer {key}"))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_counted_per_api_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.api_keys = vec!["sk-team-a".to_string(), "sk-team-b".to_string()];
+            settings.logging.content_hash_salt = Some("test-salt".to_string());
+        });
+        let request = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+
+        for key in ["sk-team-a", "sk-team-a", "sk-team-b"] {
+            post_completion_with_key(app.clone(), &request, key).await;
+        }
+
+        let usage = get_admin(app, "/admin/usage?top=5").await;
+        let counts: BTreeMap<_, _> = usage["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| {
+                let label = key["key_label"].as_str().unwrap().to_string();
+                (label, key["top_prompts"][0]["count"].as_u64().unwrap())
+            })
+            .collect();
+        let team_a = KeyLabel::for_key("sk-team-a").0;
+        let team_b = KeyLabel::for_key("sk-team-b").0;
+        assert_eq!(counts, BTreeMap::from([(team_a, 2), (team_b, 1)]));
+        assert!(!usage.to_string().contains("sk-team"));
+    }
+
+    #[tokio::test]
+    async fn test_recent_requests_are_attributed_to_api_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.api_keys = vec!["sk-team-a".to_string(), "sk-team-b".to_string()];
+        });
+        let request = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+
+        post_completion_with_key(app.clone(), &request, "sk-team-a").await;
+        post_completion_with_key(app.clone(), &request, "sk-team-b").await;
+
+        let team_a = KeyLabel::for_key("sk-team-a").0;
+        let all = get_recent(app.clone(), "").await;
+        assert_eq!(all["data"][1]["key_label"], team_a.as_str());
+        assert_eq!(all["data"][0]["key_label"], KeyLabel::for_key("sk-team-b").0.as_str());
+        assert!(!all.to_string().contains("sk-team"));
+
+        let by_key = get_recent(app, &format!("?key_label={team_a}")).await;
+        let entries = by_key["data"].as_array().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0]["key_label"], team_a.as_str());
+    }
+
+    #[test]
+    fn test_client_id_never_holds_the_raw_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut settings = (*test_settings(config::InferenceProvider::Mock {
+            responses_dir: temp_dir.path().to_path_buf(),
+            record: false,
+            record_upstream: None,
+        }))
+        .clone();
+        settings.server.api_keys = vec!["sk-team-a".to_string()];
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer sk-team-a".parse().unwrap());
+        let peer = Some(SocketAddr::from(([10, 0, 0, 1], 4000)));
+
+        let client = client_id(&settings, &headers, peer);
+
+        assert_eq!(client, format!("key:{}", KeyLabel::for_key("sk-team-a").0));
+        assert!(!client.contains("sk-team-a"));
+        settings.server.api_keys.clear();
+        assert_eq!(client_id(&settings, &headers, peer), "ip:10.0.0.1");
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_require_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let response = send(
+            mock_router(temp_dir.path()),
+            Method::GET,
+            "/admin/requests/recent",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(json_body(response).await["error"]["type"], "authentication_error");
+
+        // Without a configured key the admin routes are not registered at all
+        let response = send(counting_router(), Method::GET, "/admin/requests/recent").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_startup_report_is_served_to_admins() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        let report = get_admin(mock_router(temp_dir.path()), "/admin/startup-report").await;
+
+        assert_eq!(report["provider"], "mock");
+        assert_eq!(report["features"]["admin_api"], true);
+        assert_eq!(report["probe"]["status"], "skipped");
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics_report_build_and_runtime_info() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        let diagnostics = get_admin(mock_router(temp_dir.path()), "/admin/diagnostics").await;
+
+        assert_eq!(diagnostics["version"], env!("CARGO_PKG_VERSION"));
+        // The router was built moments ago
+        assert!(diagnostics["uptime_secs"].as_u64().unwrap() < 60);
+        assert_eq!(diagnostics["provider"], "mock");
+        assert!(diagnostics["tokio_workers"].as_u64().unwrap() >= 1);
+        assert_eq!(diagnostics["in_flight_requests"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_mock_admin_state_counts_calls_and_reset_reloads() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+        let request = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+        for _ in 0..2 {
+            assert_eq!(post_completion(app.clone(), request.clone()).await, StatusCode::OK);
+        }
+
+        let state = get_admin(app.clone(), "/admin/mock/state").await;
+        assert_eq!(state["scenarios"]["test"]["calls"], 2);
+        assert_eq!(state["scenarios"]["test"]["position"], 0);
+
+        // Edits to a cached scenario file only show after a reset
+        std::fs::write(
+            temp_dir.path().join("test.yaml"),
+            "responses:\n  - text: \"Edited reply\"\n",
+        )
+        .unwrap();
+        let reset = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/admin/mock/reset")
+                    .header(header::AUTHORIZATION, format!("Bearer {ADMIN_KEY}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reset.status(), StatusCode::NO_CONTENT);
+        let state = get_admin(app.clone(), "/admin/mock/state").await;
+        assert_eq!(state["scenarios"], serde_json::json!({}));
+
+        let response = json_body(send_completion(app, request).await).await;
+        assert_eq!(response["choices"][0]["message"]["content"], "Edited reply");
+    }
+
+    #[tokio::test]
+    async fn test_mock_admin_routes_absent_for_other_providers() {
+        let mut settings = (*test_settings(config::InferenceProvider::LMStudio)).clone();
+        settings.admin.api_key = Some(ADMIN_KEY.to_string());
+        let app = build_router(AppState::new(
+            Arc::new(CountingProvider {
+                executions: Arc::new(AtomicUsize::new(0)),
+            }),
+            Arc::new(settings),
+        ));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/mock/state")
+                    .header(header::AUTHORIZATION, format!("Bearer {ADMIN_KEY}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mock_variant_header_selects_variant() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+        std::fs::write(
+            temp_dir.path().join("render.yaml"),
+            "responses:\n  - text: \"Default\"\n    variants:\n      plain:\n        text: \"Plain\"\n",
+        )
+        .unwrap();
+        let body = serde_json::json!({
+            "model": "mock-render",
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/chat/completions")
+                    .header("content-type", "application/json")
+                    .header(MOCK_VARIANT_HEADER, "plain")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_body(response).await;
+        assert_eq!(json["choices"][0]["message"]["content"], "Plain");
+    }
+
+    #[tokio::test]
+    async fn test_response_mode_header_overrides_body() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+        let completion = |mode: &str, body: serde_json::Value| {
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .header(RESPONSE_MODE_HEADER, mode)
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let mut body = serde_json::json!({
+            "model": "mock-test",
+            "messages": [{"role": "user", "content": "Hi"}]
+        });
+
+        let response = app.clone().oneshot(completion("extended", body.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json = json_body(response).await;
+        assert_eq!(json["provider_extensions"]["provider"], "mock");
+
+        body["response_mode"] = serde_json::json!("extended");
+        let response = app.oneshot(completion("Standard", body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(json_body(response).await.get("provider_extensions").is_none());
+    }
+
+    /// Provider whose upstream never accepts the connection
+    struct TimeoutProvider;
+
+    impl InferenceProvider for TimeoutProvider {
+        fn execute(
+            &self,
+            _request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            Box::pin(async { Err(ProviderError::ConnectTimeout) })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            providers::standard_completion_response(response, original_request, self.name())
+        }
+
+        fn name(&self) -> &str {
+            "timeout"
+        }
+    }
+
+    async fn localized_error(
+        app: Router,
+        accept_language: &str,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/chat/completions")
+                    .header("content-type", "application/json")
+                    .header(header::ACCEPT_LANGUAGE, accept_language)
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        (response.status(), json_body(response).await["error"].clone())
+    }
+
+    #[tokio::test]
+    async fn test_error_messages_follow_accept_language() {
+        let locales = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            locales.path().join("fr.yaml"),
+            r#"
+validation.invalid_temperature: "La température doit être comprise entre 0.0 et 2.0, reçu {value}"
+provider.connect_timeout: "Le fournisseur d'inférence n'a pas répondu à temps"
+"#,
+        )
+        .unwrap();
+        let app = build_router(
+            AppState::new(
+                Arc::new(TimeoutProvider),
+                test_settings(config::InferenceProvider::LMStudio),
+            )
+            .with_catalog(MessageCatalog::load_dir(locales.path()).unwrap()),
+        );
+        let messages = serde_json::json!([{"role": "user", "content": "Hello"}]);
+
+        let hot = serde_json::json!({"messages": messages, "temperature": 3.5});
+        let (status, error) = localized_error(app.clone(), "fr-FR, en;q=0.5", hot.clone()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            error["message"],
+            "La température doit être comprise entre 0.0 et 2.0, reçu 3.5"
+        );
+        assert_eq!(error["type"], "invalid_request_error");
+        assert_eq!(error["param"], "temperature");
+
+        let (_, error) = localized_error(app.clone(), "en-US", hot).await;
+        assert_eq!(error["message"], "Temperature must be between 0.0 and 2.0, got 3.5");
+
+        let valid = serde_json::json!({"messages": messages});
+        let (status, error) = localized_error(app.clone(), "fr", valid).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error["message"], "Le fournisseur d'inférence n'a pas répondu à temps");
+        assert_eq!(error["type"], "timeout_error");
+        assert_eq!(error["code"], "connect_timeout");
+
+        // Keys missing from the locale fall back to English
+        let empty = serde_json::json!({"messages": []});
+        let (_, error) = localized_error(app, "fr", empty).await;
+        assert_eq!(error["message"], "Messages array cannot be empty");
+    }
+
+    /// Provider that answers long after any reasonable deadline
+    struct SlowProvider;
+
+    impl InferenceProvider for SlowProvider {
+        fn execute(
+            &self,
+            _request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                Err(ProviderError::ConnectionFailed("unreachable".to_string()))
+            })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            providers::standard_completion_response(response, original_request, self.name())
+        }
+
+        fn name(&self) -> &str {
+            "slow"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout_returns_504() {
+        let app = build_router(AppState::new(
+            Arc::new(TimeoutProvider),
+            test_settings(config::InferenceProvider::LMStudio),
+        ));
+        let body = serde_json::json!({"messages": [{"role": "user", "content": "Hello"}]});
+
+        let (status, error) = localized_error(app, "en", body).await;
+
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(error["type"], "timeout_error");
+        assert_eq!(error["code"], "connect_timeout");
+    }
+
+    #[tokio::test]
+    async fn test_deadline_exceeded_returns_408() {
+        let mut settings = (*test_settings(config::InferenceProvider::LMStudio)).clone();
+        settings.inference.timeout_secs = 1;
+        let app = build_router(AppState::new(Arc::new(SlowProvider), Arc::new(settings)));
+        let body = serde_json::json!({"messages": [{"role": "user", "content": "Hello"}]});
+
+        let (status, error) = localized_error(app, "en", body).await;
+
+        assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(error["type"], "timeout_error");
+        assert_eq!(error["code"], "deadline_exceeded");
+        assert!(
+            error["message"]
+                .as_str()
+                .unwrap()
+                .contains("1000ms deadline")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stream_ends_with_stream_stalled_event() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("stall.yaml"),
+            "responses:\n  - text: \"never arrives\"\n    delay_ms: 5000\n",
+        )
+        .unwrap();
+        let mut settings = (*test_settings(config::InferenceProvider::Mock {
+            responses_dir: temp_dir.path().to_path_buf(),
+            record: false,
+            record_upstream: None,
+        }))
+        .clone();
+        settings.inference.stream_stall_timeout_secs = Some(1);
+        let settings = Arc::new(settings);
+        let app = build_router(AppState::new(create_provider(&settings).unwrap(), settings));
+        let body = serde_json::json!({
+            "model": "mock-stall",
+            "stream": true,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/chat/completions")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8_lossy(&bytes);
+
+        assert!(text.contains(r#""code":"stream_stalled""#), "{text}");
+        // Only the role chunk arrives before the stall
+        assert!(text.contains("after 1 chunks"), "{text}");
+        assert!(text.trim_end().ends_with("data: [DONE]"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_saturated_model_limit_leaves_other_models_running() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("slow.yaml"),
+            "responses:\n  - text: \"Slow reply\"\n    delay_ms: 400\n",
+        )
+        .unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.max_concurrent_requests = Some(8);
+            settings.server.load_shedding = Some(config::LoadSheddingConfig {
+                queue_depth_threshold: 1,
+                estimated_wait_per_request_ms: 1000,
+            });
+            settings.limits.concurrency.per_model =
+                HashMap::from([("mock-sl*".to_string(), 1)]);
+        });
+        let request = |model: &str| {
+            serde_json::json!({
+                "model": model,
+                "messages": [{"role": "user", "content": "Hello"}]
+            })
+        };
+
+        // One slow request runs and one waits, filling the model's queue
+        let in_flight: Vec<_> = (0..2)
+            .map(|_| tokio::spawn(post_completion(app.clone(), request("mock-slow"))))
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let health = json_body(send(app.clone(), Method::GET, "/health").await).await;
+        assert_eq!(health["status"], "healthy");
+        assert_eq!(health["model_in_flight"]["mock-slow"], 1);
+        let metrics = text_body(send(app.clone(), Method::GET, "/metrics").await).await;
+        assert!(metrics.contains("model_in_flight{model=\"mock-slow\"} 1\n"));
+
+        let started = Instant::now();
+        assert_eq!(
+            post_completion(app.clone(), request("mock-test")).await,
+            StatusCode::OK
+        );
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert_eq!(
+            post_completion(app.clone(), request("mock-slow")).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        for request in in_flight {
+            assert_eq!(request.await.unwrap(), StatusCode::OK);
+        }
+        let health = json_body(send(app, Method::GET, "/health").await).await;
+        assert_eq!(health["model_in_flight"]["mock-slow"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_shed_responses_request_is_counted_on_its_route() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("slow.yaml"),
+            "responses:\n  - text: \"Slow reply\"\n    delay_ms: 400\n",
+        )
+        .unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.load_shedding = Some(config::LoadSheddingConfig {
+                queue_depth_threshold: 1,
+                estimated_wait_per_request_ms: 1000,
+            });
+            settings.limits.concurrency.per_model = HashMap::from([("mock-slow".to_string(), 1)]);
+        });
+        let respond = |app: Router| async move {
+            app.oneshot(
+                Request::post("/v1/responses")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        serde_json::json!({"model": "mock-slow", "input": "Hello"}).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+        };
+
+        // One request runs and one waits, filling the model's queue
+        let in_flight: Vec<_> = (0..2).map(|_| tokio::spawn(respond(app.clone()))).collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(respond(app.clone()).await, StatusCode::SERVICE_UNAVAILABLE);
+        let metrics = text_body(send(app.clone(), Method::GET, "/metrics").await).await;
+        assert!(metrics.contains("requests_shed_total{path=\"/v1/responses\"} 1\n"));
+        assert!(!metrics.contains("requests_shed_total{path=\"/v1/chat/completions\"}"));
+
+        for request in in_flight {
+            assert_eq!(request.await.unwrap(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deep_queue_sheds_immediately_with_retry_after() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("slow.yaml"),
+            "responses:\n  - text: \"Slow reply\"\n    delay_ms: 400\n",
+        )
+        .unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.server.max_concurrent_requests = Some(1);
+            settings.server.load_shedding = Some(config::LoadSheddingConfig {
+                queue_depth_threshold: 2,
+                estimated_wait_per_request_ms: 1500,
+            });
+        });
+        let slow = |stream: bool| {
+            serde_json::json!({
+                "model": "mock-slow",
+                "stream": stream,
+                "messages": [{"role": "user", "content": "Hello"}]
+            })
+        };
+
+        // One request runs and two wait, filling the queue
+        let in_flight: Vec<_> = (0..3)
+            .map(|_| tokio::spawn(post_completion(app.clone(), slow(false))))
+            .collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for stream in [false, true] {
+            let started = Instant::now();
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/v1/chat/completions")
+                        .header("content-type", "application/json")
+                        .body(Body::from(slow(stream).to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(started.elapsed() < Duration::from_millis(100));
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+            // Two queued requests at 1.5s each
+            assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+            let body = json_body(response).await;
+            assert_eq!(body["error"]["code"], "server_overloaded");
+        }
+
+        let health = send(app.clone(), Method::GET, "/health").await;
+        assert_eq!(health.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json_body(health).await["status"], "saturated");
+        let metrics = text_body(send(app.clone(), Method::GET, "/metrics").await).await;
+        assert!(metrics.contains("requests_shed_total{path=\"/v1/chat/completions\"} 2\n"));
+
+        for request in in_flight {
+            assert_eq!(request.await.unwrap(), StatusCode::OK);
+        }
+        let health = send(app, Method::GET, "/health").await;
+        assert_eq!(health.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_upstream_phases_recorded_for_mock_requests() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router_with(temp_dir.path(), |settings| {
+            settings.metrics.upstream_phases = true;
+        });
+
+        for stream in [false, true] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/v1/chat/completions")
+                        .header("content-type", "application/json")
+                        .body(Body::from(
+                            serde_json::json!({
+                                "model": "mock-test",
+                                "stream": stream,
+                                "messages": [{"role": "user", "content": "Hello"}]
+                            })
+                            .to_string(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            // Streams are recorded once fully read
+            text_body(response).await;
+        }
+
+        let metrics = text_body(send(app, Method::GET, "/metrics").await).await;
+        for phase in ["first_byte", "total"] {
+            assert!(metrics.contains(&format!(
+                "upstream_phase_seconds_count{{provider=\"mock\",phase=\"{phase}\"}} 2\n"
+            )));
+        }
+        // The mock provider never opens a connection
+        assert!(!metrics.contains("phase=\"connect\""));
+    }
+
+    #[tokio::test]
+    async fn test_completion_metrics_scraped_after_mock_requests() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+
+        for stream in [false, true] {
+            let response = send_completion(
+                app.clone(),
+                serde_json::json!({
+                    "model": "mock-test",
+                    "stream": stream,
+                    "stream_options": {"include_usage": true},
+                    "messages": [{"role": "user", "content": "Hello"}]
+                }),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            // Streams are recorded once fully read
+            text_body(response).await;
+        }
+
+        let metrics = text_body(send(app, Method::GET, "/metrics").await).await;
+        assert!(
+            metrics.contains("completion_requests_total{provider=\"mock\",status=\"200\"} 2\n")
+        );
+        assert!(metrics.contains("completion_duration_seconds_count{provider=\"mock\"} 2\n"));
+        assert!(metrics.contains("completions_in_flight 0\n"));
+        for kind in ["prompt", "completion", "total"] {
+            assert!(metrics.contains(&format!(
+                "tokens_total{{model=\"mock-test\",type=\"{kind}\"}}"
+            )));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validation_rejections_counted_by_rule() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let app = mock_router(temp_dir.path());
+        let body = serde_json::json!({
+            "model": "mock-test",
+            "temperature": 3.5,
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+
+        for _ in 0..2 {
+            assert_eq!(
+                post_completion(app.clone(), body.clone()).await,
+                StatusCode::BAD_REQUEST
+            );
+        }
+
+        let metrics = text_body(send(app, Method::GET, "/metrics").await).await;
+        assert!(metrics.contains("validation_rejections_total{rule=\"InvalidTemperature\"} 2\n"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path_returns_openai_404() {
+        let response = send(counting_router(), Method::GET, "/v1/unknown").await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = json_body(response).await;
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["code"], "not_found");
+        assert_eq!(body["error"]["message"], "Invalid URL (GET /v1/unknown)");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_report_applies_defaults_and_extensions() {
+        let settings = test_settings(config::InferenceProvider::LMStudio);
+        let state = AppState::new(create_provider(&settings).unwrap(), settings);
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Summarize the plot of Hamlet")],
+            stream: Some(true),
+            extensions: Some(HashMap::from([(
+                "top_k".to_string(),
+                serde_json::json!(40),
+            )])),
+            ..Default::default()
+        };
+
+        let report = build_dry_run_report(&state, &request).await.unwrap();
+
+        assert_eq!(report.provider, "lmstudio");
+        assert_eq!(report.model, "default-model");
+        assert!(report.stream);
+        assert_eq!(report.estimated_prompt_tokens, 7);
+        assert_eq!(report.normalized_request.model, "default-model");
+        // LM Studio's body builder fills in its own defaults
+        assert_eq!(report.upstream_body["model"], "default-model");
+        assert_eq!(report.upstream_body["max_tokens"], 100);
+        assert_eq!(report.upstream_body["top_k"], 40);
+        assert_eq!(report.upstream_body["stream"], true);
+        assert!(report.warnings[0].contains("default model"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_rejects_invalid_extension() {
+        let settings = test_settings(config::InferenceProvider::LMStudio);
+        let state = AppState::new(create_provider(&settings).unwrap(), settings);
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            extensions: Some(HashMap::from([(
+                "top_k".to_string(),
+                serde_json::json!(-1),
+            )])),
+            ..Default::default()
+        };
+
+        let result = build_dry_run_report(&state, &request).await;
+        assert!(matches!(
+            result,
+            Err(ApiError::Provider(ProviderError::InvalidExtension { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_endpoint_does_not_execute_inference() {
+        let executions = Arc::new(AtomicUsize::new(0));
+        let state = AppState::new(
+            Arc::new(CountingProvider {
+                executions: executions.clone(),
+            }),
+            test_settings(config::InferenceProvider::LMStudio),
+        );
+        let app = Router::new()
+            .route("/v1/chat/completions/dry-run", post(dry_run_completion))
+            .with_state(state);
+
+        let body = serde_json::json!({
+            "model": "some-model",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/chat/completions/dry-run")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(report["provider"], "counting");
+        assert_eq!(report["model"], "some-model");
+        assert_eq!(report["upstream_body"]["messages"][0]["content"], "Hello");
+        assert_eq!(executions.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_log_level_header_enables_debug_for_that_request_only() {
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(telemetry::RequestLevelFilter::new(
+                tracing_subscriber::EnvFilter::new("info"),
+                true,
+            ))
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(move || make_writer.clone()),
+            );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|request: Request| async move {
+                    let caller = request
+                        .headers()
+                        .get("x-caller")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    debug!("handling request for {}", caller);
+                    "ok"
+                }),
+            )
+            .layer(middleware::from_fn(log_level_override));
+
+        for (caller, level) in [("verbose-client", Some("debug")), ("quiet-client", None)] {
+            let mut builder = Request::builder().uri("/").header("x-caller", caller);
+            if let Some(level) = level {
+                builder = builder.header(LOG_LEVEL_HEADER, level);
+            }
+            let response = app
+                .clone()
+                .oneshot(builder.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let logs = writer.contents();
+        assert!(logs.contains("handling request for verbose-client"));
+        assert!(!logs.contains("handling request for quiet-client"));
+    }
+}
@@ -0,0 +1,588 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::models::Usage;
+use crate::providers::InferenceProvider;
+
+/// Label names and values identifying one series of a metric
+type Labels = Vec<(&'static str, String)>;
+
+/// Gauge holding one value per label set
+#[derive(Debug, Default)]
+pub struct Gauge {
+    values: Mutex<BTreeMap<Labels, f64>>,
+}
+
+impl Gauge {
+    pub fn set(&self, labels: &[(&'static str, &str)], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values.insert(labels, value);
+    }
+
+    pub fn add(&self, labels: &[(&'static str, &str)], delta: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        *values.entry(labels).or_default() += delta;
+    }
+
+    /// Current value of one series, 0 if it was never set
+    pub fn get(&self, labels: &[(&'static str, &str)]) -> f64 {
+        let labels: Labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        values.get(&labels).copied().unwrap_or_default()
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        for (labels, value) in values.iter() {
+            let _ = writeln!(out, "{name}{} {value}", render_labels(labels));
+        }
+    }
+}
+
+/// Monotonic counter holding one value per label set
+#[derive(Debug, Default)]
+pub struct Counter {
+    values: Mutex<BTreeMap<Labels, u64>>,
+}
+
+impl Counter {
+    pub fn inc(&self, labels: &[(&'static str, &str)]) {
+        self.add(labels, 1);
+    }
+
+    pub fn add(&self, labels: &[(&'static str, &str)], amount: u64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        *values.entry(labels).or_default() += amount;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for (labels, value) in values.iter() {
+            let _ = writeln!(out, "{name}{} {value}", render_labels(labels));
+        }
+    }
+}
+
+/// Upper bounds, in seconds, of the latency histogram buckets
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+#[derive(Debug, Default)]
+struct HistogramSeries {
+    /// Cumulative counts, one per `LATENCY_BUCKETS` bound
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/// Latency histogram holding one series per label set
+#[derive(Debug, Default)]
+pub struct Histogram {
+    values: Mutex<BTreeMap<Labels, HistogramSeries>>,
+}
+
+impl Histogram {
+    pub fn observe(&self, labels: &[(&'static str, &str)], seconds: f64) {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        let mut values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let series = values.entry(labels).or_default();
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(series.buckets.iter_mut()) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        series.count += 1;
+        series.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let values = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (labels, series) in values.iter() {
+            let bounds = LATENCY_BUCKETS.iter().map(f64::to_string);
+            let counts = series.buckets.iter().copied();
+            for (le, count) in bounds
+                .chain(["+Inf".to_string()])
+                .zip(counts.chain([series.count]))
+            {
+                let mut labels = labels.clone();
+                labels.push(("le", le));
+                let _ = writeln!(out, "{name}_bucket{} {count}", render_labels(&labels));
+            }
+            let labels = render_labels(labels);
+            let _ = writeln!(out, "{name}_sum{labels} {}", series.sum);
+            let _ = writeln!(out, "{name}_count{labels} {}", series.count);
+        }
+    }
+}
+
+/// Milestones of one upstream call, each measured from when it was dispatched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UpstreamPhase {
+    /// A new connection was established; absent when a pooled one was reused
+    Connect,
+    /// Response headers, or the first chunk for providers without HTTP
+    FirstByte,
+    /// The whole response, or the end of the stream
+    Total,
+}
+
+impl UpstreamPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpstreamPhase::Connect => "connect",
+            UpstreamPhase::FirstByte => "first_byte",
+            UpstreamPhase::Total => "total",
+        }
+    }
+}
+
+/// Phase milestones reached by one request, recorded once it completes, and
+/// how many HTTP requests it took upstream
+#[derive(Debug)]
+pub struct PhaseTimings {
+    started: Instant,
+    reached: Mutex<BTreeMap<UpstreamPhase, Duration>>,
+    attempts: AtomicU32,
+    hedges_fired: AtomicU32,
+    hedge_won: AtomicBool,
+}
+
+tokio::task_local! {
+    static CURRENT_PHASES: Arc<PhaseTimings>;
+}
+
+impl PhaseTimings {
+    pub fn start() -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            reached: Mutex::new(BTreeMap::new()),
+            attempts: AtomicU32::new(0),
+            hedges_fired: AtomicU32::new(0),
+            hedge_won: AtomicBool::new(false),
+        })
+    }
+
+    /// Upstream HTTP requests sent so far, retries and hedges included.
+    /// Providers that don't go through HTTP report none.
+    pub fn attempts(&self) -> u32 {
+        self.attempts.load(Ordering::Relaxed)
+    }
+
+    /// Note that `phase` was reached now. Only the first time counts, so
+    /// retries and hedges report when the earliest attempt got there.
+    pub fn mark(&self, phase: UpstreamPhase) {
+        let mut reached = self.reached.lock().unwrap_or_else(|e| e.into_inner());
+        reached.entry(phase).or_insert_with(|| self.started.elapsed());
+    }
+
+    /// Run `call` with these timings as the target of `mark_phase`
+    pub async fn scope<F: Future>(self: &Arc<Self>, call: F) -> F::Output {
+        CURRENT_PHASES.scope(self.clone(), call).await
+    }
+
+    pub fn record(&self, metrics: &Metrics, provider: &str) {
+        let reached = self.reached.lock().unwrap_or_else(|e| e.into_inner());
+        for (phase, elapsed) in reached.iter() {
+            metrics.upstream_phase_seconds.observe(
+                &[("provider", provider), ("phase", phase.as_str())],
+                elapsed.as_secs_f64(),
+            );
+        }
+    }
+
+    /// Count the hedges this request sent, whether or not phases are recorded
+    pub fn record_hedges(&self, metrics: &Metrics, provider: &str) {
+        let provider = [("provider", provider)];
+        let hedges_fired = self.hedges_fired.load(Ordering::Relaxed);
+        if hedges_fired > 0 {
+            metrics.hedges_fired_total.add(&provider, hedges_fired.into());
+        }
+        if self.hedge_won.load(Ordering::Relaxed) {
+            metrics.hedge_wins_total.inc(&provider);
+        }
+    }
+}
+
+/// Mark a phase on the timings of the request being dispatched, if any.
+/// Providers and the HTTP client call this; it is a no-op outside `PhaseTimings::scope`.
+pub fn mark_phase(phase: UpstreamPhase) {
+    let _ = CURRENT_PHASES.try_with(|timings| timings.mark(phase));
+}
+
+/// Count an upstream HTTP request on the request being dispatched, if any
+pub fn mark_attempt() {
+    let _ = CURRENT_PHASES.try_with(|timings| timings.attempts.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Note how many hedges a hedged request sent and whether one of them answered it
+pub fn mark_hedges(fired: u32, won: bool) {
+    let _ = CURRENT_PHASES.try_with(|timings| {
+        timings.hedges_fired.fetch_add(fired, Ordering::Relaxed);
+        if won {
+            timings.hedge_won.store(true, Ordering::Relaxed);
+        }
+    });
+}
+
+/// Process-wide metrics, served in the Prometheus text format at `GET /metrics`
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// 1 if the last background health check of a provider passed, 0 if it failed
+    pub provider_healthy: Gauge,
+    /// Requests rejected by load shedding, by path
+    pub requests_shed_total: Counter,
+    /// Completions in flight per model with a concurrency limit, refreshed on scrape
+    pub model_in_flight: Gauge,
+    /// Time from dispatch to each upstream phase, by provider and phase
+    pub upstream_phase_seconds: Histogram,
+    /// Requests rejected by validation, by `ValidationError` variant
+    pub validation_rejections_total: Counter,
+    /// Chat completions by provider and HTTP status
+    pub completion_requests_total: Counter,
+    /// Chat completion latency by provider, up to the end of the stream for streams
+    pub completion_duration_seconds: Histogram,
+    /// Chat completions being handled, including streams still being sent
+    pub completions_in_flight: Gauge,
+    /// Tokens reported by providers, by model and type (prompt, completion, total)
+    pub tokens_total: Counter,
+    /// Duplicate upstream requests sent by hedging, by provider
+    pub hedges_fired_total: Counter,
+    /// Hedged requests answered by a duplicate rather than the original, by provider
+    pub hedge_wins_total: Counter,
+}
+
+/// Request-level metrics of one chat completion. They are recorded when it
+/// is dropped: once the response is built, or at the end of a stream.
+pub struct CompletionRecorder {
+    metrics: Arc<Metrics>,
+    started: Instant,
+    provider: String,
+    model: String,
+    status: u16,
+    usage: Option<Usage>,
+}
+
+impl CompletionRecorder {
+    pub fn start(metrics: Arc<Metrics>) -> Self {
+        metrics.completions_in_flight.add(&[], 1.0);
+        Self {
+            metrics,
+            started: Instant::now(),
+            provider: String::new(),
+            model: String::new(),
+            status: 200,
+            usage: None,
+        }
+    }
+
+    /// Provider and model the completion ended up with
+    pub fn target(&mut self, provider: &str, model: &str) {
+        self.provider = provider.to_string();
+        self.model = model.to_string();
+    }
+
+    pub fn status(&mut self, status: u16) {
+        self.status = status;
+    }
+
+    pub fn usage(&mut self, usage: &Usage) {
+        self.usage = Some(usage.clone());
+    }
+}
+
+impl Drop for CompletionRecorder {
+    fn drop(&mut self) {
+        let metrics = &self.metrics;
+        metrics.completions_in_flight.add(&[], -1.0);
+        let provider = ("provider", self.provider.as_str());
+        metrics
+            .completion_requests_total
+            .inc(&[provider, ("status", &self.status.to_string())]);
+        metrics
+            .completion_duration_seconds
+            .observe(&[provider], self.started.elapsed().as_secs_f64());
+        let Some(ref usage) = self.usage else {
+            return;
+        };
+        for (kind, tokens) in [
+            ("prompt", usage.prompt_tokens),
+            ("completion", usage.completion_tokens),
+            ("total", usage.total_tokens),
+        ] {
+            if let Some(tokens) = tokens {
+                metrics
+                    .tokens_total
+                    .add(&[("model", &self.model), ("type", kind)], tokens.into());
+            }
+        }
+    }
+}
+
+/// Response extension set by `ApiError` naming the validation rule that
+/// rejected the request
+#[derive(Debug, Clone, Copy)]
+pub struct ValidationRejection(pub &'static str);
+
+impl Metrics {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.provider_healthy.render(
+            &mut out,
+            "provider_healthy",
+            "Whether the last background provider health check passed (1) or failed (0)",
+        );
+        self.requests_shed_total.render(
+            &mut out,
+            "requests_shed_total",
+            "Requests rejected with 503 because the completion queue was too deep",
+        );
+        self.model_in_flight.render(
+            &mut out,
+            "model_in_flight",
+            "Completions in flight for each model with a concurrency limit",
+        );
+        self.upstream_phase_seconds.render(
+            &mut out,
+            "upstream_phase_seconds",
+            "Seconds from dispatching a request upstream to connect, first byte and completion",
+        );
+        self.validation_rejections_total.render(
+            &mut out,
+            "validation_rejections_total",
+            "Requests rejected with 400 by request validation, by rule",
+        );
+        self.completion_requests_total.render(
+            &mut out,
+            "completion_requests_total",
+            "Chat completions by provider and HTTP status",
+        );
+        self.completion_duration_seconds.render(
+            &mut out,
+            "completion_duration_seconds",
+            "Seconds to serve a chat completion, to the end of the stream for streams",
+        );
+        self.completions_in_flight.render(
+            &mut out,
+            "completions_in_flight",
+            "Chat completions being handled, including streams still being sent",
+        );
+        self.tokens_total.render(
+            &mut out,
+            "tokens_total",
+            "Tokens reported by providers, by model and type",
+        );
+        self.hedges_fired_total.render(
+            &mut out,
+            "hedges_fired_total",
+            "Duplicate upstream requests sent because the first was slow, by provider",
+        );
+        self.hedge_wins_total.render(
+            &mut out,
+            "hedge_wins_total",
+            "Hedged requests answered by a duplicate rather than the original, by provider",
+        );
+        out
+    }
+}
+
+fn render_labels(labels: &Labels) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<_> = labels
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{name}=\"{value}\"")
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Run the provider health check once and record the result
+pub async fn record_provider_health(metrics: &Metrics, provider: &dyn InferenceProvider) {
+    let healthy = match provider.health_check().await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!(provider = provider.name(), error = %e, "Background health check failed");
+            false
+        }
+    };
+    debug!(provider = provider.name(), healthy, "Recorded provider health");
+    metrics
+        .provider_healthy
+        .set(&[("provider", provider.name())], if healthy { 1.0 } else { 0.0 });
+}
+
+/// Poll provider health every `interval`, independently of request traffic
+pub fn spawn_health_poller(
+    metrics: Arc<Metrics>,
+    provider: Arc<dyn InferenceProvider>,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            record_provider_health(&metrics, provider.as_ref()).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CompletionRequest, CompletionResponse};
+    use crate::providers::{
+        BoxFuture, InferenceRequest, InferenceResponse, ProviderError,
+        standard_completion_response,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Provider whose health check result can be flipped from the test
+    struct FlakyProvider {
+        healthy: AtomicBool,
+    }
+
+    impl InferenceProvider for FlakyProvider {
+        fn execute(
+            &self,
+            _request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            Box::pin(async { Err(ProviderError::ConnectTimeout) })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            standard_completion_response(response, original_request, self.name())
+        }
+
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+            let healthy = self.healthy.load(Ordering::SeqCst);
+            Box::pin(async move {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err(ProviderError::ConnectionFailed("down".to_string()))
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_provider_healthy_gauge_flips_with_health_check() {
+        let metrics = Metrics::default();
+        let provider = FlakyProvider {
+            healthy: AtomicBool::new(true),
+        };
+
+        record_provider_health(&metrics, &provider).await;
+        assert!(metrics.render().contains("provider_healthy{provider=\"flaky\"} 1\n"));
+
+        provider.healthy.store(false, Ordering::SeqCst);
+        record_provider_health(&metrics, &provider).await;
+        let rendered = metrics.render();
+        assert!(rendered.contains("provider_healthy{provider=\"flaky\"} 0\n"));
+        assert!(rendered.contains("# TYPE provider_healthy gauge\n"));
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let histogram = Histogram::default();
+        histogram.observe(&[("phase", "total")], 0.02);
+        histogram.observe(&[("phase", "total")], 3.0);
+        histogram.observe(&[("phase", "total")], 120.0);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "latency", "Latency");
+        assert!(out.contains("# TYPE latency histogram\n"));
+        assert!(out.contains("latency_bucket{phase=\"total\",le=\"0.01\"} 0\n"));
+        assert!(out.contains("latency_bucket{phase=\"total\",le=\"0.025\"} 1\n"));
+        assert!(out.contains("latency_bucket{phase=\"total\",le=\"5\"} 2\n"));
+        assert!(out.contains("latency_bucket{phase=\"total\",le=\"+Inf\"} 3\n"));
+        assert!(out.contains("latency_sum{phase=\"total\"} 123.02\n"));
+        assert!(out.contains("latency_count{phase=\"total\"} 3\n"));
+    }
+
+    #[tokio::test]
+    async fn test_phases_marked_only_inside_scope_and_only_once() {
+        let metrics = Metrics::default();
+        let timings = PhaseTimings::start();
+
+        mark_phase(UpstreamPhase::Connect);
+        timings
+            .scope(async {
+                mark_phase(UpstreamPhase::FirstByte);
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                mark_phase(UpstreamPhase::FirstByte);
+            })
+            .await;
+        timings.record(&metrics, "mock");
+
+        let rendered = metrics.render();
+        assert!(!rendered.contains("phase=\"connect\""));
+        // The second mark came after 30ms but the first one stands
+        assert!(rendered.contains(
+            "upstream_phase_seconds_bucket{provider=\"mock\",phase=\"first_byte\",le=\"0.025\"} 1\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_health_poller_records_on_each_tick() {
+        let metrics = Arc::new(Metrics::default());
+        let provider = Arc::new(FlakyProvider {
+            healthy: AtomicBool::new(false),
+        });
+
+        let poller =
+            spawn_health_poller(metrics.clone(), provider.clone(), Duration::from_millis(20));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(metrics.render().contains("provider_healthy{provider=\"flaky\"} 0\n"));
+
+        provider.healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(metrics.render().contains("provider_healthy{provider=\"flaky\"} 1\n"));
+        poller.abort();
+    }
+}
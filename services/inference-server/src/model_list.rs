@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use crate::validations::ValidationError;
+
+/// One entry of `/v1/models` and the provider that serves it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedModel {
+    pub id: String,
+    pub provider: String,
+}
+
+/// A slice of the model list, OpenAI list-envelope style
+#[derive(Debug)]
+pub struct ModelPage {
+    pub models: Vec<ListedModel>,
+    /// More models follow the last one on this page
+    pub has_more: bool,
+}
+
+/// Merge the model lists of several providers, given in precedence order.
+/// The result is sorted by id; an id listed by more than one provider is
+/// kept once, for the first provider that lists it.
+pub fn aggregate<P: AsRef<str>>(
+    sources: impl IntoIterator<Item = (P, Vec<String>)>,
+) -> Vec<ListedModel> {
+    let mut models = BTreeMap::new();
+    for (provider, ids) in sources {
+        for id in ids {
+            models
+                .entry(id)
+                .or_insert_with(|| provider.as_ref().to_string());
+        }
+    }
+    models
+        .into_iter()
+        .map(|(id, provider)| ListedModel { id, provider })
+        .collect()
+}
+
+/// Up to `limit` models following the id `after`. Without a limit the whole
+/// list after the cursor is returned, so unpaginated clients see everything.
+pub fn paginate(
+    models: Vec<ListedModel>,
+    limit: Option<usize>,
+    after: Option<&str>,
+) -> Result<ModelPage, ValidationError> {
+    if limit == Some(0) {
+        return Err(ValidationError::InvalidPagination {
+            param: "limit",
+            reason: "must be at least 1".to_string(),
+        });
+    }
+
+    let start = match after {
+        Some(cursor) => {
+            let position = models
+                .binary_search_by(|model| model.id.as_str().cmp(cursor))
+                .map_err(|_| ValidationError::InvalidPagination {
+                    param: "after",
+                    reason: format!("no model with id '{cursor}'"),
+                })?;
+            position + 1
+        }
+        None => 0,
+    };
+
+    let mut models: Vec<_> = models.into_iter().skip(start).collect();
+    let has_more = limit.is_some_and(|limit| models.len() > limit);
+    if let Some(limit) = limit {
+        models.truncate(limit);
+    }
+    Ok(ModelPage { models, has_more })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(models: &[ListedModel]) -> Vec<&str> {
+        models.iter().map(|model| model.id.as_str()).collect()
+    }
+
+    fn owned(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_aggregate_sorts_by_id() {
+        let models = aggregate([("lmstudio", owned(&["qwen", "llama-70b", "gemma", "llama-8b"]))]);
+
+        assert_eq!(ids(&models), ["gemma", "llama-70b", "llama-8b", "qwen"]);
+    }
+
+    #[test]
+    fn test_aggregate_collapses_duplicates_by_precedence() {
+        let models = aggregate([
+            ("openai", owned(&["gpt-4o", "shared"])),
+            ("lmstudio", owned(&["shared", "qwen", "qwen"])),
+        ]);
+
+        assert_eq!(ids(&models), ["gpt-4o", "qwen", "shared"]);
+        let shared = models.iter().find(|model| model.id == "shared").unwrap();
+        assert_eq!(shared.provider, "openai");
+    }
+
+    #[test]
+    fn test_paginate_walks_three_pages() {
+        let models = aggregate([("mock", owned(&["a", "b", "c", "d", "e", "f", "g"]))]);
+
+        let first = paginate(models.clone(), Some(3), None).unwrap();
+        assert_eq!(ids(&first.models), ["a", "b", "c"]);
+        assert!(first.has_more);
+
+        let second = paginate(models.clone(), Some(3), Some("c")).unwrap();
+        assert_eq!(ids(&second.models), ["d", "e", "f"]);
+        assert!(second.has_more);
+
+        let third = paginate(models.clone(), Some(3), Some("f")).unwrap();
+        assert_eq!(ids(&third.models), ["g"]);
+        assert!(!third.has_more);
+
+        let all = paginate(models, None, None).unwrap();
+        assert_eq!(all.models.len(), 7);
+        assert!(!all.has_more);
+    }
+
+    #[test]
+    fn test_paginate_rejects_unknown_cursor_and_zero_limit() {
+        let models = aggregate([("mock", owned(&["a", "b"]))]);
+
+        let error = paginate(models.clone(), Some(1), Some("zzz")).unwrap_err();
+        assert!(matches!(
+            error,
+            ValidationError::InvalidPagination { param: "after", .. }
+        ));
+        assert_eq!(error.to_openai_error().param, Some("after".to_string()));
+        assert!(matches!(
+            paginate(models, Some(0), None),
+            Err(ValidationError::InvalidPagination { param: "limit", .. })
+        ));
+    }
+}
@@ -57,6 +57,35 @@ impl FinishReason {
 pub enum FormatType {
     Text,
     JsonObject,
+    JsonSchema,
+    /// Any other type; kept so validation can reject it with a proper error
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl FormatType {
+    /// Every type the API knows about, in the order they are listed to clients
+    pub const KNOWN: [FormatType; 3] = [
+        FormatType::Text,
+        FormatType::JsonObject,
+        FormatType::JsonSchema,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            FormatType::Text => "text",
+            FormatType::JsonObject => "json_object",
+            FormatType::JsonSchema => "json_schema",
+            FormatType::Other(name) => name,
+        }
+    }
+}
+
+/// Requested audio output settings (OpenAI `audio` parameter)
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AudioOutput {
+    pub voice: String,
+    pub format: String,
 }
 
 /// Response mode determines whether provider-specific extensions are included in responses
@@ -147,6 +176,12 @@ pub struct CompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>, // Whether to stream responses
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_reasoning: Option<bool>, // Forward reasoning_content deltas (default true)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<String>>, // Output types, e.g. ["text", "audio"]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioOutput>, // Voice and format when audio output is requested
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>, // 0.0 to 2.0
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>, // 0.0 to 1.0
@@ -158,13 +193,10 @@ pub struct CompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[allow(dead_code)] // TODO: Implement tool/function calling support
     pub tool_choice: Option<ToolChoice>,
+    // Legacy fields, rewritten into tools/tool_choice by `legacy_functions`
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[allow(dead_code)]
-    // TODO: Implement tool/function calling support (deprecated, but supported)
     pub functions: Option<Vec<Function>>, // Deprecated: use tools
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[allow(dead_code)]
-    // TODO: Implement tool/function calling support (deprecated, but supported)
     pub function_call: Option<FunctionCallOption>, // Deprecated: use tool_choice
 
     // Additional options
@@ -176,6 +208,9 @@ pub struct CompletionRequest {
 pub struct ResponseFormat {
     #[serde(rename = "type")]
     pub format_type: FormatType,
+    /// Schema definition for `json_schema` (name, schema, strict), forwarded as is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -240,6 +275,30 @@ pub struct CompletionResponse {
     /// Provider-specific extension data (only included in extended response mode)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider_extensions: Option<ProviderExtensions>,
+    /// Non-fatal issues found while serving the request (extended mode, or when
+    /// `inference.expose_warnings` is set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<Warning>>,
+}
+
+/// Something the server did to a request that the caller should know about,
+/// without failing it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+}
+
+impl Warning {
+    pub fn new(code: &str, message: impl Into<String>, param: Option<&str>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            param: param.map(str::to_string),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -321,8 +380,12 @@ pub struct Delta {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>, // Token content
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>, // Reasoning tokens from reasoning models
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_calls: Option<Vec<ToolCallDelta>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_call: Option<FunctionCallDelta>, // Legacy requests only
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub refusal: Option<String>,
 }
 
@@ -344,6 +407,50 @@ pub struct FunctionCallDelta {
     pub arguments: Option<String>,
 }
 
+// ===== Embeddings =====
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EmbeddingRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub input: StringOrArray,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>, // Output size, for models that support shortening
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+impl EmbeddingRequest {
+    /// The texts to embed, in order
+    pub fn inputs(&self) -> Vec<&str> {
+        match self.input {
+            StringOrArray::String(ref text) => vec![text.as_str()],
+            StringOrArray::Array(ref texts) => texts.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EmbeddingResponse {
+    pub object: String, // Always "list"
+    pub data: Vec<Embedding>,
+    pub model: String,
+    pub usage: EmbeddingUsage,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Embedding {
+    pub object: String, // Always "embedding"
+    pub index: u32,
+    pub embedding: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: u32,
+    pub total_tokens: u32,
+}
+
 // ===== Error Response =====
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -351,7 +458,7 @@ pub struct OpenAIErrorResponse {
     pub error: OpenAIError,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OpenAIError {
     pub message: String,
     #[serde(rename = "type")]
@@ -0,0 +1,1332 @@
+use axum::{
+    Json,
+    http::header,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, StreamExt};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info};
+
+use crate::admission::{AdmissionControl, ScopedPermits};
+use crate::config::{OversizedResponsePolicy, Settings, StreamErrorFormat};
+use crate::content_hash::ContentHasher;
+use crate::deprecations::{self, Deprecation};
+use crate::error::ApiError;
+use crate::history::RequestOutcome;
+use crate::legacy_functions;
+use crate::metrics::{CompletionRecorder, Metrics, PhaseTimings, UpstreamPhase};
+use crate::models::{
+    self, CompletionRequest, CompletionResponse, Message, ResponseMode, Role, Warning,
+};
+use crate::providers::{self, InferenceProvider, ProviderStream};
+use crate::responses_api;
+use crate::runtime::RuntimeOptions;
+use crate::shadow::Shadow;
+use crate::telemetry::truncate_for_log;
+use crate::validations::{
+    determine_model, validate_completion_request, validate_message_sequence,
+    validate_model_allowed, validate_provider_capabilities, validate_response_format,
+};
+
+/// Request extension asking for the resolved request in `provider_extensions`.
+/// Handled by the server, so it is never passed on to the provider.
+pub const ECHO_RESOLVED_REQUEST_EXTENSION: &str = "echo_resolved_request";
+
+// Type alias for complex SSE stream type
+type SseStream = Sse<Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>>;
+
+/// State carried through the completion pipeline for one request
+pub struct RequestContext {
+    pub request: CompletionRequest,
+    pub provider: Arc<dyn InferenceProvider>,
+    pub settings: Arc<Settings>,
+    /// Requested (or default) model until `resolve_model` settles it
+    pub model: String,
+    /// Summary picked up by the access log
+    pub outcome: RequestOutcome,
+    /// Attach the resolved request to an extended-mode response
+    pub echo_resolved_request: bool,
+    /// Where upstream phase timings go when `metrics.upstream_phases` is on
+    pub metrics: Option<Arc<Metrics>>,
+    /// Per-provider and per-model concurrency limits; none when unset
+    pub admission: Option<Arc<AdmissionControl>>,
+    /// The client sent `functions`/`function_call`; answer in the same shape
+    pub legacy_functions: bool,
+    /// Wire-format options; derived from `settings` unless the app shares its own
+    pub runtime: Arc<RuntimeOptions>,
+    /// The client asked for a stream but only accepts JSON; collect it into one completion
+    pub buffer_stream: bool,
+    /// Deprecated features the request uses, announced in the response headers
+    pub deprecations: Vec<&'static Deprecation>,
+    /// Provider mirroring a sample of completions for comparison
+    pub shadow: Option<Arc<Shadow>>,
+    /// The request came in on `/v1/responses`; answer in the Responses API shape
+    pub responses_api: bool,
+    /// Who the request's streams count against for `max_concurrent_streams_per_client`
+    pub client: Option<String>,
+}
+
+/// What the provider produced, before it is turned into an HTTP response
+pub enum Dispatched {
+    Completion(Box<CompletionResponse>),
+    /// A completion already serialized for the response body
+    Encoded {
+        response: Box<CompletionResponse>,
+        body: Vec<u8>,
+    },
+    Stream(ProviderStream),
+}
+
+impl RequestContext {
+    pub fn new(
+        request: CompletionRequest,
+        provider: Arc<dyn InferenceProvider>,
+        settings: Arc<Settings>,
+    ) -> Self {
+        let model = request.model.clone().unwrap_or_else(|| {
+            settings
+                .inference
+                .default_model_for(provider.name())
+                .to_string()
+        });
+        let mut outcome = RequestOutcome::new(Some(&model), request.stream == Some(true));
+        // Hash the client's messages as sent, before any preprocessing
+        outcome.content_hashes = settings
+            .logging
+            .content_hash_salt
+            .as_deref()
+            .map(|salt| ContentHasher::new(salt).hash_messages(&request.messages));
+        let runtime = Arc::new(RuntimeOptions::new(&settings));
+        Self {
+            request,
+            provider,
+            settings,
+            model,
+            outcome,
+            echo_resolved_request: false,
+            metrics: None,
+            admission: None,
+            legacy_functions: false,
+            runtime,
+            buffer_stream: false,
+            deprecations: Vec::new(),
+            shadow: None,
+            responses_api: false,
+            client: None,
+        }
+    }
+
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn with_admission(mut self, admission: Arc<AdmissionControl>) -> Self {
+        self.admission = Some(admission);
+        self
+    }
+
+    pub fn with_runtime(mut self, runtime: Arc<RuntimeOptions>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    pub fn with_shadow(mut self, shadow: Arc<Shadow>) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    /// Metrics to record upstream phases into, if that is enabled
+    fn phase_metrics(&self) -> Option<Arc<Metrics>> {
+        self.metrics
+            .clone()
+            .filter(|_| self.settings.metrics.upstream_phases)
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.request.stream == Some(true)
+    }
+
+    /// The route the request came in on, for metric labels
+    pub fn path(&self) -> &'static str {
+        if self.responses_api {
+            "/v1/responses"
+        } else {
+            "/v1/chat/completions"
+        }
+    }
+
+    /// Record a non-fatal issue for the response and the access log
+    pub fn warn(&mut self, warning: Warning) {
+        debug!(code = %warning.code, "{}", warning.message);
+        self.outcome.warnings.push(warning);
+    }
+}
+
+/// Run every stage and convert the result (or the first error) into a response
+pub async fn run(mut ctx: RequestContext) -> Response {
+    let mut recorder = ctx.metrics.clone().map(CompletionRecorder::start);
+    let result = async {
+        detect_deprecations(&mut ctx);
+        translate_legacy_functions(&mut ctx);
+        resolve_model(&mut ctx)?;
+        route(&mut ctx).await?;
+        preprocess(&mut ctx);
+        apply_provider_hints(&mut ctx).await;
+        apply_policies(&ctx)?;
+        let permits = admit(&ctx).await?;
+        let dispatched = dispatch(&ctx).await?;
+        let dispatched = buffer_stream(&ctx, dispatched).await?;
+        mirror_to_shadow(&ctx, &dispatched);
+        let dispatched = postprocess(&mut ctx, dispatched);
+        let dispatched = cap_response_size(&mut ctx, dispatched)?;
+        Ok::<_, ApiError>(hold_permits(dispatched, permits))
+    }
+    .await;
+
+    let mut response = match result {
+        Ok(dispatched) => {
+            let dispatched = match recorder.take() {
+                Some(recorder) => record_completion(&ctx, dispatched, recorder),
+                None => dispatched,
+            };
+            respond(&ctx, dispatched)
+        }
+        Err(e) => e.into_response(),
+    };
+    if let Some(ref mut recorder) = recorder {
+        recorder.target(ctx.provider.name(), &ctx.model);
+        recorder.status(response.status().as_u16());
+    }
+    deprecations::insert_headers(&ctx.deprecations, response.headers_mut());
+    response.extensions_mut().insert(ctx.outcome);
+    response
+}
+
+/// Note deprecated features of the request as sent, before any shim rewrites them
+pub fn detect_deprecations(ctx: &mut RequestContext) {
+    if ctx.runtime.deprecations.is_empty() {
+        return;
+    }
+    ctx.deprecations = deprecations::used_by(&ctx.runtime.deprecations, &ctx.request);
+    for deprecation in ctx.deprecations.clone() {
+        ctx.warn(deprecation.warning());
+    }
+}
+
+/// Rewrite `functions`/`function_call` into tools before validation sees the request
+pub fn translate_legacy_functions(ctx: &mut RequestContext) {
+    if !legacy_functions::is_legacy(&ctx.request) {
+        return;
+    }
+    legacy_functions::translate_request(&mut ctx.request);
+    ctx.legacy_functions = true;
+}
+
+/// Validate the request shape and settle which model serves it
+pub fn resolve_model(ctx: &mut RequestContext) -> Result<(), ApiError> {
+    let inference = &ctx.settings.inference;
+
+    // Validate the incoming request structure
+    validate_completion_request(&ctx.request, inference.max_tokens_limit)?;
+
+    // Catch repeated roles before upstreams that require alternation reject them
+    if inference.enforce_alternating_roles {
+        validate_message_sequence(&ctx.request)?;
+    }
+
+    // Determine which model to use (applies defaults if needed)
+    let model = determine_model(
+        ctx.request.model.as_deref(),
+        inference.default_model_for(ctx.provider.name()),
+        inference.allowed_models.as_ref(),
+    )?;
+
+    // Validate the model is allowed (if restrictions are configured)
+    validate_model_allowed(model, inference.allowed_models.as_ref())?;
+
+    debug!("Using model: {}", model);
+    ctx.model = model.to_string();
+    Ok(())
+}
+
+/// Hand the request to the backend serving its model when the provider routes
+/// between several, so the later stages see that backend
+pub async fn route(ctx: &mut RequestContext) -> Result<(), ApiError> {
+    if let Some(backend) = ctx
+        .provider
+        .route(&ctx.model)
+        .await
+        .map_err(ApiError::Provider)?
+    {
+        debug!(model = %ctx.model, backend = backend.name(), "Routed request");
+        ctx.provider = backend;
+    }
+    Ok(())
+}
+
+/// Rewrite the request before it is sent: take out server-handled extensions
+/// and add the model's configured system prompt unless the client supplied its own
+pub fn preprocess(ctx: &mut RequestContext) {
+    take_echo_extension(ctx);
+
+    let Some(prompt) = ctx.settings.inference.model_system_prompts.get(&ctx.model) else {
+        return;
+    };
+    if ctx.request.messages.iter().any(|m| m.role == Role::System) {
+        return;
+    }
+    debug!(model = %ctx.model, "Injecting model system prompt");
+    ctx.request
+        .messages
+        .insert(0, Message::new(Role::System, prompt.as_str()));
+}
+
+/// Remove `echo_resolved_request` from the extensions and decide whether to honor it
+fn take_echo_extension(ctx: &mut RequestContext) {
+    let Some(extensions) = ctx.request.extensions.as_mut() else {
+        return;
+    };
+    let Some(value) = extensions.remove(ECHO_RESOLVED_REQUEST_EXTENSION) else {
+        return;
+    };
+    if extensions.is_empty() {
+        ctx.request.extensions = None;
+    }
+
+    if value != serde_json::Value::Bool(true) {
+        return;
+    }
+    if ctx.settings.inference.allow_resolved_request_echo {
+        ctx.echo_resolved_request = true;
+    } else {
+        ctx.warn(Warning::new(
+            "echo_not_allowed",
+            "echo_resolved_request is not enabled on this server and was ignored",
+            Some("extensions.echo_resolved_request"),
+        ));
+    }
+}
+
+/// Let the provider override the client's streaming choice for this model
+pub async fn apply_provider_hints(ctx: &mut RequestContext) {
+    if let Some(stream) = ctx.provider.streaming_preference(&ctx.model).await
+        && stream != ctx.is_stream()
+    {
+        ctx.warn(Warning::new(
+            "stream_overridden",
+            format!("Model '{}' is served with stream={stream}", ctx.model),
+            Some("stream"),
+        ));
+        ctx.request.stream = Some(stream);
+        ctx.outcome.stream = stream;
+    }
+}
+
+/// Reject requests the configured provider can't serve
+pub fn apply_policies(ctx: &RequestContext) -> Result<(), ApiError> {
+    validate_provider_capabilities(
+        &ctx.request,
+        ctx.provider.supports_streaming(),
+        ctx.runtime.supports_tools,
+        ctx.provider.supports_audio_output(),
+        ctx.provider.max_stop_sequences(),
+        ctx.provider.name(),
+    )?;
+    validate_response_format(
+        &ctx.request,
+        ctx.provider.supports_json_mode(),
+        ctx.settings.inference.allowed_response_formats.as_deref(),
+    )?;
+    Ok(())
+}
+
+/// Wait for the provider and model slots. The global slot is taken earlier by
+/// the route middleware, so the order is always global, provider, model.
+/// A stream also takes one of its client's stream slots, checked first so an
+/// over-limit client is turned away without waiting.
+pub async fn admit(ctx: &RequestContext) -> Result<ScopedPermits, ApiError> {
+    let Some(ref admission) = ctx.admission else {
+        return Ok(ScopedPermits::default());
+    };
+    let stream = match ctx.client {
+        Some(ref client) if ctx.is_stream() && !ctx.buffer_stream => {
+            admission.admit_stream(client)?
+        }
+        _ => None,
+    };
+    let admitted = admission
+        .admit_scoped(ctx.provider.name(), &ctx.model)
+        .await
+        .map(|permits| permits.with_stream(stream));
+    if admitted.is_err()
+        && let Some(ref metrics) = ctx.metrics
+    {
+        metrics.requests_shed_total.inc(&[("path", ctx.path())]);
+    }
+    admitted
+}
+
+/// Hand the request metrics the usage of a completion, or keep them with the
+/// stream so they are recorded when it ends
+fn record_completion(
+    ctx: &RequestContext,
+    dispatched: Dispatched,
+    mut recorder: CompletionRecorder,
+) -> Dispatched {
+    recorder.target(ctx.provider.name(), &ctx.model);
+    match dispatched {
+        Dispatched::Stream(stream) => Dispatched::Stream(Box::pin(stream.map(move |chunk| {
+            if let Ok(ref chunk) = chunk
+                && let Some(ref usage) = chunk.usage
+            {
+                recorder.usage(usage);
+            }
+            chunk
+        }))),
+        Dispatched::Completion(ref response) | Dispatched::Encoded { ref response, .. } => {
+            if let Some(ref usage) = response.usage {
+                recorder.usage(usage);
+            }
+            dispatched
+        }
+    }
+}
+
+/// Keep the slots until a stream ends; a completion releases them once built
+fn hold_permits(dispatched: Dispatched, permits: ScopedPermits) -> Dispatched {
+    match dispatched {
+        Dispatched::Stream(stream) => Dispatched::Stream(Box::pin(stream.map(move |chunk| {
+            let _permits = &permits;
+            chunk
+        }))),
+        completion => completion,
+    }
+}
+
+/// Send the request to the provider. The provider must answer (or start streaming)
+/// within `inference.timeout_secs`, stretched while the provider is busy when
+/// `limits.concurrency.adaptive_timeout` is set, as are the upstream requests'
+/// own timeouts; streams are also cut off if they stall.
+pub async fn dispatch(ctx: &RequestContext) -> Result<Dispatched, ApiError> {
+    let stretch = match ctx.admission {
+        Some(ref admission) => admission.timeout_factor(ctx.provider.name()),
+        None => 1.0,
+    };
+    let budget = Duration::from_secs(ctx.settings.inference.timeout_secs).mul_f64(stretch);
+    let timings = PhaseTimings::start();
+
+    if ctx.is_stream() {
+        let call = providers::with_budget(
+            budget,
+            stretch,
+            ctx.provider.stream(&ctx.request, &ctx.model),
+        );
+        let result = timings.scope(call).await;
+        record_attempts(&timings);
+        let mut stream = result.map_err(ApiError::Provider)?;
+        if let Some(metrics) = ctx.phase_metrics() {
+            stream = record_stream_phases(stream, timings, metrics, ctx.provider.name());
+        }
+        if let Some(idle_secs) = ctx.settings.inference.stream_stall_timeout_secs {
+            stream = providers::with_stall_timeout(stream, Duration::from_secs(idle_secs));
+        }
+        if let Some(capacity) = ctx.settings.inference.stream_buffer_chunks {
+            stream = providers::with_bounded_buffer(stream, capacity);
+        }
+        // After the stall timeout, which the keep-alive chunks would otherwise reset
+        if let Some(interval) = ctx.runtime.first_token_keep_alive
+            && !ctx.buffer_stream
+        {
+            stream = providers::with_first_token_keep_alive(stream, interval, &ctx.model);
+        }
+        return Ok(Dispatched::Stream(stream));
+    }
+
+    let call = providers::with_budget(
+        budget,
+        stretch,
+        ctx.provider.generate(&ctx.request, &ctx.model),
+    );
+    let result = timings.scope(call).await;
+    let attempts = record_attempts(&timings);
+    if let Some(ref metrics) = ctx.metrics {
+        timings.record_hedges(metrics, ctx.provider.name());
+    }
+    let mut response = result.map_err(ApiError::Provider)?;
+    if attempts > 0 {
+        providers::attach_provider_data(
+            &mut response,
+            &ctx.request,
+            ctx.provider.name(),
+            "upstream_attempts",
+            serde_json::json!(attempts),
+        );
+    }
+    if let Some(metrics) = ctx.phase_metrics() {
+        timings.mark(UpstreamPhase::Total);
+        timings.record(&metrics, ctx.provider.name());
+    }
+    Ok(Dispatched::Completion(Box::new(response)))
+}
+
+/// Note the upstream attempts of a dispatch on the request span
+fn record_attempts(timings: &PhaseTimings) -> u32 {
+    let attempts = timings.attempts();
+    if attempts > 0 {
+        tracing::Span::current().record("upstream_attempts", attempts);
+    }
+    attempts
+}
+
+/// Collect the stream into a completion when `buffer_stream` is set, so the usual
+/// completion post-processing applies to it
+pub async fn buffer_stream(
+    ctx: &RequestContext,
+    dispatched: Dispatched,
+) -> Result<Dispatched, ApiError> {
+    match dispatched {
+        Dispatched::Stream(stream) if ctx.buffer_stream => {
+            debug!("Buffering stream for a JSON-only client");
+            let response = providers::collect_stream(stream)
+                .await
+                .map_err(ApiError::Provider)?;
+            Ok(Dispatched::Completion(Box::new(response)))
+        }
+        other => Ok(other),
+    }
+}
+
+/// Send a sample of completions to the shadow provider as well. Streams the
+/// client reads as SSE are not mirrored, since there is no full response to compare.
+pub fn mirror_to_shadow(ctx: &RequestContext, dispatched: &Dispatched) {
+    let (Some(shadow), Dispatched::Completion(response)) = (&ctx.shadow, dispatched) else {
+        return;
+    };
+    if shadow.sampled() {
+        shadow.mirror(ctx.request.clone(), ctx.model.clone(), response);
+    }
+}
+
+/// Mark the first chunk and the end of a stream, recording the timings once it ends.
+/// Streams dropped early by the client are not recorded.
+fn record_stream_phases(
+    stream: ProviderStream,
+    timings: Arc<PhaseTimings>,
+    metrics: Arc<Metrics>,
+    provider: &str,
+) -> ProviderStream {
+    let provider = provider.to_string();
+    let first_chunk = timings.clone();
+    let chunks = stream.inspect(move |_| first_chunk.mark(UpstreamPhase::FirstByte));
+    let end = futures_util::stream::once(async move {
+        timings.mark(UpstreamPhase::Total);
+        timings.record(&metrics, &provider);
+    })
+    .filter_map(|()| std::future::ready(None));
+    Box::pin(chunks.chain(end))
+}
+
+/// Apply response transforms and record usage for the access log
+pub fn postprocess(ctx: &mut RequestContext, dispatched: Dispatched) -> Dispatched {
+    let footer = ctx.settings.inference.response_footer.clone();
+
+    match dispatched {
+        Dispatched::Stream(mut stream) => {
+            // Before the footer, which is emitted ahead of every finish chunk
+            if ctx.settings.inference.normalize_stream_finish {
+                stream = providers::normalize_stream_finish(stream);
+            }
+            if ctx.legacy_functions {
+                stream = legacy_functions::convert_stream(stream);
+            }
+            if ctx.request.include_reasoning == Some(false) {
+                stream = providers::strip_reasoning_from_stream(stream);
+            }
+            if ctx.settings.inference.strip_control_chars {
+                stream = providers::strip_control_chars_from_stream(stream);
+            }
+            if let Some(footer) = footer {
+                stream = providers::append_footer_to_stream(stream, footer);
+            }
+            Dispatched::Stream(stream)
+        }
+        Dispatched::Completion(mut response) => {
+            if ctx.legacy_functions {
+                legacy_functions::convert_response(&mut response);
+            }
+            if ctx.settings.inference.strip_control_chars {
+                providers::strip_response_control_chars(&mut response);
+            }
+            if let Some(ref footer) = footer {
+                providers::apply_response_footer(&mut response, footer);
+            }
+            if ctx.echo_resolved_request
+                && let Some(resolved) = resolved_request(ctx)
+            {
+                providers::attach_provider_data(
+                    &mut response,
+                    &ctx.request,
+                    ctx.provider.name(),
+                    "resolved_request",
+                    resolved,
+                );
+            }
+            if ctx.settings.inference.extended_includes_request
+                && let Some(effective) = resolved_request(ctx)
+            {
+                providers::attach_provider_data(
+                    &mut response,
+                    &ctx.request,
+                    ctx.provider.name(),
+                    "effective_request",
+                    effective,
+                );
+            }
+            attach_warnings(ctx, &mut response);
+            ctx.outcome.record_response(&response);
+            Dispatched::Completion(response)
+        }
+        encoded @ Dispatched::Encoded { .. } => encoded,
+    }
+}
+
+/// Copy the warnings so far into the response, if the client gets to see them
+fn attach_warnings(ctx: &RequestContext, response: &mut CompletionResponse) {
+    if !ctx.outcome.warnings.is_empty()
+        && (ctx.request.response_mode == Some(ResponseMode::Extended)
+            || ctx.settings.inference.expose_warnings)
+    {
+        response.warnings = Some(ctx.outcome.warnings.clone());
+    }
+}
+
+/// Serialize a completion once and hold it to `server.max_response_bytes`.
+/// Over the cap, the `degrade` policy drops the logprobs with a warning; the
+/// request fails with `response_too_large` under `reject`, or when the
+/// completion is still too large without them. Streams are not capped here.
+pub fn cap_response_size(
+    ctx: &mut RequestContext,
+    dispatched: Dispatched,
+) -> Result<Dispatched, ApiError> {
+    let Some(max_bytes) = ctx.settings.server.max_response_bytes else {
+        return Ok(dispatched);
+    };
+    let Dispatched::Completion(mut response) = dispatched else {
+        return Ok(dispatched);
+    };
+    // Serialization failures are left for `respond` to report
+    let Ok(mut body) = serde_json::to_vec(&response) else {
+        return Ok(Dispatched::Completion(response));
+    };
+
+    let has_logprobs = response
+        .choices
+        .iter()
+        .any(|choice| choice.logprobs.is_some());
+    if body.len() > max_bytes
+        && has_logprobs
+        && ctx.settings.server.oversized_response == OversizedResponsePolicy::Degrade
+    {
+        for choice in &mut response.choices {
+            choice.logprobs = None;
+        }
+        ctx.warn(Warning::new(
+            "logprobs_dropped",
+            format!(
+                "The completion was {} bytes, over the {max_bytes}-byte limit; logprobs were removed",
+                body.len()
+            ),
+            Some("logprobs"),
+        ));
+        attach_warnings(ctx, &mut response);
+        let Ok(degraded) = serde_json::to_vec(&response) else {
+            return Ok(Dispatched::Completion(response));
+        };
+        body = degraded;
+    }
+
+    if body.len() > max_bytes {
+        return Err(ApiError::ResponseTooLarge {
+            size: body.len(),
+            max_bytes,
+        });
+    }
+    Ok(Dispatched::Encoded { response, body })
+}
+
+/// The normalized request as dispatched, with message content truncated per
+/// `logging.max_logged_body_chars`. Only extended-mode responses carry it.
+fn resolved_request(ctx: &RequestContext) -> Option<serde_json::Value> {
+    if ctx.request.response_mode != Some(ResponseMode::Extended) {
+        return None;
+    }
+    let mut inference_req = ctx
+        .provider
+        .build_inference_request(&ctx.request, &ctx.model)
+        .ok()?;
+    let max_chars = ctx.settings.logging.max_logged_body_chars;
+    for message in &mut inference_req.messages {
+        if let Some(content) = message.content.as_mut() {
+            *content = truncate_for_log(content, max_chars).into_owned();
+        }
+    }
+
+    let mut resolved = serde_json::to_value(&inference_req).ok()?;
+    resolved["provider"] = serde_json::json!(ctx.provider.name());
+    resolved["extensions"] = serde_json::json!(ctx.request.extensions);
+    Some(resolved)
+}
+
+/// Turn the provider output into JSON or an SSE stream
+pub fn respond(ctx: &RequestContext, dispatched: Dispatched) -> Response {
+    let model = ctx.model.as_str();
+
+    match dispatched {
+        Dispatched::Stream(stream) => {
+            info!(model = model, stream = true, "Streaming completion started");
+            sse_response(stream, &ctx.runtime).into_response()
+        }
+        Dispatched::Completion(response) | Dispatched::Encoded { response, .. }
+            if ctx.responses_api =>
+        {
+            log_completion(model, &response);
+            Json(responses_api::from_completion(&response)).into_response()
+        }
+        Dispatched::Completion(response) => {
+            log_completion(model, &response);
+            Json(response).into_response()
+        }
+        Dispatched::Encoded { response, body } => {
+            log_completion(model, &response);
+            ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+        }
+    }
+}
+
+fn log_completion(model: &str, response: &CompletionResponse) {
+    // Log only if we have usage information
+    if let Some(ref usage) = response.usage {
+        info!(
+            model = model,
+            choices_count = response.choices.len(),
+            total_tokens = ?usage.total_tokens,
+            prompt_tokens = ?usage.prompt_tokens,
+            completion_tokens = ?usage.completion_tokens,
+            stream = false,
+            "Completion successful"
+        );
+    } else {
+        info!(
+            model = model,
+            choices_count = response.choices.len(),
+            stream = false,
+            "Completion successful (no usage data)"
+        );
+    }
+}
+
+/// Convert provider chunks into SSE events, terminated by `[DONE]` unless
+/// `runtime.stream_done_marker` is off
+fn sse_response(stream: ProviderStream, runtime: &RuntimeOptions) -> SseStream {
+    let error_format = runtime.stream_errors;
+    let events = stream.map(move |chunk_result| {
+        match chunk_result {
+            Ok(chunk) => {
+                // Format as SSE: "data: {json}\n\n"
+                match serde_json::to_string(&chunk) {
+                    Ok(json) => Ok(Event::default().data(json)),
+                    Err(e) => {
+                        tracing::error!("Failed to serialize chunk: {}", e);
+                        // Send OpenAI-compatible error event
+                        let error = models::OpenAIError {
+                            message: format!("Serialization error: {}", e),
+                            error_type: "api_error".to_string(),
+                            param: None,
+                            code: Some("stream_serialization_error".to_string()),
+                        };
+                        Ok(error_event(error, error_format))
+                    }
+                }
+            }
+            // Send OpenAI-compatible error in stream
+            Err(e) => Ok(error_event(e.to_openai_error(), error_format)),
+        }
+    });
+
+    let done = runtime
+        .done_marker
+        .then(|| Ok(Event::default().data("[DONE]")));
+    let sse_stream = events.chain(futures_util::stream::iter(done));
+
+    let sse = Sse::new(Box::pin(sse_stream) as Pin<Box<dyn Stream<Item = Result<Event, Infallible>> + Send>>);
+    match runtime.keep_alive {
+        Some(interval) => sse.keep_alive(KeepAlive::new().interval(interval)),
+        None => sse,
+    }
+}
+
+/// An error as an SSE event, framed per `runtime.stream_errors`
+fn error_event(error: models::OpenAIError, format: StreamErrorFormat) -> Event {
+    let error_json = serde_json::to_string(&models::OpenAIErrorResponse { error })
+        .unwrap_or_else(|_| r#"{"error":{"message":"Stream error","type":"api_error"}}"#.to_string());
+    let event = match format {
+        StreamErrorFormat::Data => Event::default(),
+        StreamErrorFormat::Event => Event::default().event("error"),
+    };
+    event.data(error_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::lmstudio::LMStudioProvider;
+    use crate::providers::mock::MockProvider;
+    use crate::providers::ollama::OllamaProvider;
+    use crate::providers::ProviderError;
+    use std::collections::HashMap;
+    use crate::validations::ValidationError;
+    use axum::http::StatusCode;
+    use tempfile::TempDir;
+
+    /// Mock-backed settings with a single `mock-test` scenario
+    fn mock_context(request: CompletionRequest) -> (TempDir, RequestContext) {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("test.yaml"),
+            r#"
+responses:
+  - text: "Mock reply"
+    model_used: "mock-test"
+    prompt_tokens: 5
+    completion_tokens: 3
+    total_tokens: 8
+settings:
+  mode: first
+"#,
+        )
+        .unwrap();
+
+        let settings: Settings = serde_yml::from_str(&format!(
+            r#"
+server: {{}}
+inference:
+  provider: mock
+  base_url: "http://localhost:1234"
+  default_model: mock-test
+  responses_dir: {:?}
+  response_footer: " [footer]"
+  model_system_prompts:
+    mock-test: "You are a mock."
+logging: {{}}
+"#,
+            temp_dir.path()
+        ))
+        .unwrap();
+        let settings = Arc::new(settings);
+        let provider = Arc::new(MockProvider::new(settings.clone()).unwrap());
+        (temp_dir, RequestContext::new(request, provider, settings))
+    }
+
+    fn user_request(model: &str, stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            model: Some(model.to_string()),
+            messages: vec![Message::new(Role::User, "Hello")],
+            stream: Some(stream),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_model_rejects_invalid_request() {
+        let (_dir, mut ctx) = mock_context(CompletionRequest::default());
+
+        let result = resolve_model(&mut ctx);
+
+        assert!(matches!(
+            result,
+            Err(ApiError::Validation(ValidationError::EmptyMessages))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_model_applies_default() {
+        let mut request = user_request("ignored", false);
+        request.model = None;
+        let (_dir, mut ctx) = mock_context(request);
+
+        resolve_model(&mut ctx).unwrap();
+
+        assert_eq!(ctx.model, "mock-test");
+    }
+
+    #[test]
+    fn test_each_provider_applies_its_own_default_model() {
+        let (_dir, ctx) = mock_context(CompletionRequest::default());
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.provider_default_models = HashMap::from([
+            ("mock".to_string(), "mock-fallback".to_string()),
+            ("lmstudio".to_string(), "qwen2.5-7b-instruct".to_string()),
+        ]);
+        let settings = Arc::new(settings);
+        let providers: [Arc<dyn InferenceProvider>; 3] = [
+            Arc::new(MockProvider::new(settings.clone()).unwrap()),
+            Arc::new(LMStudioProvider::new(settings.clone()).unwrap()),
+            Arc::new(OllamaProvider::new(settings.clone()).unwrap()),
+        ];
+        let mut request = user_request("ignored", false);
+        request.model = None;
+
+        let resolved: Vec<_> = providers
+            .into_iter()
+            .map(|provider| {
+                let mut ctx = RequestContext::new(request.clone(), provider, settings.clone());
+                resolve_model(&mut ctx).unwrap();
+                ctx.model
+            })
+            .collect();
+
+        // Ollama has no entry and falls back to the global default
+        assert_eq!(resolved, ["mock-fallback", "qwen2.5-7b-instruct", "mock-test"]);
+    }
+
+    #[test]
+    fn test_preprocess_injects_system_prompt_only_for_its_model() {
+        let (_dir, mut ctx) = mock_context(user_request("mock-test", false));
+        resolve_model(&mut ctx).unwrap();
+        preprocess(&mut ctx);
+        assert_eq!(ctx.request.messages.len(), 2);
+        assert_eq!(ctx.request.messages[0].role, Role::System);
+        assert_eq!(
+            ctx.request.messages[0].content.as_deref(),
+            Some("You are a mock.")
+        );
+
+        let (_dir, mut ctx) = mock_context(user_request("mock-other", false));
+        resolve_model(&mut ctx).unwrap();
+        preprocess(&mut ctx);
+        assert_eq!(ctx.request.messages.len(), 1);
+
+        // A client system message is left alone
+        let mut request = user_request("mock-test", false);
+        request
+            .messages
+            .insert(0, Message::new(Role::System, "Client prompt"));
+        let (_dir, mut ctx) = mock_context(request);
+        resolve_model(&mut ctx).unwrap();
+        preprocess(&mut ctx);
+        assert_eq!(ctx.request.messages.len(), 2);
+        assert_eq!(ctx.request.messages[0].content.as_deref(), Some("Client prompt"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_surfaces_provider_error() {
+        let (_dir, mut ctx) = mock_context(user_request("mock-missing", false));
+        resolve_model(&mut ctx).unwrap();
+        apply_policies(&ctx).unwrap();
+
+        let result = dispatch(&ctx).await;
+
+        assert!(matches!(
+            result,
+            Err(ApiError::Provider(ProviderError::Configuration(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_timeout_gives_a_busy_upstream_more_time() {
+        use crate::config::{AdaptiveTimeoutConfig, HttpConfigSchema, InferenceProvider as Backend};
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({
+                        "id": "test-123",
+                        "object": "chat.completion",
+                        "created": 1234567890,
+                        "model": "llama",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": "Slow reply"},
+                            "finish_reason": "stop"
+                        }]
+                    }))
+                    .set_delay(Duration::from_millis(1500)),
+            )
+            .mount(&server)
+            .await;
+        let mut settings = Settings::for_tests(Backend::LMStudio, &server.uri(), "llama");
+        settings.inference.timeout_secs = 1;
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 1,
+            connect_timeout_secs: 1,
+            ..Default::default()
+        });
+        settings.limits.concurrency.per_provider = HashMap::from([("lmstudio".to_string(), 4)]);
+        settings.limits.concurrency.adaptive_timeout = Some(AdaptiveTimeoutConfig {
+            per_in_flight: 1.0,
+            max_factor: 2.0,
+        });
+        let settings = Arc::new(settings);
+        let admission = Arc::new(AdmissionControl::new(&settings));
+        let provider = Arc::new(LMStudioProvider::new(settings.clone()).unwrap());
+        let ctx = RequestContext::new(user_request("llama", false), provider, settings)
+            .with_admission(admission.clone());
+        let _own_slot = admission.admit_scoped("lmstudio", "llama").await.unwrap();
+
+        // Alone on the provider, the one-second timeouts cut the reply off
+        assert!(matches!(
+            dispatch(&ctx).await,
+            Err(ApiError::Provider(ProviderError::DeadlineExceeded { .. }))
+        ));
+
+        // With another completion in flight, the deadline and the upstream
+        // request's timeout both double
+        let _other_slot = admission.admit_scoped("lmstudio", "llama").await.unwrap();
+        assert!(matches!(
+            dispatch(&ctx).await,
+            Ok(Dispatched::Completion(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_branches_on_stream_flag() {
+        let (_dir, ctx) = mock_context(user_request("mock-test", false));
+        assert!(matches!(
+            dispatch(&ctx).await,
+            Ok(Dispatched::Completion(_))
+        ));
+
+        let (_dir, ctx) = mock_context(user_request("mock-test", true));
+        assert!(matches!(dispatch(&ctx).await, Ok(Dispatched::Stream(_))));
+    }
+
+    #[tokio::test]
+    async fn test_postprocess_applies_footer_and_records_usage() {
+        let (_dir, mut ctx) = mock_context(user_request("mock-test", false));
+        let dispatched = dispatch(&ctx).await.unwrap();
+
+        let Dispatched::Completion(response) = postprocess(&mut ctx, dispatched) else {
+            panic!("Expected a completion");
+        };
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Mock reply [footer]"));
+        assert_eq!(ctx.outcome.total_tokens, Some(8));
+    }
+
+    #[tokio::test]
+    async fn test_postprocess_strips_control_chars_when_enabled() {
+        let (_dir, mut ctx) = mock_context(user_request("mock-test", false));
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.strip_control_chars = true;
+        ctx.settings = Arc::new(settings);
+        let Dispatched::Completion(mut response) = dispatch(&ctx).await.unwrap() else {
+            panic!("Expected a completion");
+        };
+        response.choices[0].message.as_mut().unwrap().content =
+            Some("Null\0 byte\n\tkept".to_string());
+
+        let Dispatched::Completion(response) =
+            postprocess(&mut ctx, Dispatched::Completion(response))
+        else {
+            panic!("Expected a completion");
+        };
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Null byte\n\tkept [footer]"));
+    }
+
+    /// A dispatched mock completion carrying a few kilobytes of logprobs
+    async fn logprobs_completion(
+        max_bytes: usize,
+        policy: OversizedResponsePolicy,
+    ) -> (TempDir, RequestContext, Dispatched) {
+        let (dir, mut ctx) = mock_context(user_request("mock-test", false));
+        let mut settings = (*ctx.settings).clone();
+        settings.server.max_response_bytes = Some(max_bytes);
+        settings.server.oversized_response = policy;
+        ctx.settings = Arc::new(settings);
+
+        let Dispatched::Completion(mut response) = dispatch(&ctx).await.unwrap() else {
+            panic!("Expected a completion");
+        };
+        let top = models::TopLogProb {
+            token: "token".to_string(),
+            logprob: -0.5,
+            bytes: None,
+        };
+        let token = models::TokenLogProb {
+            token: "token".to_string(),
+            logprob: -0.1,
+            bytes: None,
+            top_logprobs: vec![top; 20],
+        };
+        response.choices[0].logprobs = Some(models::LogProbs {
+            content: Some(vec![token; 10]),
+        });
+        (dir, ctx, Dispatched::Completion(response))
+    }
+
+    #[tokio::test]
+    async fn test_oversized_response_degrades_by_dropping_logprobs() {
+        let (_dir, mut ctx, dispatched) =
+            logprobs_completion(1024, OversizedResponsePolicy::Degrade).await;
+
+        let Dispatched::Encoded { response, body } =
+            cap_response_size(&mut ctx, dispatched).unwrap()
+        else {
+            panic!("Expected an encoded completion");
+        };
+
+        assert!(body.len() <= 1024);
+        assert!(response.choices[0].logprobs.is_none());
+        assert_eq!(ctx.outcome.warnings[0].code, "logprobs_dropped");
+        assert_eq!(ctx.outcome.warnings[0].param.as_deref(), Some("logprobs"));
+
+        // The measured bytes are sent as they are, not serialized again
+        let sent = respond(
+            &ctx,
+            Dispatched::Encoded {
+                response,
+                body: body.clone(),
+            },
+        );
+        assert_eq!(sent.headers()[header::CONTENT_TYPE], "application/json");
+        let sent = axum::body::to_bytes(sent.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(sent, body);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_response_rejected_with_413() {
+        let (_dir, mut ctx, dispatched) =
+            logprobs_completion(1024, OversizedResponsePolicy::Reject).await;
+
+        let error = cap_response_size(&mut ctx, dispatched).err().unwrap();
+
+        assert!(matches!(
+            error,
+            ApiError::ResponseTooLarge {
+                max_bytes: 1024,
+                ..
+            }
+        ));
+        assert!(ctx.outcome.warnings.is_empty());
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "response_too_large");
+    }
+
+    #[tokio::test]
+    async fn test_response_under_cap_is_serialized_once_and_kept() {
+        let (_dir, mut ctx, dispatched) =
+            logprobs_completion(1 << 20, OversizedResponsePolicy::Reject).await;
+
+        let Dispatched::Encoded { response, body } =
+            cap_response_size(&mut ctx, dispatched).unwrap()
+        else {
+            panic!("Expected an encoded completion");
+        };
+
+        assert!(response.choices[0].logprobs.is_some());
+        assert_eq!(body, serde_json::to_vec(&response).unwrap());
+    }
+
+    fn echo_request(temperature: f32) -> CompletionRequest {
+        CompletionRequest {
+            model: None,
+            messages: vec![Message::new(Role::User, "Hello there, mock")],
+            temperature: Some(temperature),
+            response_mode: Some(ResponseMode::Extended),
+            extensions: Some(
+                [(
+                    ECHO_RESOLVED_REQUEST_EXTENSION.to_string(),
+                    serde_json::json!(true),
+                )]
+                .into(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    async fn echoed(mut ctx: RequestContext) -> Option<serde_json::Value> {
+        resolve_model(&mut ctx).unwrap();
+        preprocess(&mut ctx);
+        assert!(ctx.request.extensions.is_none());
+        let dispatched = dispatch(&ctx).await.unwrap();
+        let Dispatched::Completion(response) = postprocess(&mut ctx, dispatched) else {
+            panic!("Expected a completion");
+        };
+        response
+            .provider_extensions
+            .and_then(|mut ext| ext.data.remove("resolved_request"))
+    }
+
+    #[tokio::test]
+    async fn test_resolved_request_echo_reflects_rewrites() {
+        let (_dir, mut ctx) = mock_context(echo_request(0.2));
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.allow_resolved_request_echo = true;
+        settings.logging.max_logged_body_chars = Some(5);
+        ctx.settings = Arc::new(settings);
+
+        let resolved = echoed(ctx).await.unwrap();
+
+        assert_eq!(resolved["provider"], "mock");
+        // Default model applied and its system prompt injected
+        assert_eq!(resolved["model"], "mock-test");
+        assert_eq!(resolved["messages"][0]["role"], "system");
+        assert_eq!(resolved["messages"][0]["content"], "You a...[truncated]");
+        assert_eq!(resolved["messages"][1]["content"], "Hello...[truncated]");
+        assert!((resolved["temperature"].as_f64().unwrap() - 0.2).abs() < 0.001);
+        assert!(resolved["extensions"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_resolved_request_echo_requires_policy_and_opt_in() {
+        // Not allowed by the server
+        let (_dir, ctx) = mock_context(echo_request(0.2));
+        assert!(echoed(ctx).await.is_none());
+
+        // Allowed, but the client didn't ask
+        let mut request = echo_request(0.2);
+        request.extensions = Some(
+            [(
+                ECHO_RESOLVED_REQUEST_EXTENSION.to_string(),
+                serde_json::json!(false),
+            )]
+            .into(),
+        );
+        let (_dir, mut ctx) = mock_context(request);
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.allow_resolved_request_echo = true;
+        ctx.settings = Arc::new(settings);
+        assert!(echoed(ctx).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_extended_responses_include_effective_request() {
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            response_mode: Some(ResponseMode::Extended),
+            ..Default::default()
+        };
+        let (_dir, mut ctx) = mock_context(request);
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.extended_includes_request = true;
+        ctx.settings = Arc::new(settings);
+
+        resolve_model(&mut ctx).unwrap();
+        preprocess(&mut ctx);
+        let dispatched = dispatch(&ctx).await.unwrap();
+        let Dispatched::Completion(response) = postprocess(&mut ctx, dispatched) else {
+            panic!("Expected a completion");
+        };
+
+        let data = response.provider_extensions.unwrap().data;
+        assert!(!data.contains_key("resolved_request"));
+        let effective = &data["effective_request"];
+        // Default model applied and its system prompt injected
+        assert_eq!(effective["model"], "mock-test");
+        assert_eq!(effective["provider"], "mock");
+        assert_eq!(effective["messages"][0]["content"], "You are a mock.");
+        assert_eq!(effective["messages"][1]["content"], "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_effective_request_only_in_extended_mode() {
+        let (_dir, mut ctx) = mock_context(user_request("mock-test", false));
+        let mut settings = (*ctx.settings).clone();
+        settings.inference.extended_includes_request = true;
+        ctx.settings = Arc::new(settings);
+
+        resolve_model(&mut ctx).unwrap();
+        let dispatched = dispatch(&ctx).await.unwrap();
+        let Dispatched::Completion(response) = postprocess(&mut ctx, dispatched) else {
+            panic!("Expected a completion");
+        };
+
+        assert!(response.provider_extensions.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_warnings_accumulate_across_stages() {
+        async fn served(response_mode: ResponseMode) -> CompletionResponse {
+            let mut request = echo_request(0.2);
+            request.model = Some("mock-unstreamed".to_string());
+            request.stream = Some(true);
+            request.response_mode = Some(response_mode);
+            let (dir, mut ctx) = mock_context(request);
+            std::fs::write(
+                dir.path().join("unstreamed.yaml"),
+                "responses:\n  - text: \"Whole\"\nsettings:\n  force_streaming: false\n",
+            )
+            .unwrap();
+
+            resolve_model(&mut ctx).unwrap();
+            preprocess(&mut ctx);
+            apply_provider_hints(&mut ctx).await;
+            let codes: Vec<_> = ctx.outcome.warnings.iter().map(|w| w.code.as_str()).collect();
+            assert_eq!(codes, ["echo_not_allowed", "stream_overridden"]);
+
+            let dispatched = dispatch(&ctx).await.unwrap();
+            let Dispatched::Completion(response) = postprocess(&mut ctx, dispatched) else {
+                panic!("Expected a completion");
+            };
+            *response
+        }
+
+        let extended = served(ResponseMode::Extended).await;
+        let warnings = extended.warnings.unwrap();
+        assert_eq!(warnings[0].param.as_deref(), Some("extensions.echo_resolved_request"));
+        assert_eq!(warnings[1].code, "stream_overridden");
+
+        let standard = served(ResponseMode::Standard).await;
+        assert!(standard.warnings.is_none());
+        let json = serde_json::to_value(&standard).unwrap();
+        assert!(json.get("warnings").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_provider_hint_forces_streaming() {
+        let (dir, ctx) = mock_context(user_request("mock-forced", false));
+        std::fs::write(
+            dir.path().join("forced.yaml"),
+            "responses:\n  - text: \"Streamed\"\nsettings:\n  force_streaming: true\n",
+        )
+        .unwrap();
+
+        let response = run(ctx).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        assert!(response.extensions().get::<RequestOutcome>().unwrap().stream);
+    }
+
+    #[tokio::test]
+    async fn test_run_maps_errors_and_attaches_outcome() {
+        let (_dir, ctx) = mock_context(user_request("mock-missing", false));
+
+        let response = run(ctx).await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let outcome = response.extensions().get::<RequestOutcome>().unwrap();
+        assert_eq!(outcome.model.as_deref(), Some("mock-missing"));
+    }
+}
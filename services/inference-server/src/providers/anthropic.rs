@@ -0,0 +1,692 @@
+use super::{
+    BoxFuture, EventStream, HttpProviderClient, InferenceProvider, InferenceRequest,
+    InferenceResponse, ProviderError, ProviderStream, standard_completion_response,
+};
+use crate::config::{HttpConfigSchema, Settings};
+use crate::models::{
+    CompletionRequest, CompletionResponse, FinishReason, FunctionCall, Role, StreamChunk, ToolCall,
+    Usage,
+};
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+/// `anthropic-version` sent when the config doesn't pin one
+const DEFAULT_API_VERSION: &str = "2023-06-01";
+
+/// Anthropic authenticates with this header instead of `Authorization`
+const SECRET_HEADERS: &[&str] = &["x-api-key"];
+
+/// The Messages API requires `max_tokens`; used when the client sends none
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+/// Anthropic's Messages API (`/v1/messages`). System messages move to the
+/// top-level `system` field and streams arrive as typed SSE events.
+pub struct AnthropicProvider {
+    http: HttpProviderClient,
+}
+
+/// A `/v1/messages` response
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    id: String,
+    model: String,
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<MessagesUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct MessagesUsage {
+    #[serde(default)]
+    input_tokens: Option<u32>,
+    #[serde(default)]
+    output_tokens: Option<u32>,
+}
+
+/// Map Anthropic's `stop_reason` onto the OpenAI finish reasons
+fn finish_reason(stop_reason: Option<&str>) -> FinishReason {
+    match stop_reason {
+        Some("max_tokens") => FinishReason::Length,
+        Some("tool_use") => FinishReason::ToolCalls,
+        Some("refusal") => FinishReason::ContentFilter,
+        // end_turn, stop_sequence, pause_turn
+        _ => FinishReason::Stop,
+    }
+}
+
+impl AnthropicProvider {
+    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
+        let (api_key, version) = match &settings.inference.provider {
+            crate::config::InferenceProvider::Anthropic { api_key, version } => {
+                (api_key.clone(), version.clone())
+            }
+            _ => {
+                return Err(ProviderError::Configuration(
+                    "Invalid provider configuration for AnthropicProvider".to_string(),
+                ));
+            }
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-api-key",
+            HeaderValue::from_str(&api_key).map_err(|e| {
+                ProviderError::Configuration(format!("Invalid API key format: {e}"))
+            })?,
+        );
+        let version = version.as_deref().unwrap_or(DEFAULT_API_VERSION);
+        headers.insert(
+            "anthropic-version",
+            HeaderValue::from_str(version).map_err(|e| {
+                ProviderError::Configuration(format!("Invalid Anthropic version: {e}"))
+            })?,
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let http = HttpProviderClient::new(
+            &settings.inference.base_url,
+            settings.inference.http.as_ref(),
+            Some(headers),
+        )?
+        .with_secret_headers(SECRET_HEADERS)
+        .with_max_logged_body_chars(settings.logging.max_logged_body_chars)
+        .with_extra_request_fields(settings.inference.extra_request_fields.clone())
+        .with_invalid_utf8(settings.inference.stream_invalid_utf8);
+
+        debug!(
+            "Initialized Anthropic provider with base URL: {}",
+            settings.inference.base_url
+        );
+
+        Ok(Self { http })
+    }
+
+    /// Build the `/v1/messages` body. System messages are joined into `system`;
+    /// tool and function results are sent as user turns, the only other role
+    /// the Messages API accepts besides assistant.
+    fn build_request_body(&self, request: &InferenceRequest, stream: bool) -> serde_json::Value {
+        let system: Vec<&str> = request
+            .messages
+            .iter()
+            .filter(|message| message.role == Role::System)
+            .filter_map(|message| message.content.as_deref())
+            .collect();
+        let messages: Vec<_> = request
+            .messages
+            .iter()
+            .filter(|message| message.role != Role::System)
+            .map(|message| {
+                let role = match message.role {
+                    Role::Assistant => "assistant",
+                    _ => "user",
+                };
+                serde_json::json!({
+                    "role": role,
+                    "content": message.content.as_deref().unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let mut body = serde_json::json!({
+            "model": request.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        });
+        if !system.is_empty() {
+            body["system"] = serde_json::json!(system.join("\n\n"));
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(ref stop) = request.stop_sequences {
+            body["stop_sequences"] = serde_json::json!(stop);
+        }
+        if let Some(ref user) = request.user {
+            body["metadata"] = serde_json::json!({ "user_id": user });
+        }
+        if stream {
+            body["stream"] = serde_json::json!(true);
+        }
+
+        self.http.merge_extra_request_fields(&mut body);
+        body
+    }
+
+    fn parse_response_body(
+        &self,
+        response: serde_json::Value,
+    ) -> Result<InferenceResponse, ProviderError> {
+        let response: MessagesResponse = serde_json::from_value(response).map_err(|e| {
+            ProviderError::InvalidResponse(format!("Failed to parse response: {e}"))
+        })?;
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        for block in response.content {
+            match block {
+                ContentBlock::Text { text: part } => text.push_str(&part),
+                ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    tool_type: "function".to_string(),
+                    function: FunctionCall {
+                        name,
+                        arguments: input.to_string(),
+                    },
+                }),
+                ContentBlock::Other => {}
+            }
+        }
+
+        let usage = response.usage.unwrap_or_default();
+        let total_tokens = match (usage.input_tokens, usage.output_tokens) {
+            (Some(input), Some(output)) => Some(input + output),
+            _ => None,
+        };
+        Ok(InferenceResponse {
+            text,
+            model_used: response.model,
+            finish_reason: Some(finish_reason(response.stop_reason.as_deref())),
+            total_tokens,
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            latency_ms: None,
+            provider_request_id: Some(response.id),
+            system_fingerprint: None,
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            logprobs: None,
+            provider_data: None,
+        })
+    }
+}
+
+/// Tracks what later stream events need from `message_start`
+struct StreamState {
+    id: String,
+    model: String,
+    input_tokens: u32,
+}
+
+impl StreamState {
+    /// Convert one SSE event into OpenAI chunks: `message_start` opens with the
+    /// role, each `text_delta` becomes a content chunk, and `message_delta`
+    /// carries the finish reason and usage. Other events produce nothing.
+    fn convert(&mut self, event: &str, data: &str) -> Result<Vec<StreamChunk>, ProviderError> {
+        let value: serde_json::Value = match event {
+            "message_start" | "content_block_delta" | "message_delta" | "error" => {
+                serde_json::from_str(data)
+                    .map_err(|e| ProviderError::StreamError(format!("Invalid stream event: {e}")))?
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        match event {
+            "message_start" => {
+                let message = &value["message"];
+                if let Some(id) = message["id"].as_str() {
+                    self.id = id.to_string();
+                }
+                if let Some(model) = message["model"].as_str() {
+                    self.model = model.to_string();
+                }
+                self.input_tokens = message["usage"]["input_tokens"].as_u64().unwrap_or(0) as u32;
+                Ok(vec![super::create_first_chunk(
+                    &self.id,
+                    &self.model,
+                    Role::Assistant,
+                )])
+            }
+            "content_block_delta" => match value["delta"]["text"].as_str() {
+                Some(text) if value["delta"]["type"] == "text_delta" => {
+                    Ok(vec![super::create_content_chunk(
+                        &self.id,
+                        &self.model,
+                        text,
+                    )])
+                }
+                _ => Ok(Vec::new()),
+            },
+            "message_delta" => {
+                let output_tokens = value["usage"]["output_tokens"].as_u64().unwrap_or(0) as u32;
+                Ok(vec![super::create_final_chunk(
+                    &self.id,
+                    &self.model,
+                    finish_reason(value["delta"]["stop_reason"].as_str()),
+                    Some(Usage::new(self.input_tokens, output_tokens)),
+                )])
+            }
+            _ => Err(ProviderError::StreamError(
+                value["error"]["message"]
+                    .as_str()
+                    .unwrap_or("Unknown stream error")
+                    .to_string(),
+            )),
+        }
+    }
+}
+
+fn convert_stream(events: EventStream, id: String, model: String) -> ProviderStream {
+    let mut state = StreamState {
+        id,
+        model,
+        input_tokens: 0,
+    };
+    Box::pin(events.flat_map(move |event| {
+        let chunks = match event.and_then(|event| state.convert(&event.event, &event.data)) {
+            Ok(chunks) => chunks.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        futures_util::stream::iter(chunks)
+    }))
+}
+
+impl InferenceProvider for AnthropicProvider {
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request_body = self.build_request_body(request, false);
+
+        Box::pin(async move {
+            debug!(
+                "Sending request to Anthropic: {}",
+                self.http.loggable_body(&request_body)
+            );
+            let start = std::time::Instant::now();
+            let response_body = self.http.post_json("messages", &request_body).await?;
+            let latency_ms = start.elapsed().as_millis() as u64;
+            debug!(
+                "Anthropic response: {}",
+                self.http.loggable_body(&response_body)
+            );
+
+            let mut inference_response = self.parse_response_body(response_body)?;
+            inference_response.latency_ms = Some(latency_ms);
+            Ok(inference_response)
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        standard_completion_response(response, original_request, self.name())
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let inference_req = self.build_inference_request(request, model)?;
+        Ok(self.build_request_body(&inference_req, request.stream == Some(true)))
+    }
+
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        Some(self.http.http_config())
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            let response = self.http.get("models").await?;
+            if response.status().is_success() {
+                Ok(())
+            } else if response.status() == 401 {
+                Err(ProviderError::Configuration("Invalid API key".to_string()))
+            } else {
+                Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Health check failed".to_string(),
+                })
+            }
+        })
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct ModelsResponse {
+                data: Vec<ModelInfo>,
+            }
+            #[derive(Deserialize)]
+            struct ModelInfo {
+                id: String,
+            }
+
+            let response = self.http.get("models").await?;
+            if !response.status().is_success() {
+                return Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Failed to list models".to_string(),
+                });
+            }
+            let models: ModelsResponse = response.json().await.map_err(|e| {
+                ProviderError::InvalidResponse(format!("Invalid models response: {e}"))
+            })?;
+            Ok(models.data.into_iter().map(|m| m.id).collect())
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let inference_req = match self.build_inference_request(request, model) {
+            Ok(req) => req,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        let request_body = self.build_request_body(&inference_req, true);
+        let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+
+        Box::pin(async move {
+            debug!(
+                "Sending streaming request to Anthropic: {}",
+                self.http.loggable_body(&request_body)
+            );
+            let events = self.http.post_sse_events("messages", &request_body).await?;
+            Ok(convert_stream(events, id, inference_req.model))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Message, StringOrArray};
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn create_test_settings(base_url: &str) -> Arc<Settings> {
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::Anthropic {
+                api_key: "test-key".to_string(),
+                version: None,
+            },
+            base_url,
+            "claude-sonnet-4-5",
+        ))
+    }
+
+    fn chat_request(stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![
+                Message::new(Role::System, "Be brief."),
+                Message::new(Role::User, "Hello"),
+            ],
+            stream: Some(stream),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_request_body_extracts_system() {
+        let provider =
+            AnthropicProvider::new(create_test_settings("https://api.anthropic.com/v1")).unwrap();
+        let mut request = chat_request(false);
+        request.messages.push(Message::new(Role::Assistant, "Hi"));
+        request
+            .messages
+            .push(Message::tool_response("call_1", "42"));
+        request.temperature = Some(0.5);
+        request.stop = Some(StringOrArray::String("###".to_string()));
+        let inference_req = provider
+            .build_inference_request(&request, "claude-sonnet-4-5")
+            .unwrap();
+
+        let body = provider.build_request_body(&inference_req, false);
+
+        assert_eq!(body["system"], "Be brief.");
+        assert_eq!(
+            body["messages"],
+            serde_json::json!([
+                {"role": "user", "content": "Hello"},
+                {"role": "assistant", "content": "Hi"},
+                {"role": "user", "content": "42"}
+            ])
+        );
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(body["stop_sequences"], serde_json::json!(["###"]));
+        assert_eq!(body["temperature"], 0.5);
+        assert!(body.get("stream").is_none());
+    }
+
+    #[test]
+    fn test_system_messages_anywhere_are_joined_into_system() {
+        let provider =
+            AnthropicProvider::new(create_test_settings("https://api.anthropic.com/v1")).unwrap();
+        let request = CompletionRequest {
+            messages: vec![
+                Message::new(Role::System, "Be brief."),
+                Message::new(Role::User, "Hello"),
+                Message::new(Role::System, "Answer in French."),
+            ],
+            max_tokens: Some(100),
+            ..Default::default()
+        };
+        let inference_req = provider
+            .build_inference_request(&request, "claude-sonnet-4-5")
+            .unwrap();
+
+        let body = provider.build_request_body(&inference_req, false);
+
+        assert_eq!(body["system"], "Be brief.\n\nAnswer in French.");
+        assert_eq!(
+            body["messages"],
+            serde_json::json!([{"role": "user", "content": "Hello"}])
+        );
+        assert_eq!(body["max_tokens"], 100);
+
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            ..Default::default()
+        };
+        let inference_req = provider
+            .build_inference_request(&request, "claude-sonnet-4-5")
+            .unwrap();
+        let body = provider.build_request_body(&inference_req, false);
+        assert!(body.get("system").is_none());
+    }
+
+    #[test]
+    fn test_parse_response_maps_usage_and_stop_reason() {
+        let provider =
+            AnthropicProvider::new(create_test_settings("https://api.anthropic.com/v1")).unwrap();
+
+        let response = provider
+            .parse_response_body(serde_json::json!({
+                "id": "msg_03",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-sonnet-4-5",
+                "content": [{"type": "text", "text": "Bonjour"}],
+                "stop_reason": "end_turn",
+                "usage": {"input_tokens": 9, "output_tokens": 3}
+            }))
+            .unwrap();
+
+        assert_eq!(response.text, "Bonjour");
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.prompt_tokens, Some(9));
+        assert_eq!(response.completion_tokens, Some(3));
+        assert_eq!(response.total_tokens, Some(12));
+        assert_eq!(response.provider_request_id.as_deref(), Some("msg_03"));
+        assert!(response.tool_calls.is_none());
+    }
+
+    #[test]
+    fn test_stop_reason_mapping() {
+        assert_eq!(finish_reason(Some("end_turn")), FinishReason::Stop);
+        assert_eq!(finish_reason(Some("stop_sequence")), FinishReason::Stop);
+        assert_eq!(finish_reason(Some("max_tokens")), FinishReason::Length);
+        assert_eq!(finish_reason(Some("tool_use")), FinishReason::ToolCalls);
+        assert_eq!(finish_reason(None), FinishReason::Stop);
+    }
+
+    #[test]
+    fn test_api_key_header_is_redacted_in_logs() {
+        let provider =
+            AnthropicProvider::new(create_test_settings("http://localhost:1234")).unwrap();
+
+        let logged = provider.http.loggable_headers();
+
+        assert!(logged.contains("x-api-key: [REDACTED]"), "{logged}");
+        assert!(logged.contains("anthropic-version: 2023-06-01"), "{logged}");
+        assert!(!logged.contains("test-key"), "{logged}");
+    }
+
+    #[tokio::test]
+    async fn test_generate_sends_auth_headers_and_converts_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/messages"))
+            .and(header("x-api-key", "test-key"))
+            .and(header("anthropic-version", DEFAULT_API_VERSION))
+            .and(body_partial_json(
+                serde_json::json!({"system": "Be brief."}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-sonnet-4-5",
+                "content": [
+                    {"type": "text", "text": "Let me check."},
+                    {"type": "tool_use", "id": "toolu_01", "name": "lookup", "input": {"q": "x"}}
+                ],
+                "stop_reason": "tool_use",
+                "usage": {"input_tokens": 12, "output_tokens": 7}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let provider = AnthropicProvider::new(create_test_settings(&server.uri())).unwrap();
+
+        let response = provider
+            .generate(&chat_request(false), "claude-sonnet-4-5")
+            .await
+            .unwrap();
+
+        let choice = &response.choices[0];
+        let message = choice.message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Let me check."));
+        let calls = message.tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "toolu_01");
+        assert_eq!(calls[0].function.name, "lookup");
+        assert_eq!(calls[0].function.arguments, r#"{"q":"x"}"#);
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(response.usage.unwrap().total_tokens, Some(19));
+    }
+
+    #[tokio::test]
+    async fn test_stream_translates_events_to_chunks() {
+        let events = [
+            (
+                "message_start",
+                r#"{"type":"message_start","message":{"id":"msg_02","model":"claude-sonnet-4-5","usage":{"input_tokens":3,"output_tokens":1}}}"#,
+            ),
+            (
+                "content_block_start",
+                r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            ),
+            ("ping", r#"{"type":"ping"}"#),
+            (
+                "content_block_delta",
+                r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hel"}}"#,
+            ),
+            (
+                "content_block_delta",
+                r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"lo"}}"#,
+            ),
+            (
+                "content_block_stop",
+                r#"{"type":"content_block_stop","index":0}"#,
+            ),
+            (
+                "message_delta",
+                r#"{"type":"message_delta","delta":{"stop_reason":"max_tokens"},"usage":{"output_tokens":2}}"#,
+            ),
+            ("message_stop", r#"{"type":"message_stop"}"#),
+        ];
+        let body: String = events
+            .iter()
+            .map(|(event, data)| format!("event: {event}\ndata: {data}\n\n"))
+            .collect();
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/messages"))
+            .and(body_partial_json(serde_json::json!({"stream": true})))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+            .mount(&server)
+            .await;
+        let provider = AnthropicProvider::new(create_test_settings(&server.uri())).unwrap();
+
+        let stream = provider
+            .stream(&chat_request(true), "claude-sonnet-4-5")
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.map(Result::unwrap).collect().await;
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0].choices[0].delta.role, Some(Role::Assistant));
+        let text: String = chunks
+            .iter()
+            .filter_map(|c| c.choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "Hello");
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::Length));
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, Some(5));
+        assert!(chunks.iter().all(|c| c.id == "msg_02"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_event_ends_with_error() {
+        let body = "event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}\n\n";
+        let events = super::super::sse_events(
+            futures_util::stream::iter([Ok::<_, std::io::Error>(body)]),
+            Default::default(),
+        );
+
+        let items: Vec<_> = convert_stream(events, "id".to_string(), "claude".to_string())
+            .collect()
+            .await;
+
+        assert!(matches!(
+            items[..],
+            [Err(ProviderError::StreamError(ref message))] if message == "Overloaded"
+        ));
+    }
+}
@@ -0,0 +1,462 @@
+use super::{
+    BoxFuture, EndpointHealth, ExtensionSpec, InferenceProvider, InferenceRequest,
+    InferenceResponse, ProviderError, ProviderStream, mock,
+};
+use crate::config::{CircuitBreakerConfig, HttpConfigSchema};
+use crate::models::{CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Whether requests are passed on to the wrapped provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests go through
+    Closed,
+    /// Requests fail fast until the cooldown has passed
+    Open,
+    /// The cooldown has passed; the next request is a trial
+    HalfOpen,
+}
+
+/// State of one provider's circuit breaker, for `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitHealth {
+    pub provider: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+/// Leave to send one request. Dropping it without `finish` counts as a failure:
+/// that is how a call the pipeline deadline cancels on a hung upstream ends.
+struct Permit<'a> {
+    provider: &'a CircuitBreakerProvider,
+    trial: bool,
+    finished: bool,
+}
+
+impl Permit<'_> {
+    fn finish<T>(mut self, result: &Result<T, ProviderError>) {
+        self.finished = true;
+        let failed = result.as_ref().err().is_some_and(counts_as_failure);
+        self.provider.record(self.trial, failed);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.provider.record(self.trial, true);
+        }
+    }
+}
+
+/// Whether `error` says the upstream is in trouble, as opposed to rejecting
+/// the request itself
+fn counts_as_failure(error: &ProviderError) -> bool {
+    match error {
+        ProviderError::ConnectionFailed(_)
+        | ProviderError::ConnectTimeout
+        | ProviderError::DeadlineExceeded { .. } => true,
+        ProviderError::RequestFailed { status, .. } => *status == 0 || *status >= 500,
+        _ => false,
+    }
+}
+
+/// Wraps an HTTP provider and stops calling it after `failure_threshold`
+/// consecutive failures. While open, requests fail with `ConnectionFailed`
+/// without reaching the upstream; after the cooldown one trial request is let
+/// through, closing the circuit on success and reopening it on failure.
+pub struct CircuitBreakerProvider {
+    inner: Arc<dyn InferenceProvider>,
+    config: CircuitBreakerConfig,
+    breaker: Mutex<Breaker>,
+}
+
+impl CircuitBreakerProvider {
+    pub fn new(inner: Arc<dyn InferenceProvider>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            inner,
+            config,
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Breaker> {
+        self.breaker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let breaker = self.lock();
+        match breaker.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.config.cooldown() => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Let a request through, or fail fast while the circuit is open
+    fn admit(&self) -> Result<Permit<'_>, ProviderError> {
+        let mut breaker = self.lock();
+        let trial = match breaker.opened_at {
+            None => false,
+            Some(opened_at)
+                if opened_at.elapsed() >= self.config.cooldown() && !breaker.trial_in_flight =>
+            {
+                breaker.trial_in_flight = true;
+                true
+            }
+            Some(_) => return Err(ProviderError::ConnectionFailed("circuit open".to_string())),
+        };
+        Ok(Permit {
+            provider: self,
+            trial,
+            finished: false,
+        })
+    }
+
+    fn record(&self, trial: bool, failed: bool) {
+        let mut breaker = self.lock();
+        if trial {
+            breaker.trial_in_flight = false;
+        }
+        if !failed {
+            if breaker.opened_at.is_some() {
+                info!(provider = self.inner.name(), "Circuit closed");
+            }
+            *breaker = Breaker::default();
+            return;
+        }
+        breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+        if trial || breaker.consecutive_failures == self.config.failure_threshold {
+            warn!(
+                provider = self.inner.name(),
+                failures = breaker.consecutive_failures,
+                cooldown_secs = self.config.cooldown_secs,
+                "Circuit opened"
+            );
+            breaker.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+impl InferenceProvider for CircuitBreakerProvider {
+    fn build_inference_request(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<InferenceRequest, ProviderError> {
+        self.inner.build_inference_request(request, model)
+    }
+
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            let permit = self.admit()?;
+            let result = self.inner.execute(&request).await;
+            permit.finish(&result);
+            result
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        self.inner
+            .build_completion_response(response, original_request)
+    }
+
+    fn generate(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<CompletionResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            let permit = self.admit()?;
+            let result = self.inner.generate(&request, &model).await;
+            permit.finish(&result);
+            result
+        })
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        self.inner.preview_request_body(request, model)
+    }
+
+    /// Judged by whether the stream opens; errors after that don't count
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            let permit = self.admit()?;
+            let result = self.inner.stream(&request, &model).await;
+            permit.finish(&result);
+            result
+        })
+    }
+
+    fn embed(
+        &self,
+        request: &EmbeddingRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            let permit = self.admit()?;
+            let result = self.inner.embed(&request, &model).await;
+            permit.finish(&result);
+            result
+        })
+    }
+
+    fn streaming_preference(&self, model: &str) -> BoxFuture<'_, Option<bool>> {
+        self.inner.streaming_preference(model)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        self.inner.http_config()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.inner.supports_streaming()
+    }
+
+    fn supports_audio_output(&self) -> bool {
+        self.inner.supports_audio_output()
+    }
+
+    fn supports_json_mode(&self) -> bool {
+        self.inner.supports_json_mode()
+    }
+
+    fn max_stop_sequences(&self) -> usize {
+        self.inner.max_stop_sequences()
+    }
+
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        self.inner.extension_specs()
+    }
+
+    fn supported_extensions(&self) -> Vec<&'static str> {
+        self.inner.supported_extensions()
+    }
+
+    fn validate_extensions(
+        &self,
+        extensions: &HashMap<String, serde_json::Value>,
+    ) -> Result<(), ProviderError> {
+        self.inner.validate_extensions(extensions)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        self.inner.list_models()
+    }
+
+    /// Probes the upstream even while the circuit is open
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        self.inner.health_check()
+    }
+
+    fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        self.inner.endpoint_health()
+    }
+
+    fn circuit_health(&self) -> Vec<CircuitHealth> {
+        let consecutive_failures = self.lock().consecutive_failures;
+        vec![CircuitHealth {
+            provider: self.name().to_string(),
+            state: self.state(),
+            consecutive_failures,
+        }]
+    }
+
+    fn mock_control(&self) -> Option<mock::MockControl> {
+        self.inner.mock_control()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Message, Role};
+    use crate::providers::standard_completion_response;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Fails with a connection error while `down`, or never answers while `hung`,
+    /// counting the calls that reach it
+    #[derive(Default)]
+    struct FlakyProvider {
+        down: AtomicBool,
+        hung: AtomicBool,
+        calls: AtomicUsize,
+    }
+
+    impl InferenceProvider for FlakyProvider {
+        fn execute(
+            &self,
+            request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            let model = request.model.clone();
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                if self.hung.load(Ordering::SeqCst) {
+                    std::future::pending::<()>().await;
+                }
+                if self.down.load(Ordering::SeqCst) {
+                    return Err(ProviderError::ConnectionFailed("refused".to_string()));
+                }
+                Ok(InferenceResponse {
+                    text: "ok".to_string(),
+                    model_used: model,
+                    total_tokens: None,
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    finish_reason: None,
+                    latency_ms: None,
+                    provider_request_id: None,
+                    system_fingerprint: None,
+                    tool_calls: None,
+                    logprobs: None,
+                    provider_data: None,
+                })
+            })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            standard_completion_response(response, original_request, self.name())
+        }
+
+        fn name(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    fn request() -> InferenceRequest {
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            ..Default::default()
+        };
+        FlakyProvider::default()
+            .build_inference_request(&request, "model")
+            .unwrap()
+    }
+
+    fn breaker(cooldown_secs: u64) -> (Arc<FlakyProvider>, CircuitBreakerProvider) {
+        let upstream = Arc::new(FlakyProvider::default());
+        upstream.down.store(true, Ordering::SeqCst);
+        let provider = CircuitBreakerProvider::new(
+            upstream.clone(),
+            CircuitBreakerConfig {
+                failure_threshold: 3,
+                cooldown_secs,
+            },
+        );
+        (upstream, provider)
+    }
+
+    #[tokio::test]
+    async fn test_opens_after_threshold_and_fails_fast() {
+        let (upstream, provider) = breaker(60);
+
+        for _ in 0..3 {
+            assert!(provider.execute(&request()).await.is_err());
+        }
+        assert_eq!(upstream.calls.load(Ordering::SeqCst), 3);
+        assert_eq!(provider.state(), CircuitState::Open);
+
+        // Open: the upstream isn't called, even once it has recovered
+        upstream.down.store(false, Ordering::SeqCst);
+        let error = provider.execute(&request()).await.unwrap_err();
+        assert!(matches!(error, ProviderError::ConnectionFailed(ref msg) if msg == "circuit open"));
+        assert_eq!(upstream.calls.load(Ordering::SeqCst), 3);
+
+        let health = provider.circuit_health();
+        assert_eq!(health[0].provider, "flaky");
+        assert_eq!(health[0].state, CircuitState::Open);
+        assert_eq!(health[0].consecutive_failures, 3);
+    }
+
+    #[tokio::test]
+    async fn test_trial_after_cooldown_closes_or_reopens() {
+        let (upstream, provider) = breaker(0);
+        for _ in 0..3 {
+            assert!(provider.execute(&request()).await.is_err());
+        }
+        assert_eq!(provider.state(), CircuitState::HalfOpen);
+
+        // A failed trial reopens the circuit straight away
+        assert!(provider.execute(&request()).await.is_err());
+        assert_eq!(upstream.calls.load(Ordering::SeqCst), 4);
+
+        // A successful one closes it
+        upstream.down.store(false, Ordering::SeqCst);
+        assert!(provider.execute(&request()).await.is_ok());
+        assert_eq!(provider.state(), CircuitState::Closed);
+        assert_eq!(provider.circuit_health()[0].consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_upstream_cut_off_by_deadline_opens_the_circuit() {
+        let (upstream, provider) = breaker(60);
+        upstream.hung.store(true, Ordering::SeqCst);
+
+        for _ in 0..3 {
+            let call = provider.execute(&request());
+            assert!(tokio::time::timeout(Duration::from_secs(30), call).await.is_err());
+        }
+
+        assert_eq!(provider.state(), CircuitState::Open);
+        assert_eq!(provider.circuit_health()[0].consecutive_failures, 3);
+        let error = provider.execute(&request()).await.unwrap_err();
+        assert!(matches!(error, ProviderError::ConnectionFailed(ref msg) if msg == "circuit open"));
+        assert_eq!(upstream.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_do_not_open_the_circuit() {
+        let (_upstream, provider) = breaker(60);
+        let rejected: Result<(), _> = Err(ProviderError::RequestFailed {
+            status: 400,
+            message: "bad request".to_string(),
+        });
+        for _ in 0..5 {
+            provider.admit().unwrap().finish(&rejected);
+        }
+        assert_eq!(provider.state(), CircuitState::Closed);
+    }
+}
@@ -0,0 +1,535 @@
+use super::{
+    BoxFuture, ExtensionSpec, InferenceProvider, InferenceRequest, InferenceResponse,
+    ProviderError, ProviderStream, attach_provider_data, budget_stretch, remaining_budget,
+    with_budget,
+};
+use crate::config::HttpConfigSchema;
+use crate::models::{
+    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, StreamChunk,
+};
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::warn;
+
+/// One backend of a failover chain
+pub struct FailoverBackend {
+    pub name: String,
+    pub provider: Arc<dyn InferenceProvider>,
+    /// Model requested from this backend when it stands in for an earlier one;
+    /// the client's model is passed on unchanged when unset
+    pub model: Option<String>,
+}
+
+/// Tries its backends in order, moving on to the next one only when a backend
+/// can't be reached or times out. Within a request budget each backend gets an
+/// equal share of the time left, so one that hangs leaves the rest time to answer.
+/// Capabilities are those of the first backend.
+pub struct FailoverProvider {
+    backends: Vec<FailoverBackend>,
+}
+
+impl FailoverProvider {
+    pub fn new(backends: Vec<FailoverBackend>) -> Result<Self, ProviderError> {
+        if backends.is_empty() {
+            return Err(ProviderError::Configuration(
+                "Failover needs at least one backend".to_string(),
+            ));
+        }
+        Ok(Self { backends })
+    }
+
+    fn primary(&self) -> &Arc<dyn InferenceProvider> {
+        &self.backends[0].provider
+    }
+
+    /// Model to request from the backend at `position` in the chain
+    fn model_for<'a>(&'a self, position: usize, model: &'a str) -> &'a str {
+        match self.backends[position].model {
+            Some(ref fallback) if position > 0 => fallback,
+            _ => model,
+        }
+    }
+
+    /// Whether `error` means the backend is unavailable, rather than that it
+    /// rejected the request
+    fn should_fail_over(error: &ProviderError) -> bool {
+        matches!(
+            error,
+            ProviderError::ConnectionFailed(_)
+                | ProviderError::ConnectTimeout
+                | ProviderError::DeadlineExceeded { .. }
+        )
+    }
+
+    /// Run a call on the backend at `position` within its share of the time left
+    async fn within_share<T>(
+        &self,
+        position: usize,
+        call: impl Future<Output = Result<T, ProviderError>>,
+    ) -> Result<T, ProviderError> {
+        let backends_left = (self.backends.len() - position) as u32;
+        match remaining_budget() {
+            Some(remaining) if backends_left > 1 => {
+                with_budget(remaining / backends_left, budget_stretch(), call).await
+            }
+            _ => call.await,
+        }
+    }
+
+    /// Log a failed backend; true if the next one should be tried
+    fn failed(&self, position: usize, error: &ProviderError) -> bool {
+        let fail_over = Self::should_fail_over(error) && position + 1 < self.backends.len();
+        if fail_over {
+            warn!(
+                backend = self.backends[position].name,
+                next = self.backends[position + 1].name,
+                error = %error,
+                "Backend unavailable, failing over"
+            );
+        }
+        fail_over
+    }
+}
+
+/// Open a stream and wait for its first chunk
+async fn first_chunk(
+    stream: BoxFuture<'_, Result<ProviderStream, ProviderError>>,
+) -> Result<(ProviderStream, Option<Result<StreamChunk, ProviderError>>), ProviderError> {
+    let mut stream = stream.await?;
+    let first = stream.next().await;
+    Ok((stream, first))
+}
+
+impl InferenceProvider for FailoverProvider {
+    fn build_inference_request(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<InferenceRequest, ProviderError> {
+        self.primary().build_inference_request(request, model)
+    }
+
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            for (position, backend) in self.backends.iter().enumerate() {
+                let mut attempt = request.clone();
+                attempt.model = self.model_for(position, &request.model).to_string();
+                match self.within_share(position, backend.provider.execute(&attempt)).await {
+                    Err(e) if self.failed(position, &e) => continue,
+                    result => return result,
+                }
+            }
+            unreachable!("the last backend's result is always returned")
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        self.primary()
+            .build_completion_response(response, original_request)
+    }
+
+    /// The served backend is noted as `backend` in extended mode
+    fn generate(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<CompletionResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            for (position, backend) in self.backends.iter().enumerate() {
+                let served_model = self.model_for(position, &model);
+                let call = backend.provider.generate(&request, served_model);
+                match self.within_share(position, call).await {
+                    Err(e) if self.failed(position, &e) => continue,
+                    Err(e) => return Err(e),
+                    Ok(mut response) => {
+                        attach_provider_data(
+                            &mut response,
+                            &request,
+                            backend.provider.name(),
+                            "backend",
+                            serde_json::json!(backend.name),
+                        );
+                        return Ok(response);
+                    }
+                }
+            }
+            unreachable!("the last backend's result is always returned")
+        })
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        self.primary().preview_request_body(request, model)
+    }
+
+    /// Fails over only until a backend produces its first chunk; a stream that
+    /// breaks after that ends with the error as usual
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            for (position, backend) in self.backends.iter().enumerate() {
+                let served_model = self.model_for(position, &model);
+                let call = first_chunk(backend.provider.stream(&request, served_model));
+                let (stream, first) = match self.within_share(position, call).await {
+                    Err(e) if self.failed(position, &e) => continue,
+                    Err(e) => return Err(e),
+                    Ok(opened) => opened,
+                };
+                let first = match first {
+                    Some(Err(e)) if self.failed(position, &e) => continue,
+                    Some(first) => first,
+                    None => return Ok(stream),
+                };
+                let stream: ProviderStream =
+                    Box::pin(futures_util::stream::once(async move { first }).chain(stream));
+                return Ok(stream);
+            }
+            unreachable!("the last backend's result is always returned")
+        })
+    }
+
+    fn embed(
+        &self,
+        request: &EmbeddingRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            for (position, backend) in self.backends.iter().enumerate() {
+                let served_model = self.model_for(position, &model);
+                let call = backend.provider.embed(&request, served_model);
+                match self.within_share(position, call).await {
+                    Err(e) if self.failed(position, &e) => continue,
+                    result => return result,
+                }
+            }
+            unreachable!("the last backend's result is always returned")
+        })
+    }
+
+    fn streaming_preference(&self, model: &str) -> BoxFuture<'_, Option<bool>> {
+        self.primary().streaming_preference(model)
+    }
+
+    fn name(&self) -> &str {
+        self.primary().name()
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        self.primary().http_config()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.primary().supports_streaming()
+    }
+
+    fn supports_audio_output(&self) -> bool {
+        self.primary().supports_audio_output()
+    }
+
+    fn supports_json_mode(&self) -> bool {
+        self.primary().supports_json_mode()
+    }
+
+    fn max_stop_sequences(&self) -> usize {
+        self.primary().max_stop_sequences()
+    }
+
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        self.primary().extension_specs()
+    }
+
+    fn supported_extensions(&self) -> Vec<&'static str> {
+        self.primary().supported_extensions()
+    }
+
+    fn validate_extensions(
+        &self,
+        extensions: &HashMap<String, serde_json::Value>,
+    ) -> Result<(), ProviderError> {
+        self.primary().validate_extensions(extensions)
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        self.primary().list_models()
+    }
+
+    /// Healthy while any backend is
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            let mut first_error = None;
+            for backend in &self.backends {
+                match backend.provider.health_check().await {
+                    Ok(()) => return Ok(()),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            Err(first_error.expect("at least one backend"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Message, ResponseMode, Role};
+    use crate::providers::{create_content_chunk, standard_completion_response};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Answers with its own name, fails every call with `error`, or never answers
+    /// while `hung`
+    struct StubProvider {
+        name: &'static str,
+        error: Option<fn() -> ProviderError>,
+        hung: bool,
+        models: Mutex<Vec<String>>,
+    }
+
+    impl StubProvider {
+        fn up(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                error: None,
+                hung: false,
+                models: Mutex::default(),
+            })
+        }
+
+        fn down(name: &'static str, error: fn() -> ProviderError) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                error: Some(error),
+                hung: false,
+                models: Mutex::default(),
+            })
+        }
+
+        fn hung(name: &'static str) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                error: None,
+                hung: true,
+                models: Mutex::default(),
+            })
+        }
+    }
+
+    impl InferenceProvider for StubProvider {
+        fn execute(
+            &self,
+            request: &InferenceRequest,
+        ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+            self.models.lock().unwrap().push(request.model.clone());
+            let result = match self.error {
+                Some(error) => Err(error()),
+                None => Ok(InferenceResponse {
+                    text: format!("from {}", self.name),
+                    model_used: request.model.clone(),
+                    finish_reason: None,
+                    total_tokens: None,
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    latency_ms: None,
+                    provider_request_id: None,
+                    system_fingerprint: None,
+                    tool_calls: None,
+                    logprobs: None,
+                    provider_data: None,
+                }),
+            };
+            let hung = self.hung;
+            Box::pin(async move {
+                if hung {
+                    std::future::pending::<()>().await;
+                }
+                result
+            })
+        }
+
+        fn build_completion_response(
+            &self,
+            response: &InferenceResponse,
+            original_request: &CompletionRequest,
+        ) -> CompletionResponse {
+            standard_completion_response(response, original_request, self.name())
+        }
+
+        /// Streams its name, or fails on the first chunk
+        fn stream(
+            &self,
+            _request: &CompletionRequest,
+            model: &str,
+        ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+            self.models.lock().unwrap().push(model.to_string());
+            let chunk = match self.error {
+                Some(error) => Err(error()),
+                None => Ok(create_content_chunk("chatcmpl-1", model, self.name)),
+            };
+            Box::pin(async move {
+                let stream: ProviderStream = Box::pin(futures_util::stream::iter([chunk]));
+                Ok(stream)
+            })
+        }
+
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    fn backend(name: &str, provider: Arc<StubProvider>, model: Option<&str>) -> FailoverBackend {
+        FailoverBackend {
+            name: name.to_string(),
+            provider,
+            model: model.map(str::to_string),
+        }
+    }
+
+    fn extended_request() -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            response_mode: Some(ResponseMode::Extended),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_primary_fails_over_with_fallback_model() {
+        let primary = StubProvider::down("lmstudio", || {
+            ProviderError::ConnectionFailed("refused".to_string())
+        });
+        let fallback = StubProvider::up("openai");
+        let failover = FailoverProvider::new(vec![
+            backend("studio", primary.clone(), None),
+            backend("cloud", fallback.clone(), Some("gpt-4o-mini")),
+        ])
+        .unwrap();
+
+        let response = failover
+            .generate(&extended_request(), "llama3.1-8b")
+            .await
+            .unwrap();
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("from openai"));
+        let extensions = response.provider_extensions.unwrap();
+        assert_eq!(extensions.provider, "openai");
+        assert_eq!(extensions.data["backend"], "cloud");
+        assert_eq!(*primary.models.lock().unwrap(), ["llama3.1-8b"]);
+        assert_eq!(*fallback.models.lock().unwrap(), ["gpt-4o-mini"]);
+    }
+
+    #[tokio::test]
+    async fn test_hung_primary_fails_over_within_the_budget() {
+        let fallback = StubProvider::up("openai");
+        let failover = FailoverProvider::new(vec![
+            backend("studio", StubProvider::hung("lmstudio"), None),
+            backend("cloud", fallback.clone(), None),
+        ])
+        .unwrap();
+        let started = std::time::Instant::now();
+
+        let call = failover.generate(&extended_request(), "llama3.1-8b");
+        let response = with_budget(Duration::from_millis(400), 1.0, call)
+            .await
+            .unwrap();
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("from openai"));
+        assert_eq!(response.provider_extensions.unwrap().data["backend"], "cloud");
+        // The primary was given half the budget
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests_do_not_fail_over() {
+        let primary = StubProvider::down("lmstudio", || ProviderError::RequestFailed {
+            status: 400,
+            message: "bad request".to_string(),
+        });
+        let fallback = StubProvider::up("openai");
+        let failover = FailoverProvider::new(vec![
+            backend("studio", primary, None),
+            backend("cloud", fallback.clone(), None),
+        ])
+        .unwrap();
+
+        let result = failover.generate(&extended_request(), "llama3.1-8b").await;
+
+        assert!(matches!(
+            result,
+            Err(ProviderError::RequestFailed { status: 400, .. })
+        ));
+        assert!(fallback.models.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_last_backend_error_is_returned() {
+        let failover = FailoverProvider::new(vec![
+            backend(
+                "studio",
+                StubProvider::down("lmstudio", || ProviderError::ConnectTimeout),
+                None,
+            ),
+            backend(
+                "cloud",
+                StubProvider::down("openai", || {
+                    ProviderError::ConnectionFailed("refused".to_string())
+                }),
+                None,
+            ),
+        ])
+        .unwrap();
+
+        let result = failover.generate(&extended_request(), "llama3.1-8b").await;
+
+        assert!(matches!(result, Err(ProviderError::ConnectionFailed(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stream_fails_over_before_first_chunk() {
+        let failover = FailoverProvider::new(vec![
+            backend(
+                "studio",
+                StubProvider::down("lmstudio", || ProviderError::ConnectTimeout),
+                None,
+            ),
+            backend("cloud", StubProvider::up("openai"), None),
+        ])
+        .unwrap();
+
+        let stream = failover
+            .stream(&extended_request(), "llama3.1-8b")
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+
+        assert_eq!(chunks.len(), 1);
+        let chunk = chunks[0].as_ref().unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("openai"));
+    }
+}
@@ -1,236 +1,189 @@
 use super::{
-    BoxFuture, HttpProviderClient, InferenceProvider, InferenceRequest, InferenceResponse,
-    ProviderError, ProviderStream, standard_completion_response,
+    BoxFuture, Bound, EndpointHealth, ExtensionKind, ExtensionSpec, HttpProviderClient,
+    InferenceProvider, InferenceRequest, InferenceResponse, ProviderError, ProviderStream,
+    attach_provider_data, standard_completion_response,
 };
 use crate::config::{HttpConfigSchema, Settings};
-use crate::models::{CompletionRequest, CompletionResponse, StreamChunk};
-use futures_util::TryStreamExt;
+use crate::models::{CompletionRequest, CompletionResponse};
+use crate::startup::redact_url;
 use serde::Deserialize;
 use serde_json;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tracing::{debug, error};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, warn};
+
+/// How long an endpoint that could not be reached is skipped before it gets traffic again
+const ENDPOINT_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Endpoints one request may try: the one it lands on and one more
+const ENDPOINT_ATTEMPTS: usize = 2;
 
 /// LM Studio supported extension parameters
 /// These are parameters beyond the standard OpenAI spec that LM Studio supports
-const LM_STUDIO_EXTENSIONS: &[&str] = &[
-    "top_k",          // Top-k sampling
-    "min_p",          // Minimum probability threshold
-    "repeat_penalty", // Repetition penalty
-    "mirostat_mode",  // Mirostat sampling mode (0, 1, or 2)
-    "mirostat_tau",   // Mirostat target entropy
-    "mirostat_eta",   // Mirostat learning rate
-    "grammar",        // Grammar-based constrained generation
-    "cache_prompt",   // Whether to cache the prompt
-    "n_probs",        // Number of probabilities to return (alternative to top_logprobs)
-    "tfs_z",          // Tail-free sampling parameter
-    "typical_p",      // Typical sampling parameter
-    "min_tokens",     // Minimum number of tokens to generate
+pub(super) const LM_STUDIO_EXTENSIONS: &[ExtensionSpec] = &[
+    ExtensionSpec::new("top_k", ExtensionKind::Integer, "Top-k sampling")
+        .min(Bound::exclusive(0.0)),
+    ExtensionSpec::new("min_p", ExtensionKind::Number, "Minimum probability threshold")
+        .min(Bound::inclusive(0.0))
+        .max(Bound::inclusive(1.0)),
+    ExtensionSpec::new("repeat_penalty", ExtensionKind::Number, "Repetition penalty")
+        .min(Bound::inclusive(0.0)),
+    ExtensionSpec::new(
+        "mirostat_mode",
+        ExtensionKind::Integer,
+        "Mirostat sampling mode (0 off, 1 or 2)",
+    )
+    .min(Bound::inclusive(0.0))
+    .max(Bound::inclusive(2.0)),
+    ExtensionSpec::new("mirostat_tau", ExtensionKind::Number, "Mirostat target entropy")
+        .min(Bound::exclusive(0.0)),
+    ExtensionSpec::new("mirostat_eta", ExtensionKind::Number, "Mirostat learning rate")
+        .min(Bound::inclusive(0.0))
+        .max(Bound::inclusive(1.0)),
+    ExtensionSpec::new(
+        "grammar",
+        ExtensionKind::String,
+        "Grammar-based constrained generation",
+    ),
+    ExtensionSpec::new("cache_prompt", ExtensionKind::Boolean, "Whether to cache the prompt"),
+    ExtensionSpec::new(
+        "n_probs",
+        ExtensionKind::Integer,
+        "Number of probabilities to return (alternative to top_logprobs)",
+    )
+    .min(Bound::inclusive(0.0)),
+    ExtensionSpec::new("tfs_z", ExtensionKind::Number, "Tail-free sampling parameter")
+        .min(Bound::inclusive(0.0)),
+    ExtensionSpec::new("typical_p", ExtensionKind::Number, "Typical sampling parameter")
+        .min(Bound::inclusive(0.0))
+        .max(Bound::inclusive(1.0)),
+    ExtensionSpec::new(
+        "min_tokens",
+        ExtensionKind::Integer,
+        "Minimum number of tokens to generate",
+    )
+    .min(Bound::inclusive(0.0)),
 ];
 
-pub struct LMStudioProvider {
+/// One LM Studio instance
+struct Endpoint {
     http: HttpProviderClient,
+    base_url: String,
+    /// When the endpoint last failed; cleared by the next success
+    down_since: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    /// Up, or down long enough to be worth another try
+    fn available(&self) -> bool {
+        self.down_since
+            .lock()
+            .unwrap()
+            .is_none_or(|since| since.elapsed() >= ENDPOINT_RETRY_AFTER)
+    }
+
+    fn mark(&self, up: bool) {
+        let mut down_since = self.down_since.lock().unwrap();
+        if up {
+            *down_since = None;
+        } else {
+            down_since.get_or_insert_with(Instant::now);
+        }
+    }
+}
+
+/// Sends requests to LM Studio, spreading them round-robin when
+/// `inference.base_urls` lists several instances
+pub struct LMStudioProvider {
+    endpoints: Vec<Endpoint>,
+    next: AtomicUsize,
 }
 
 impl LMStudioProvider {
-    /// Validate LM Studio-specific extension parameters
-    fn validate_lm_studio_extensions(
-        extensions: &HashMap<String, serde_json::Value>,
-    ) -> Result<(), ProviderError> {
-        for (key, value) in extensions {
-            match key.as_str() {
-                "top_k" => {
-                    if let Some(v) = value.as_i64() {
-                        if v <= 0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be > 0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be an integer".to_string(),
-                        });
-                    }
-                }
-                "min_p" => {
-                    if let Some(v) = value.as_f64() {
-                        if !(0.0..=1.0).contains(&v) {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be between 0.0 and 1.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "repeat_penalty" => {
-                    if let Some(v) = value.as_f64() {
-                        if v < 0.0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be >= 0.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "mirostat_mode" => {
-                    if let Some(v) = value.as_i64() {
-                        if !(0..=2).contains(&v) {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be 0, 1, or 2".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be an integer".to_string(),
-                        });
-                    }
-                }
-                "mirostat_tau" => {
-                    if let Some(v) = value.as_f64() {
-                        if v <= 0.0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be > 0.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "mirostat_eta" => {
-                    if let Some(v) = value.as_f64() {
-                        if !(0.0..=1.0).contains(&v) {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be between 0.0 and 1.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "typical_p" => {
-                    if let Some(v) = value.as_f64() {
-                        if !(0.0..=1.0).contains(&v) {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be between 0.0 and 1.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "tfs_z" => {
-                    if let Some(v) = value.as_f64() {
-                        if v < 0.0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be >= 0.0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a number".to_string(),
-                        });
-                    }
-                }
-                "min_tokens" => {
-                    if let Some(v) = value.as_i64() {
-                        if v < 0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be >= 0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be an integer".to_string(),
-                        });
-                    }
-                }
-                "n_probs" => {
-                    if let Some(v) = value.as_i64() {
-                        if v < 0 {
-                            return Err(ProviderError::InvalidExtension {
-                                param: key.clone(),
-                                reason: "must be >= 0".to_string(),
-                            });
-                        }
-                    } else {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be an integer".to_string(),
-                        });
-                    }
-                }
-                "grammar" => {
-                    // Grammar should be a string
-                    if !value.is_string() {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a string".to_string(),
-                        });
-                    }
-                }
-                "cache_prompt" => {
-                    // cache_prompt should be a boolean
-                    if !value.is_boolean() {
-                        return Err(ProviderError::InvalidExtension {
-                            param: key.clone(),
-                            reason: "must be a boolean".to_string(),
-                        });
+    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
+        let inference = &settings.inference;
+        let base_urls = if inference.base_urls.is_empty() {
+            std::slice::from_ref(&inference.base_url)
+        } else {
+            inference.base_urls.as_slice()
+        };
+        let endpoints = base_urls
+            .iter()
+            .map(|base_url| {
+                let http = HttpProviderClient::new(base_url, inference.http.as_ref(), None)?
+                    .with_max_logged_body_chars(settings.logging.max_logged_body_chars)
+                    .with_extra_request_fields(inference.extra_request_fields.clone())
+                    .with_invalid_utf8(inference.stream_invalid_utf8)
+                    .with_stream_reconnect(inference.stream_reconnect);
+                Ok(Endpoint {
+                    http,
+                    base_url: base_url.clone(),
+                    down_since: Mutex::new(None),
+                })
+            })
+            .collect::<Result<_, ProviderError>>()?;
+
+        Ok(Self {
+            endpoints,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// Client whose settings every endpoint shares, for building and logging bodies
+    fn http(&self) -> &HttpProviderClient {
+        &self.endpoints[0].http
+    }
+
+    /// Endpoints for one request in the order to try them: the next available
+    /// one in turn, then one more to retry on. When none is available they are
+    /// tried in turn anyway. Never more than `ENDPOINT_ATTEMPTS`.
+    fn attempt_order(&self) -> Vec<&Endpoint> {
+        let count = self.endpoints.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % count;
+        let rotation = (0..count).map(|i| &self.endpoints[(start + i) % count]);
+        let available: Vec<_> =
+            rotation.clone().filter(|e| e.available()).take(ENDPOINT_ATTEMPTS).collect();
+        if available.is_empty() {
+            rotation.take(ENDPOINT_ATTEMPTS).collect()
+        } else {
+            available
+        }
+    }
+
+    /// Run `call` against the next endpoint, retrying once on another one if
+    /// the first can't be reached; the error of that second one is returned
+    /// without trying further endpoints. A stream stays on the endpoint that opened it.
+    async fn with_endpoint<'a, T, F, Fut>(&'a self, call: F) -> Result<T, ProviderError>
+    where
+        F: Fn(&'a HttpProviderClient) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let order = self.attempt_order();
+        let last = order.len() - 1;
+        for (attempt, endpoint) in order.into_iter().enumerate() {
+            let result = call(&endpoint.http).await;
+            match result {
+                Err(e) if e.is_retryable() => {
+                    endpoint.mark(false);
+                    if attempt == last {
+                        return Err(e);
                     }
+                    warn!(
+                        endpoint = %redact_url(&endpoint.base_url),
+                        error = %e,
+                        "LM Studio endpoint unreachable, retrying on the next one"
+                    );
                 }
-                _ => {
-                    // This shouldn't happen if supported_extensions() is correct
-                    // but good to have as a safety check
-                    return Err(ProviderError::InvalidExtension {
-                        param: key.clone(),
-                        reason: format!(
-                            "unknown parameter (supported: {})",
-                            LM_STUDIO_EXTENSIONS.join(", ")
-                        ),
-                    });
+                result => {
+                    endpoint.mark(true);
+                    return result;
                 }
             }
         }
-        Ok(())
-    }
-
-    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
-        let http = HttpProviderClient::new(
-            &settings.inference.base_url,
-            settings.inference.http.as_ref(),
-            None,
-        )?;
-
-        Ok(Self { http })
+        unreachable!("the last endpoint's result is always returned")
     }
 
     /// Build request body for LM Studio (OpenAI-compatible format)
@@ -293,6 +246,7 @@ impl LMStudioProvider {
             );
         }
 
+        self.http().merge_extra_request_fields(&mut body);
         body
     }
 
@@ -394,12 +348,24 @@ impl InferenceProvider for LMStudioProvider {
     ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
         let request_body = self.build_request_body(request, None);
         let model = request.model.clone();
+        let hedgeable = self.http().should_hedge(request);
 
         Box::pin(async move {
-            debug!("Sending request to LM Studio: {}", request_body);
-            let response_body = self.http.post_json("v1/chat/completions", &request_body).await?;
-            debug!("LM Studio response: {}", response_body);
-            self.parse_response_body(response_body, &model)
+            debug!("Sending request to LM Studio: {}", self.http().loggable_body(&request_body));
+            let (response_body, hedge) = self
+                .with_endpoint(|http| {
+                    http.post_json_hedged("v1/chat/completions", &request_body, hedgeable)
+                })
+                .await?;
+            debug!("LM Studio response: {}", self.http().loggable_body(&response_body));
+            let mut inference_response = self.parse_response_body(response_body, &model)?;
+            if let Some(hedge) = hedge {
+                inference_response
+                    .provider_data
+                    .get_or_insert_with(HashMap::new)
+                    .insert("hedge".to_string(), hedge.to_provider_data());
+            }
+            Ok(inference_response)
         })
     }
 
@@ -416,23 +382,45 @@ impl InferenceProvider for LMStudioProvider {
     }
 
     fn http_config(&self) -> Option<&HttpConfigSchema> {
-        Some(self.http.http_config())
+        Some(self.http().http_config())
     }
 
+    /// Probes every endpoint; healthy while at least one answers
     fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
         Box::pin(async move {
-            let response = self.http.get("v1/models").await?;
-            if response.status().is_success() {
-                Ok(())
-            } else {
-                Err(ProviderError::RequestFailed {
-                    status: response.status().as_u16(),
-                    message: "Health check failed".to_string(),
-                })
+            let probes = self.endpoints.iter().map(|endpoint| async move {
+                let result = match endpoint.http.get("v1/models").await {
+                    Ok(response) if response.status().is_success() => Ok(()),
+                    Ok(response) => Err(ProviderError::RequestFailed {
+                        status: response.status().as_u16(),
+                        message: "Health check failed".to_string(),
+                    }),
+                    Err(e) => Err(e),
+                };
+                endpoint.mark(result.is_ok());
+                result
+            });
+            let results = futures_util::future::join_all(probes).await;
+            if results.iter().any(Result::is_ok) {
+                return Ok(());
             }
+            results.into_iter().find_map(Result::err).map_or(Ok(()), Err)
         })
     }
 
+    fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        if self.endpoints.len() < 2 {
+            return Vec::new();
+        }
+        self.endpoints
+            .iter()
+            .map(|endpoint| EndpointHealth {
+                url: redact_url(&endpoint.base_url),
+                healthy: endpoint.down_since.lock().unwrap().is_none(),
+            })
+            .collect()
+    }
+
     fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
         Box::pin(async move {
             #[derive(Deserialize)]
@@ -444,7 +432,7 @@ impl InferenceProvider for LMStudioProvider {
                 id: String,
             }
 
-            let response = self.http.get("v1/models").await?;
+            let response = self.with_endpoint(|http| http.get("v1/models")).await?;
             if !response.status().is_success() {
                 return Err(ProviderError::RequestFailed {
                     status: response.status().as_u16(),
@@ -462,15 +450,33 @@ impl InferenceProvider for LMStudioProvider {
         true
     }
 
-    fn supported_extensions(&self) -> Vec<&'static str> {
-        LM_STUDIO_EXTENSIONS.to_vec()
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        LM_STUDIO_EXTENSIONS
     }
 
-    fn validate_extensions(
+    fn supports_json_mode(&self) -> bool {
+        true
+    }
+
+    fn max_stop_sequences(&self) -> usize {
+        // llama.cpp has no fixed limit; this only keeps requests reasonable
+        16
+    }
+
+    fn preview_request_body(
         &self,
-        extensions: &HashMap<String, serde_json::Value>,
-    ) -> Result<(), ProviderError> {
-        Self::validate_lm_studio_extensions(extensions)
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        if let Some(ref exts) = request.extensions {
+            self.validate_extensions(exts)?;
+        }
+        let inference_req = self.build_inference_request(request, model)?;
+        let mut body = self.build_request_body(&inference_req, request.extensions.as_ref());
+        if request.stream == Some(true) {
+            body["stream"] = serde_json::json!(true);
+        }
+        Ok(body)
     }
 
     fn generate(
@@ -493,28 +499,47 @@ impl InferenceProvider for LMStudioProvider {
         };
 
         let request_body = self.build_request_body(&inference_req, extensions_validated.as_ref());
+        let hedgeable = self.http().should_hedge(&inference_req);
         let model = model.to_string();
         let request_clone = request.clone();
 
         Box::pin(async move {
-            debug!("Sending request to LM Studio: {}", request_body);
-            let response_body = self.http.post_json("v1/chat/completions", &request_body).await?;
-            debug!("LM Studio response: {}", response_body);
+            debug!("Sending request to LM Studio: {}", self.http().loggable_body(&request_body));
+            let (response_body, hedge) = self
+                .with_endpoint(|http| {
+                    http.post_json_hedged("v1/chat/completions", &request_body, hedgeable)
+                })
+                .await?;
+            debug!("LM Studio response: {}", self.http().loggable_body(&response_body));
 
             // Parse as full CompletionResponse (handles all n choices)
-            if let Ok(completion_response) =
-                serde_json::from_value::<CompletionResponse>(response_body.clone())
-            {
-                debug!(
-                    "LM Studio request completed with {} choices",
-                    completion_response.choices.len()
+            let mut completion_response = match serde_json::from_value::<CompletionResponse>(
+                response_body.clone(),
+            ) {
+                Ok(completion_response) => {
+                    debug!(
+                        "LM Studio request completed with {} choices",
+                        completion_response.choices.len()
+                    );
+                    completion_response
+                }
+                Err(_) => {
+                    // Fallback: parse as single-choice response
+                    let inference_resp = self.parse_response_body(response_body, &model)?;
+                    self.build_completion_response(&inference_resp, &request_clone)
+                }
+            };
+
+            if let Some(hedge) = hedge {
+                attach_provider_data(
+                    &mut completion_response,
+                    &request_clone,
+                    self.name(),
+                    "hedge",
+                    hedge.to_provider_data(),
                 );
-                return Ok(completion_response);
             }
-
-            // Fallback: parse as single-choice response
-            let inference_resp = self.parse_response_body(response_body, &model)?;
-            Ok(self.build_completion_response(&inference_resp, &request_clone))
+            Ok(completion_response)
         })
     }
 
@@ -540,37 +565,14 @@ impl InferenceProvider for LMStudioProvider {
         let mut request_body =
             self.build_request_body(&inference_req, extensions_validated.as_ref());
         request_body["stream"] = serde_json::json!(true);
+        let seeded = inference_req.seed.is_some();
 
         Box::pin(async move {
-            use eventsource_stream::Eventsource;
-            use futures_util::stream::StreamExt;
-
-            debug!("Sending streaming request to LM Studio: {}", request_body);
-            let response = self.http.post_stream("v1/chat/completions", &request_body).await?;
-
-            let bytes_stream = response.bytes_stream().map_err(std::io::Error::other);
-            let sse_stream = bytes_stream
-                .eventsource()
-                .filter_map(|event_result| async move {
-                    match event_result {
-                        Ok(event) => {
-                            let data = &event.data;
-                            if data == "[DONE]" {
-                                None
-                            } else {
-                                match serde_json::from_str::<StreamChunk>(data) {
-                                    Ok(chunk) => Some(Ok(chunk)),
-                                    Err(e) => Some(Err(ProviderError::StreamError(format!(
-                                        "Invalid stream chunk: {e}"
-                                    )))),
-                                }
-                            }
-                        }
-                        Err(e) => Some(Err(ProviderError::StreamError(format!("SSE error: {e}")))),
-                    }
-                });
-
-            Ok(Box::pin(sse_stream) as ProviderStream)
+            debug!("Sending streaming request to LM Studio: {}", self.http().loggable_body(&request_body));
+            self.with_endpoint(|http| {
+                http.post_sse_resumable("v1/chat/completions", &request_body, seeded)
+            })
+            .await
         })
     }
 }
@@ -578,30 +580,14 @@ impl InferenceProvider for LMStudioProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{InferenceConfig, LogFormat, LogOutput, LoggingConfig, ServerConfig};
     use crate::models::{FinishReason, Message, Role};
 
     fn create_test_settings() -> Arc<Settings> {
-        Arc::new(Settings {
-            server: ServerConfig {
-                host: "localhost".to_string(),
-                port: 3000,
-            },
-            inference: InferenceConfig {
-                base_url: "http://localhost:1234".to_string(),
-                default_model: "test-model".to_string(),
-                allowed_models: None,
-                timeout_secs: 30,
-                http: Some(HttpConfigSchema::default()),
-                provider: crate::config::InferenceProvider::LMStudio,
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: LogFormat::Pretty,
-                output: LogOutput::Stdout,
-                file: None,
-            },
-        })
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::LMStudio,
+            "http://localhost:1234",
+            "test-model",
+        ))
     }
 
     #[test]
@@ -758,4 +744,215 @@ mod tests {
             Some(FinishReason::Stop)
         );
     }
+
+    #[test]
+    fn test_extension_table_matches_previous_validation() {
+        use serde_json::json;
+
+        let settings = create_test_settings();
+        let provider = LMStudioProvider::new(settings).unwrap();
+        let reason = |name: &str, value: serde_json::Value| {
+            match provider.validate_extensions(&HashMap::from([(name.to_string(), value)])) {
+                Ok(()) => None,
+                Err(ProviderError::InvalidExtension { param, reason }) => {
+                    assert_eq!(param, name);
+                    Some(reason)
+                }
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        };
+
+        let valid = [
+            ("top_k", json!(1)),
+            ("top_k", json!(40)),
+            ("min_p", json!(0.0)),
+            ("min_p", json!(1)),
+            ("repeat_penalty", json!(1.1)),
+            ("mirostat_mode", json!(0)),
+            ("mirostat_mode", json!(2)),
+            ("mirostat_tau", json!(5.0)),
+            ("mirostat_eta", json!(0.1)),
+            ("typical_p", json!(1.0)),
+            ("tfs_z", json!(0)),
+            ("min_tokens", json!(0)),
+            ("n_probs", json!(5)),
+            ("grammar", json!("root ::= \"yes\"")),
+            ("cache_prompt", json!(true)),
+        ];
+        let invalid = [
+            ("top_k", json!(0), "must be > 0"),
+            ("top_k", json!(1.5), "must be an integer"),
+            ("min_p", json!(1.1), "must be between 0.0 and 1.0"),
+            ("min_p", json!("x"), "must be a number"),
+            ("repeat_penalty", json!(-0.1), "must be >= 0.0"),
+            ("mirostat_mode", json!(3), "must be between 0 and 2"),
+            ("mirostat_mode", json!(1.0), "must be an integer"),
+            ("mirostat_tau", json!(0.0), "must be > 0.0"),
+            ("mirostat_eta", json!(-1), "must be between 0.0 and 1.0"),
+            ("typical_p", json!(2), "must be between 0.0 and 1.0"),
+            ("tfs_z", json!(-1.0), "must be >= 0.0"),
+            ("min_tokens", json!(-1), "must be >= 0"),
+            ("min_tokens", json!("5"), "must be an integer"),
+            ("n_probs", json!(-1), "must be >= 0"),
+            ("grammar", json!(1), "must be a string"),
+            ("cache_prompt", json!("true"), "must be a boolean"),
+        ];
+
+        for spec in LM_STUDIO_EXTENSIONS {
+            assert!(valid.iter().any(|(name, _)| *name == spec.name), "{}", spec.name);
+            assert!(invalid.iter().any(|(name, ..)| *name == spec.name), "{}", spec.name);
+        }
+        for (name, value) in valid {
+            assert_eq!(reason(name, value.clone()), None, "{name}={value}");
+        }
+        for (name, value, expected) in invalid {
+            assert_eq!(
+                reason(name, value.clone()).as_deref(),
+                Some(expected),
+                "{name}={value}"
+            );
+        }
+
+        let unknown = reason("top_a", json!(1)).unwrap();
+        assert!(unknown.starts_with("Not supported by provider 'lmstudio'"));
+    }
+
+    #[test]
+    fn test_extension_constraints_use_interval_notation() {
+        let constraint = |name: &str| {
+            LM_STUDIO_EXTENSIONS
+                .iter()
+                .find(|spec| spec.name == name)
+                .and_then(|spec| spec.constraint())
+        };
+
+        assert_eq!(constraint("top_k").as_deref(), Some("(0, inf)"));
+        assert_eq!(constraint("min_p").as_deref(), Some("[0.0, 1.0]"));
+        assert_eq!(constraint("mirostat_mode").as_deref(), Some("[0, 2]"));
+        assert_eq!(constraint("grammar"), None);
+    }
+
+    #[tokio::test]
+    async fn test_dead_endpoint_is_skipped_and_reported() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"id": "test-model"}]
+            })))
+            .mount(&server)
+            .await;
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.base_urls = vec!["http://127.0.0.1:9".to_string(), server.uri()];
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 5,
+            connect_timeout_secs: 1,
+            max_retries: 0,
+            ..Default::default()
+        });
+        let provider = LMStudioProvider::new(Arc::new(settings)).unwrap();
+
+        // The first request lands on the dead endpoint and is retried on the live one
+        assert_eq!(provider.list_models().await.unwrap(), vec!["test-model"]);
+        let health = provider.endpoint_health();
+        assert_eq!(health.len(), 2);
+        assert!(!health[0].healthy);
+        assert!(health[1].healthy);
+
+        // Healthy while any endpoint answers
+        provider.health_check().await.unwrap();
+        assert!(!provider.endpoints[0].available());
+    }
+
+    #[tokio::test]
+    async fn test_request_tries_at_most_one_more_endpoint() {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v1/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [{"id": "test-model"}]
+            })))
+            .mount(&server)
+            .await;
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.base_urls = vec![
+            "http://127.0.0.1:9".to_string(),
+            "http://127.0.0.1:9".to_string(),
+            server.uri(),
+        ];
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 5,
+            connect_timeout_secs: 1,
+            max_retries: 0,
+            ..Default::default()
+        });
+        let provider = LMStudioProvider::new(Arc::new(settings)).unwrap();
+
+        // Both endpoints tried are dead; the live third one is left for later requests
+        assert!(provider.list_models().await.is_err());
+        assert_eq!(server.received_requests().await.unwrap().len(), 0);
+        assert_eq!(provider.list_models().await.unwrap(), vec!["test-model"]);
+    }
+
+    #[tokio::test]
+    async fn test_logged_bodies_are_truncated() {
+        use crate::telemetry::{CaptureWriter, TRUNCATION_SUFFIX};
+        use tracing_subscriber::layer::SubscriberExt;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let long_reply = "y".repeat(500);
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "test-123",
+                "object": "chat.completion",
+                "created": 1234567890,
+                "model": "test-model",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": long_reply},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .mount(&server)
+            .await;
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.base_url = server.uri();
+        settings.logging.max_logged_body_chars = Some(40);
+        let provider = LMStudioProvider::new(Arc::new(settings)).unwrap();
+
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || make_writer.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let long_prompt = "x".repeat(500);
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, &long_prompt)],
+            ..Default::default()
+        };
+        provider.generate(&request, "test-model").await.unwrap();
+
+        let logs = writer.contents();
+        for prefix in ["Sending request to LM Studio: ", "LM Studio response: "] {
+            let line = logs.lines().find(|line| line.contains(prefix)).unwrap();
+            let body = line.split(prefix).nth(1).unwrap();
+            assert!(body.ends_with(TRUNCATION_SUFFIX), "{line}");
+            assert_eq!(body.chars().count(), 40 + TRUNCATION_SUFFIX.len());
+        }
+        assert!(!logs.contains(&long_prompt));
+        assert!(!logs.contains(&long_reply));
+    }
 }
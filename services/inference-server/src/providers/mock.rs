@@ -1,31 +1,188 @@
+use super::openai_compatible::GenericOpenAIProvider;
 use super::{
-    BoxFuture, InferenceProvider, InferenceRequest, InferenceResponse, ProviderError,
-    ProviderStream, standard_completion_response,
+    BoxFuture, ChoiceOutput, ExtensionKind, ExtensionSpec, InferenceProvider, InferenceRequest,
+    InferenceResponse, ProviderError, ProviderStream, estimate_prompt_tokens, estimate_tokens,
+    multi_choice_completion_response, normalize_stop_sequences, standard_completion_response,
 };
 use crate::config::Settings;
-use crate::models::{CompletionRequest, CompletionResponse, FinishReason, Role};
+use crate::metrics::{UpstreamPhase, mark_phase};
+use crate::startup::redact_url;
+use crate::models::{
+    CompletionRequest, CompletionResponse, Embedding, EmbeddingRequest, EmbeddingResponse,
+    EmbeddingUsage, FinishReason, Message, Role,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use tracing::{debug, info, warn};
-use uuid::Uuid;
 
 /// Mock provider for deterministic testing
 pub struct MockProvider {
     responses_dir: PathBuf,
     // Cache loaded responses to avoid repeated file I/O
     response_cache: Arc<Mutex<HashMap<String, MockResponseFile>>>,
+    // Per-scenario position and call count, shared with the admin routes
+    sequential: Arc<SequentialState>,
+    // Set when `record: true`, to fill in scenarios that have no file
+    recorder: Option<Recorder>,
+}
+
+/// Answers scenarios without a file from a real backend and saves the answers
+struct Recorder {
+    upstream: GenericOpenAIProvider,
+    model: Option<String>,
+    // Held while recording, so concurrent first requests call the upstream once
+    lock: tokio::sync::Mutex<()>,
+}
+
+/// Where each scenario is in its responses and how often it has been called
+#[derive(Debug, Default)]
+struct SequentialState {
+    scenarios: Mutex<HashMap<String, ScenarioPosition>>,
+}
+
+/// Progress of one scenario, as reported by `GET /admin/mock/state`
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct ScenarioPosition {
+    /// Index of the response the next sequential request starts at
+    pub position: usize,
+    /// Requests served from the scenario since startup or the last reset
+    pub calls: u64,
+}
+
+impl SequentialState {
+    /// Count a request for `choices` responses against `scenario` and return
+    /// the position it starts at. In sequential mode the position moves past
+    /// those responses, wrapping after the last one.
+    fn record_call(&self, scenario: &str, responses: &MockResponseFile, choices: usize) -> usize {
+        let mut scenarios = self.scenarios.lock().unwrap();
+        let state = scenarios.entry(scenario.to_string()).or_default();
+        state.calls += 1;
+        let start = state.position;
+        if let ResponseMode::Sequential = responses.settings.mode {
+            state.position = (start + choices) % responses.mode_pool().len();
+        }
+        start
+    }
+}
+
+/// Handle for inspecting and resetting a mock provider's state from outside,
+/// so tests can start from a known point without restarting the server
+#[derive(Clone)]
+pub struct MockControl {
+    response_cache: Arc<Mutex<HashMap<String, MockResponseFile>>>,
+    sequential: Arc<SequentialState>,
+}
+
+impl MockControl {
+    /// Forget every scenario's position and call count, and drop the cached
+    /// response files so they are read again on the next request
+    pub fn reset(&self) {
+        self.sequential.scenarios.lock().unwrap().clear();
+        self.response_cache.lock().unwrap().clear();
+        info!("Mock provider state reset");
+    }
+
+    /// Position and call count of every scenario called so far
+    pub fn state(&self) -> BTreeMap<String, ScenarioPosition> {
+        self.sequential
+            .scenarios
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(scenario, state)| (scenario.clone(), *state))
+            .collect()
+    }
 }
 
 /// Structure of a mock response YAML file
 #[derive(Debug, Clone, Deserialize, Serialize)]
-struct MockResponseFile {
+pub(crate) struct MockResponseFile {
     responses: Vec<MockResponse>,
     #[serde(default)]
     settings: MockSettings,
 }
 
+impl MockResponseFile {
+    /// Responses the mode picks from outside `match` mode: those without a
+    /// `match` block, or all of them when every response has one
+    fn mode_pool(&self) -> Vec<&MockResponse> {
+        let unmatched: Vec<_> = self.responses.iter().filter(|r| r.matcher.is_none()).collect();
+        if unmatched.is_empty() {
+            self.responses.iter().collect()
+        } else {
+            unmatched
+        }
+    }
+
+    /// The part of the mode pool that suits the request: the responses whose
+    /// `when` clause holds, or else the ones without a clause
+    fn candidates(&self, params: &Parameters) -> Vec<&MockResponse> {
+        let pool = self.mode_pool();
+        let chosen: Vec<_> = pool
+            .iter()
+            .copied()
+            .filter(|r| r.when.as_ref().is_some_and(|when| when.holds(params)))
+            .collect();
+        if !chosen.is_empty() {
+            return chosen;
+        }
+        pool.into_iter().filter(|r| r.when.is_none()).collect()
+    }
+}
+
+impl MockResponse {
+    /// Whether the request has the parameters this response's `when` clause requires
+    fn suits(&self, params: &Parameters) -> bool {
+        self.when.as_ref().is_none_or(|when| when.holds(params))
+    }
+
+    /// Whether the response's `match` block fits the request
+    fn matches(&self, prompt: &Prompt) -> bool {
+        self.matcher.as_ref().is_some_and(|m| m.matches(prompt))
+    }
+}
+
+/// Scenario file written by recording: the upstream's answer as the only response
+#[derive(Debug, Serialize)]
+struct RecordedFile {
+    responses: Vec<RecordedResponse>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecordedResponse {
+    text: String,
+    model_used: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_tokens: Option<u32>,
+    finish_reason: String,
+}
+
+impl From<InferenceResponse> for RecordedResponse {
+    fn from(response: InferenceResponse) -> Self {
+        Self {
+            text: response.text,
+            model_used: response.model_used,
+            prompt_tokens: response.prompt_tokens,
+            completion_tokens: response.completion_tokens,
+            total_tokens: response.total_tokens,
+            finish_reason: response
+                .finish_reason
+                .unwrap_or(FinishReason::Stop)
+                .as_str()
+                .to_string(),
+        }
+    }
+}
+
 /// Individual mock response
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct MockResponse {
@@ -52,8 +209,324 @@ struct MockResponse {
     function_call: Option<crate::models::FunctionCall>,
     #[serde(default)]
     logprobs: Option<crate::models::LogProbs>,
+    // Optional: alternate renderings picked with `X-Mock-Variant` or the `mock_variant` extension
+    #[serde(default)]
+    variants: HashMap<String, MockVariant>,
+    // `match` mode: the request this response answers
+    #[serde(default, rename = "match", skip_serializing_if = "Option::is_none")]
+    matcher: Option<MockMatch>,
+    // `match` mode: answer requests no other response matches
+    #[serde(default)]
+    default: bool,
+    // Optional: generation parameters the request must have for this response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    when: Option<MockWhen>,
+    // Optional: how this response is streamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    streaming: Option<MockStreaming>,
+    // `weighted` mode: relative chance of picking this response
+    #[serde(default = "default_weight")]
+    weight: f64,
+}
+
+/// Streaming controls for one response, for testing slow consumers and broken streams
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct MockStreaming {
+    /// Words per content chunk (default 1)
+    #[serde(default)]
+    chunk_tokens: Option<usize>,
+    /// Delay before each chunk, instead of `delay_ms` or the scenario's `chunk_delay_ms`
+    #[serde(default)]
+    chunk_delay_ms: Option<u64>,
+    /// Fail the stream with an error after this many content chunks
+    #[serde(default)]
+    error_after_chunks: Option<usize>,
+    /// End the stream after the content, without the final chunk or finish_reason
+    #[serde(default)]
+    drop_connection: bool,
+}
+
+/// What a response answers in `match` mode. Every criterion given must hold.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MockMatch {
+    /// Substring of the user messages
+    #[serde(default)]
+    contains: Option<String>,
+    /// Pattern the user messages must match
+    #[serde(default)]
+    regex: Option<String>,
+    /// Look at the last user message alone for `contains` and `regex`
+    #[serde(default)]
+    last_user_only: bool,
+    /// Role of the last message
+    #[serde(default)]
+    role: Option<Role>,
+    /// `regex`, compiled when the scenario file is loaded
+    #[serde(skip)]
+    compiled: Option<Regex>,
+}
+
+impl MockMatch {
+    fn compile(&mut self) -> Result<(), regex::Error> {
+        if let Some(ref pattern) = self.regex {
+            self.compiled = Some(Regex::new(pattern)?);
+        }
+        Ok(())
+    }
+
+    fn matches(&self, prompt: &Prompt) -> bool {
+        let text = if self.last_user_only {
+            &prompt.last_user
+        } else {
+            &prompt.user_text
+        };
+        self.contains
+            .as_deref()
+            .is_none_or(|needle| text.contains(needle))
+            && self
+                .compiled
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(text))
+            && self
+                .role
+                .as_ref()
+                .is_none_or(|role| prompt.last_role.as_ref() == Some(role))
+    }
+}
+
+/// Generation parameters a response requires, in its `when` clause. Every
+/// condition given must hold.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct MockWhen {
+    #[serde(default)]
+    temperature: Option<Comparison>,
+    #[serde(default)]
+    max_tokens: Option<Comparison>,
+    #[serde(default)]
+    top_p: Option<Comparison>,
+    #[serde(default)]
+    seed: Option<Comparison>,
+    /// Whether the request offers tools
+    #[serde(default)]
+    tools: Option<bool>,
+}
+
+impl MockWhen {
+    fn holds(&self, params: &Parameters) -> bool {
+        self.temperature
+            .as_ref()
+            .is_none_or(|c| c.holds(params.temperature, |bound| bound as f32))
+            && self
+                .max_tokens
+                .as_ref()
+                .is_none_or(|c| c.holds(params.max_tokens, |bound| bound as u32))
+            && self
+                .top_p
+                .as_ref()
+                .is_none_or(|c| c.holds(params.top_p, |bound| bound as f32))
+            && self
+                .seed
+                .as_ref()
+                .is_none_or(|c| c.holds(params.seed, |bound| bound as u64))
+            && self.tools.is_none_or(|tools| tools == params.tools)
+    }
+}
+
+/// Bounds on one parameter, such as `{gte: 1.0}`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Comparison {
+    #[serde(default)]
+    eq: Option<f64>,
+    #[serde(default)]
+    lt: Option<f64>,
+    #[serde(default)]
+    lte: Option<f64>,
+    #[serde(default)]
+    gt: Option<f64>,
+    #[serde(default)]
+    gte: Option<f64>,
+}
+
+impl Comparison {
+    /// Whether `value` meets every bound, each converted to the parameter's type
+    /// first so `0.7` compares equal to a temperature of 0.7. A request without
+    /// the parameter meets none.
+    fn holds<T: PartialOrd>(&self, value: Option<T>, convert: impl Fn(f64) -> T) -> bool {
+        let Some(value) = value else {
+            return false;
+        };
+        self.eq.is_none_or(|bound| value == convert(bound))
+            && self.lt.is_none_or(|bound| value < convert(bound))
+            && self.lte.is_none_or(|bound| value <= convert(bound))
+            && self.gt.is_none_or(|bound| value > convert(bound))
+            && self.gte.is_none_or(|bound| value >= convert(bound))
+    }
+}
+
+/// The generation parameters `when` clauses test
+#[derive(Debug, Default)]
+struct Parameters {
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    top_p: Option<f32>,
+    seed: Option<u64>,
+    tools: bool,
+}
+
+impl From<&CompletionRequest> for Parameters {
+    fn from(request: &CompletionRequest) -> Self {
+        Self {
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            seed: request.seed,
+            tools: request.tools.as_ref().is_some_and(|tools| !tools.is_empty()),
+        }
+    }
+}
+
+/// `InferenceRequest` carries no tools, so `tools` is always false
+impl From<&InferenceRequest> for Parameters {
+    fn from(request: &InferenceRequest) -> Self {
+        Self {
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            top_p: request.top_p,
+            seed: request.seed,
+            tools: false,
+        }
+    }
+}
+
+/// The parts of a request response selection and templates look at
+#[derive(Debug, Default)]
+struct Prompt {
+    /// Content of the last user message, empty without one
+    last_user: String,
+    /// Content of every user message, one per line
+    user_text: String,
+    last_role: Option<Role>,
+    message_count: usize,
+    params: Parameters,
+    /// Estimated prompt size, for templated responses without token counts
+    estimated_tokens: u32,
+}
+
+impl Prompt {
+    fn new(messages: &[Message], params: Parameters) -> Self {
+        Self {
+            last_user: messages
+                .iter()
+                .rev()
+                .find(|message| message.role == Role::User)
+                .and_then(|message| message.content.clone())
+                .unwrap_or_default(),
+            user_text: messages
+                .iter()
+                .filter(|message| message.role == Role::User)
+                .filter_map(|message| message.content.as_deref())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            last_role: messages.last().map(|message| message.role.clone()),
+            message_count: messages.len(),
+            params,
+            estimated_tokens: estimate_prompt_tokens(messages),
+        }
+    }
+}
+
+/// Placeholders response text can use, written as `{{name}}`
+const TEMPLATE_VARIABLES: &[&str] = &[
+    "last_user_message",
+    "model",
+    "message_count",
+    "temperature",
+    "request_id",
+];
+
+/// Values substituted for the placeholders in one response
+struct TemplateValues<'a> {
+    prompt: &'a Prompt,
+    model: &'a str,
+    request_id: &'a str,
+}
+
+impl TemplateValues<'_> {
+    /// Value of a known placeholder; an absent temperature renders as empty text
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "last_user_message" => Some(self.prompt.last_user.clone()),
+            "model" => Some(self.model.to_string()),
+            "message_count" => Some(self.prompt.message_count.to_string()),
+            "temperature" => Some(
+                self.prompt
+                    .params
+                    .temperature
+                    .map(|temperature| temperature.to_string())
+                    .unwrap_or_default(),
+            ),
+            "request_id" => Some(self.request_id.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Every `{{name}}` placeholder in `text`, with its byte range and trimmed name
+fn placeholders(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut from = 0;
+    while let Some(open) = text[from..].find("{{").map(|i| from + i) {
+        let Some(close) = text[open + 2..].find("}}").map(|i| open + 2 + i) else {
+            break;
+        };
+        found.push((open..close + 2, text[open + 2..close].trim()));
+        from = close + 2;
+    }
+    found
+}
+
+/// Substitute the placeholders `value` knows, leaving the others as written
+fn render_template(text: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (span, name) in placeholders(text) {
+        if let Some(value) = value(name) {
+            rendered.push_str(&text[copied..span.start]);
+            rendered.push_str(&value);
+            copied = span.end;
+        }
+    }
+    rendered.push_str(&text[copied..]);
+    rendered
+}
+
+/// Request parameters that end a scripted response early, as they would a model
+#[derive(Debug, Default)]
+struct OutputLimits {
+    stop: Vec<String>,
+    max_tokens: Option<u32>,
+}
+
+/// Alternate rendering of a mock response
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MockVariant {
+    text: String,
 }
 
+/// Extension key (and header value source) selecting a response variant
+pub const MOCK_VARIANT_EXTENSION: &str = "mock_variant";
+
+/// Size of mock embeddings when the request doesn't ask for `dimensions`
+const MOCK_EMBEDDING_DIMENSIONS: usize = 16;
+
+const MOCK_EXTENSIONS: &[ExtensionSpec] = &[ExtensionSpec::new(
+    MOCK_VARIANT_EXTENSION,
+    ExtensionKind::String,
+    "Name of the response variant to return",
+)];
+
 /// Settings for how to serve responses
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct MockSettings {
@@ -61,6 +534,18 @@ struct MockSettings {
     mode: ResponseMode,
     #[serde(default = "default_chunk_delay_ms")]
     chunk_delay_ms: u64,
+    // Optional: always (or never) stream this scenario, whatever the client asked for
+    #[serde(default)]
+    force_streaming: Option<bool>,
+    // Optional: repeat the text forever instead of finishing the stream
+    #[serde(default)]
+    stream_never_ends: bool,
+    // Optional: go silent after this many content chunks, without finishing the stream
+    #[serde(default)]
+    hang_after_chunks: Option<usize>,
+    // Optional: reject unknown `{{placeholders}}` when loading instead of leaving them as written
+    #[serde(default)]
+    strict_templates: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -69,6 +554,8 @@ enum ResponseMode {
     First,      // Always return first response
     Sequential, // Cycle through responses
     Random,     // Random selection
+    Match,      // First response whose `match` fits the request
+    Weighted,   // Random selection in proportion to `weight`
 }
 
 fn default_model() -> String {
@@ -79,6 +566,10 @@ fn default_finish_reason() -> String {
     "stop".to_string()
 }
 
+fn default_weight() -> f64 {
+    1.0
+}
+
 /// Parse a finish_reason string into the typed enum, defaulting to Stop for unknown values
 fn parse_finish_reason(s: &str) -> FinishReason {
     match s {
@@ -91,6 +582,55 @@ fn parse_finish_reason(s: &str) -> FinishReason {
     }
 }
 
+/// Variant requested through the `mock_variant` extension, if any
+fn requested_variant(request: &CompletionRequest) -> Option<String> {
+    request
+        .extensions
+        .as_ref()?
+        .get(MOCK_VARIANT_EXTENSION)?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Randomness for one request. A request `seed` makes every random choice
+/// reproducible; without one a fresh seed is drawn, and either way the
+/// effective seed is reported so a run can be replayed.
+struct SeededRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl SeededRng {
+    fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| rand::rng().random());
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Provider request id, stable for a given seed
+    fn request_id(&mut self, scenario: &str) -> String {
+        format!("mock-{scenario}-{:016x}", self.rng.random::<u64>())
+    }
+}
+
+/// Deterministic unit vector for `text`, seeded with its FNV-1a hash
+fn mock_embedding(text: &str, dimensions: usize) -> Vec<f32> {
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    let mut rng = StdRng::seed_from_u64(hash);
+    let vector: Vec<f32> = (0..dimensions)
+        .map(|_| rng.random_range(-1.0..=1.0))
+        .collect();
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector;
+    }
+    vector.into_iter().map(|x| x / norm).collect()
+}
+
 fn default_mode() -> ResponseMode {
     ResponseMode::First
 }
@@ -104,14 +644,25 @@ impl Default for MockSettings {
         Self {
             mode: default_mode(),
             chunk_delay_ms: default_chunk_delay_ms(),
+            force_streaming: None,
+            stream_never_ends: false,
+            hang_after_chunks: None,
+            strict_templates: false,
         }
     }
 }
 
 impl MockProvider {
     pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
-        let responses_dir = match &settings.inference.provider {
-            crate::config::InferenceProvider::Mock { responses_dir } => responses_dir.clone(),
+        let (responses_dir, record_upstream) = match &settings.inference.provider {
+            crate::config::InferenceProvider::Mock {
+                responses_dir,
+                record,
+                record_upstream,
+            } => (
+                responses_dir.clone(),
+                record_upstream.as_ref().filter(|_| *record),
+            ),
             _ => {
                 return Err(ProviderError::Configuration(
                     "Invalid provider configuration for MockProvider".to_string(),
@@ -137,9 +688,28 @@ impl MockProvider {
             responses_dir
         );
 
+        let recorder = match record_upstream {
+            Some(upstream) => {
+                info!(
+                    "Recording missing mock scenarios from {}",
+                    redact_url(&upstream.base_url)
+                );
+                Some(Recorder {
+                    upstream: GenericOpenAIProvider::new(Arc::new(
+                        settings.for_record_upstream(upstream),
+                    ))?,
+                    model: upstream.model.clone(),
+                    lock: tokio::sync::Mutex::new(()),
+                })
+            }
+            None => None,
+        };
+
         Ok(Self {
             responses_dir,
             response_cache: Arc::new(Mutex::new(HashMap::new())),
+            sequential: Arc::new(SequentialState::default()),
+            recorder,
         })
     }
 
@@ -160,6 +730,56 @@ impl MockProvider {
             .map(|s| s.to_string())
     }
 
+    /// With recording on, answer a scenario that has no file yet from the
+    /// upstream and save the answer as `{scenario}.yaml` for later requests
+    async fn record_if_missing(&self, request: &InferenceRequest) -> Result<(), ProviderError> {
+        let Some(ref recorder) = self.recorder else {
+            return Ok(());
+        };
+        let scenario = self.extract_scenario(&request.model)?;
+        if scenario.contains('/') || scenario.contains('\\') || scenario.contains("..") {
+            return Err(ProviderError::Configuration(format!(
+                "Cannot record mock scenario '{scenario}': contains path separators"
+            )));
+        }
+        let path = self.responses_dir().join(format!("{scenario}.yaml"));
+        if path.exists() {
+            return Ok(());
+        }
+
+        let _recording = recorder.lock.lock().await;
+        // Another request may have recorded the scenario while this one waited
+        if path.exists() {
+            return Ok(());
+        }
+
+        info!("Recording mock scenario '{}' from upstream", scenario);
+        let mut upstream_request = request.clone();
+        upstream_request.model = recorder.model.clone().unwrap_or_else(|| scenario.clone());
+        upstream_request.stream = Some(false);
+        upstream_request.n = None;
+        let response = recorder.upstream.execute(&upstream_request).await?;
+
+        let recorded = RecordedFile {
+            responses: vec![response.into()],
+        };
+        let contents = serde_yml::to_string(&recorded).map_err(|e| {
+            ProviderError::Configuration(format!("Failed to serialize recorded scenario: {e}"))
+        })?;
+        // Write next to the scenario file and rename, so no reader sees it half written
+        let temp_path = self
+            .responses_dir()
+            .join(format!(".{scenario}.yaml.{}.tmp", std::process::id()));
+        tokio::fs::write(&temp_path, contents).await.map_err(|e| {
+            ProviderError::Configuration(format!("Failed to write mock file {temp_path:?}: {e}"))
+        })?;
+        tokio::fs::rename(&temp_path, &path).await.map_err(|e| {
+            ProviderError::Configuration(format!("Failed to write mock file {path:?}: {e}"))
+        })?;
+        info!("Recorded mock scenario '{}' to {:?}", scenario, path);
+        Ok(())
+    }
+
     /// Load responses from YAML file
     async fn load_responses(&self, scenario: &str) -> Result<MockResponseFile, ProviderError> {
         // Check cache first
@@ -198,9 +818,39 @@ impl MockProvider {
             ProviderError::Configuration(format!("Failed to read mock file {path:?}: {e}"))
         })?;
 
-        let response_file: MockResponseFile = serde_yml::from_str(&contents).map_err(|e| {
+        let mut response_file: MockResponseFile = serde_yml::from_str(&contents).map_err(|e| {
             ProviderError::Configuration(format!("Failed to parse YAML from {path:?}: {e}"))
         })?;
+        for (index, response) in response_file.responses.iter_mut().enumerate() {
+            if !(response.weight > 0.0 && response.weight.is_finite()) {
+                return Err(ProviderError::Configuration(format!(
+                    "Invalid weight {} in response {} of {path:?}: weights must be positive",
+                    response.weight,
+                    index + 1
+                )));
+            }
+            if response_file.settings.strict_templates {
+                let texts = std::iter::once(&response.text)
+                    .chain(response.variants.values().map(|variant| &variant.text));
+                let unknown = texts
+                    .flat_map(|text| placeholders(text))
+                    .find(|(_, name)| !TEMPLATE_VARIABLES.contains(name));
+                if let Some((_, name)) = unknown {
+                    return Err(ProviderError::Configuration(format!(
+                        "Unknown template placeholder '{{{{{name}}}}}' in response {} of {path:?}",
+                        index + 1
+                    )));
+                }
+            }
+            if let Some(ref mut matcher) = response.matcher {
+                matcher.compile().map_err(|e| {
+                    ProviderError::Configuration(format!(
+                        "Invalid match regex in response {} of {path:?}: {e}",
+                        index + 1
+                    ))
+                })?;
+            }
+        }
 
         if response_file.responses.is_empty() {
             return Err(ProviderError::Configuration(format!(
@@ -224,83 +874,322 @@ impl MockProvider {
         Ok(response_file)
     }
 
-    /// Select a response based on the mode
-    fn select_response(&self, responses: &MockResponseFile, scenario: &str) -> MockResponse {
-        match responses.settings.mode {
+    /// Swap in the requested variant's text. Returns a warning when the variant is unknown,
+    /// in which case the top-level text is kept.
+    fn apply_variant(response: &mut MockResponse, variant: Option<&str>) -> Option<String> {
+        let variant = variant?;
+        match response.variants.get(variant) {
+            Some(selected) => {
+                debug!("Using mock variant: {}", variant);
+                response.text = selected.text.clone();
+                None
+            }
+            None => {
+                let warning = format!("Unknown mock variant '{variant}', using default text");
+                warn!("{}", warning);
+                Some(warning)
+            }
+        }
+    }
+
+    /// Cut the text at the first occurrence of any of the request's stop sequences,
+    /// finishing with `stop` as a model would. Text without one is left alone.
+    fn apply_stop_sequences(response: &mut MockResponse, stop: &[String]) {
+        let cut = stop
+            .iter()
+            .filter(|sequence| !sequence.is_empty())
+            .filter_map(|sequence| response.text.find(sequence.as_str()))
+            .min();
+        if let Some(cut) = cut {
+            debug!("Truncating mock response at stop sequence, byte {}", cut);
+            response.text.truncate(cut);
+            response.finish_reason = "stop".to_string();
+        }
+    }
+
+    /// Keep at most `max_tokens` of the text's streaming tokens, finishing with
+    /// `length` and counting only the kept tokens as completion tokens.
+    fn apply_max_tokens(response: &mut MockResponse, max_tokens: Option<u32>) {
+        let Some(max_tokens) = max_tokens else {
+            return;
+        };
+        let tokens = super::tokenize_for_streaming(&response.text);
+        if tokens.len() <= max_tokens as usize {
+            return;
+        }
+        debug!("Truncating mock response to {} tokens", max_tokens);
+        response.text = tokens[..max_tokens as usize].concat();
+        response.finish_reason = "length".to_string();
+        response.completion_tokens = Some(max_tokens);
+        if let Some(prompt_tokens) = response.prompt_tokens {
+            response.total_tokens = Some(prompt_tokens + max_tokens);
+        }
+    }
+
+    /// Fill in the text's placeholders. Token counts the file leaves out are
+    /// estimated for templated text, since its length depends on the request.
+    fn apply_template(response: &mut MockResponse, values: &TemplateValues) {
+        let rendered = render_template(&response.text, |name| values.get(name));
+        if rendered == response.text {
+            return;
+        }
+        response.text = rendered;
+        let prompt_tokens = *response
+            .prompt_tokens
+            .get_or_insert(values.prompt.estimated_tokens);
+        let completion_tokens = *response
+            .completion_tokens
+            .get_or_insert_with(|| estimate_tokens([response.text.as_str()]));
+        response
+            .total_tokens
+            .get_or_insert(prompt_tokens + completion_tokens);
+    }
+
+    fn apply_limits(response: &mut MockResponse, limits: &OutputLimits) {
+        Self::apply_stop_sequences(response, &limits.stop);
+        Self::apply_max_tokens(response, limits.max_tokens);
+    }
+
+    /// Select a response based on the mode. `sequence` is the scenario's
+    /// position plus the choice index, used in sequential mode.
+    ///
+    /// Outside `match` mode, a response whose `match` block fits the request is
+    /// returned first; the mode picks among the other responses when none does.
+    /// Responses with a `when` clause are only picked when it holds, and those
+    /// that qualify are preferred over responses without one.
+    fn select_response(
+        &self,
+        responses: &MockResponseFile,
+        scenario: &str,
+        prompt: &Prompt,
+        rng: &mut SeededRng,
+        sequence: usize,
+    ) -> Result<MockResponse, ProviderError> {
+        if !matches!(responses.settings.mode, ResponseMode::Match)
+            && let Some(index) = responses
+                .responses
+                .iter()
+                .position(|r| r.suits(&prompt.params) && r.matches(prompt))
+        {
+            debug!("Response {} matches the request", index + 1);
+            return Ok(responses.responses[index].clone());
+        }
+
+        let pool = responses.candidates(&prompt.params);
+        if pool.is_empty() && !matches!(responses.settings.mode, ResponseMode::Match) {
+            return Err(ProviderError::Configuration(format!(
+                "No response in mock scenario '{scenario}' suits the request's parameters, \
+                 and none is without a `when` clause"
+            )));
+        }
+        let selected = match responses.settings.mode {
             ResponseMode::First => {
                 debug!("Using first response for scenario: {}", scenario);
-                responses.responses[0].clone()
+                pool[0].clone()
             }
             ResponseMode::Sequential => {
-                // This is simplified - in production you'd want persistent state
-                // For now, just use the first response
-                // TODO: Implement proper sequential tracking
-                debug!("Sequential mode - returning first response (TODO: implement cycling)");
-                responses.responses[0].clone()
+                // Requests and the choices within them continue where the
+                // scenario's previous request left off
+                let index = sequence % pool.len();
+                debug!(
+                    "Sequential mode - returning response {} of {}",
+                    index + 1,
+                    pool.len()
+                );
+                pool[index].clone()
             }
             ResponseMode::Random => {
-                use rand::Rng;
-                let mut rng = rand::rng();
-                let index = rng.random_range(0..responses.responses.len());
+                let index = rng.rng.random_range(0..pool.len());
+                debug!("Random mode - selected response {} of {}", index + 1, pool.len());
+                pool[index].clone()
+            }
+            ResponseMode::Weighted => {
+                let total: f64 = pool.iter().map(|r| r.weight).sum();
+                let mut point = rng.rng.random_range(0.0..total);
+                // Float rounding can leave `point` just past the last weight
+                let index = pool
+                    .iter()
+                    .position(|r| {
+                        point -= r.weight;
+                        point < 0.0
+                    })
+                    .unwrap_or(pool.len() - 1);
+                debug!("Weighted mode - selected response {} of {}", index + 1, pool.len());
+                pool[index].clone()
+            }
+            ResponseMode::Match => {
+                let suits = |r: &MockResponse| r.suits(&prompt.params);
+                let matched = responses
+                    .responses
+                    .iter()
+                    .position(|r| suits(r) && r.matches(prompt));
+                let index = matched
+                    .or_else(|| responses.responses.iter().position(|r| r.default && suits(r)))
+                    .ok_or_else(|| {
+                        ProviderError::Configuration(format!(
+                            "No response in mock scenario '{scenario}' matches the request, \
+                             and none is marked default"
+                        ))
+                    })?;
                 debug!(
-                    "Random mode - selected response {} of {}",
+                    "Match mode - selected response {} ({})",
                     index + 1,
-                    responses.responses.len()
+                    if matched.is_some() { "matched" } else { "default" }
                 );
                 responses.responses[index].clone()
             }
-        }
+        };
+        Ok(selected)
     }
-}
 
-impl InferenceProvider for MockProvider {
-    fn execute(
+    /// Produce `n` scripted choices for `model`, optionally rendered as `variant`.
+    /// The response describes the first choice, with usage covering all of them;
+    /// the output of every choice is returned alongside it.
+    fn respond(
         &self,
-        request: &InferenceRequest,
-    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
-        let model = request.model.clone();
-
+        model: String,
+        variant: Option<String>,
+        seed: Option<u64>,
+        n: u32,
+        prompt: Prompt,
+        limits: OutputLimits,
+    ) -> BoxFuture<'_, Result<(InferenceResponse, Vec<ChoiceOutput>), ProviderError>> {
         Box::pin(async move {
             // Extract scenario from model name
             let scenario = self.extract_scenario(&model)?;
 
             // Load responses for this scenario
             let response_file = self.load_responses(&scenario).await?;
-
-            // Select a response based on mode
-            let mock_response = self.select_response(&response_file, &scenario);
-
-            // Simulate latency if specified
-            if let Some(delay_ms) = mock_response.delay_ms {
+            let n = n.max(1) as usize;
+            let start = self.sequential.record_call(&scenario, &response_file, n);
+
+            // Select responses based on mode, then the requested rendering of them
+            let mut rng = SeededRng::new(seed);
+            let mut choices = (0..n)
+                .map(|choice| {
+                    let sequence = start + choice;
+                    self.select_response(&response_file, &scenario, &prompt, &mut rng, sequence)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let request_id = rng.request_id(&scenario);
+            let values = TemplateValues {
+                prompt: &prompt,
+                model: &model,
+                request_id: &request_id,
+            };
+            let mut variant_warning = None;
+            for choice in &mut choices {
+                variant_warning = Self::apply_variant(choice, variant.as_deref());
+                Self::apply_template(choice, &values);
+                Self::apply_limits(choice, &limits);
+            }
+            let outputs: Vec<ChoiceOutput> = choices
+                .iter()
+                .map(|c| ChoiceOutput {
+                    text: c.text.clone(),
+                    finish_reason: Some(parse_finish_reason(&c.finish_reason)),
+                    tool_calls: c.tool_calls.clone(),
+                })
+                .collect();
+            // Choices after the first only add their completion tokens
+            let extra_completion_tokens: u32 =
+                choices[1..].iter().filter_map(|c| c.completion_tokens).sum();
+            let delay_ms = choices.iter().filter_map(|c| c.delay_ms).max();
+            let mock_response = choices.swap_remove(0);
+
+            // Simulate latency if specified; choices are generated side by side
+            if let Some(delay_ms) = delay_ms {
                 debug!("Simulating {}ms latency", delay_ms);
                 tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
             }
+            mark_phase(UpstreamPhase::FirstByte);
+
+            let mut provider_data: HashMap<String, serde_json::Value> = [
+                ("scenario".to_string(), serde_json::json!(scenario)),
+                (
+                    "mode".to_string(),
+                    serde_json::json!(format!("{:?}", response_file.settings.mode)),
+                ),
+                ("seed".to_string(), serde_json::json!(rng.seed)),
+            ]
+            .into_iter()
+            .collect();
+            if let Some(variant) = variant {
+                provider_data.insert("variant".to_string(), serde_json::json!(variant));
+            }
+            if let Some(warning) = variant_warning {
+                provider_data.insert("warning".to_string(), serde_json::json!(warning));
+            }
 
             // Build the inference response
-            Ok(InferenceResponse {
+            let response = InferenceResponse {
                 text: mock_response.text,
                 model_used: mock_response.model_used,
-                total_tokens: mock_response.total_tokens,
+                total_tokens: mock_response
+                    .total_tokens
+                    .map(|tokens| tokens + extra_completion_tokens),
                 prompt_tokens: mock_response.prompt_tokens,
-                completion_tokens: mock_response.completion_tokens,
+                completion_tokens: mock_response
+                    .completion_tokens
+                    .map(|tokens| tokens + extra_completion_tokens),
                 finish_reason: Some(parse_finish_reason(&mock_response.finish_reason)),
-                latency_ms: mock_response.delay_ms,
-                provider_request_id: Some(format!("mock-{}-{}", scenario, Uuid::now_v7())),
+                latency_ms: delay_ms,
+                provider_request_id: Some(request_id),
                 system_fingerprint: mock_response.system_fingerprint,
                 tool_calls: mock_response.tool_calls,
                 logprobs: mock_response.logprobs,
-                provider_data: Some(
-                    [
-                        ("scenario".to_string(), serde_json::json!(scenario)),
-                        (
-                            "mode".to_string(),
-                            serde_json::json!(format!("{:?}", response_file.settings.mode)),
-                        ),
-                    ]
-                    .into_iter()
-                    .collect(),
-                ),
-            })
+                provider_data: Some(provider_data),
+            };
+            Ok((response, outputs))
+        })
+    }
+}
+
+impl InferenceProvider for MockProvider {
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let prompt = Prompt::new(&request.messages, Parameters::from(request));
+        let limits = OutputLimits {
+            stop: request.stop_sequences.clone().unwrap_or_default(),
+            max_tokens: request.max_tokens,
+        };
+        let respond = self.respond(request.model.clone(), None, request.seed, 1, prompt, limits);
+        let request = request.clone();
+        Box::pin(async move {
+            self.record_if_missing(&request).await?;
+            respond.await.map(|(response, _)| response)
+        })
+    }
+
+    /// Same as the default flow, but carries the `mock_variant` extension through
+    fn generate(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<CompletionResponse, ProviderError>> {
+        let variant = requested_variant(request);
+        let model = model.to_string();
+        let prompt = Prompt::new(&request.messages, Parameters::from(request));
+        let limits = OutputLimits {
+            stop: normalize_stop_sequences(&request.stop).unwrap_or_default(),
+            max_tokens: request.max_tokens,
+        };
+        let inference_req = self.build_inference_request(request, &model);
+        let request_clone = request.clone();
+        Box::pin(async move {
+            self.record_if_missing(&inference_req?).await?;
+            let n = request_clone.n.unwrap_or(1);
+            let (inference_resp, outputs) = self
+                .respond(model, variant, request_clone.seed, n, prompt, limits)
+                .await?;
+            Ok(multi_choice_completion_response(
+                &inference_resp,
+                &outputs,
+                &request_clone,
+                self.name(),
+            ))
         })
     }
 
@@ -317,15 +1206,59 @@ impl InferenceProvider for MockProvider {
         "mock"
     }
 
-    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
-        Box::pin(async move {
-            // Check that we can access the responses directory
-            if !self.responses_dir().exists() {
-                return Err(ProviderError::Configuration(format!(
-                    "Mock responses directory no longer exists: {:?}",
-                    self.responses_dir()
-                )));
-            }
+    /// Unit vectors derived from a hash of each input, so equal texts always
+    /// embed the same. Any model name is accepted.
+    fn embed(
+        &self,
+        request: &EmbeddingRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        let dimensions = request
+            .dimensions
+            .map_or(MOCK_EMBEDDING_DIMENSIONS, |dimensions| dimensions as usize);
+        let inputs = request.inputs();
+        let data = inputs
+            .iter()
+            .enumerate()
+            .map(|(index, text)| Embedding {
+                object: "embedding".to_string(),
+                index: index as u32,
+                embedding: mock_embedding(text, dimensions),
+            })
+            .collect();
+        let tokens = estimate_tokens(inputs);
+        let response = EmbeddingResponse {
+            object: "list".to_string(),
+            data,
+            model: model.to_string(),
+            usage: EmbeddingUsage {
+                prompt_tokens: tokens,
+                total_tokens: tokens,
+            },
+        };
+        Box::pin(async move { Ok(response) })
+    }
+
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        MOCK_EXTENSIONS
+    }
+
+    fn mock_control(&self) -> Option<MockControl> {
+        Some(MockControl {
+            response_cache: self.response_cache.clone(),
+            sequential: self.sequential.clone(),
+        })
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            // Check that we can access the responses directory
+            if !self.responses_dir().exists() {
+                return Err(ProviderError::Configuration(format!(
+                    "Mock responses directory no longer exists: {:?}",
+                    self.responses_dir()
+                )));
+            }
             Ok(())
         })
     }
@@ -366,6 +1299,23 @@ impl InferenceProvider for MockProvider {
         true
     }
 
+    /// Accepts every response format so clients can be developed against it;
+    /// fixtures decide what the text actually looks like
+    fn supports_json_mode(&self) -> bool {
+        true
+    }
+
+    /// Scenarios can pin the streaming mode with `settings.force_streaming`.
+    /// Load errors are left for `generate`/`stream` to report.
+    fn streaming_preference(&self, model: &str) -> BoxFuture<'_, Option<bool>> {
+        let model = model.to_string();
+        Box::pin(async move {
+            let scenario = self.extract_scenario(&model).ok()?;
+            let response_file = self.load_responses(&scenario).await.ok()?;
+            response_file.settings.force_streaming
+        })
+    }
+
     /// Stream completion by chunking the mock response with realistic delays
     fn stream(
         &self,
@@ -377,22 +1327,57 @@ impl InferenceProvider for MockProvider {
             Ok(req) => req,
             Err(e) => return Box::pin(async move { Err(e) }),
         };
+        let variant = requested_variant(request);
+        let params = Parameters::from(request);
 
         Box::pin(async move {
             use futures_util::stream::{self, StreamExt};
             use std::time::Duration;
-            use uuid::Uuid;
 
+            self.record_if_missing(&inference_req).await?;
             let scenario = self.extract_scenario(&inference_req.model)?;
             let response_file = self.load_responses(&scenario).await?;
-            let mock_response = self.select_response(&response_file, &scenario);
+            let start = self.sequential.record_call(&scenario, &response_file, 1);
+            let mut rng = SeededRng::new(inference_req.seed);
+            let prompt = Prompt::new(&inference_req.messages, params);
+            let mut mock_response =
+                self.select_response(&response_file, &scenario, &prompt, &mut rng, start)?;
+            // Stream ID, repeated by requests with the same seed
+            let request_id = rng.request_id(&scenario);
+            let values = TemplateValues {
+                prompt: &prompt,
+                model: &inference_req.model,
+                request_id: &request_id,
+            };
+            Self::apply_variant(&mut mock_response, variant.as_deref());
+            Self::apply_template(&mut mock_response, &values);
+            Self::apply_stop_sequences(
+                &mut mock_response,
+                inference_req.stop_sequences.as_deref().unwrap_or_default(),
+            );
+            Self::apply_max_tokens(&mut mock_response, inference_req.max_tokens);
 
-            // Generate a unique request ID for this stream
-            let request_id = format!("mock-{}-{}", scenario, Uuid::now_v7());
             let model_name = mock_response.model_used.clone();
 
-            // Split response into tokens for streaming
+            // Split response into tokens for streaming, grouped into chunks if asked
+            let streaming = mock_response.streaming.clone().unwrap_or_default();
             let tokens = super::tokenize_for_streaming(&mock_response.text);
+            let tokens: Vec<String> = match streaming.chunk_tokens {
+                Some(per_chunk) if per_chunk > 1 => {
+                    tokens.chunks(per_chunk).map(|chunk| chunk.concat()).collect()
+                }
+                _ => tokens,
+            };
+            let tokens: Box<dyn Iterator<Item = String> + Send> =
+                if response_file.settings.stream_never_ends {
+                    Box::new(tokens.into_iter().cycle())
+                } else {
+                    Box::new(tokens.into_iter())
+                };
+            let hang_after_chunks = response_file.settings.hang_after_chunks;
+            let error_after_chunks = streaming.error_after_chunks;
+            let chunk_limit = hang_after_chunks.into_iter().chain(error_after_chunks).min();
+            let tokens = tokens.take(chunk_limit.unwrap_or(usize::MAX));
 
             // Clone values for the final chunk closure
             let final_request_id = request_id.clone();
@@ -404,35 +1389,33 @@ impl InferenceProvider for MockProvider {
                 mock_response.total_tokens,
             );
 
-            // Get chunk delay from settings or response-specific delay
-            let chunk_delay =
-                mock_response.delay_ms.unwrap_or(response_file.settings.chunk_delay_ms);
-
-            // Create stream that yields chunks with delay
-            let chunks_stream =
-                stream::iter(tokens.into_iter().enumerate()).then(move |(i, token)| {
-                    let chunk_id = request_id.clone();
-                    let chunk_model = model_name.clone();
-
-                    async move {
-                        // Simulate realistic token generation delay
-                        if chunk_delay > 0 {
-                            tokio::time::sleep(Duration::from_millis(chunk_delay)).await;
-                        }
-
-                        if i == 0 {
-                            // First chunk includes role
-                            Ok(super::create_first_chunk(
-                                &chunk_id,
-                                &chunk_model,
-                                Role::Assistant,
-                            ))
-                        } else {
-                            // Content chunks
-                            Ok(super::create_content_chunk(&chunk_id, &chunk_model, &token))
-                        }
+            // Get chunk delay from the streaming block, the response's delay or settings
+            let chunk_delay = streaming
+                .chunk_delay_ms
+                .or(mock_response.delay_ms)
+                .unwrap_or(response_file.settings.chunk_delay_ms);
+
+            // First chunk carries only the role
+            let role_chunk = stream::once(std::future::ready(Ok(super::create_first_chunk(
+                &request_id,
+                &model_name,
+                Role::Assistant,
+            ))));
+
+            // Then one content chunk per token, with delay
+            let chunks_stream = stream::iter(tokens).then(move |token| {
+                let chunk_id = request_id.clone();
+                let chunk_model = model_name.clone();
+
+                async move {
+                    // Simulate realistic token generation delay
+                    if chunk_delay > 0 {
+                        tokio::time::sleep(Duration::from_millis(chunk_delay)).await;
                     }
-                });
+
+                    Ok(super::create_content_chunk(&chunk_id, &chunk_model, &token))
+                }
+            });
 
             // Add final chunk with finish_reason and usage
             let final_chunk_stream = stream::once(async move {
@@ -457,8 +1440,22 @@ impl InferenceProvider for MockProvider {
                 ))
             });
 
+            // A hung scenario stays open without another chunk, like a stalled upstream;
+            // whichever of the error and the hang comes first wins
+            let tail = match (error_after_chunks, hang_after_chunks) {
+                (Some(after), hang) if hang.is_none_or(|hang| after <= hang) => {
+                    stream::once(std::future::ready(Err(ProviderError::StreamError(format!(
+                        "Mock stream failed after {after} chunks"
+                    )))))
+                    .boxed()
+                }
+                (_, Some(_)) => stream::pending().boxed(),
+                _ if streaming.drop_connection => stream::empty().boxed(),
+                _ => final_chunk_stream.boxed(),
+            };
+
             // Combine the streams
-            let combined_stream = chunks_stream.chain(final_chunk_stream);
+            let combined_stream = role_chunk.chain(chunks_stream).chain(tail);
 
             Ok(Box::pin(combined_stream) as ProviderStream)
         })
@@ -468,33 +1465,20 @@ impl InferenceProvider for MockProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{
-        HttpConfigSchema, InferenceConfig, LogFormat, LogOutput, LoggingConfig, ServerConfig,
-    };
+    use crate::models::StreamChunk;
     use std::fs;
     use tempfile::TempDir;
 
     fn create_test_settings(responses_dir: PathBuf) -> Arc<Settings> {
-        Arc::new(Settings {
-            server: ServerConfig {
-                host: "localhost".to_string(),
-                port: 3000,
-            },
-            inference: InferenceConfig {
-                base_url: "http://localhost:1234".to_string(),
-                default_model: "test-model".to_string(),
-                allowed_models: None,
-                timeout_secs: 30,
-                http: Some(HttpConfigSchema::default()),
-                provider: crate::config::InferenceProvider::Mock { responses_dir },
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: LogFormat::Pretty,
-                output: LogOutput::Stdout,
-                file: None,
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::Mock {
+                responses_dir,
+                record: false,
+                record_upstream: None,
             },
-        })
+            "http://localhost:1234",
+            "test-model",
+        ))
     }
 
     #[test]
@@ -537,4 +1521,1111 @@ settings:
         assert_eq!(response_file.responses.len(), 1);
         assert_eq!(response_file.responses[0].text, "Test response");
     }
+
+    #[tokio::test]
+    async fn test_streaming_preference_follows_scenario_setting() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("forced.yaml"),
+            r#"
+responses:
+  - text: "Always streamed"
+settings:
+  force_streaming: true
+"#,
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("plain.yaml"),
+            "responses:\n  - text: \"Client decides\"\n",
+        )
+        .unwrap();
+
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+
+        assert_eq!(provider.streaming_preference("mock-forced").await, Some(true));
+        assert_eq!(provider.streaming_preference("mock-plain").await, None);
+        assert_eq!(provider.streaming_preference("mock-missing").await, None);
+    }
+
+    fn variant_request(variant: Option<&str>, stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![crate::models::Message::new(Role::User, "Hi")],
+            stream: Some(stream),
+            response_mode: Some(crate::models::ResponseMode::Extended),
+            extensions: variant.map(|variant| {
+                HashMap::from([(MOCK_VARIANT_EXTENSION.to_string(), serde_json::json!(variant))])
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn variants_provider() -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("render.yaml"),
+            r#"
+responses:
+  - text: "Default text"
+    variants:
+      markdown:
+        text: "**Bold** text"
+      plain:
+        text: "Plain variant text"
+      json:
+        text: '{"text": "json"}'
+settings:
+  chunk_delay_ms: 0
+"#,
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    #[tokio::test]
+    async fn test_variants_select_text_and_fall_back() {
+        let (_dir, provider) = variants_provider();
+
+        for (variant, expected) in [
+            (Some("markdown"), "**Bold** text"),
+            (Some("plain"), "Plain variant text"),
+            (Some("json"), r#"{"text": "json"}"#),
+            (None, "Default text"),
+            (Some("html"), "Default text"),
+        ] {
+            let response = provider
+                .generate(&variant_request(variant, false), "mock-render")
+                .await
+                .unwrap();
+            let message = response.choices[0].message.as_ref().unwrap();
+            assert_eq!(message.content.as_deref(), Some(expected));
+
+            let data = &response.provider_extensions.unwrap().data;
+            assert_eq!(data.contains_key("warning"), variant == Some("html"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_variant_applies_to_streams() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) = variants_provider();
+
+        let stream = provider
+            .stream(&variant_request(Some("plain"), true), "mock-render")
+            .await
+            .unwrap();
+        let text: String = stream
+            .filter_map(|chunk| async move {
+                chunk.ok()?.choices.first()?.delta.content.clone()
+            })
+            .collect()
+            .await;
+
+        assert_eq!(text, "Plain variant text ");
+    }
+
+    fn endless_provider(settings: &str) -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("endless.yaml"),
+            format!("responses:\n  - text: \"One two three\"\nsettings:\n{settings}"),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    #[tokio::test]
+    async fn test_never_ending_stream_keeps_producing_past_the_text() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) =
+            endless_provider("  chunk_delay_ms: 0\n  stream_never_ends: true\n");
+
+        let stream = provider
+            .stream(&variant_request(None, true), "mock-endless")
+            .await
+            .unwrap();
+        let chunks: Vec<_> = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            stream.take(100).collect::<Vec<_>>(),
+        )
+        .await
+        .unwrap();
+
+        // Three tokens would normally finish by the fifth chunk
+        assert_eq!(chunks.len(), 100);
+        for chunk in chunks {
+            assert!(chunk.unwrap().choices[0].finish_reason.is_none());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_hangs_after_configured_chunks() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) = endless_provider(
+            "  chunk_delay_ms: 10\n  stream_never_ends: true\n  hang_after_chunks: 4\n",
+        );
+
+        let mut stream = provider
+            .stream(&variant_request(None, true), "mock-endless")
+            .await
+            .unwrap();
+        // The role chunk, then four content chunks
+        for _ in 0..5 {
+            let chunk = stream.next().await.unwrap().unwrap();
+            assert!(chunk.choices[0].finish_reason.is_none());
+        }
+
+        let next = tokio::time::timeout(std::time::Duration::from_secs(60), stream.next()).await;
+        assert!(next.is_err(), "Stream should still be open and silent");
+    }
+
+    /// Stream the response to `streaming` through to its end, chunk results included
+    async fn collect_streaming(streaming: &str) -> Vec<Result<StreamChunk, ProviderError>> {
+        use futures_util::StreamExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("chunky.yaml"),
+            format!(
+                "responses:\n  - text: \"One two three four five\"\n    streaming:\n{streaming}\
+                 settings:\n  chunk_delay_ms: 0\n"
+            ),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        provider
+            .stream(&variant_request(None, true), "mock-chunky")
+            .await
+            .unwrap()
+            .collect()
+            .await
+    }
+
+    fn chunk_texts(chunks: &[Result<StreamChunk, ProviderError>]) -> Vec<String> {
+        chunks
+            .iter()
+            .filter_map(|chunk| chunk.as_ref().ok()?.choices.first()?.delta.content.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_streaming_groups_words_into_chunks() {
+        let chunks = collect_streaming("      chunk_tokens: 2\n      chunk_delay_ms: 1\n").await;
+
+        assert_eq!(chunk_texts(&chunks), ["One two ", "three four ", "five "]);
+        let last = chunks.last().unwrap().as_ref().unwrap();
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_fails_after_configured_chunks() {
+        let chunks = collect_streaming("      error_after_chunks: 2\n").await;
+
+        // The role chunk, two content chunks, then the error ends the stream
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunk_texts(&chunks), ["One ", "two "]);
+        assert!(matches!(chunks[3], Err(ProviderError::StreamError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_can_drop_the_connection() {
+        let chunks = collect_streaming("      drop_connection: true\n").await;
+
+        assert_eq!(chunk_texts(&chunks).concat(), "One two three four five ");
+        for chunk in &chunks {
+            let chunk = chunk.as_ref().unwrap();
+            assert!(chunk.choices[0].finish_reason.is_none());
+            assert!(chunk.usage.is_none());
+        }
+    }
+
+    fn seeded_request(seed: Option<u64>, stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            seed,
+            ..variant_request(None, stream)
+        }
+    }
+
+    fn random_provider() -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        let responses: String = (1..=8)
+            .map(|n| format!("  - text: \"Answer number {n}\"\n"))
+            .collect();
+        fs::write(
+            temp_dir.path().join("random.yaml"),
+            format!("responses:\n{responses}settings:\n  mode: random\n  chunk_delay_ms: 0\n"),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    /// The response as sent, minus `created`, which is wall-clock time
+    async fn seeded_completion(provider: &MockProvider, seed: Option<u64>) -> String {
+        let response = provider
+            .generate(&seeded_request(seed, false), "mock-random")
+            .await
+            .unwrap();
+        let mut value = serde_json::to_value(response).unwrap();
+        value.as_object_mut().unwrap().remove("created");
+        value.to_string()
+    }
+
+    async fn seeded_stream(provider: &MockProvider, seed: u64) -> Vec<String> {
+        use futures_util::StreamExt;
+
+        provider
+            .stream(&seeded_request(Some(seed), true), "mock-random")
+            .await
+            .unwrap()
+            .map(|chunk| {
+                let mut value = serde_json::to_value(chunk.unwrap()).unwrap();
+                value.as_object_mut().unwrap().remove("created");
+                value.to_string()
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_reproduces_response() {
+        let (_dir, provider) = random_provider();
+        let mut texts = std::collections::HashSet::new();
+
+        for seed in 0..32 {
+            let first = seeded_completion(&provider, Some(seed)).await;
+            assert_eq!(first, seeded_completion(&provider, Some(seed)).await);
+            assert_eq!(seeded_stream(&provider, seed).await, seeded_stream(&provider, seed).await);
+
+            let value: serde_json::Value = serde_json::from_str(&first).unwrap();
+            assert_eq!(value["provider_extensions"]["data"]["seed"], seed);
+            texts.insert(value["choices"][0]["message"]["content"].clone());
+        }
+        // Different seeds pick different responses
+        assert!(texts.len() > 1, "32 seeds all picked {texts:?}");
+    }
+
+    #[tokio::test]
+    async fn test_unseeded_requests_report_a_replayable_seed() {
+        let (_dir, provider) = random_provider();
+
+        let unseeded: serde_json::Value =
+            serde_json::from_str(&seeded_completion(&provider, None).await).unwrap();
+        let seed = unseeded["provider_extensions"]["data"]["seed"]
+            .as_u64()
+            .unwrap();
+        let replayed: serde_json::Value =
+            serde_json::from_str(&seeded_completion(&provider, Some(seed)).await).unwrap();
+
+        assert_eq!(replayed, unseeded);
+        let other: serde_json::Value =
+            serde_json::from_str(&seeded_completion(&provider, None).await).unwrap();
+        assert_ne!(other["provider_extensions"]["data"]["seed"], seed);
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_are_deterministic_unit_vectors() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        let request: EmbeddingRequest =
+            serde_json::from_value(serde_json::json!({"input": ["alpha", "beta", "alpha"]}))
+                .unwrap();
+
+        let response = provider.embed(&request, "mock-embed").await.unwrap();
+
+        assert_eq!(response.object, "list");
+        assert_eq!(response.model, "mock-embed");
+        assert_eq!(response.data.len(), 3);
+        for (i, item) in response.data.iter().enumerate() {
+            assert_eq!(item.index, i as u32);
+            assert_eq!(item.embedding.len(), MOCK_EMBEDDING_DIMENSIONS);
+            let norm = item.embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-4, "norm {norm}");
+        }
+        assert_eq!(response.data[0].embedding, response.data[2].embedding);
+        assert_ne!(response.data[0].embedding, response.data[1].embedding);
+        assert!(response.usage.prompt_tokens > 0);
+        assert_eq!(response.usage.total_tokens, response.usage.prompt_tokens);
+
+        let request: EmbeddingRequest =
+            serde_json::from_value(serde_json::json!({"input": "alpha", "dimensions": 4}))
+                .unwrap();
+        let response = provider.embed(&request, "mock-embed").await.unwrap();
+        assert_eq!(response.data[0].embedding.len(), 4);
+    }
+
+    fn stop_provider() -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("stop.yaml"),
+            r#"
+responses:
+  - text: "Hello world STOP more"
+    finish_reason: length
+    prompt_tokens: 5
+    completion_tokens: 4
+    total_tokens: 9
+settings:
+  chunk_delay_ms: 0
+"#,
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    fn stop_request(stop: Option<&str>, stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![crate::models::Message::new(Role::User, "Hi")],
+            stop: stop.map(|stop| crate::models::StringOrArray::Array(vec![stop.to_string()])),
+            stream: Some(stream),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_truncates_response() {
+        let (_dir, provider) = stop_provider();
+
+        let response = provider
+            .generate(&stop_request(Some("STOP"), false), "mock-stop")
+            .await
+            .unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello world "));
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+
+        // No match leaves the text and finish reason as configured
+        let response = provider
+            .generate(&stop_request(Some("###"), false), "mock-stop")
+            .await
+            .unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello world STOP more"));
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Length));
+    }
+
+    #[tokio::test]
+    async fn test_stop_sequence_ends_stream_early() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) = stop_provider();
+
+        let chunks: Vec<_> = provider
+            .stream(&stop_request(Some("STOP"), true), "mock-stop")
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.choices.first()?.delta.content.clone())
+            .collect();
+
+        assert_eq!(text, "Hello world ");
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_truncates_response() {
+        let (_dir, provider) = stop_provider();
+        let request = CompletionRequest {
+            max_tokens: Some(2),
+            ..stop_request(None, false)
+        };
+
+        let response = provider.generate(&request, "mock-stop").await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello world "));
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Length));
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.completion_tokens, Some(2));
+        assert_eq!(usage.total_tokens, Some(7));
+
+        // A cap above the length leaves the response as configured
+        let request = CompletionRequest {
+            max_tokens: Some(10),
+            ..stop_request(None, false)
+        };
+        let response = provider.generate(&request, "mock-stop").await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hello world STOP more"));
+        assert_eq!(response.usage.unwrap().completion_tokens, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_ends_stream_early() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) = stop_provider();
+        let request = CompletionRequest {
+            max_tokens: Some(3),
+            ..stop_request(None, true)
+        };
+
+        let chunks: Vec<_> = provider
+            .stream(&request, "mock-stop")
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.choices.first()?.delta.content.clone())
+            .collect();
+
+        assert_eq!(text, "Hello world STOP ");
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::Length));
+        assert_eq!(last.usage.as_ref().unwrap().completion_tokens, Some(3));
+    }
+
+    fn match_provider() -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("tasks.yaml"),
+            r#"
+responses:
+  - text: "Hello again"
+    match:
+      contains: "hello"
+      last_user_only: true
+  - text: "A short summary"
+    match:
+      contains: "summarize"
+  - text: "Une traduction"
+    match:
+      regex: "(?i)^translate\\b"
+  - text: "Tool result noted"
+    match:
+      role: tool
+  - text: "I can summarize or translate"
+    default: true
+settings:
+  mode: match
+  chunk_delay_ms: 0
+"#,
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    fn prompt_request(messages: Vec<crate::models::Message>) -> CompletionRequest {
+        CompletionRequest {
+            messages,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_mode_answers_by_user_messages() {
+        use crate::models::Message;
+
+        let (_dir, provider) = match_provider();
+        let tool_reply = Message {
+            tool_call_id: Some("call-1".to_string()),
+            ..Message::new(Role::Tool, "42")
+        };
+
+        for (messages, expected) in [
+            (vec![Message::new(Role::User, "Please summarize this")], "A short summary"),
+            (vec![Message::new(Role::User, "Translate to French")], "Une traduction"),
+            (vec![Message::new(Role::User, "Can you translate?")], "I can summarize or translate"),
+            (
+                vec![Message::new(Role::User, "Look it up"), tool_reply],
+                "Tool result noted",
+            ),
+            (
+                vec![
+                    Message::new(Role::User, "summarize this"),
+                    Message::new(Role::Assistant, "Sure"),
+                    Message::new(Role::User, "Thanks"),
+                ],
+                "A short summary",
+            ),
+            (
+                vec![
+                    Message::new(Role::User, "summarize this"),
+                    Message::new(Role::Assistant, "Sure"),
+                    Message::new(Role::User, "hello"),
+                ],
+                "Hello again",
+            ),
+            (
+                vec![
+                    Message::new(Role::User, "hello"),
+                    Message::new(Role::Assistant, "Hi"),
+                    Message::new(Role::User, "Can you translate?"),
+                ],
+                "I can summarize or translate",
+            ),
+        ] {
+            let response = provider
+                .generate(&prompt_request(messages), "mock-tasks")
+                .await
+                .unwrap();
+            let message = response.choices[0].message.as_ref().unwrap();
+            assert_eq!(message.content.as_deref(), Some(expected));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_match_mode_without_default_or_valid_regex_fails() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("strict.yaml"),
+            "responses:\n  - text: \"Hi\"\n    match:\n      contains: \"hello\"\nsettings:\n  mode: match\n",
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.path().join("broken.yaml"),
+            "responses:\n  - text: \"Hi\"\n    match:\n      regex: \"(unclosed\"\nsettings:\n  mode: match\n",
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        let request = prompt_request(vec![crate::models::Message::new(Role::User, "Bye")]);
+
+        let miss = provider.generate(&request, "mock-strict").await.unwrap_err();
+        assert!(
+            matches!(miss, ProviderError::Configuration(ref m) if m.contains("none is marked default"))
+        );
+
+        let broken = provider.load_responses("broken").await.unwrap_err();
+        assert!(matches!(
+            broken,
+            ProviderError::Configuration(ref m) if m.contains("Invalid match regex in response 1")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_match_blocks_take_precedence_in_other_modes() {
+        use crate::models::Message;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("mixed.yaml"),
+            r#"
+responses:
+  - text: "A short summary"
+    match:
+      contains: "summarize"
+  - text: "One"
+  - text: "An order number"
+    match:
+      regex: "\\b\\d{5}\\b"
+  - text: "Two"
+settings:
+  mode: sequential
+"#,
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+
+        // Requests no block fits cycle through the responses without one; the
+        // sequence moves on with every request, matched or not
+        for (prompt, expected) in [
+            ("Hello", "One"),
+            ("Please summarize this", "A short summary"),
+            ("Where is order 12345?", "An order number"),
+            ("Order 123 please", "Two"),
+            ("Hello again", "One"),
+        ] {
+            let request = prompt_request(vec![Message::new(Role::User, prompt)]);
+            let response = provider.generate(&request, "mock-mixed").await.unwrap();
+            let message = response.choices[0].message.as_ref().unwrap();
+            assert_eq!(message.content.as_deref(), Some(expected), "{prompt}");
+        }
+    }
+
+    fn when_provider(mode: &str) -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("params.yaml"),
+            format!(
+                r#"
+responses:
+  - text: "Seeded and creative"
+    when:
+      temperature: {{gte: 1.0}}
+      seed: {{eq: 7}}
+  - text: "Creative"
+    when:
+      temperature: {{gte: 1.0}}
+  - text: "Wild"
+    when:
+      temperature: {{gt: 1.0}}
+  - text: "Boring"
+  - text: "Cut short"
+    finish_reason: "length"
+    when:
+      max_tokens: {{lt: 50}}
+  - text: "Calling a tool"
+    when:
+      tools: true
+settings:
+  mode: {mode}
+"#
+            ),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    async fn when_text(provider: &MockProvider, request: CompletionRequest) -> String {
+        let request = CompletionRequest {
+            messages: vec![crate::models::Message::new(Role::User, "Hello")],
+            ..request
+        };
+        let response = provider.generate(&request, "mock-params").await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        message.content.clone().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_when_clauses_select_by_request_parameters() {
+        let (_dir, provider) = when_provider("first");
+        let tool: crate::models::Tool = serde_json::from_value(serde_json::json!({
+            "type": "function",
+            "function": {"name": "lookup"}
+        }))
+        .unwrap();
+
+        for (request, expected) in [
+            (CompletionRequest::default(), "Boring"),
+            (
+                CompletionRequest {
+                    temperature: Some(0.7),
+                    ..Default::default()
+                },
+                "Boring",
+            ),
+            (
+                CompletionRequest {
+                    temperature: Some(1.0),
+                    ..Default::default()
+                },
+                "Creative",
+            ),
+            // Every condition of a clause must hold
+            (
+                CompletionRequest {
+                    temperature: Some(1.0),
+                    seed: Some(7),
+                    ..Default::default()
+                },
+                "Seeded and creative",
+            ),
+            (
+                CompletionRequest {
+                    max_tokens: Some(10),
+                    ..Default::default()
+                },
+                "Cut short",
+            ),
+            (
+                CompletionRequest {
+                    tools: Some(vec![tool]),
+                    ..Default::default()
+                },
+                "Calling a tool",
+            ),
+        ] {
+            assert_eq!(when_text(&provider, request).await, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_when_clauses_narrow_the_random_pool() {
+        let (_dir, provider) = when_provider("random");
+
+        let mut hot = std::collections::HashSet::new();
+        // Seeds other than 7, which would also qualify the seeded response
+        for seed in 100..132 {
+            let request = CompletionRequest {
+                temperature: Some(1.5),
+                seed: Some(seed),
+                ..Default::default()
+            };
+            hot.insert(when_text(&provider, request).await);
+        }
+        let expected: std::collections::HashSet<String> =
+            ["Creative".to_string(), "Wild".to_string()].into();
+        assert_eq!(hot, expected);
+
+        let request = CompletionRequest {
+            temperature: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(when_text(&provider, request).await, "Boring");
+    }
+
+    #[tokio::test]
+    async fn test_n_choices_cycle_through_sequential_responses() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("multi.yaml"),
+            r#"
+responses:
+  - text: "First"
+    prompt_tokens: 5
+    completion_tokens: 2
+    total_tokens: 7
+  - text: "Second"
+    completion_tokens: 3
+    finish_reason: "length"
+settings:
+  mode: sequential
+"#,
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        let request = CompletionRequest {
+            messages: vec![crate::models::Message::new(Role::User, "Hello")],
+            n: Some(3),
+            ..Default::default()
+        };
+
+        let response = provider.generate(&request, "mock-multi").await.unwrap();
+
+        assert_eq!(response.choices.len(), 3);
+        let indices: Vec<u32> = response.choices.iter().map(|c| c.index).collect();
+        assert_eq!(indices, [0, 1, 2]);
+        let texts: Vec<_> = response
+            .choices
+            .iter()
+            .map(|c| c.message.as_ref().and_then(|m| m.content.as_deref()))
+            .collect();
+        assert_eq!(texts, [Some("First"), Some("Second"), Some("First")]);
+        let finish_reasons: Vec<_> =
+            response.choices.iter().map(|c| c.finish_reason.clone()).collect();
+        assert_eq!(
+            finish_reasons,
+            [Some(FinishReason::Stop), Some(FinishReason::Length), Some(FinishReason::Stop)]
+        );
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(5));
+        assert_eq!(usage.completion_tokens, Some(7));
+        assert_eq!(usage.total_tokens, Some(12));
+    }
+
+    fn sequential_provider() -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        for scenario in ["test", "integration"] {
+            fs::write(
+                temp_dir.path().join(format!("{scenario}.yaml")),
+                r#"
+responses:
+  - text: "One"
+  - text: "Two"
+  - text: "Three"
+settings:
+  mode: sequential
+  chunk_delay_ms: 0
+"#,
+            )
+            .unwrap();
+        }
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    async fn sequential_text(provider: &MockProvider, model: &str) -> String {
+        let request = CompletionRequest {
+            messages: vec![crate::models::Message::new(Role::User, "Hello")],
+            ..Default::default()
+        };
+        let response = provider.generate(&request, model).await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        message.content.clone().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sequential_mode_cycles_across_requests() {
+        let (_dir, provider) = sequential_provider();
+
+        for expected in ["One", "Two", "Three", "One"] {
+            assert_eq!(sequential_text(&provider, "mock-test").await, expected);
+        }
+        // Each scenario keeps its own position
+        assert_eq!(sequential_text(&provider, "mock-integration").await, "One");
+        assert_eq!(sequential_text(&provider, "mock-test").await, "Two");
+
+        let state = provider.mock_control().unwrap().state();
+        assert_eq!(state["test"].position, 2);
+        assert_eq!(state["test"].calls, 5);
+        assert_eq!(state["integration"].position, 1);
+    }
+
+    #[tokio::test]
+    async fn test_sequential_mode_streams_continue_the_sequence() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) = sequential_provider();
+        assert_eq!(sequential_text(&provider, "mock-test").await, "One");
+
+        let text: String = provider
+            .stream(&variant_request(None, true), "mock-test")
+            .await
+            .unwrap()
+            .filter_map(|chunk| async move {
+                chunk.unwrap().choices.first()?.delta.content.clone()
+            })
+            .collect()
+            .await;
+        assert_eq!(text, "Two ");
+        assert_eq!(sequential_text(&provider, "mock-test").await, "Three");
+    }
+
+    fn weighted_provider(weights: [&str; 2]) -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("weighted.yaml"),
+            format!(
+                "responses:\n  - text: \"Common\"\n    weight: {}\n  \
+                 - text: \"Rare\"\n    weight: {}\nsettings:\n  mode: weighted\n",
+                weights[0], weights[1]
+            ),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    #[tokio::test]
+    async fn test_weighted_mode_picks_in_proportion_to_weights() {
+        let (_dir, provider) = weighted_provider(["3", "1"]);
+        let responses = provider.load_responses("weighted").await.unwrap();
+
+        // Seeds 0..4000 always draw the same picks
+        let common = (0..4000)
+            .filter(|&seed| {
+                let mut rng = SeededRng::new(Some(seed));
+                let response = provider
+                    .select_response(&responses, "weighted", &Prompt::default(), &mut rng, 0)
+                    .unwrap();
+                response.text == "Common"
+            })
+            .count();
+
+        // Expect 3000 of 4000; the band is far wider than the sampling noise
+        assert!((2800..=3200).contains(&common), "{common} of 4000 were common");
+    }
+
+    #[tokio::test]
+    async fn test_weighted_mode_rejects_non_positive_weights() {
+        for weights in [["1", "0"], ["-2", "1"]] {
+            let (_dir, provider) = weighted_provider(weights);
+
+            let error = provider.load_responses("weighted").await.unwrap_err();
+            let ProviderError::Configuration(ref message) = error else {
+                panic!("expected a configuration error, got {error}");
+            };
+            assert!(message.contains("weights must be positive"), "{message}");
+        }
+    }
+
+    fn template_provider(text: &str, strict: bool) -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("echo.yaml"),
+            format!("responses:\n  - text: \"{text}\"\nsettings:\n  strict_templates: {strict}\n"),
+        )
+        .unwrap();
+        let provider =
+            MockProvider::new(create_test_settings(temp_dir.path().to_path_buf())).unwrap();
+        (temp_dir, provider)
+    }
+
+    #[tokio::test]
+    async fn test_templates_echo_the_request() {
+        use crate::models::Message;
+
+        let (_dir, provider) = template_provider(
+            "You said {{last_user_message}} to {{ model }} in {{message_count}} messages \
+             at {{temperature}} as {{request_id}}, {{unknown}}",
+            false,
+        );
+        let request = CompletionRequest {
+            temperature: Some(0.5),
+            ..prompt_request(vec![
+                Message::new(Role::System, "Be brief"),
+                Message::new(Role::User, "hello there"),
+            ])
+        };
+        let inference_req = provider.build_inference_request(&request, "mock-echo").unwrap();
+
+        let response = provider.execute(&inference_req).await.unwrap();
+        let request_id = response.provider_request_id.unwrap();
+        assert_eq!(
+            response.text,
+            format!(
+                "You said hello there to mock-echo in 2 messages at 0.5 as {request_id}, \
+                 {{{{unknown}}}}"
+            )
+        );
+        // The file gives no token counts, so they are estimated from the texts
+        let completion_tokens = estimate_tokens([response.text.as_str()]);
+        let prompt_tokens = estimate_prompt_tokens(&request.messages);
+        assert_eq!(response.completion_tokens, Some(completion_tokens));
+        assert_eq!(response.prompt_tokens, Some(prompt_tokens));
+        assert_eq!(response.total_tokens, Some(prompt_tokens + completion_tokens));
+    }
+
+    #[tokio::test]
+    async fn test_templates_apply_to_streams() {
+        use futures_util::StreamExt;
+
+        let (_dir, provider) =
+            template_provider("{{request_id}} said {{last_user_message}} to {{model}}", false);
+        let request = CompletionRequest {
+            stream: Some(true),
+            ..prompt_request(vec![crate::models::Message::new(Role::User, "hello there")])
+        };
+
+        let chunks: Vec<StreamChunk> = provider
+            .stream(&request, "mock-echo")
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        let text: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.choices[0].delta.content.as_deref())
+            .collect();
+        let request_id = &chunks[0].id;
+        assert_eq!(text, format!("{request_id} said hello there to mock-echo "));
+        let usage = chunks.last().unwrap().usage.as_ref().unwrap();
+        assert_eq!(usage.completion_tokens, Some(estimate_tokens([text.trim_end()])));
+    }
+
+    #[tokio::test]
+    async fn test_strict_templates_reject_unknown_placeholders() {
+        let (_dir, provider) = template_provider("Hi {{user_name}}", true);
+
+        let error = provider.load_responses("echo").await.unwrap_err();
+        let ProviderError::Configuration(ref message) = error else {
+            panic!("expected a configuration error, got {error}");
+        };
+        assert!(message.contains("'{{user_name}}'"), "{message}");
+
+        let (_dir, provider) = template_provider("Hi {{model}}", true);
+        assert!(provider.load_responses("echo").await.is_ok());
+    }
+
+    fn recording_provider(upstream: &str) -> (TempDir, MockProvider) {
+        let temp_dir = TempDir::new().unwrap();
+        let mut settings = (*create_test_settings(temp_dir.path().to_path_buf())).clone();
+        settings.inference.provider = crate::config::InferenceProvider::Mock {
+            responses_dir: temp_dir.path().to_path_buf(),
+            record: true,
+            record_upstream: Some(crate::config::RecordUpstream {
+                base_url: upstream.to_string(),
+                api_key: Some("sk-test".to_string()),
+                model: None,
+            }),
+        };
+        let provider = MockProvider::new(Arc::new(settings)).unwrap();
+        (temp_dir, provider)
+    }
+
+    #[tokio::test]
+    async fn test_record_saves_missing_scenario_once() {
+        use wiremock::matchers::{body_partial_json, header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .and(header("authorization", "Bearer sk-test"))
+            .and(body_partial_json(serde_json::json!({"model": "gpt-4o-mini"})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "gpt-4o-mini-2024-07-18",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Recorded answer"},
+                    "finish_reason": "length"
+                }],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let (dir, provider) = recording_provider(&server.uri());
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hi")],
+            ..Default::default()
+        };
+
+        // Concurrent first requests share one recording
+        let responses = futures_util::future::join_all(
+            (0..4).map(|_| provider.generate(&request, "mock-gpt-4o-mini")),
+        )
+        .await;
+        for response in responses {
+            let response = response.unwrap();
+            let message = response.choices[0].message.as_ref().unwrap();
+            assert_eq!(message.content.as_deref(), Some("Recorded answer"));
+            assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Length));
+        }
+
+        let saved = fs::read_to_string(dir.path().join("gpt-4o-mini.yaml")).unwrap();
+        let file: MockResponseFile = serde_yml::from_str(&saved).unwrap();
+        assert_eq!(file.responses.len(), 1);
+        assert_eq!(file.responses[0].text, "Recorded answer");
+        assert_eq!(file.responses[0].model_used, "gpt-4o-mini-2024-07-18");
+        assert_eq!(file.responses[0].completion_tokens, Some(2));
+        assert_eq!(file.responses[0].total_tokens, Some(7));
+        assert_eq!(file.responses[0].finish_reason, "length");
+        // Nothing but the scenario file is left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Later requests replay the file
+        let response = provider.generate(&request, "mock-gpt-4o-mini").await.unwrap();
+        assert_eq!(response.usage.unwrap().completion_tokens, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_record_leaves_existing_scenarios_alone() {
+        // Nothing listens upstream, so any recording attempt would fail
+        let (dir, provider) = recording_provider("http://127.0.0.1:9");
+        fs::write(dir.path().join("known.yaml"), "responses:\n  - text: \"From the file\"\n")
+            .unwrap();
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hi")],
+            ..Default::default()
+        };
+
+        let response = provider.generate(&request, "mock-known").await.unwrap();
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("From the file"));
+    }
 }
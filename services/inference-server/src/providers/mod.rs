@@ -1,18 +1,94 @@
-use crate::config::HttpConfigSchema;
-use crate::models::{Choice, CompletionRequest, CompletionResponse, FinishReason, Message, Role, Usage};
+use crate::config::{HttpConfigSchema, InvalidUtf8};
+use crate::metrics::{UpstreamPhase, mark_attempt, mark_hedges, mark_phase};
+use crate::models::{
+    Choice, CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse,
+    FinishReason, Message, Role, Usage,
+};
+use futures_util::stream::FuturesUnordered;
+use futures_util::{Stream, StreamExt};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::future::Future;
 use std::pin::Pin;
-use tracing::{debug, error};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tracing::{Instrument, debug, error, info, warn};
 use uuid::Uuid;
 
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
+/// Last known state of one upstream endpoint, for `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointHealth {
+    pub url: String,
+    pub healthy: bool,
+}
+
+/// Model ids listed under the name of the provider serving them
+pub type ProviderModels = Vec<(String, Vec<String>)>;
+
+pub mod anthropic;
+pub mod circuit_breaker;
+pub mod failover;
 pub mod lmstudio;
 pub mod mock;
+pub mod ollama;
 pub mod openai;
+pub mod openai_compatible;
+pub mod routing;
+pub mod triton;
+
+// ===== Request budget =====
+
+/// Time limits of the completion being served, set by `with_budget`
+#[derive(Debug, Clone, Copy)]
+struct RequestBudget {
+    deadline: Instant,
+    /// How far the completion deadline was stretched under load; upstream
+    /// requests get `http.timeout_secs` stretched by the same factor
+    stretch: f64,
+}
+
+tokio::task_local! {
+    static REQUEST_BUDGET: RequestBudget;
+}
+
+/// Run a provider call within `budget`, failing with `DeadlineExceeded` once it
+/// runs out. The upstream requests it sends time out with the budget at the latest,
+/// and get `stretch` times their `http.timeout_secs`.
+pub async fn with_budget<T>(
+    budget: Duration,
+    stretch: f64,
+    call: impl Future<Output = Result<T, ProviderError>>,
+) -> Result<T, ProviderError> {
+    let started = Instant::now();
+    let limits = RequestBudget {
+        deadline: started + budget,
+        stretch,
+    };
+    tokio::time::timeout(budget, REQUEST_BUDGET.scope(limits, call))
+        .await
+        .unwrap_or_else(|_| {
+            Err(ProviderError::DeadlineExceeded {
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                budget_ms: budget.as_millis() as u64,
+            })
+        })
+}
+
+/// Time left of the budget the current provider call runs in; `None` outside `with_budget`
+pub fn remaining_budget() -> Option<Duration> {
+    REQUEST_BUDGET
+        .try_with(|budget| budget.deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+/// Stretch the current budget applies to upstream request timeouts
+pub fn budget_stretch() -> f64 {
+    REQUEST_BUDGET.try_with(|budget| budget.stretch).unwrap_or(1.0)
+}
 
 // ===== HttpProviderClient =====
 
@@ -22,6 +98,37 @@ pub struct HttpProviderClient {
     client: reqwest::Client,
     base_url: url::Url,
     http_config: HttpConfigSchema,
+    max_logged_body_chars: Option<usize>,
+    extra_request_fields: Option<serde_json::Map<String, serde_json::Value>>,
+    invalid_utf8: InvalidUtf8,
+    // Kept for logging; the client sends them with every request
+    default_headers: reqwest::header::HeaderMap,
+    secret_headers: &'static [&'static str],
+    stream_reconnect: bool,
+}
+
+/// Headers redacted in logs for every provider. `api-key` is Azure OpenAI's.
+const SECRET_HEADERS: &[&str] = &["authorization", "proxy-authorization", "api-key"];
+
+/// Render `headers` as `name: value` pairs for logging, with the values of
+/// `SECRET_HEADERS` and `extra_secrets` replaced by `[REDACTED]`
+pub fn redact_headers(headers: &reqwest::header::HeaderMap, extra_secrets: &[&str]) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let secret = SECRET_HEADERS
+                .iter()
+                .chain(extra_secrets)
+                .any(|secret| name.as_str().eq_ignore_ascii_case(secret));
+            let value = if secret {
+                "[REDACTED]"
+            } else {
+                value.to_str().unwrap_or("<binary>")
+            };
+            format!("{name}: {value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl HttpProviderClient {
@@ -42,11 +149,14 @@ impl HttpProviderClient {
             .timeout(config.timeout())
             .connect_timeout(config.connect_timeout())
             .pool_idle_timeout(config.keep_alive())
-            .pool_max_idle_per_host(config.max_idle_connections.unwrap_or(10));
+            .pool_max_idle_per_host(config.max_idle_connections.unwrap_or(10))
+            // Sends `Accept-Encoding: gzip, br` and decodes those bodies transparently
+            .gzip(true)
+            .brotli(true)
+            .connector_layer(ConnectTimingLayer);
 
-        if let Some(headers) = default_headers {
-            builder = builder.default_headers(headers);
-        }
+        let default_headers = default_headers.unwrap_or_default();
+        builder = builder.default_headers(default_headers.clone());
 
         let client = builder.build().map_err(|e| {
             ProviderError::Configuration(format!("Failed to build HTTP client: {e}"))
@@ -56,9 +166,70 @@ impl HttpProviderClient {
             client,
             base_url: parsed_url,
             http_config: config,
+            max_logged_body_chars: None,
+            extra_request_fields: None,
+            invalid_utf8: InvalidUtf8::default(),
+            default_headers,
+            secret_headers: &[],
+            stream_reconnect: false,
         })
     }
 
+    /// Let `post_sse_resumable` request a dropped stream again
+    pub fn with_stream_reconnect(mut self, stream_reconnect: bool) -> Self {
+        self.stream_reconnect = stream_reconnect;
+        self
+    }
+
+    /// Provider-specific headers, such as `x-api-key`, whose values must never
+    /// be logged. Authorization headers are always redacted.
+    pub fn with_secret_headers(mut self, names: &'static [&'static str]) -> Self {
+        self.secret_headers = names;
+        self
+    }
+
+    /// Render the headers sent with every request for debug logs, secrets redacted
+    pub fn loggable_headers(&self) -> String {
+        redact_headers(&self.default_headers, self.secret_headers)
+    }
+
+    /// Limit how much of each request/response body is written to debug logs
+    pub fn with_max_logged_body_chars(mut self, max_chars: Option<usize>) -> Self {
+        self.max_logged_body_chars = max_chars;
+        self
+    }
+
+    /// Fields added to every request body that doesn't already set them
+    pub fn with_extra_request_fields(
+        mut self,
+        fields: Option<serde_json::Map<String, serde_json::Value>>,
+    ) -> Self {
+        self.extra_request_fields = fields;
+        self
+    }
+
+    /// How `post_sse` treats bytes that are not valid UTF-8
+    pub fn with_invalid_utf8(mut self, invalid_utf8: InvalidUtf8) -> Self {
+        self.invalid_utf8 = invalid_utf8;
+        self
+    }
+
+    /// Add the configured extra fields to a finished request body, keeping
+    /// any value the request already set
+    pub fn merge_extra_request_fields(&self, body: &mut serde_json::Value) {
+        let (Some(extra), Some(body)) = (&self.extra_request_fields, body.as_object_mut()) else {
+            return;
+        };
+        for (key, value) in extra {
+            body.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+
+    /// Render a JSON body for logging, truncated per the logging config
+    pub fn loggable_body(&self, body: &serde_json::Value) -> String {
+        crate::telemetry::truncate_for_log(&body.to_string(), self.max_logged_body_chars).into_owned()
+    }
+
     /// Build a full URL by joining a path onto the base URL.
     pub fn url(&self, path: &str) -> String {
         // Use simple string concatenation since base_url may or may not have trailing slash
@@ -72,12 +243,37 @@ impl HttpProviderClient {
         &self.http_config
     }
 
-    /// Map a reqwest error to a ProviderError.
-    pub fn map_reqwest_error(e: &reqwest::Error) -> ProviderError {
-        if e.is_timeout() {
-            ProviderError::Timeout
-        } else if e.is_connect() {
-            ProviderError::ConnectionFailed(format!("Connection failed: {e}"))
+    /// Timeout for one upstream request: `http.timeout_secs`, stretched like the
+    /// completion deadline. A non-streaming request also ends with that deadline;
+    /// a stream outlives it.
+    fn request_timeout(&self, streaming: bool) -> Duration {
+        let timeout = self.http_config.timeout();
+        REQUEST_BUDGET
+            .try_with(|budget| {
+                let stretched = timeout.mul_f64(budget.stretch);
+                if streaming {
+                    stretched
+                } else {
+                    stretched.min(budget.deadline.saturating_duration_since(Instant::now()))
+                }
+            })
+            .unwrap_or(timeout)
+    }
+
+    /// Map a reqwest error to a ProviderError. `started` is when the attempt was sent,
+    /// used to report how much of the request budget was spent.
+    pub fn map_reqwest_error(&self, e: &reqwest::Error, started: Instant) -> ProviderError {
+        if e.is_connect() {
+            if e.is_timeout() {
+                ProviderError::ConnectTimeout
+            } else {
+                ProviderError::ConnectionFailed(format!("Connection failed: {e}"))
+            }
+        } else if e.is_timeout() {
+            ProviderError::DeadlineExceeded {
+                elapsed_ms: started.elapsed().as_millis() as u64,
+                budget_ms: self.http_config.timeout().as_millis() as u64,
+            }
         } else {
             ProviderError::RequestFailed {
                 status: 0,
@@ -87,7 +283,10 @@ impl HttpProviderClient {
     }
 
     /// Send a POST request with JSON body and return the parsed JSON response.
-    /// Includes exponential backoff retry on timeout and connection failures.
+    /// Includes exponential backoff retry on connect timeouts, connection failures
+    /// and 5xx responses. A 429 is retried only when it carries a `Retry-After`
+    /// that fits `max_retry_after_secs` and the request budget, which then
+    /// replaces the backoff delay.
     pub async fn post_json(
         &self,
         path: &str,
@@ -95,71 +294,198 @@ impl HttpProviderClient {
     ) -> Result<serde_json::Value, ProviderError> {
         let url = self.url(path);
         let max_retries = self.http_config.max_retries;
-        let backoff_ms = self.http_config.retry_backoff_ms;
 
         let mut last_error = None;
+        let mut retry_after = None;
 
         for attempt in 0..=max_retries {
             if attempt > 0 {
-                let delay = backoff_ms * 2u64.pow(attempt - 1);
-                debug!("Retrying request (attempt {}/{}) after {}ms", attempt + 1, max_retries + 1, delay);
-                tokio::time::sleep(tokio::time::Duration::from_millis(delay)).await;
-            }
-
-            match self.client.post(&url).json(body).send().await {
-                Ok(response) => {
-                    let status = response.status();
-                    if !status.is_success() {
-                        let error_text = response
-                            .text()
-                            .await
-                            .unwrap_or_else(|_| "Unknown error".to_string());
-                        // Don't retry on 4xx errors
-                        if status.is_client_error() {
-                            return Err(ProviderError::RequestFailed {
-                                status: status.as_u16(),
-                                message: error_text,
-                            });
-                        }
-                        // Retry on 5xx errors
-                        last_error = Some(ProviderError::RequestFailed {
-                            status: status.as_u16(),
-                            message: error_text,
-                        });
-                        continue;
+                let delay = retry_after
+                    .take()
+                    .unwrap_or_else(|| self.http_config.retry_backoff(attempt));
+                debug!(
+                    "Retrying request (attempt {}/{}) after {}ms",
+                    attempt + 1,
+                    max_retries + 1,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+            }
+
+            match self.send_json(&url, body).await {
+                Ok(value) => {
+                    if attempt > 0 {
+                        debug!(retries = attempt, "Upstream request succeeded after retrying");
                     }
-                    return response.json().await.map_err(|e| {
-                        ProviderError::InvalidResponse(format!("Invalid JSON response: {e}"))
-                    });
+                    return Ok(value);
                 }
-                Err(e) => {
-                    let provider_err = Self::map_reqwest_error(&e);
-                    match provider_err {
-                        ProviderError::Timeout | ProviderError::ConnectionFailed(_) => {
-                            error!("Request failed (attempt {}/{}): {}", attempt + 1, max_retries + 1, e);
-                            last_error = Some(provider_err);
-                            continue;
-                        }
-                        _ => return Err(provider_err),
-                    }
+                Err(failed) if !failed.retryable => return Err(failed.error),
+                Err(failed) => {
+                    retry_after = failed.retry_after;
+                    last_error = Some(failed.error);
                 }
             }
         }
 
+        debug!(retries = max_retries, "Upstream request failed after exhausting retries");
         Err(last_error.unwrap_or(ProviderError::ConnectionFailed(
             "All retry attempts exhausted".to_string(),
         )))
     }
 
+    /// Send one POST request with a JSON body, without retrying. A failure says
+    /// whether sending the request again may succeed.
+    async fn send_json(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, FailedAttempt> {
+        debug!("POST {} with headers [{}]", url, self.loggable_headers());
+        let started = Instant::now();
+        mark_attempt();
+        let request = self.client.post(url).timeout(self.request_timeout(false));
+        let response = match request.json(body).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                let error = self.map_reqwest_error(&e, started);
+                if error.is_retryable() {
+                    error!("Request failed: {}", e);
+                }
+                return Err(FailedAttempt {
+                    retryable: error.is_retryable(),
+                    error,
+                    retry_after: None,
+                });
+            }
+        };
+        mark_phase(UpstreamPhase::FirstByte);
+
+        let status = response.status();
+        if status.is_success() {
+            return read_json(response).await.map_err(FailedAttempt::fatal);
+        }
+        let retry_after = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            parse_retry_after(response.headers()).filter(|wait| self.can_wait(*wait))
+        } else {
+            None
+        };
+        let error_text = read_error_text(response).await;
+        // 5xx errors are retried; 4xx errors only when the upstream or body says it's transient
+        let retryable = !status.is_client_error()
+            || retry_after.is_some()
+            || self.is_retryable_message(&error_text);
+        Err(FailedAttempt {
+            error: ProviderError::from_status(status.as_u16(), error_text),
+            retryable,
+            retry_after,
+        })
+    }
+
+    /// Whether a `Retry-After` wait is short enough to sit out before retrying
+    fn can_wait(&self, wait: Duration) -> bool {
+        wait <= Duration::from_secs(self.http_config.max_retry_after_secs)
+            && remaining_budget().is_none_or(|remaining| wait < remaining)
+    }
+
+    /// Whether an error body matches one of `retry_on_messages`
+    fn is_retryable_message(&self, error_text: &str) -> bool {
+        self.http_config
+            .retry_on_messages
+            .as_ref()
+            .is_some_and(|messages| messages.iter().any(|m| error_text.contains(m.as_str())))
+    }
+
+    /// Whether a request is eligible for hedging under the configured policy.
+    /// With `deterministic_only`, only seeded or temperature-0 requests are hedged so we
+    /// don't pay for two divergent generations.
+    pub fn should_hedge(&self, request: &InferenceRequest) -> bool {
+        match self.http_config.hedging {
+            Some(ref hedging) => {
+                !hedging.deterministic_only
+                    || request.seed.is_some()
+                    || request.temperature == Some(0.0)
+            }
+            None => false,
+        }
+    }
+
+    /// Send a POST request like `post_json`, but if no response arrives within the hedging
+    /// delay, send up to `max_hedges` duplicates and take the first successful response.
+    /// Each attempt is sent once, without retries. Losing attempts are cancelled by
+    /// dropping their futures. The request fails once every attempt sent has failed;
+    /// after an error that is not retryable no further hedges are sent.
+    pub async fn post_json_hedged(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+        hedgeable: bool,
+    ) -> Result<(serde_json::Value, Option<HedgeOutcome>), ProviderError> {
+        let hedging = match self.http_config.hedging {
+            Some(ref hedging) if hedgeable => hedging.clone(),
+            _ => return self.post_json(path, body).await.map(|value| (value, None)),
+        };
+
+        let url = self.url(path);
+        let mut attempts = FuturesUnordered::new();
+        attempts.push(self.tagged_send_json(0, &url, body));
+        let mut hedges_fired = 0;
+        let mut last_error = None;
+        let mut stop_hedging = false;
+
+        let result = loop {
+            let can_hedge = hedges_fired < hedging.max_hedges && !stop_hedging;
+            if attempts.is_empty() && !can_hedge {
+                break Err(last_error.expect("an attempt failed before hedging stopped"));
+            }
+            tokio::select! {
+                Some((attempt, result)) = attempts.next() => match result {
+                    Ok(value) => {
+                        let outcome = HedgeOutcome { hedges_fired, winner: attempt };
+                        info!(
+                            hedges_fired = outcome.hedges_fired,
+                            winner = outcome.winner_label(),
+                            "Hedged request completed"
+                        );
+                        break Ok((value, Some(outcome)));
+                    }
+                    Err(failed) => {
+                        debug!("Hedge attempt {} failed: {}", attempt, failed.error);
+                        // A duplicate would fail the same way, but those already sent may not
+                        stop_hedging |= !failed.retryable;
+                        last_error = Some(failed.error);
+                    }
+                },
+                _ = tokio::time::sleep(hedging.delay()), if can_hedge => {
+                    hedges_fired += 1;
+                    debug!("No response after {}ms, sending hedge {}", hedging.delay_ms, hedges_fired);
+                    attempts.push(self.tagged_send_json(hedges_fired, &url, body));
+                }
+            }
+        };
+        let won_by_hedge = matches!(result, Ok((_, Some(ref outcome))) if outcome.winner > 0);
+        mark_hedges(hedges_fired, won_by_hedge);
+        result
+    }
+
+    async fn tagged_send_json(
+        &self,
+        attempt: u32,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> (u32, Result<serde_json::Value, FailedAttempt>) {
+        (attempt, self.send_json(url, body).await)
+    }
+
     /// Send a GET request and return the response.
     pub async fn get(&self, path: &str) -> Result<reqwest::Response, ProviderError> {
         let url = self.url(path);
+        let started = Instant::now();
         let response = self
             .client
             .get(&url)
             .send()
             .await
-            .map_err(|e| Self::map_reqwest_error(&e))?;
+            .map_err(|e| self.map_reqwest_error(&e, started))?;
         Ok(response)
     }
 
@@ -171,37 +497,416 @@ impl HttpProviderClient {
         body: &serde_json::Value,
     ) -> Result<reqwest::Response, ProviderError> {
         let url = self.url(path);
+        debug!("POST {} (streaming) with headers [{}]", url, self.loggable_headers());
+        let started = Instant::now();
+        mark_attempt();
         let response = self
             .client
             .post(&url)
+            .timeout(self.request_timeout(true))
             .json(body)
             .send()
             .await
             .map_err(|e| {
                 error!("Failed to send streaming request: {}", e);
-                Self::map_reqwest_error(&e)
+                self.map_reqwest_error(&e, started)
             })?;
+        mark_phase(UpstreamPhase::FirstByte);
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(ProviderError::RequestFailed {
-                status: status.as_u16(),
-                message: error_text,
-            });
+            let error_text = read_error_text(response).await;
+            return Err(ProviderError::from_status(status.as_u16(), error_text));
         }
 
         Ok(response)
     }
+
+    /// Send a streaming POST and parse the OpenAI-style SSE response into chunks.
+    /// Like `post_stream`, this is never retried.
+    pub async fn post_sse(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<ProviderStream, ProviderError> {
+        let response = self.post_stream(path, body).await?;
+        Ok(parse_sse_stream(response.bytes_stream(), self.invalid_utf8))
+    }
+
+    /// Like `post_sse`, but with `stream_reconnect` on and a `deterministic` request
+    /// (one with a `seed`), a stream that fails or ends before its finish chunk is
+    /// requested again, up to `max_retries` times. The chunks the consumer already
+    /// has are skipped, relying on the seeded upstream to send them again unchanged.
+    pub async fn post_sse_resumable(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+        deterministic: bool,
+    ) -> Result<ProviderStream, ProviderError> {
+        let stream = self.post_sse(path, body).await?;
+        if !(self.stream_reconnect && deterministic) {
+            return Ok(stream);
+        }
+        let reconnect = Reconnect {
+            client: self.client.clone(),
+            url: self.url(path),
+            body: body.clone(),
+            http_config: self.http_config.clone(),
+            invalid_utf8: self.invalid_utf8,
+        };
+        Ok(resume_on_drop(stream, Arc::new(reconnect)))
+    }
+
+    /// Send a streaming POST and split the newline-delimited JSON response into lines.
+    /// Like `post_stream`, this is never retried.
+    pub async fn post_ndjson(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<LineStream, ProviderError> {
+        let response = self.post_stream(path, body).await?;
+        Ok(split_lines(response.bytes_stream(), self.invalid_utf8))
+    }
+
+    /// Send a streaming POST and return the raw SSE events, for upstreams whose
+    /// events are not OpenAI chunks. Like `post_stream`, this is never retried.
+    pub async fn post_sse_events(
+        &self,
+        path: &str,
+        body: &serde_json::Value,
+    ) -> Result<EventStream, ProviderError> {
+        let response = self.post_stream(path, body).await?;
+        Ok(sse_events(response.bytes_stream(), self.invalid_utf8))
+    }
+}
+
+/// Non-empty lines of a newline-delimited upstream response
+pub type LineStream = Pin<Box<dyn Stream<Item = Result<String, ProviderError>> + Send>>;
+
+/// Split a byte stream into lines, decoding whole characters only. A last line
+/// without a trailing newline is still returned.
+pub fn split_lines<S, B, E>(bytes: S, invalid_utf8: InvalidUtf8) -> LineStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: fmt::Display + Send + 'static,
+{
+    let mut decoder = Utf8Decoder::new(invalid_utf8);
+    let mut buffer = String::new();
+    let lines = bytes
+        .map(Some)
+        .chain(futures_util::stream::once(std::future::ready(None)))
+        .map(move |read| {
+            let text = match read {
+                Some(Ok(bytes)) => decoder.push(bytes.as_ref()),
+                Some(Err(e)) => Err(ProviderError::StreamError(format!("Stream read error: {e}"))),
+                None => decoder.finish().map(|rest| rest + "\n"),
+            };
+            let mut lines = Vec::new();
+            match text {
+                Ok(text) => {
+                    buffer.push_str(&text);
+                    while let Some(end) = buffer.find('\n') {
+                        let line: String = buffer.drain(..=end).collect();
+                        let line = line.trim();
+                        if !line.is_empty() {
+                            lines.push(Ok(line.to_string()));
+                        }
+                    }
+                }
+                Err(e) => lines.push(Err(e)),
+            }
+            futures_util::stream::iter(lines)
+        })
+        .flatten();
+    Box::pin(lines)
+}
+
+/// Incremental UTF-8 decoder for byte streams. A multi-byte character split
+/// across reads is held back until the rest of it arrives, so text is never
+/// decoded from half a character.
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+    invalid_utf8: InvalidUtf8,
+}
+
+impl Utf8Decoder {
+    pub fn new(invalid_utf8: InvalidUtf8) -> Self {
+        Self {
+            pending: Vec::new(),
+            invalid_utf8,
+        }
+    }
+
+    /// Decode everything up to the last complete character
+    pub fn push(&mut self, bytes: &[u8]) -> Result<String, ProviderError> {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        loop {
+            let error = match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+                    return Ok(text);
+                }
+                Err(error) => error,
+            };
+            let valid = error.valid_up_to();
+            text.push_str(std::str::from_utf8(&self.pending[..valid]).unwrap_or_default());
+            match error.error_len() {
+                // An incomplete character at the end: wait for the next read
+                None => {
+                    self.pending.drain(..valid);
+                    return Ok(text);
+                }
+                Some(invalid) => {
+                    self.invalid_bytes()?;
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    self.pending.drain(..valid + invalid);
+                }
+            }
+        }
+    }
+
+    /// End of input: anything still pending is a truncated character
+    pub fn finish(&mut self) -> Result<String, ProviderError> {
+        if self.pending.is_empty() {
+            return Ok(String::new());
+        }
+        self.pending.clear();
+        self.invalid_bytes()?;
+        Ok(char::REPLACEMENT_CHARACTER.to_string())
+    }
+
+    fn invalid_bytes(&self) -> Result<(), ProviderError> {
+        match self.invalid_utf8 {
+            InvalidUtf8::Replace => Ok(()),
+            InvalidUtf8::Error => Err(ProviderError::StreamError(
+                "Upstream stream is not valid UTF-8".to_string(),
+            )),
+        }
+    }
+}
+
+/// Server-sent events of an upstream response, with their event names
+pub type EventStream =
+    Pin<Box<dyn Stream<Item = Result<eventsource_stream::Event, ProviderError>> + Send>>;
+
+/// Parse an OpenAI-style SSE byte stream into chunks, ending at `[DONE]`.
+pub fn parse_sse_stream<S, B, E>(bytes: S, invalid_utf8: InvalidUtf8) -> ProviderStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: fmt::Display + Send + 'static,
+{
+    let chunks = sse_events(bytes, invalid_utf8).filter_map(|event_result| async move {
+        match event_result {
+            Ok(event) => {
+                let data = &event.data;
+                if data == "[DONE]" {
+                    None
+                } else {
+                    match serde_json::from_str::<crate::models::StreamChunk>(data) {
+                        Ok(chunk) => Some(Ok(chunk)),
+                        Err(e) => Some(Err(ProviderError::StreamError(format!(
+                            "Invalid stream chunk: {e}"
+                        )))),
+                    }
+                }
+            }
+            Err(e) => Some(Err(e)),
+        }
+    });
+    Box::pin(chunks)
+}
+
+/// What a resumable stream needs to send its request again
+struct Reconnect {
+    client: reqwest::Client,
+    url: String,
+    body: serde_json::Value,
+    http_config: HttpConfigSchema,
+    invalid_utf8: InvalidUtf8,
+}
+
+impl Reconnect {
+    async fn open(&self) -> Result<ProviderStream, ProviderError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&self.body)
+            .send()
+            .await
+            .map_err(|e| ProviderError::StreamError(format!("Stream reconnect failed: {e}")))?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = read_error_text(response).await;
+            return Err(ProviderError::from_status(status.as_u16(), error_text));
+        }
+        Ok(parse_sse_stream(response.bytes_stream(), self.invalid_utf8))
+    }
+}
+
+/// How far a resumable stream has got
+struct Resume {
+    stream: ProviderStream,
+    /// Chunks handed to the consumer
+    delivered: usize,
+    /// Chunks of the current stream still to skip, already delivered by an earlier one
+    skip: usize,
+    reconnects: u32,
+    finished: bool,
+}
+
+/// Reissue the request behind `stream` whenever it fails or ends before a finish
+/// chunk, skipping as many chunks of each new stream as were already delivered
+fn resume_on_drop(stream: ProviderStream, reconnect: Arc<Reconnect>) -> ProviderStream {
+    let start = Resume {
+        stream,
+        delivered: 0,
+        skip: 0,
+        reconnects: 0,
+        finished: false,
+    };
+    Box::pin(futures_util::stream::unfold(Some(start), move |resume| {
+        let reconnect = reconnect.clone();
+        async move {
+            let mut resume = resume?;
+            loop {
+                // The error to report if reconnecting is not possible
+                let dropped = match resume.stream.next().await {
+                    Some(Ok(_)) if resume.skip > 0 => {
+                        resume.skip -= 1;
+                        continue;
+                    }
+                    Some(Ok(chunk)) => {
+                        resume.delivered += 1;
+                        resume.finished |= chunk.choices.iter().any(|c| c.finish_reason.is_some());
+                        return Some((Ok(chunk), Some(resume)));
+                    }
+                    Some(Err(e @ ProviderError::StreamError(_))) if !resume.finished => Some(e),
+                    Some(Err(e)) => return Some((Err(e), None)),
+                    None if resume.finished => return None,
+                    None => None,
+                };
+
+                if resume.reconnects >= reconnect.http_config.max_retries {
+                    return dropped.map(|e| (Err(e), None));
+                }
+                resume.reconnects += 1;
+                warn!(
+                    reconnect = resume.reconnects,
+                    delivered_chunks = resume.delivered,
+                    error = ?dropped,
+                    "Upstream stream dropped, requesting it again"
+                );
+                tokio::time::sleep(reconnect.http_config.retry_backoff(resume.reconnects)).await;
+                match reconnect.open().await {
+                    Ok(stream) => {
+                        resume.stream = stream;
+                        resume.skip = resume.delivered;
+                    }
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
+        }
+    }))
+}
+
+/// Split an SSE byte stream into events. Bytes are decoded to text before event
+/// parsing, so no event ever holds half a character.
+pub fn sse_events<S, B, E>(bytes: S, invalid_utf8: InvalidUtf8) -> EventStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: fmt::Display + Send + 'static,
+{
+    use eventsource_stream::Eventsource;
+
+    let mut decoder = Utf8Decoder::new(invalid_utf8);
+    let text = bytes
+        .map(Some)
+        .chain(futures_util::stream::once(std::future::ready(None)))
+        .map(move |read| match read {
+            Some(Ok(bytes)) => decoder.push(bytes.as_ref()),
+            Some(Err(e)) => Err(ProviderError::StreamError(format!("Stream read error: {e}"))),
+            None => decoder.finish(),
+        });
+
+    Box::pin(
+        text.eventsource()
+            .map(|event| event.map_err(|e| ProviderError::StreamError(format!("SSE error: {e}")))),
+    )
+}
+
+/// Connector layer marking `UpstreamPhase::Connect` whenever a new upstream
+/// connection (TCP plus TLS) is established. Reused pooled connections never reach it.
+#[derive(Clone)]
+struct ConnectTimingLayer;
+
+impl<S> tower::Layer<S> for ConnectTimingLayer {
+    type Service = ConnectTiming<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTiming(inner)
+    }
+}
+
+#[derive(Clone)]
+struct ConnectTiming<S>(S);
+
+impl<S, R> tower::Service<R> for ConnectTiming<S>
+where
+    S: tower::Service<R>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: R) -> Self::Future {
+        let connecting = self.0.call(request);
+        Box::pin(async move {
+            let connection = connecting.await?;
+            mark_phase(UpstreamPhase::Connect);
+            Ok(connection)
+        })
+    }
+}
+
+/// Result of a hedged request: how many duplicates were sent and which attempt won
+#[derive(Debug, Clone, Serialize)]
+pub struct HedgeOutcome {
+    pub hedges_fired: u32,
+    pub winner: u32, // 0 is the primary request, 1.. are hedges
+}
+
+impl HedgeOutcome {
+    pub fn winner_label(&self) -> String {
+        if self.winner == 0 {
+            "primary".to_string()
+        } else {
+            format!("hedge-{}", self.winner)
+        }
+    }
+
+    /// Representation used in extended-mode provider data
+    pub fn to_provider_data(&self) -> serde_json::Value {
+        serde_json::json!({
+            "hedges_fired": self.hedges_fired,
+            "winner": self.winner_label(),
+        })
+    }
 }
 
 // ===== Internal Service Models =====
 
 /// Normalized request format that all providers understand
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct InferenceRequest {
     // Core fields that all providers need
     pub messages: Vec<Message>,
@@ -231,6 +936,10 @@ pub struct InferenceRequest {
 
     // Token biasing
     pub logit_bias: Option<serde_json::Map<String, serde_json::Value>>, // Token ID to bias value (-100 to 100)
+
+    // Output modalities
+    pub modalities: Option<Vec<String>>,
+    pub audio: Option<crate::models::AudioOutput>,
 }
 
 /// Normalized response format that all providers return
@@ -274,7 +983,22 @@ pub enum ProviderError {
         status: u16,
         message: String,
     },
-    Timeout,
+    /// Upstream rejected the request body or URL as too large (HTTP 413/414)
+    PayloadTooLarge {
+        status: u16,
+        message: String,
+    },
+    /// No connection within the connect timeout; the upstream is likely down
+    ConnectTimeout,
+    /// The overall request budget ran out before the provider answered
+    DeadlineExceeded {
+        elapsed_ms: u64,
+        budget_ms: u64,
+    },
+    /// A stream produced no chunk within the stall timeout
+    StreamStalled {
+        after_chunks: u64,
+    },
     Configuration(String),
     StreamingNotSupported,
     StreamError(String),
@@ -301,7 +1025,20 @@ impl fmt::Display for ProviderError {
             ProviderError::RequestFailed { status, message } => {
                 write!(f, "Request failed with status {status}: {message}")
             }
-            ProviderError::Timeout => write!(f, "Request timed out"),
+            ProviderError::PayloadTooLarge { status, message } => {
+                write!(f, "Request too large for provider (status {status}): {message}")
+            }
+            ProviderError::ConnectTimeout => write!(f, "Timed out connecting to provider"),
+            ProviderError::DeadlineExceeded {
+                elapsed_ms,
+                budget_ms,
+            } => write!(
+                f,
+                "Request exceeded its {budget_ms}ms deadline after {elapsed_ms}ms"
+            ),
+            ProviderError::StreamStalled { after_chunks } => {
+                write!(f, "Stream stalled after {after_chunks} chunks")
+            }
             ProviderError::Configuration(msg) => write!(f, "Configuration error: {msg}"),
             ProviderError::StreamingNotSupported => {
                 write!(f, "Streaming is not supported by this provider")
@@ -316,53 +1053,338 @@ impl fmt::Display for ProviderError {
 
 impl std::error::Error for ProviderError {}
 
-/// Type alias for the stream type returned by providers
-pub type ProviderStream =
-    Pin<Box<dyn futures_util::Stream<Item = Result<crate::models::StreamChunk, ProviderError>> + Send>>;
-
-/// Common trait that all inference providers must implement
-pub trait InferenceProvider: Send + Sync {
-    /// Transform OpenAI-format request to our internal normalized format
-    fn build_inference_request(
-        &self,
-        request: &CompletionRequest,
-        model: &str, // Model already determined by validation layer
-    ) -> Result<InferenceRequest, ProviderError> {
-        Ok(InferenceRequest {
-            messages: request.messages.clone(),
-            model: model.to_string(),
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            top_p: request.top_p,
-            frequency_penalty: request.frequency_penalty,
-            presence_penalty: request.presence_penalty,
-            stop_sequences: normalize_stop_sequences(&request.stop),
-            seed: request.seed,
-            stream: request.stream,
-            n: request.n,
-            logprobs: request.logprobs,
-            top_logprobs: request.top_logprobs,
-            user: request.user.clone(),
-            response_format: request.response_format.clone(),
-            logit_bias: request.logit_bias.clone(),
-        })
+impl ProviderError {
+    /// Classify a non-success upstream HTTP status
+    pub fn from_status(status: u16, message: String) -> Self {
+        match status {
+            413 | 414 => ProviderError::PayloadTooLarge { status, message },
+            _ => ProviderError::RequestFailed { status, message },
+        }
     }
 
-    /// Execute the inference request against the provider
-    fn execute(
-        &self,
-        request: &InferenceRequest,
-    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>>;
+    /// Whether sending the same request again may succeed. Deadline overruns are not
+    /// retried: the same prompt would most likely run out of time again.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ProviderError::ConnectTimeout | ProviderError::ConnectionFailed(_)
+        )
+    }
+}
 
-    /// Transform our internal response format to OpenAI-compatible format
-    fn build_completion_response(
-        &self,
-        response: &InferenceResponse,
-        original_request: &CompletionRequest,
-    ) -> CompletionResponse;
+/// `Content-Encoding` still on a response. reqwest drops the header when it decodes
+/// gzip or brotli itself, so an encoding left here reached us undecoded.
+fn content_encoding(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(reqwest::header::CONTENT_ENCODING)?
+        .to_str()
+        .ok()
+        .map(|encoding| encoding.trim().to_ascii_lowercase())
+        .filter(|encoding| !encoding.is_empty() && encoding != "identity")
+}
 
-    /// High-level method that orchestrates the full flow
-    fn generate(
+/// Read a whole response body, decoding gzip or deflate that reqwest left encoded
+/// (e.g. `x-gzip`). Errors carry the encoding and byte counts.
+async fn read_body(response: reqwest::Response) -> Result<Vec<u8>, ProviderError> {
+    let encoding = content_encoding(response.headers());
+    let bytes = response.bytes().await.map_err(|e| {
+        let cause = if e.is_decode() {
+            "compressed body could not be decoded"
+        } else {
+            "body could not be read"
+        };
+        ProviderError::InvalidResponse(format!("Failed to read response body ({cause}): {e}"))
+    })?;
+    match encoding {
+        Some(encoding) => decode_body(&bytes, &encoding),
+        None => Ok(bytes.to_vec()),
+    }
+}
+
+fn decode_body(bytes: &[u8], encoding: &str) -> Result<Vec<u8>, ProviderError> {
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    let result = match encoding {
+        "gzip" | "x-gzip" => flate2::read::GzDecoder::new(bytes).read_to_end(&mut decoded),
+        "deflate" => flate2::read::ZlibDecoder::new(bytes).read_to_end(&mut decoded),
+        _ => {
+            return Err(ProviderError::InvalidResponse(format!(
+                "Unsupported content-encoding '{encoding}' ({} bytes received)",
+                bytes.len()
+            )));
+        }
+    };
+    result.map_err(|e| {
+        ProviderError::InvalidResponse(format!(
+            "Failed to decode {encoding} response body ({} bytes received): {e}",
+            bytes.len()
+        ))
+    })?;
+    debug!(encoding, received = bytes.len(), decoded = decoded.len(), "Decoded response body");
+    Ok(decoded)
+}
+
+/// Read a success body as JSON
+async fn read_json(response: reqwest::Response) -> Result<serde_json::Value, ProviderError> {
+    let encoding = content_encoding(response.headers());
+    let body = read_body(response).await?;
+    serde_json::from_slice(&body).map_err(|e| {
+        let size = match encoding {
+            Some(encoding) => format!("{} bytes after {encoding} decoding", body.len()),
+            None => format!("{} bytes", body.len()),
+        };
+        ProviderError::InvalidResponse(format!("Invalid JSON response ({size}): {e}"))
+    })
+}
+
+/// Read an error body as text; a body that can't be read is described instead
+async fn read_error_text(response: reqwest::Response) -> String {
+    match read_body(response).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => format!("Unknown error ({e})"),
+    }
+}
+
+/// An upstream attempt that didn't succeed
+struct FailedAttempt {
+    error: ProviderError,
+    /// Sending the same request again may succeed
+    retryable: bool,
+    /// Wait the upstream asked for before trying again
+    retry_after: Option<Duration>,
+}
+
+impl FailedAttempt {
+    fn fatal(error: ProviderError) -> Self {
+        Self {
+            error,
+            retryable: false,
+            retry_after: None,
+        }
+    }
+}
+
+/// Delay-seconds form of a `Retry-After` header; HTTP dates are not supported
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Type alias for the stream type returned by providers
+pub type ProviderStream =
+    Pin<Box<dyn futures_util::Stream<Item = Result<crate::models::StreamChunk, ProviderError>> + Send>>;
+
+/// JSON type an extension value must have
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtensionKind {
+    Integer,
+    Number,
+    String,
+    Boolean,
+    Object,
+    Array,
+    /// Any JSON value, for passthrough parameters the server validates itself
+    Any,
+}
+
+impl ExtensionKind {
+    fn article(self) -> &'static str {
+        match self {
+            ExtensionKind::Integer => "an integer",
+            ExtensionKind::Number => "a number",
+            ExtensionKind::String => "a string",
+            ExtensionKind::Boolean => "a boolean",
+            ExtensionKind::Object => "an object",
+            ExtensionKind::Array => "an array",
+            ExtensionKind::Any => "any JSON value",
+        }
+    }
+}
+
+/// One end of an extension's allowed range
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Bound {
+    pub value: f64,
+    pub inclusive: bool,
+}
+
+impl Bound {
+    pub const fn inclusive(value: f64) -> Self {
+        Self {
+            value,
+            inclusive: true,
+        }
+    }
+
+    pub const fn exclusive(value: f64) -> Self {
+        Self {
+            value,
+            inclusive: false,
+        }
+    }
+}
+
+/// Declarative description of a provider extension. Drives both request
+/// validation and `GET /v1/extensions`, so the two can't drift apart.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtensionSpec {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: ExtensionKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<Bound>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<Bound>,
+    pub description: &'static str,
+}
+
+impl ExtensionSpec {
+    pub const fn new(name: &'static str, kind: ExtensionKind, description: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            min: None,
+            max: None,
+            description,
+        }
+    }
+
+    pub const fn min(mut self, bound: Bound) -> Self {
+        self.min = Some(bound);
+        self
+    }
+
+    pub const fn max(mut self, bound: Bound) -> Self {
+        self.max = Some(bound);
+        self
+    }
+
+    /// Check a value against the spec, returning the reason it was rejected
+    pub fn validate(&self, value: &serde_json::Value) -> Result<(), String> {
+        let number = match self.kind {
+            ExtensionKind::Integer => value.as_i64().map(|v| v as f64),
+            ExtensionKind::Number => value.as_f64(),
+            ExtensionKind::String => value.is_string().then_some(0.0),
+            ExtensionKind::Boolean => value.is_boolean().then_some(0.0),
+            ExtensionKind::Object => value.is_object().then_some(0.0),
+            ExtensionKind::Array => value.is_array().then_some(0.0),
+            ExtensionKind::Any => Some(0.0),
+        };
+        let Some(number) = number else {
+            return Err(format!("must be {}", self.kind.article()));
+        };
+
+        let above_min = self.min.is_none_or(|b| {
+            if b.inclusive { number >= b.value } else { number > b.value }
+        });
+        let below_max = self.max.is_none_or(|b| {
+            if b.inclusive { number <= b.value } else { number < b.value }
+        });
+        if above_min && below_max {
+            Ok(())
+        } else {
+            Err(format!("must be {}", self.range_description()))
+        }
+    }
+
+    /// Allowed range in interval notation, e.g. `[0.0, 1.0]` or `(0, inf)`
+    pub fn constraint(&self) -> Option<String> {
+        if self.min.is_none() && self.max.is_none() {
+            return None;
+        }
+        let (open, low) = match self.min {
+            Some(b) => (if b.inclusive { "[" } else { "(" }, self.format_value(b.value)),
+            None => ("(", "-inf".to_string()),
+        };
+        let (high, close) = match self.max {
+            Some(b) => (self.format_value(b.value), if b.inclusive { "]" } else { ")" }),
+            None => ("inf".to_string(), ")"),
+        };
+        Some(format!("{open}{low}, {high}{close}"))
+    }
+
+    fn range_description(&self) -> String {
+        let op = |b: &Bound, inclusive: &'static str, exclusive: &'static str| {
+            format!(
+                "{} {}",
+                if b.inclusive { inclusive } else { exclusive },
+                self.format_value(b.value)
+            )
+        };
+        match (&self.min, &self.max) {
+            (Some(min), Some(max)) if min.inclusive && max.inclusive => format!(
+                "between {} and {}",
+                self.format_value(min.value),
+                self.format_value(max.value)
+            ),
+            (Some(min), Some(max)) => format!("{} and {}", op(min, ">=", ">"), op(max, "<=", "<")),
+            (Some(min), None) => op(min, ">=", ">"),
+            (None, Some(max)) => op(max, "<=", "<"),
+            (None, None) => "valid".to_string(),
+        }
+    }
+
+    fn format_value(&self, value: f64) -> String {
+        match self.kind {
+            ExtensionKind::Integer => format!("{}", value as i64),
+            _ => format!("{value:?}"),
+        }
+    }
+}
+
+/// Common trait that all inference providers must implement
+pub trait InferenceProvider: Send + Sync {
+    /// Transform OpenAI-format request to our internal normalized format
+    fn build_inference_request(
+        &self,
+        request: &CompletionRequest,
+        model: &str, // Model already determined by validation layer
+    ) -> Result<InferenceRequest, ProviderError> {
+        Ok(InferenceRequest {
+            messages: request.messages.clone(),
+            model: model.to_string(),
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop_sequences: normalize_stop_sequences(&request.stop),
+            seed: request.seed,
+            stream: request.stream,
+            n: request.n,
+            logprobs: request.logprobs,
+            top_logprobs: request.top_logprobs,
+            user: request.user.clone(),
+            response_format: request.response_format.clone(),
+            logit_bias: request.logit_bias.clone(),
+            modalities: request.modalities.clone(),
+            audio: request.audio.clone(),
+        })
+    }
+
+    /// Execute the inference request against the provider
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>>;
+
+    /// Transform our internal response format to OpenAI-compatible format
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse;
+
+    /// High-level method that orchestrates the full flow
+    fn generate(
         &self,
         request: &CompletionRequest,
         model: &str,
@@ -378,6 +1400,19 @@ pub trait InferenceProvider: Send + Sync {
         })
     }
 
+    /// Preview the body that would be sent upstream, without executing inference.
+    /// Default returns the normalized request; HTTP providers return their wire format.
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let inference_req = self.build_inference_request(request, model)?;
+        serde_json::to_value(&inference_req).map_err(|e| {
+            ProviderError::InvalidResponse(format!("Failed to serialize request preview: {e}"))
+        })
+    }
+
     /// Stream completion tokens as they're generated
     /// Default implementation returns streaming not supported
     fn stream(
@@ -388,6 +1423,35 @@ pub trait InferenceProvider: Send + Sync {
         Box::pin(async { Err(ProviderError::StreamingNotSupported) })
     }
 
+    /// Embed each input text with `model`
+    /// Default implementation returns embeddings not supported
+    fn embed(
+        &self,
+        _request: &EmbeddingRequest,
+        _model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        Box::pin(async {
+            Err(ProviderError::Configuration(
+                "embeddings not supported".to_string(),
+            ))
+        })
+    }
+
+    /// Streaming mode the provider wants for this model regardless of the
+    /// client's `stream` flag. `None` leaves the client's choice alone.
+    fn streaming_preference(&self, _model: &str) -> BoxFuture<'_, Option<bool>> {
+        Box::pin(async { None })
+    }
+
+    /// Provider that serves `model`, for providers that route between several.
+    /// `None` means this provider serves it itself.
+    fn route(
+        &self,
+        _model: &str,
+    ) -> BoxFuture<'_, Result<Option<Arc<dyn InferenceProvider>>, ProviderError>> {
+        Box::pin(async { Ok(None) })
+    }
+
     /// Get the name of this provider (for logging/metrics)
     fn name(&self) -> &str;
 
@@ -401,14 +1465,41 @@ pub trait InferenceProvider: Send + Sync {
         false
     }
 
+    /// Whether `modalities` may include `audio`
+    fn supports_audio_output(&self) -> bool {
+        false
+    }
+
+    /// Whether `response_format` may ask for `json_object` or `json_schema`
+    fn supports_json_mode(&self) -> bool {
+        false
+    }
+
+    /// Most stop sequences one request may carry (OpenAI allows 4)
+    fn max_stop_sequences(&self) -> usize {
+        4
+    }
+
+    /// Extension parameters this provider accepts, with their types and bounds
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        &[]
+    }
+
+    /// Extension specs per serving provider, so `/v1/extensions` can list
+    /// every backend behind a router
+    fn extension_specs_by_provider(&self) -> Vec<(String, &'static [ExtensionSpec])> {
+        vec![(self.name().to_string(), self.extension_specs())]
+    }
+
     /// Get list of supported extension parameters for this provider
     /// Returns empty vec if no extensions are supported
     fn supported_extensions(&self) -> Vec<&'static str> {
-        Vec::new()
+        self.extension_specs().iter().map(|spec| spec.name).collect()
     }
 
     /// Validate provider-specific extension parameters
-    /// Default implementation rejects all extensions
+    /// Default implementation checks names against `supported_extensions` and
+    /// values against `extension_specs`
     fn validate_extensions(
         &self,
         extensions: &HashMap<String, serde_json::Value>,
@@ -444,6 +1535,16 @@ pub trait InferenceProvider: Send + Sync {
             }
         }
 
+        for spec in self.extension_specs() {
+            if let Some(value) = extensions.get(spec.name) {
+                spec.validate(value)
+                    .map_err(|reason| ProviderError::InvalidExtension {
+                        param: spec.name.to_string(),
+                        reason,
+                    })?;
+            }
+        }
+
         Ok(())
     }
 
@@ -456,10 +1557,34 @@ pub trait InferenceProvider: Send + Sync {
         })
     }
 
+    /// Models listed per serving provider, in precedence order, so
+    /// `/v1/models` can credit each model to its own provider
+    fn list_models_by_provider(&self) -> BoxFuture<'_, Result<ProviderModels, ProviderError>> {
+        Box::pin(async move { Ok(vec![(self.name().to_string(), self.list_models().await?)]) })
+    }
+
     /// Optional: Check if the provider is healthy/reachable
     fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
         Box::pin(async { Ok(()) })
     }
+
+    /// Per-endpoint state for providers spreading requests over several
+    /// upstreams; empty for a single upstream
+    fn endpoint_health(&self) -> Vec<EndpointHealth> {
+        Vec::new()
+    }
+
+    /// Circuit breaker state of this provider, or of each backend behind a
+    /// router; empty without `http.circuit_breaker`
+    fn circuit_health(&self) -> Vec<circuit_breaker::CircuitHealth> {
+        Vec::new()
+    }
+
+    /// Handle on the mock provider's scenario state, for the `/admin/mock` routes;
+    /// `None` for every real provider
+    fn mock_control(&self) -> Option<mock::MockControl> {
+        None
+    }
 }
 
 // ===== Helper Functions =====
@@ -472,22 +1597,57 @@ pub fn standard_completion_response(
     original_request: &CompletionRequest,
     provider_name: &str,
 ) -> CompletionResponse {
-    // Build the message with optional fields
-    let mut message = Message::new(Role::Assistant, &response.text);
+    multi_choice_completion_response(
+        response,
+        &[ChoiceOutput::from(response)],
+        original_request,
+        provider_name,
+    )
+}
+
+/// What one choice of an `n > 1` response says and how it finished
+#[derive(Debug, Clone)]
+pub struct ChoiceOutput {
+    pub text: String,
+    pub finish_reason: Option<FinishReason>,
+    pub tool_calls: Option<Vec<crate::models::ToolCall>>,
+}
 
-    // Add tool calls if present
-    if let Some(ref tool_calls) = response.tool_calls {
-        message.tool_calls = Some(tool_calls.clone());
+impl From<&InferenceResponse> for ChoiceOutput {
+    fn from(response: &InferenceResponse) -> Self {
+        Self {
+            text: response.text.clone(),
+            finish_reason: response.finish_reason.clone(),
+            tool_calls: response.tool_calls.clone(),
+        }
     }
+}
 
-    // Create choice with all optional fields
-    let choice = Choice {
-        index: 0,
-        message: Some(message),
-        delta: None,
-        finish_reason: response.finish_reason.clone(),
-        logprobs: response.logprobs.clone(),
-    };
+/// Like `standard_completion_response`, with one choice per entry of `outputs`
+/// for `n > 1`. Each choice keeps its own finish reason and tool calls; logprobs
+/// describe the first output, so only the first choice carries them.
+/// The token counts in `response` must already cover all choices.
+pub fn multi_choice_completion_response(
+    response: &InferenceResponse,
+    outputs: &[ChoiceOutput],
+    original_request: &CompletionRequest,
+    provider_name: &str,
+) -> CompletionResponse {
+    let choices = outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| {
+            let mut message = Message::new(Role::Assistant, &output.text);
+            message.tool_calls = output.tool_calls.clone();
+            Choice {
+                index: index as u32,
+                message: Some(message),
+                delta: None,
+                finish_reason: output.finish_reason.clone(),
+                logprobs: if index == 0 { response.logprobs.clone() } else { None },
+            }
+        })
+        .collect();
 
     // Build usage with optional fields
     let usage = if response.prompt_tokens.is_some()
@@ -529,10 +1689,352 @@ pub fn standard_completion_response(
             .unwrap()
             .as_secs(),
         model: response.model_used.clone(),
-        choices: vec![choice],
+        choices,
         usage,
         system_fingerprint: response.system_fingerprint.clone(),
         provider_extensions,
+        warnings: None,
+    }
+}
+
+/// Attach a provider-specific value to a response's extension data.
+/// Only applies in extended response mode; standard responses are left untouched.
+pub fn attach_provider_data(
+    response: &mut CompletionResponse,
+    original_request: &CompletionRequest,
+    provider_name: &str,
+    key: &str,
+    value: serde_json::Value,
+) {
+    if original_request.response_mode != Some(crate::models::ResponseMode::Extended) {
+        return;
+    }
+    response
+        .provider_extensions
+        .get_or_insert_with(|| crate::models::ProviderExtensions {
+            provider: provider_name.to_string(),
+            data: HashMap::new(),
+        })
+        .data
+        .insert(key.to_string(), value);
+}
+
+/// Append the configured footer to each assistant message.
+/// Tool-call messages are left alone since they aren't shown to the user.
+pub fn apply_response_footer(response: &mut CompletionResponse, footer: &str) {
+    for message in response.choices.iter_mut().filter_map(|c| c.message.as_mut()) {
+        if message.tool_calls.is_some() {
+            continue;
+        }
+        message.content.get_or_insert_with(String::new).push_str(footer);
+    }
+}
+
+/// Remove control characters other than newline and tab, in place
+fn strip_control_chars_from(text: &mut String) {
+    text.retain(|c| !c.is_control() || c == '\n' || c == '\t');
+}
+
+/// Strip control characters from the text of each choice
+pub fn strip_response_control_chars(response: &mut CompletionResponse) {
+    for message in response.choices.iter_mut().filter_map(|c| c.message.as_mut()) {
+        for text in [&mut message.content, &mut message.refusal].into_iter().flatten() {
+            strip_control_chars_from(text);
+        }
+    }
+}
+
+/// Strip control characters from the text of every delta
+pub fn strip_control_chars_from_stream(stream: ProviderStream) -> ProviderStream {
+    Box::pin(stream.map(|item| {
+        item.map(|mut chunk| {
+            for choice in &mut chunk.choices {
+                let delta = &mut choice.delta;
+                for text in [&mut delta.content, &mut delta.reasoning_content, &mut delta.refusal]
+                    .into_iter()
+                    .flatten()
+                {
+                    strip_control_chars_from(text);
+                }
+            }
+            chunk
+        })
+    }))
+}
+
+/// Emit the configured footer as a content chunk just before each choice's finish chunk
+pub fn append_footer_to_stream(stream: ProviderStream, footer: String) -> ProviderStream {
+    Box::pin(stream.flat_map(move |item| {
+        let mut chunks = Vec::new();
+        if let Ok(ref chunk) = item {
+            for choice in &chunk.choices {
+                if matches!(choice.finish_reason, Some(ref r) if *r != FinishReason::ToolCalls) {
+                    let mut footer_chunk = create_content_chunk(&chunk.id, &chunk.model, &footer);
+                    footer_chunk.choices[0].index = choice.index;
+                    chunks.push(Ok(footer_chunk));
+                }
+            }
+        }
+        chunks.push(item);
+        futures_util::stream::iter(chunks)
+    }))
+}
+
+/// Drop `reasoning_content` from every delta, and any chunk left with nothing to say
+pub fn strip_reasoning_from_stream(stream: ProviderStream) -> ProviderStream {
+    Box::pin(stream.filter_map(|item| async move {
+        let Ok(mut chunk) = item else {
+            return Some(item);
+        };
+        let mut stripped = false;
+        for choice in &mut chunk.choices {
+            stripped |= choice.delta.reasoning_content.take().is_some();
+        }
+        if stripped && is_empty_chunk(&chunk) { None } else { Some(Ok(chunk)) }
+    }))
+}
+
+/// Whether a chunk carries no delta, finish_reason or usage
+fn is_empty_chunk(chunk: &crate::models::StreamChunk) -> bool {
+    chunk.usage.is_none()
+        && chunk.choices.iter().all(|choice| {
+            let delta = &choice.delta;
+            choice.finish_reason.is_none()
+                && delta.role.is_none()
+                && delta.content.is_none()
+                && delta.reasoning_content.is_none()
+                && delta.tool_calls.is_none()
+                && delta.function_call.is_none()
+                && delta.refusal.is_none()
+        })
+}
+
+/// Keep `finish_reason` on only the first finish chunk of each choice, and drop
+/// chunks left with nothing to say once a choice has finished. Usage chunks are kept.
+pub fn normalize_stream_finish(stream: ProviderStream) -> ProviderStream {
+    let mut finished = HashSet::new();
+    let chunks = stream.map(move |item| {
+        let Ok(mut chunk) = item else {
+            return Some(item);
+        };
+        for choice in &mut chunk.choices {
+            if choice.finish_reason.is_some() && !finished.insert(choice.index) {
+                choice.finish_reason = None;
+            }
+        }
+        if !finished.is_empty() && is_empty_chunk(&chunk) {
+            debug!("Dropping redundant chunk after the finish chunk");
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    });
+    Box::pin(chunks.filter_map(std::future::ready))
+}
+
+/// End the stream with `StreamStalled` if the provider goes `idle` without sending a chunk
+pub fn with_stall_timeout(stream: ProviderStream, idle: Duration) -> ProviderStream {
+    Box::pin(futures_util::stream::unfold(
+        Some((stream, 0u64)),
+        move |state| async move {
+            let (mut stream, chunks) = state?;
+            match tokio::time::timeout(idle, stream.next()).await {
+                Ok(Some(item)) => Some((item, Some((stream, chunks + 1)))),
+                Ok(None) => None,
+                Err(_) => {
+                    error!(after_chunks = chunks, "Provider stream stalled");
+                    Some((
+                        Err(ProviderError::StreamStalled {
+                            after_chunks: chunks,
+                        }),
+                        None,
+                    ))
+                }
+            }
+        },
+    ))
+}
+
+/// Whether a chunk carries generated output or a finish, as opposed to only the role
+fn carries_output(chunk: &crate::models::StreamChunk) -> bool {
+    chunk.choices.iter().any(|choice| {
+        let delta = &choice.delta;
+        choice.finish_reason.is_some()
+            || delta.content.is_some()
+            || delta.reasoning_content.is_some()
+            || delta.tool_calls.is_some()
+            || delta.function_call.is_some()
+            || delta.refusal.is_some()
+    })
+}
+
+/// Until the first output arrives, send a chunk with an empty delta every `interval`
+/// so proxies that time out idle responses see data, not just SSE comments. The
+/// chunks reuse the stream's id once one has been seen; `model` names them before.
+pub fn with_first_token_keep_alive(
+    stream: ProviderStream,
+    interval: Duration,
+    model: &str,
+) -> ProviderStream {
+    let id = format!("chatcmpl-{}", Uuid::now_v7());
+    Box::pin(futures_util::stream::unfold(
+        (stream, Some((id, model.to_string()))),
+        move |(mut stream, waiting)| async move {
+            let Some((id, model)) = waiting else {
+                let item = stream.next().await?;
+                return Some((item, (stream, None)));
+            };
+            match tokio::time::timeout(interval, stream.next()).await {
+                Ok(Some(Ok(chunk))) => {
+                    let waiting =
+                        (!carries_output(&chunk)).then(|| (chunk.id.clone(), chunk.model.clone()));
+                    Some((Ok(chunk), (stream, waiting)))
+                }
+                Ok(Some(Err(e))) => Some((Err(e), (stream, None))),
+                Ok(None) => None,
+                Err(_) => {
+                    let mut chunk = create_content_chunk(&id, &model, "");
+                    chunk.choices[0].delta.content = None;
+                    Some((Ok(chunk), (stream, Some((id, model)))))
+                }
+            }
+        },
+    ))
+}
+
+/// Read `stream` in a task that hands chunks over a channel of `capacity`. Once the
+/// consumer falls `capacity` chunks behind, the task stops reading upstream until it
+/// catches up; the upstream is dropped when the consumer goes away. The task stays
+/// in the caller's span, so upstream logs keep the request's fields and log level.
+pub fn with_bounded_buffer(mut stream: ProviderStream, capacity: usize) -> ProviderStream {
+    let (tx, rx) = tokio::sync::mpsc::channel(capacity);
+    let read = async move {
+        loop {
+            let item = tokio::select! {
+                item = stream.next() => item,
+                () = tx.closed() => break,
+            };
+            let Some(item) = item else { break };
+            if tx.send(item).await.is_err() {
+                break;
+            }
+        }
+    };
+    tokio::spawn(read.in_current_span());
+    Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+}
+
+/// Read a stream to the end and fold its chunks into one completion, for clients
+/// that asked for a stream but only accept JSON. Reasoning deltas have no place
+/// in a completion message and are dropped; the first error ends the collection.
+pub async fn collect_stream(
+    mut stream: ProviderStream,
+) -> Result<CompletionResponse, ProviderError> {
+    let mut response: Option<CompletionResponse> = None;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let response = response.get_or_insert_with(|| CompletionResponse {
+            id: chunk.id.clone(),
+            object: "chat.completion".to_string(),
+            created: chunk.created,
+            model: chunk.model.clone(),
+            choices: Vec::new(),
+            usage: None,
+            system_fingerprint: None,
+            provider_extensions: None,
+            warnings: None,
+        });
+        if chunk.system_fingerprint.is_some() {
+            response.system_fingerprint = chunk.system_fingerprint;
+        }
+        if chunk.usage.is_some() {
+            response.usage = chunk.usage;
+        }
+        for choice in chunk.choices {
+            let position = response.choices.iter().position(|c| c.index == choice.index);
+            let index = match position {
+                Some(index) => index,
+                None => {
+                    response.choices.push(Choice {
+                        index: choice.index,
+                        message: Some(Message {
+                            role: Role::Assistant,
+                            ..Default::default()
+                        }),
+                        delta: None,
+                        finish_reason: None,
+                        logprobs: None,
+                    });
+                    response.choices.len() - 1
+                }
+            };
+            let collected = &mut response.choices[index];
+            fold_delta(collected.message.get_or_insert_default(), choice.delta);
+            if choice.finish_reason.is_some() {
+                collected.finish_reason = choice.finish_reason;
+            }
+            if let Some(tokens) = choice.logprobs.and_then(|logprobs| logprobs.content) {
+                collected
+                    .logprobs
+                    .get_or_insert(crate::models::LogProbs { content: None })
+                    .content
+                    .get_or_insert_default()
+                    .extend(tokens);
+            }
+        }
+    }
+    response.ok_or_else(|| {
+        ProviderError::InvalidResponse("Stream ended without any chunks".to_string())
+    })
+}
+
+/// Append one delta to the message collected so far. A tool call delta with an
+/// id starts a new call; one without continues the arguments of the last call.
+fn fold_delta(message: &mut Message, delta: crate::models::Delta) {
+    if let Some(role) = delta.role {
+        message.role = role;
+    }
+    if let Some(content) = delta.content {
+        message.content.get_or_insert_default().push_str(&content);
+    }
+    if let Some(refusal) = delta.refusal {
+        message.refusal.get_or_insert_default().push_str(&refusal);
+    }
+    if let Some(call) = delta.function_call {
+        let collected = message
+            .function_call
+            .get_or_insert_with(|| crate::models::FunctionCall {
+                name: String::new(),
+                arguments: String::new(),
+            });
+        collected.name.push_str(call.name.as_deref().unwrap_or_default());
+        collected
+            .arguments
+            .push_str(call.arguments.as_deref().unwrap_or_default());
+    }
+    for call in delta.tool_calls.unwrap_or_default() {
+        let calls = message.tool_calls.get_or_insert_default();
+        if call.id.is_some() || calls.is_empty() {
+            calls.push(crate::models::ToolCall {
+                id: call.id.unwrap_or_default(),
+                tool_type: call.tool_type.unwrap_or_else(|| "function".to_string()),
+                function: crate::models::FunctionCall {
+                    name: String::new(),
+                    arguments: String::new(),
+                },
+            });
+        }
+        let collected = calls.last_mut().expect("a call was just pushed");
+        if let Some(function) = call.function {
+            collected
+                .function
+                .name
+                .push_str(function.name.as_deref().unwrap_or_default());
+            collected
+                .function
+                .arguments
+                .push_str(function.arguments.as_deref().unwrap_or_default());
+        }
     }
 }
 
@@ -550,6 +2052,17 @@ pub fn normalize_stop_sequences(
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Rough prompt token estimate (~4 characters per token) for reporting without a tokenizer
+pub fn estimate_prompt_tokens(messages: &[Message]) -> u32 {
+    estimate_tokens(messages.iter().filter_map(|m| m.content.as_deref()))
+}
+
+/// Same estimate for plain texts
+pub fn estimate_tokens<'a>(texts: impl IntoIterator<Item = &'a str>) -> u32 {
+    let chars: usize = texts.into_iter().map(|text| text.chars().count()).sum();
+    chars.div_ceil(4) as u32
+}
+
 /// Convert text to chunked tokens for streaming
 pub fn tokenize_for_streaming(text: &str) -> Vec<String> {
     // Simple word-based tokenization for now
@@ -574,7 +2087,9 @@ pub fn create_first_chunk(id: &str, model: &str, role: Role) -> crate::models::S
             delta: crate::models::Delta {
                 role: Some(role),
                 content: None,
+                reasoning_content: None,
                 tool_calls: None,
+                function_call: None,
                 refusal: None,
             },
             finish_reason: None,
@@ -600,7 +2115,9 @@ pub fn create_content_chunk(id: &str, model: &str, content: &str) -> crate::mode
             delta: crate::models::Delta {
                 role: None,
                 content: Some(content.to_string()),
+                reasoning_content: None,
                 tool_calls: None,
+                function_call: None,
                 refusal: None,
             },
             finish_reason: None,
@@ -636,3 +2153,996 @@ pub fn create_final_chunk(
         usage,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HedgingConfig;
+    use crate::metrics::{Metrics, PhaseTimings};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use wiremock::matchers::{header_regex, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn hedged_client(base_url: &str, deterministic_only: bool) -> HttpProviderClient {
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 0,
+            hedging: Some(HedgingConfig {
+                delay_ms: 50,
+                max_hedges: 1,
+                deterministic_only,
+            }),
+            ..Default::default()
+        };
+        HttpProviderClient::new(base_url, Some(&http_config), None).unwrap()
+    }
+
+    fn inference_request(seed: Option<u64>, temperature: Option<f32>) -> InferenceRequest {
+        InferenceRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            model: "test-model".to_string(),
+            max_tokens: None,
+            temperature,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            seed,
+            stream: None,
+            n: None,
+            logprobs: None,
+            top_logprobs: None,
+            user: None,
+            response_format: None,
+            logit_bias: None,
+            modalities: None,
+            audio: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_phase_marked_only_for_new_connections() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .mount(&server)
+            .await;
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+        let metrics = Metrics::default();
+
+        for _ in 0..2 {
+            let timings = PhaseTimings::start();
+            timings
+                .scope(client.post_json("chat/completions", &serde_json::json!({})))
+                .await
+                .unwrap();
+            timings.record(&metrics, "test");
+        }
+
+        // The second call reuses the pooled connection
+        let rendered = metrics.render();
+        assert!(rendered.contains(
+            "upstream_phase_seconds_count{provider=\"test\",phase=\"connect\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "upstream_phase_seconds_count{provider=\"test\",phase=\"first_byte\"} 2\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sse_reassembles_character_split_across_reads() {
+        let chunk = create_content_chunk("chatcmpl-1", "test-model", "héllo 👋 世界");
+        let body = format!("data: {}\n\ndata: [DONE]\n\n", serde_json::to_string(&chunk).unwrap());
+        let body = body.into_bytes();
+        // Cut inside the 4-byte emoji, then inside a 3-byte CJK character
+        let emoji = body.windows(4).position(|w| w == "👋".as_bytes()).unwrap();
+        let cjk = body.windows(3).position(|w| w == "世".as_bytes()).unwrap();
+        let reads: Vec<Result<Vec<u8>, std::io::Error>> = vec![
+            Ok(body[..emoji + 2].to_vec()),
+            Ok(body[emoji + 2..cjk + 1].to_vec()),
+            Ok(body[cjk + 1..].to_vec()),
+        ];
+
+        let chunks: Vec<_> = parse_sse_stream(futures_util::stream::iter(reads), InvalidUtf8::Error)
+            .collect()
+            .await;
+
+        assert_eq!(chunks.len(), 1);
+        let chunk = chunks.into_iter().next().unwrap().unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("héllo 👋 世界"));
+    }
+
+    #[test]
+    fn test_invalid_utf8_follows_policy() {
+        let mut lenient = Utf8Decoder::new(InvalidUtf8::Replace);
+        assert_eq!(lenient.push(b"ok \xff then \xe4").unwrap(), "ok \u{FFFD} then ");
+        assert_eq!(lenient.push(b"\xb8").unwrap(), "");
+        assert_eq!(lenient.push(b"\x96!").unwrap(), "世!");
+        lenient.push(b"\xe4\xb8").unwrap();
+        assert_eq!(lenient.finish().unwrap(), "\u{FFFD}");
+
+        let mut strict = Utf8Decoder::new(InvalidUtf8::Error);
+        assert!(matches!(
+            strict.push(b"ok \xff"),
+            Err(ProviderError::StreamError(_))
+        ));
+        let mut strict = Utf8Decoder::new(InvalidUtf8::Error);
+        strict.push(b"\xe4\xb8").unwrap();
+        assert!(strict.finish().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_stream_reconnects_after_connection_drops() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let events: String = [
+            create_first_chunk("chatcmpl-1", "test-model", Role::Assistant),
+            create_content_chunk("chatcmpl-1", "test-model", "Hello "),
+            create_content_chunk("chatcmpl-1", "test-model", "world"),
+            create_final_chunk("chatcmpl-1", "test-model", FinishReason::Stop, None),
+        ]
+        .iter()
+        .map(|chunk| format!("data: {}\n\n", serde_json::to_string(chunk).unwrap()))
+        .collect();
+        let full = format!("{events}data: [DONE]\n\n");
+        // Two events, then the connection closes well short of the promised length
+        let cut: usize = events.match_indices("\n\n").nth(1).unwrap().0 + 2;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let first = counter.fetch_add(1, Ordering::SeqCst) == 0;
+                let full = full.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 8192];
+                    let _ = socket.read(&mut request).await;
+                    let body = if first { &full[..cut] } else { &full[..] };
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+                         content-length: {}\r\nconnection: close\r\n\r\n",
+                        full.len()
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+                    let _ = socket.write_all(body.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            retry_backoff_ms: 10,
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&format!("http://{addr}"), Some(&http_config), None)
+            .unwrap()
+            .with_stream_reconnect(true);
+
+        let body = serde_json::json!({"stream": true, "seed": 7});
+        let stream = client.post_sse_resumable("chat/completions", &body, true).await.unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+
+        let chunks: Vec<_> = chunks.into_iter().map(Result::unwrap).collect();
+        let text: String = chunks
+            .iter()
+            .filter_map(|c| c.choices[0].delta.content.as_deref())
+            .collect();
+        assert_eq!(text, "Hello world");
+        let finishes = chunks.iter().filter(|c| c.choices[0].finish_reason.is_some()).count();
+        assert_eq!(finishes, 1);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_error_retried_only_on_configured_message() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/busy"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Server busy, try again"))
+            .expect(3) // First attempt plus two retries
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/invalid"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid prompt"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            retry_on_messages: Some(vec!["busy".to_string()]),
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+
+        let busy = client.post_json("busy", &serde_json::json!({})).await;
+        assert!(matches!(
+            busy,
+            Err(ProviderError::RequestFailed { status: 400, ref message }) if message.contains("busy")
+        ));
+        let invalid = client.post_json("invalid", &serde_json::json!({})).await;
+        assert!(matches!(
+            invalid,
+            Err(ProviderError::RequestFailed { status: 400, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_jitter_spreads_concurrent_retriers() {
+        let http_config = Arc::new(HttpConfigSchema {
+            retry_backoff_ms: 100,
+            retry_jitter_ms: 50,
+            ..Default::default()
+        });
+
+        // Retriers failing together each draw their own wait for the second retry
+        let delays: Vec<Duration> = futures_util::future::join_all((0..32).map(|_| {
+            let http_config = http_config.clone();
+            tokio::spawn(async move { http_config.retry_backoff(2) })
+        }))
+        .await
+        .into_iter()
+        .map(|delay| delay.unwrap())
+        .collect();
+
+        for delay in &delays {
+            assert!(*delay >= Duration::from_millis(200));
+            assert!(*delay <= Duration::from_millis(250));
+        }
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+
+        // Without jitter every retrier waits the plain backoff
+        let http_config = HttpConfigSchema {
+            retry_backoff_ms: 100,
+            ..Default::default()
+        };
+        assert_eq!(http_config.retry_backoff(3), Duration::from_millis(400));
+    }
+
+    fn retrying_client(base_url: &str) -> HttpProviderClient {
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            retry_backoff_ms: 1,
+            ..Default::default()
+        };
+        HttpProviderClient::new(base_url, Some(&http_config), None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_retried_until_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("Model loading"))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = retrying_client(&server.uri());
+        let body = client
+            .post_json("chat/completions", &serde_json::json!({}))
+            .await
+            .unwrap();
+
+        assert_eq!(body, serde_json::json!({"ok": true}));
+    }
+
+    fn gzip(body: &str) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn encoded(status: u16, encoding: &str, body: Vec<u8>) -> ResponseTemplate {
+        ResponseTemplate::new(status)
+            .insert_header("content-encoding", encoding)
+            .insert_header("content-type", "application/json")
+            .set_body_bytes(body)
+    }
+
+    #[tokio::test]
+    async fn test_compressed_bodies_are_decoded() {
+        let completion = r#"{"id":"chatcmpl-1","choices":[{"message":{"content":"Hi"}}]}"#;
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/gzip"))
+            .and(header_regex("accept-encoding", "gzip"))
+            .respond_with(encoded(200, "gzip", gzip(completion)))
+            .mount(&server)
+            .await;
+        // Not an encoding reqwest handles, so it arrives still compressed
+        Mock::given(method("POST"))
+            .and(path("/x-gzip"))
+            .respond_with(encoded(200, "x-gzip", gzip(completion)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rejected"))
+            .respond_with(encoded(400, "x-gzip", gzip(r#"{"error":"context too long"}"#)))
+            .mount(&server)
+            .await;
+        let client = retrying_client(&server.uri());
+        let expected: serde_json::Value = serde_json::from_str(completion).unwrap();
+
+        for route in ["gzip", "x-gzip"] {
+            let body = client.post_json(route, &serde_json::json!({})).await.unwrap();
+            assert_eq!(body, expected, "{route}");
+        }
+        let err = client
+            .post_json("rejected", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ProviderError::RequestFailed { status: 400, ref message }
+                if message.contains("context too long")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_compressed_body_reports_encoding_and_size() {
+        let mut corrupted = gzip(r#"{"id":"chatcmpl-1"}"#);
+        corrupted.truncate(12);
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/x-gzip"))
+            .respond_with(encoded(200, "x-gzip", corrupted.clone()))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/gzip"))
+            .respond_with(encoded(200, "gzip", corrupted))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/html"))
+            .respond_with(encoded(200, "x-gzip", gzip("<html>Bad gateway</html>")))
+            .mount(&server)
+            .await;
+        let client = retrying_client(&server.uri());
+        let message = |err| match err {
+            ProviderError::InvalidResponse(message) => message,
+            other => panic!("Expected InvalidResponse, got {other:?}"),
+        };
+
+        let err = client.post_json("x-gzip", &serde_json::json!({})).await;
+        let detail = message(err.unwrap_err());
+        assert!(
+            detail.starts_with("Failed to decode x-gzip response body (12 bytes received)"),
+            "{detail}"
+        );
+
+        let err = client.post_json("gzip", &serde_json::json!({})).await;
+        let detail = message(err.unwrap_err());
+        assert!(detail.contains("compressed body could not be decoded"), "{detail}");
+
+        let err = client.post_json("html", &serde_json::json!({})).await;
+        let detail = message(err.unwrap_err());
+        assert!(
+            detail.starts_with("Invalid JSON response (24 bytes after x-gzip decoding)"),
+            "{detail}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_retried_only_with_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/limited"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Slow down"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hinted"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "0")
+                    .set_body_string("Slow down"),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/hinted"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = retrying_client(&server.uri());
+
+        let limited = client.post_json("limited", &serde_json::json!({})).await;
+        assert!(matches!(
+            limited,
+            Err(ProviderError::RequestFailed { status: 429, .. })
+        ));
+        let hinted = client.post_json("hinted", &serde_json::json!({})).await;
+        assert_eq!(hinted.unwrap(), serde_json::json!({"ok": true}));
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_returns_the_429_at_once() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/an-hour"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "3600")
+                    .set_body_string("Slow down"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/five-seconds"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("Retry-After", "5")
+                    .set_body_string("Slow down"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            max_retry_after_secs: 10,
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+        let started = Instant::now();
+
+        // Longer than `max_retry_after_secs`
+        let result = client.post_json("an-hour", &serde_json::json!({})).await;
+        assert!(matches!(result, Err(ProviderError::RequestFailed { status: 429, .. })));
+        // Within the maximum, but past the request deadline
+        let body = serde_json::json!({});
+        let result =
+            with_budget(Duration::from_secs(2), 1.0, client.post_json("five-seconds", &body)).await;
+        assert!(matches!(result, Err(ProviderError::RequestFailed { status: 429, .. })));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_upstream_413_maps_to_payload_too_large() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(413).set_body_string("Request Entity Too Large"))
+            .expect(1) // Client errors are never retried
+            .mount(&server)
+            .await;
+
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+        let err = client
+            .post_json("chat/completions", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+
+        match err {
+            ProviderError::PayloadTooLarge { status, message } => {
+                assert_eq!(status, 413);
+                assert_eq!(message, "Request Entity Too Large");
+            }
+            other => panic!("Expected PayloadTooLarge, got {other:?}"),
+        }
+        assert!(matches!(
+            ProviderError::from_status(414, String::new()),
+            ProviderError::PayloadTooLarge { status: 414, .. }
+        ));
+        assert!(matches!(
+            ProviderError::from_status(400, String::new()),
+            ProviderError::RequestFailed { status: 400, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_slow_upstream_exceeds_deadline_without_retry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(3)))
+            .expect(1) // Deadline overruns are never retried
+            .mount(&server)
+            .await;
+
+        let http_config = HttpConfigSchema {
+            timeout_secs: 1,
+            max_retries: 2,
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+        let err = client
+            .post_json("chat/completions", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+
+        match err {
+            ref deadline @ ProviderError::DeadlineExceeded {
+                elapsed_ms,
+                budget_ms,
+            } => {
+                assert!(!deadline.is_retryable());
+                assert_eq!(budget_ms, 1000);
+                assert!(elapsed_ms >= 1000);
+            }
+            other => panic!("Expected DeadlineExceeded, got {other:?}"),
+        }
+        assert!(ProviderError::ConnectTimeout.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_stall_timeout_ends_stream_with_chunk_count() {
+        let chunk = create_content_chunk("id", "model", "token");
+        let stalled: ProviderStream = Box::pin(
+            futures_util::stream::iter(vec![Ok(chunk.clone()), Ok(chunk)])
+                .chain(futures_util::stream::pending()),
+        );
+
+        let items: Vec<_> = with_stall_timeout(stalled, Duration::from_millis(50))
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            items[2],
+            Err(ProviderError::StreamStalled { after_chunks: 2 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bounded_buffer_pauses_upstream_for_slow_consumer() {
+        let read = Arc::new(AtomicUsize::new(0));
+        let counter = read.clone();
+        let upstream: ProviderStream = Box::pin(futures_util::stream::iter(0..100).map(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(create_content_chunk("id", "model", "token"))
+        }));
+
+        let mut buffered = with_bounded_buffer(upstream, 4);
+        buffered.next().await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // One chunk taken, four queued and one held by the blocked sender
+        assert_eq!(read.load(Ordering::SeqCst), 6);
+
+        assert_eq!(buffered.count().await, 99);
+        assert_eq!(read.load(Ordering::SeqCst), 100);
+    }
+
+    #[tokio::test]
+    async fn test_bounded_buffer_reads_upstream_in_the_request_span() {
+        let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry());
+        let upstream: ProviderStream = Box::pin(futures_util::stream::iter(0..2).map(|_| {
+            let span = tracing::Span::current();
+            let name = span.metadata().map(|metadata| metadata.name());
+            assert_eq!(name, Some("request"));
+            Ok(create_content_chunk("id", "model", "token"))
+        }));
+
+        let buffered = {
+            let _request = tracing::info_span!("request").entered();
+            with_bounded_buffer(upstream, 4)
+        };
+
+        let chunks: Vec<_> = buffered.collect().await;
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_empty_chunks_keep_a_slow_stream_alive_until_first_token() {
+        let role = create_first_chunk("id", "model", Role::Assistant);
+        let first_token = async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok::<_, ProviderError>(create_content_chunk("id", "model", "Hello"))
+        };
+        let finish = async {
+            // Waits after the first token get no keep-alive chunks
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(create_final_chunk("id", "model", FinishReason::Stop, None))
+        };
+        let stream: ProviderStream = Box::pin(
+            futures_util::stream::iter([Ok(role)])
+                .chain(futures_util::stream::once(first_token))
+                .chain(futures_util::stream::once(finish)),
+        );
+
+        let chunks: Vec<_> = with_first_token_keep_alive(stream, Duration::from_secs(3), "model")
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        // Role, keep-alives at 3s, 6s and 9s, the token at 10s, then the finish
+        assert_eq!(chunks.len(), 6);
+        for keep_alive in &chunks[1..4] {
+            assert_eq!(keep_alive.id, "id");
+            let choice = &keep_alive.choices[0];
+            assert!(choice.finish_reason.is_none());
+            assert_eq!(serde_json::to_value(&choice.delta).unwrap(), serde_json::json!({}));
+        }
+        assert_eq!(chunks[4].choices[0].delta.content.as_deref(), Some("Hello"));
+        assert_eq!(chunks[5].choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_folds_chunks_into_completion() {
+        let mut last = create_final_chunk("id", "model", FinishReason::Stop, None);
+        last.usage = Some(Usage {
+            prompt_tokens: Some(3),
+            completion_tokens: Some(2),
+            total_tokens: Some(5),
+        });
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(create_first_chunk("id", "model", Role::Assistant)),
+            Ok(create_content_chunk("id", "model", "Hello ")),
+            Ok(create_content_chunk("id", "model", "world")),
+            Ok(last),
+        ]));
+
+        let response = collect_stream(stream).await.unwrap();
+
+        assert_eq!(response.id, "id");
+        assert_eq!(response.object, "chat.completion");
+        assert_eq!(response.choices.len(), 1);
+        let choice = &response.choices[0];
+        let message = choice.message.as_ref().unwrap();
+        assert_eq!(message.role, Role::Assistant);
+        assert_eq!(message.content.as_deref(), Some("Hello world"));
+        assert_eq!(choice.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.usage.unwrap().total_tokens, Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_stops_at_first_error() {
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(vec![
+            Ok(create_content_chunk("id", "model", "partial")),
+            Err(ProviderError::StreamStalled { after_chunks: 1 }),
+        ]));
+        assert!(matches!(
+            collect_stream(stream).await,
+            Err(ProviderError::StreamStalled { after_chunks: 1 })
+        ));
+
+        let empty: ProviderStream = Box::pin(futures_util::stream::empty());
+        assert!(matches!(
+            collect_stream(empty).await,
+            Err(ProviderError::InvalidResponse(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_wins_when_primary_is_slow() {
+        let server = MockServer::start().await;
+        // First request stalls, any duplicate answers immediately
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"served_by": "slow"}))
+                    .set_delay(Duration::from_secs(2)),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"served_by": "fast"})),
+            )
+            .mount(&server)
+            .await;
+
+        let client = hedged_client(&server.uri(), true);
+        let metrics = Metrics::default();
+        let timings = PhaseTimings::start();
+        let start = Instant::now();
+        let (body, outcome) = timings
+            .scope(client.post_json_hedged("chat/completions", &serde_json::json!({}), true))
+            .await
+            .unwrap();
+        timings.record_hedges(&metrics, "test");
+
+        assert_eq!(body["served_by"], "fast");
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.hedges_fired, 1);
+        assert_eq!(outcome.winner_label(), "hedge-1");
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let rendered = metrics.render();
+        assert!(rendered.contains("hedges_fired_total{provider=\"test\"} 1\n"));
+        assert!(rendered.contains("hedge_wins_total{provider=\"test\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error_is_not_hedged() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+            .mount(&server)
+            .await;
+
+        let client = hedged_client(&server.uri(), true);
+        let result = client
+            .post_json_hedged("chat/completions", &serde_json::json!({}), true)
+            .await;
+
+        assert!(matches!(result, Err(ProviderError::RequestFailed { status: 400, .. })));
+        // Give a stray hedge time to arrive before counting requests
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hedge_succeeds_after_primary_is_rejected() {
+        let server = MockServer::start().await;
+        // The first request is rejected after the hedge has gone out
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .set_body_string("bad request")
+                    .set_delay(Duration::from_millis(200)),
+            )
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"served_by": "hedge"}))
+                    .set_delay(Duration::from_millis(400)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = hedged_client(&server.uri(), true);
+        let (body, outcome) = client
+            .post_json_hedged("chat/completions", &serde_json::json!({}), true)
+            .await
+            .unwrap();
+
+        assert_eq!(body["served_by"], "hedge");
+        assert_eq!(outcome.unwrap().winner_label(), "hedge-1");
+    }
+
+    #[tokio::test]
+    async fn test_hedged_attempts_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("overloaded"))
+            .mount(&server)
+            .await;
+        let http_config = HttpConfigSchema {
+            timeout_secs: 5,
+            max_retries: 2,
+            hedging: Some(HedgingConfig {
+                delay_ms: 50,
+                max_hedges: 1,
+                deterministic_only: false,
+            }),
+            ..Default::default()
+        };
+        let client = HttpProviderClient::new(&server.uri(), Some(&http_config), None).unwrap();
+
+        let result = client
+            .post_json_hedged("chat/completions", &serde_json::json!({}), true)
+            .await;
+
+        assert!(matches!(result, Err(ProviderError::RequestFailed { status: 503, .. })));
+        // One primary and one hedge, neither retried
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_hedge_for_fast_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({"ok": true})))
+            .mount(&server)
+            .await;
+
+        let client = hedged_client(&server.uri(), true);
+        let (_, outcome) = client
+            .post_json_hedged("chat/completions", &serde_json::json!({}), true)
+            .await
+            .unwrap();
+
+        let outcome = outcome.unwrap();
+        assert_eq!(outcome.hedges_fired, 0);
+        assert_eq!(outcome.winner_label(), "primary");
+        // Give a stray hedge time to arrive before counting requests
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_loggable_body_is_truncated() {
+        let body = serde_json::json!({"prompt": "x".repeat(1000)});
+
+        let client = HttpProviderClient::new("http://localhost:1234", None, None)
+            .unwrap()
+            .with_max_logged_body_chars(Some(16));
+        assert_eq!(client.loggable_body(&body), "{\"prompt\":\"xxxxx...[truncated]");
+
+        let unlimited = HttpProviderClient::new("http://localhost:1234", None, None).unwrap();
+        assert_eq!(unlimited.loggable_body(&body), body.to_string());
+    }
+
+    #[test]
+    fn test_should_hedge_requires_deterministic_request() {
+        let client = hedged_client("http://localhost:1234", true);
+        assert!(client.should_hedge(&inference_request(Some(42), Some(0.7))));
+        assert!(client.should_hedge(&inference_request(None, Some(0.0))));
+        assert!(!client.should_hedge(&inference_request(None, Some(0.7))));
+
+        let client = hedged_client("http://localhost:1234", false);
+        assert!(client.should_hedge(&inference_request(None, Some(0.7))));
+
+        let client = HttpProviderClient::new("http://localhost:1234", None, None).unwrap();
+        assert!(!client.should_hedge(&inference_request(Some(42), Some(0.0))));
+    }
+
+    fn text_response(text: &str) -> InferenceResponse {
+        InferenceResponse {
+            text: text.to_string(),
+            model_used: "test-model".to_string(),
+            finish_reason: Some(FinishReason::Stop),
+            total_tokens: None,
+            prompt_tokens: None,
+            completion_tokens: None,
+            latency_ms: None,
+            provider_request_id: None,
+            system_fingerprint: None,
+            tool_calls: None,
+            logprobs: None,
+            provider_data: None,
+        }
+    }
+
+    #[test]
+    fn test_response_footer_appended_to_message() {
+        let mut response = standard_completion_response(
+            &text_response("The answer is 42."),
+            &CompletionRequest::default(),
+            "test",
+        );
+
+        apply_response_footer(&mut response, "\n\n(AI-generated)");
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(
+            message.content.as_deref(),
+            Some("The answer is 42.\n\n(AI-generated)")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_control_chars_stripped_from_stream() {
+        let chunks: Vec<Result<crate::models::StreamChunk, ProviderError>> = vec![
+            Ok(create_content_chunk("id-1", "test-model", "a\0b\u{7f}c\n")),
+        ];
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(chunks));
+
+        let out: Vec<_> = strip_control_chars_from_stream(stream)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(out[0].choices[0].delta.content.as_deref(), Some("abc\n"));
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_finish_chunks_normalized_to_one() {
+        let mut usage_chunk = create_final_chunk("id-1", "test-model", FinishReason::Stop, None);
+        usage_chunk.usage = Some(Usage {
+            prompt_tokens: Some(3),
+            completion_tokens: Some(1),
+            total_tokens: Some(4),
+        });
+        let mut empty_chunk = create_content_chunk("id-1", "test-model", "");
+        empty_chunk.choices[0].delta.content = None;
+        let chunks: Vec<Result<crate::models::StreamChunk, ProviderError>> = vec![
+            Ok(create_first_chunk("id-1", "test-model", Role::Assistant)),
+            Ok(create_content_chunk("id-1", "test-model", "Hello")),
+            Ok(create_final_chunk("id-1", "test-model", FinishReason::Stop, None)),
+            Ok(create_final_chunk("id-1", "test-model", FinishReason::Stop, None)),
+            Ok(usage_chunk),
+            Ok(empty_chunk),
+        ];
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(chunks));
+
+        let out: Vec<_> = normalize_stream_finish(stream)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        // The duplicate and the empty trailing chunk are gone; usage survives
+        assert_eq!(out.len(), 4);
+        let finishes: Vec<_> = out
+            .iter()
+            .flat_map(|chunk| &chunk.choices)
+            .filter_map(|choice| choice.finish_reason.clone())
+            .collect();
+        assert_eq!(finishes, vec![FinishReason::Stop]);
+        assert_eq!(out[2].choices[0].finish_reason, Some(FinishReason::Stop));
+        assert_eq!(out[3].usage.as_ref().unwrap().total_tokens, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_response_footer_emitted_before_final_chunk() {
+        let chunks: Vec<Result<crate::models::StreamChunk, ProviderError>> = vec![
+            Ok(create_first_chunk("id-1", "test-model", Role::Assistant)),
+            Ok(create_content_chunk("id-1", "test-model", "Hello")),
+            Ok(create_final_chunk("id-1", "test-model", FinishReason::Stop, None)),
+        ];
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(chunks));
+
+        let out: Vec<_> = append_footer_to_stream(stream, " [disclaimer]".to_string())
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 4);
+        assert_eq!(out[2].choices[0].delta.content.as_deref(), Some(" [disclaimer]"));
+        assert_eq!(out[2].choices[0].finish_reason, None);
+        assert_eq!(out[3].choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn test_reasoning_deltas_suppressed() {
+        let mut thinking = create_content_chunk("id-1", "test-model", "");
+        thinking.choices[0].delta.content = None;
+        thinking.choices[0].delta.reasoning_content = Some("Let me think".to_string());
+        let mut mixed = create_content_chunk("id-1", "test-model", "Hi");
+        mixed.choices[0].delta.reasoning_content = Some("done".to_string());
+        let chunks: Vec<Result<crate::models::StreamChunk, ProviderError>> = vec![
+            Ok(create_first_chunk("id-1", "test-model", Role::Assistant)),
+            Ok(thinking),
+            Ok(mixed),
+            Ok(create_final_chunk("id-1", "test-model", FinishReason::Stop, None)),
+        ];
+        let stream: ProviderStream = Box::pin(futures_util::stream::iter(chunks));
+
+        let out: Vec<_> = strip_reasoning_from_stream(stream)
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(out.len(), 3);
+        assert!(out.iter().all(|c| c.choices[0].delta.reasoning_content.is_none()));
+        assert_eq!(out[1].choices[0].delta.content.as_deref(), Some("Hi"));
+        assert_eq!(out[2].choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+}
@@ -0,0 +1,508 @@
+use super::{
+    BoxFuture, HttpProviderClient, InferenceProvider, InferenceRequest, InferenceResponse,
+    LineStream, ProviderError, ProviderStream, standard_completion_response,
+};
+use crate::config::{HttpConfigSchema, Settings};
+use crate::models::{
+    CompletionRequest, CompletionResponse, FinishReason, FormatType, Role, StreamChunk, Usage,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Ollama's native chat API (`/api/chat`), which streams newline-delimited JSON
+/// rather than SSE and takes sampling parameters under `options`.
+pub struct OllamaProvider {
+    http: HttpProviderClient,
+}
+
+/// One `/api/chat` response, or one line of a streamed one
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    message: Option<ChatMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    eval_count: Option<u32>,
+    /// Sent in place of a chunk when generation fails mid-stream
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: String,
+}
+
+impl ChatResponse {
+    fn finish_reason(&self) -> FinishReason {
+        match self.done_reason.as_deref() {
+            Some("length") => FinishReason::Length,
+            _ => FinishReason::Stop,
+        }
+    }
+
+    /// Token counts, present on the final response only
+    fn usage(&self) -> Option<Usage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        Some(Usage::new(
+            self.prompt_eval_count.unwrap_or(0),
+            self.eval_count.unwrap_or(0),
+        ))
+    }
+}
+
+impl OllamaProvider {
+    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
+        let http = HttpProviderClient::new(
+            &settings.inference.base_url,
+            settings.inference.http.as_ref(),
+            None,
+        )?
+        .with_max_logged_body_chars(settings.logging.max_logged_body_chars)
+        .with_extra_request_fields(settings.inference.extra_request_fields.clone())
+        .with_invalid_utf8(settings.inference.stream_invalid_utf8);
+
+        Ok(Self { http })
+    }
+
+    /// Build the `/api/chat` body. Sampling parameters go under `options`
+    /// with Ollama's names, e.g. `max_tokens` becomes `num_predict`.
+    fn build_request_body(&self, request: &InferenceRequest, stream: bool) -> serde_json::Value {
+        let messages: Vec<_> = request
+            .messages
+            .iter()
+            .map(|message| {
+                serde_json::json!({
+                    "role": message.role.as_str(),
+                    "content": message.content.as_deref().unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let mut options = serde_json::Map::new();
+        if let Some(max_tokens) = request.max_tokens {
+            options.insert("num_predict".to_string(), max_tokens.into());
+        }
+        if let Some(temperature) = request.temperature {
+            options.insert("temperature".to_string(), temperature.into());
+        }
+        if let Some(top_p) = request.top_p {
+            options.insert("top_p".to_string(), top_p.into());
+        }
+        if let Some(freq_penalty) = request.frequency_penalty {
+            options.insert("frequency_penalty".to_string(), freq_penalty.into());
+        }
+        if let Some(pres_penalty) = request.presence_penalty {
+            options.insert("presence_penalty".to_string(), pres_penalty.into());
+        }
+        if let Some(ref stop) = request.stop_sequences {
+            options.insert("stop".to_string(), serde_json::json!(stop));
+        }
+        if let Some(seed) = request.seed {
+            options.insert("seed".to_string(), seed.into());
+        }
+
+        let mut body = serde_json::json!({
+            "model": request.model,
+            "messages": messages,
+            "stream": stream,
+        });
+        if !options.is_empty() {
+            body["options"] = serde_json::Value::Object(options);
+        }
+        // Ollama takes "json" for JSON mode, or the schema itself
+        match request.response_format.as_ref() {
+            Some(format) if format.format_type == FormatType::JsonObject => {
+                body["format"] = serde_json::json!("json");
+            }
+            Some(format) if format.format_type == FormatType::JsonSchema => {
+                if let Some(schema) = format.json_schema.as_ref().and_then(|s| s.get("schema")) {
+                    body["format"] = schema.clone();
+                }
+            }
+            _ => {}
+        }
+
+        self.http.merge_extra_request_fields(&mut body);
+        body
+    }
+
+    fn parse_response_body(
+        &self,
+        response: serde_json::Value,
+    ) -> Result<InferenceResponse, ProviderError> {
+        let response: ChatResponse = serde_json::from_value(response).map_err(|e| {
+            ProviderError::InvalidResponse(format!("Failed to parse response: {e}"))
+        })?;
+        if let Some(error) = response.error {
+            return Err(ProviderError::InvalidResponse(error));
+        }
+
+        let usage = response.usage();
+        let finish_reason = response.finish_reason();
+        Ok(InferenceResponse {
+            text: response.message.map(|m| m.content).unwrap_or_default(),
+            model_used: response.model,
+            finish_reason: Some(finish_reason),
+            total_tokens: usage.as_ref().and_then(|u| u.total_tokens),
+            prompt_tokens: usage.as_ref().and_then(|u| u.prompt_tokens),
+            completion_tokens: usage.as_ref().and_then(|u| u.completion_tokens),
+            latency_ms: None,
+            provider_request_id: None,
+            system_fingerprint: None,
+            tool_calls: None,
+            logprobs: None,
+            provider_data: None,
+        })
+    }
+}
+
+/// Convert streamed `/api/chat` lines into OpenAI chunks: a role chunk first,
+/// one content chunk per line, and a final chunk with usage once `done` arrives
+fn convert_stream(lines: LineStream, id: String, model: String) -> ProviderStream {
+    let first = super::create_first_chunk(&id, &model, Role::Assistant);
+    let chunks = lines.flat_map(move |line| {
+        let chunks = match line.and_then(|line| parse_stream_line(&line, &id, &model)) {
+            Ok(chunks) => chunks.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        futures_util::stream::iter(chunks)
+    });
+    Box::pin(futures_util::stream::once(std::future::ready(Ok(first))).chain(chunks))
+}
+
+fn parse_stream_line(line: &str, id: &str, model: &str) -> Result<Vec<StreamChunk>, ProviderError> {
+    let response: ChatResponse = serde_json::from_str(line)
+        .map_err(|e| ProviderError::StreamError(format!("Invalid stream chunk: {e}")))?;
+    if let Some(error) = response.error {
+        return Err(ProviderError::StreamError(error));
+    }
+
+    let mut chunks = Vec::new();
+    if let Some(ref message) = response.message
+        && !message.content.is_empty()
+    {
+        chunks.push(super::create_content_chunk(id, model, &message.content));
+    }
+    if response.done {
+        chunks.push(super::create_final_chunk(
+            id,
+            model,
+            response.finish_reason(),
+            response.usage(),
+        ));
+    }
+    Ok(chunks)
+}
+
+impl InferenceProvider for OllamaProvider {
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request_body = self.build_request_body(request, false);
+
+        Box::pin(async move {
+            debug!(
+                "Sending request to Ollama: {}",
+                self.http.loggable_body(&request_body)
+            );
+            let response_body = self.http.post_json("api/chat", &request_body).await?;
+            debug!(
+                "Ollama response: {}",
+                self.http.loggable_body(&response_body)
+            );
+            self.parse_response_body(response_body)
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        standard_completion_response(response, original_request, self.name())
+    }
+
+    fn name(&self) -> &str {
+        "ollama"
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        Some(self.http.http_config())
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            let response = self.http.get("api/version").await?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Health check failed".to_string(),
+                })
+            }
+        })
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct TagsResponse {
+                models: Vec<ModelInfo>,
+            }
+            #[derive(Deserialize)]
+            struct ModelInfo {
+                name: String,
+            }
+
+            let response = self.http.get("api/tags").await?;
+            if !response.status().is_success() {
+                return Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Failed to list models".to_string(),
+                });
+            }
+            let tags: TagsResponse = response.json().await.map_err(|e| {
+                ProviderError::InvalidResponse(format!("Invalid tags response: {e}"))
+            })?;
+            Ok(tags.models.into_iter().map(|m| m.name).collect())
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supports_json_mode(&self) -> bool {
+        true
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let inference_req = self.build_inference_request(request, model)?;
+        Ok(self.build_request_body(&inference_req, request.stream == Some(true)))
+    }
+
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let inference_req = match self.build_inference_request(request, model) {
+            Ok(req) => req,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        let request_body = self.build_request_body(&inference_req, true);
+        let id = format!("chatcmpl-{}", uuid::Uuid::new_v4());
+
+        Box::pin(async move {
+            debug!(
+                "Sending streaming request to Ollama: {}",
+                self.http.loggable_body(&request_body)
+            );
+            let lines = self.http.post_ndjson("api/chat", &request_body).await?;
+            Ok(convert_stream(lines, id, inference_req.model))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Message, StringOrArray};
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn create_test_settings(base_url: &str) -> Arc<Settings> {
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::Ollama,
+            base_url,
+            "llama3",
+        ))
+    }
+
+    fn chat_request(stream: bool) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            max_tokens: Some(64),
+            temperature: Some(0.2),
+            stop: Some(StringOrArray::Array(vec!["###".to_string()])),
+            stream: Some(stream),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_request_body_maps_options() {
+        let provider = OllamaProvider::new(create_test_settings("http://localhost:11434")).unwrap();
+        let mut request = chat_request(false);
+        request.response_format = Some(crate::models::ResponseFormat {
+            format_type: FormatType::JsonObject,
+            json_schema: None,
+        });
+        let inference_req = provider
+            .build_inference_request(&request, "llama3")
+            .unwrap();
+
+        let body = provider.build_request_body(&inference_req, false);
+
+        assert_eq!(body["model"], "llama3");
+        assert_eq!(body["stream"], false);
+        assert_eq!(
+            body["messages"][0],
+            serde_json::json!({"role": "user", "content": "Hello"})
+        );
+        assert_eq!(body["options"]["num_predict"], 64);
+        assert_eq!(body["options"]["stop"], serde_json::json!(["###"]));
+        assert!((body["options"]["temperature"].as_f64().unwrap() - 0.2).abs() < 0.001);
+        assert!(body.get("max_tokens").is_none());
+        assert_eq!(body["format"], "json");
+    }
+
+    #[tokio::test]
+    async fn test_generate_parses_eval_counts_into_usage() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({"stream": false})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "model": "llama3",
+                "created_at": "2024-05-01T12:00:00Z",
+                "message": {"role": "assistant", "content": "Hi there"},
+                "done": true,
+                "done_reason": "length",
+                "prompt_eval_count": 12,
+                "eval_count": 64
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let provider = OllamaProvider::new(create_test_settings(&server.uri())).unwrap();
+
+        let response = provider
+            .generate(&chat_request(false), "llama3")
+            .await
+            .unwrap();
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hi there"));
+        assert_eq!(
+            response.choices[0].finish_reason,
+            Some(FinishReason::Length)
+        );
+        let usage = response.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, Some(12));
+        assert_eq!(usage.completion_tokens, Some(64));
+        assert_eq!(usage.total_tokens, Some(76));
+    }
+
+    #[tokio::test]
+    async fn test_stream_converts_ndjson_lines_to_chunks() {
+        let lines = [
+            r#"{"model":"llama3","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            r#"{"model":"llama3","message":{"role":"assistant","content":"lo"},"done":false}"#,
+            r#"{"model":"llama3","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","prompt_eval_count":3,"eval_count":2}"#,
+        ];
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/api/chat"))
+            .and(body_partial_json(serde_json::json!({"stream": true})))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(lines.join("\n") + "\n", "application/x-ndjson"),
+            )
+            .mount(&server)
+            .await;
+        let provider = OllamaProvider::new(create_test_settings(&server.uri())).unwrap();
+
+        let stream = provider
+            .stream(&chat_request(true), "llama3")
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.map(Result::unwrap).collect().await;
+
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks[0].choices[0].delta.role, Some(Role::Assistant));
+        let text: String = chunks
+            .iter()
+            .filter_map(|c| c.choices[0].delta.content.clone())
+            .collect();
+        assert_eq!(text, "Hello");
+        let last = chunks.last().unwrap();
+        assert_eq!(last.choices[0].finish_reason, Some(FinishReason::Stop));
+        assert_eq!(last.usage.as_ref().unwrap().total_tokens, Some(5));
+        assert!(
+            chunks
+                .iter()
+                .all(|c| c.id == chunks[0].id && c.model == "llama3")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lines_split_across_reads_and_stream_errors() {
+        let reads: Vec<Result<&[u8], std::io::Error>> = vec![
+            Ok(br#"{"model":"llama3","message":{"content":"Hi"},"#),
+            Ok(b"\"done\":false}\n{\"error\":\"model crashed\"}"),
+        ];
+        let lines =
+            super::super::split_lines(futures_util::stream::iter(reads), Default::default());
+
+        let items: Vec<_> = convert_stream(lines, "id".to_string(), "llama3".to_string())
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 3);
+        let content = items[1].as_ref().unwrap().choices[0].delta.content.clone();
+        assert_eq!(content.as_deref(), Some("Hi"));
+        assert!(matches!(
+            items[2],
+            Err(ProviderError::StreamError(ref message)) if message == "model crashed"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_and_tags() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/version"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"version": "0.5.1"})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/tags"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "models": [
+                    {"name": "llama3:latest", "model": "llama3:latest", "size": 4661224676u64},
+                    {"name": "qwen2.5:7b", "model": "qwen2.5:7b", "size": 4683087332u64}
+                ]
+            })))
+            .mount(&server)
+            .await;
+        let provider = OllamaProvider::new(create_test_settings(&server.uri())).unwrap();
+
+        provider.health_check().await.unwrap();
+        assert_eq!(
+            provider.list_models().await.unwrap(),
+            ["llama3:latest", "qwen2.5:7b"]
+        );
+    }
+}
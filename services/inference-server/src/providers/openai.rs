@@ -1,12 +1,12 @@
 use super::{
     BoxFuture, HttpProviderClient, InferenceProvider, InferenceRequest, InferenceResponse,
-    ProviderError, ProviderStream, standard_completion_response,
+    ProviderError, ProviderStream, attach_provider_data, standard_completion_response,
 };
 use crate::config::{HttpConfigSchema, Settings};
-use crate::models::{CompletionRequest, CompletionResponse, StreamChunk};
-use futures_util::TryStreamExt;
+use crate::models::{CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse};
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
 use serde_json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::debug;
 
@@ -51,7 +51,11 @@ impl OpenAIProvider {
             &settings.inference.base_url,
             settings.inference.http.as_ref(),
             Some(headers),
-        )?;
+        )?
+        .with_max_logged_body_chars(settings.logging.max_logged_body_chars)
+        .with_extra_request_fields(settings.inference.extra_request_fields.clone())
+        .with_invalid_utf8(settings.inference.stream_invalid_utf8)
+        .with_stream_reconnect(settings.inference.stream_reconnect);
 
         debug!(
             "Initialized OpenAI provider with base URL: {}",
@@ -108,18 +112,36 @@ impl OpenAIProvider {
         if let Some(top_logprobs) = request.top_logprobs {
             body["top_logprobs"] = serde_json::json!(top_logprobs);
         }
+        if let Some(ref modalities) = request.modalities {
+            body["modalities"] = serde_json::json!(modalities);
+        }
+        if let Some(ref audio) = request.audio {
+            body["audio"] = serde_json::json!(audio);
+        }
 
         // Always set stream=false for now (streaming handled separately)
         body["stream"] = serde_json::json!(false);
 
+        self.http.merge_extra_request_fields(&mut body);
         body
     }
 
+    /// Audio output of the first choice; `Message` doesn't model audio, so it is
+    /// passed through as provider data
+    fn response_audio(response: &serde_json::Value) -> Option<serde_json::Value> {
+        response["choices"][0]["message"]
+            .get("audio")
+            .filter(|audio| !audio.is_null())
+            .cloned()
+    }
+
     /// Parse OpenAI response into our internal format
     fn parse_response_body(
         &self,
         response: serde_json::Value,
     ) -> Result<InferenceResponse, ProviderError> {
+        let audio = Self::response_audio(&response);
+
         // Try to parse as CompletionResponse first (success case)
         if let Ok(completion_response) =
             serde_json::from_value::<CompletionResponse>(response.clone())
@@ -159,7 +181,7 @@ impl OpenAIProvider {
                 system_fingerprint: completion_response.system_fingerprint,
                 tool_calls: choice.message.as_ref().and_then(|m| m.tool_calls.clone()),
                 logprobs: choice.logprobs,
-                provider_data: None,
+                provider_data: audio.map(|audio| HashMap::from([("audio".to_string(), audio)])),
             });
         }
 
@@ -225,19 +247,29 @@ impl InferenceProvider for OpenAIProvider {
         request: &InferenceRequest,
     ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
         let request_body = self.build_request_body(request);
+        let hedgeable = self.http.should_hedge(request);
 
         Box::pin(async move {
-            debug!("Sending request to OpenAI: {}", request_body);
+            debug!("Sending request to OpenAI: {}", self.http.loggable_body(&request_body));
             let start = std::time::Instant::now();
 
             // OpenAI returns JSON errors even on non-200 status, so we use post_json with retry
-            let response_body = self.http.post_json("chat/completions", &request_body).await?;
+            let (response_body, hedge) = self
+                .http
+                .post_json_hedged("chat/completions", &request_body, hedgeable)
+                .await?;
 
             let latency_ms = start.elapsed().as_millis() as u64;
-            debug!("OpenAI response: {}", response_body);
+            debug!("OpenAI response: {}", self.http.loggable_body(&response_body));
 
             let mut inference_response = self.parse_response_body(response_body)?;
             inference_response.latency_ms = Some(latency_ms);
+            if let Some(hedge) = hedge {
+                inference_response
+                    .provider_data
+                    .get_or_insert_with(HashMap::new)
+                    .insert("hedge".to_string(), hedge.to_provider_data());
+            }
             Ok(inference_response)
         })
     }
@@ -261,16 +293,21 @@ impl InferenceProvider for OpenAIProvider {
             Err(e) => return Box::pin(async move { Err(e) }),
         };
         let request_body = self.build_request_body(&inference_req);
+        let hedgeable = self.http.should_hedge(&inference_req);
+        let request_clone = request.clone();
 
         Box::pin(async move {
-            debug!("Sending request to OpenAI: {}", request_body);
+            debug!("Sending request to OpenAI: {}", self.http.loggable_body(&request_body));
             let start = std::time::Instant::now();
-            let response_body = self.http.post_json("chat/completions", &request_body).await?;
+            let (response_body, hedge) = self
+                .http
+                .post_json_hedged("chat/completions", &request_body, hedgeable)
+                .await?;
             let latency_ms = start.elapsed().as_millis() as u64;
-            debug!("OpenAI response: {}", response_body);
+            debug!("OpenAI response: {}", self.http.loggable_body(&response_body));
 
             // Parse as full CompletionResponse (handles all n choices)
-            if let Ok(completion_response) =
+            if let Ok(mut completion_response) =
                 serde_json::from_value::<CompletionResponse>(response_body.clone())
             {
                 debug!(
@@ -278,6 +315,24 @@ impl InferenceProvider for OpenAIProvider {
                     latency_ms,
                     completion_response.choices.len()
                 );
+                if let Some(hedge) = hedge {
+                    attach_provider_data(
+                        &mut completion_response,
+                        &request_clone,
+                        self.name(),
+                        "hedge",
+                        hedge.to_provider_data(),
+                    );
+                }
+                if let Some(audio) = Self::response_audio(&response_body) {
+                    attach_provider_data(
+                        &mut completion_response,
+                        &request_clone,
+                        self.name(),
+                        "audio",
+                        audio,
+                    );
+                }
                 return Ok(completion_response);
             }
 
@@ -326,10 +381,43 @@ impl InferenceProvider for OpenAIProvider {
         })
     }
 
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let inference_req = self.build_inference_request(request, model)?;
+        let mut body = self.build_request_body(&inference_req);
+        if request.stream == Some(true) {
+            body["stream"] = serde_json::json!(true);
+        }
+        Ok(body)
+    }
+
     fn name(&self) -> &str {
         "openai"
     }
 
+    fn embed(
+        &self,
+        request: &EmbeddingRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        let mut request = request.clone();
+        request.model = Some(model.to_string());
+        Box::pin(async move {
+            let body = serde_json::to_value(&request).map_err(|e| {
+                ProviderError::InvalidResponse(format!(
+                    "Failed to serialize embedding request: {e}"
+                ))
+            })?;
+            let response = self.http.post_json("embeddings", &body).await?;
+            serde_json::from_value(response).map_err(|e| {
+                ProviderError::InvalidResponse(format!("Invalid embeddings response: {e}"))
+            })
+        })
+    }
+
     fn http_config(&self) -> Option<&HttpConfigSchema> {
         Some(self.http.http_config())
     }
@@ -386,6 +474,14 @@ impl InferenceProvider for OpenAIProvider {
         true
     }
 
+    fn supports_audio_output(&self) -> bool {
+        true
+    }
+
+    fn supports_json_mode(&self) -> bool {
+        true
+    }
+
     fn stream(
         &self,
         request: &CompletionRequest,
@@ -397,37 +493,13 @@ impl InferenceProvider for OpenAIProvider {
         };
         let mut request_body = self.build_request_body(&inference_req);
         request_body["stream"] = serde_json::json!(true);
+        let seeded = inference_req.seed.is_some();
 
         Box::pin(async move {
-            use eventsource_stream::Eventsource;
-            use futures_util::stream::StreamExt;
-
-            debug!("Sending streaming request to OpenAI: {}", request_body);
-            let response = self.http.post_stream("chat/completions", &request_body).await?;
-
-            let bytes_stream = response.bytes_stream().map_err(std::io::Error::other);
-            let sse_stream = bytes_stream
-                .eventsource()
-                .filter_map(|event_result| async move {
-                    match event_result {
-                        Ok(event) => {
-                            let data = &event.data;
-                            if data == "[DONE]" {
-                                None
-                            } else {
-                                match serde_json::from_str::<StreamChunk>(data) {
-                                    Ok(chunk) => Some(Ok(chunk)),
-                                    Err(e) => Some(Err(ProviderError::StreamError(format!(
-                                        "Invalid stream chunk: {e}"
-                                    )))),
-                                }
-                            }
-                        }
-                        Err(e) => Some(Err(ProviderError::StreamError(format!("SSE error: {e}")))),
-                    }
-                });
-
-            Ok(Box::pin(sse_stream) as ProviderStream)
+            debug!("Sending streaming request to OpenAI: {}", self.http.loggable_body(&request_body));
+            self.http
+                .post_sse_resumable("chat/completions", &request_body, seeded)
+                .await
         })
     }
 }
@@ -435,33 +507,17 @@ impl InferenceProvider for OpenAIProvider {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{InferenceConfig, LogFormat, LogOutput, LoggingConfig, ServerConfig};
-    use crate::models::{Message, Role};
+    use crate::models::{AudioOutput, Message, Role};
 
     fn create_test_settings() -> Arc<Settings> {
-        Arc::new(Settings {
-            server: ServerConfig {
-                host: "localhost".to_string(),
-                port: 3000,
-            },
-            inference: InferenceConfig {
-                base_url: "https://api.openai.com/v1".to_string(),
-                default_model: "gpt-3.5-turbo".to_string(),
-                allowed_models: None,
-                timeout_secs: 30,
-                http: Some(HttpConfigSchema::default()),
-                provider: crate::config::InferenceProvider::OpenAI {
-                    api_key: "test-key".to_string(),
-                    organization_id: None,
-                },
-            },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: LogFormat::Pretty,
-                output: LogOutput::Stdout,
-                file: None,
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::OpenAI {
+                api_key: "test-key".to_string(),
+                organization_id: None,
             },
-        })
+            "https://api.openai.com/v1",
+            "gpt-3.5-turbo",
+        ))
     }
 
     #[test]
@@ -485,6 +541,8 @@ mod tests {
             user: None,
             response_format: None,
             logit_bias: None,
+            modalities: None,
+            audio: None,
         };
 
         let body = provider.build_request_body(&request);
@@ -501,6 +559,88 @@ mod tests {
         assert_eq!(body["stream"], false);
     }
 
+    #[test]
+    fn test_extra_request_fields_do_not_override_client_values() {
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.extra_request_fields = serde_json::json!({
+            "echo": false,
+            "logit_bias": {},
+        })
+        .as_object()
+        .cloned();
+        let provider = OpenAIProvider::new(Arc::new(settings)).unwrap();
+
+        let mut request = InferenceRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            model: "gpt-3.5-turbo".to_string(),
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            seed: None,
+            stream: None,
+            n: None,
+            logprobs: None,
+            top_logprobs: None,
+            user: None,
+            response_format: None,
+            logit_bias: None,
+            modalities: None,
+            audio: None,
+        };
+
+        let body = provider.build_request_body(&request);
+        assert_eq!(body["echo"], false);
+        assert_eq!(body["logit_bias"], serde_json::json!({}));
+
+        request.logit_bias = serde_json::json!({"50256": -100}).as_object().cloned();
+        let body = provider.build_request_body(&request);
+        assert_eq!(body["echo"], false);
+        assert_eq!(body["logit_bias"], serde_json::json!({"50256": -100}));
+    }
+
+    #[test]
+    fn test_stop_sequence_limit_follows_provider() {
+        use crate::models::StringOrArray;
+        use crate::validations::{ValidationError, validate_provider_capabilities};
+
+        let provider = OpenAIProvider::new(create_test_settings()).unwrap();
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            stop: Some(StringOrArray::Array(
+                (1..=6).map(|i| format!("STOP{i}")).collect(),
+            )),
+            ..Default::default()
+        };
+
+        // A provider allowing 8 accepts them
+        assert!(
+            validate_provider_capabilities(&request, true, false, true, 8, provider.name()).is_ok()
+        );
+
+        let result = validate_provider_capabilities(
+            &request,
+            true,
+            false,
+            true,
+            provider.max_stop_sequences(),
+            provider.name(),
+        );
+        let Err(error) = result else {
+            panic!("expected too many stop sequences to be rejected");
+        };
+        assert!(matches!(
+            error,
+            ValidationError::TooManyStopSequences { count: 6, max: 4, .. }
+        ));
+        assert_eq!(
+            error.to_openai_error().message,
+            "Provider 'openai' allows at most 4 stop sequences, got 6"
+        );
+    }
+
     #[test]
     fn test_parse_error_response() {
         let provider = OpenAIProvider::new(create_test_settings()).unwrap();
@@ -546,6 +686,8 @@ mod tests {
             user: None,
             response_format: None,
             logit_bias: None,
+            modalities: None,
+            audio: None,
         };
 
         let body = provider.build_request_body(&request);
@@ -577,6 +719,8 @@ mod tests {
             user: None,
             response_format: None,
             logit_bias: None,
+            modalities: None,
+            audio: None,
         };
 
         let body = provider.build_request_body(&request);
@@ -585,4 +729,156 @@ mod tests {
         assert_eq!(body["logprobs"], true);
         assert_eq!(body["top_logprobs"], 5);
     }
+
+    #[test]
+    fn test_audio_output_round_trips() {
+        let provider = OpenAIProvider::new(create_test_settings()).unwrap();
+
+        let request = InferenceRequest {
+            messages: vec![Message::new(Role::User, "Say hello")],
+            model: "gpt-4o-audio-preview".to_string(),
+            max_tokens: None,
+            temperature: None,
+            n: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop_sequences: None,
+            seed: None,
+            stream: None,
+            logprobs: None,
+            top_logprobs: None,
+            user: None,
+            response_format: None,
+            logit_bias: None,
+            modalities: Some(vec!["text".to_string(), "audio".to_string()]),
+            audio: Some(AudioOutput {
+                voice: "alloy".to_string(),
+                format: "wav".to_string(),
+            }),
+        };
+
+        let body = provider.build_request_body(&request);
+        assert_eq!(body["modalities"], serde_json::json!(["text", "audio"]));
+        assert_eq!(body["audio"], serde_json::json!({"voice": "alloy", "format": "wav"}));
+
+        let response = provider
+            .parse_response_body(serde_json::json!({
+                "id": "chatcmpl-audio",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "gpt-4o-audio-preview",
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "audio": {"id": "audio_1", "data": "UklGRg==", "transcript": "Hello"}
+                    },
+                    "finish_reason": "stop"
+                }]
+            }))
+            .unwrap();
+        let audio = &response.provider_data.unwrap()["audio"];
+        assert_eq!(audio["transcript"], "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_embed_posts_to_embeddings() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/embeddings"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "text-embedding-3-small",
+                "input": ["Hello", "World"]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    {"object": "embedding", "index": 0, "embedding": [0.1, 0.2]},
+                    {"object": "embedding", "index": 1, "embedding": [0.3, 0.4]}
+                ],
+                "model": "text-embedding-3-small",
+                "usage": {"prompt_tokens": 2, "total_tokens": 2}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.base_url = server.uri();
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 5,
+            ..Default::default()
+        });
+        let provider = OpenAIProvider::new(Arc::new(settings)).unwrap();
+        let request: EmbeddingRequest =
+            serde_json::from_value(serde_json::json!({"input": ["Hello", "World"]})).unwrap();
+
+        let response = provider
+            .embed(&request, "text-embedding-3-small")
+            .await
+            .unwrap();
+
+        assert_eq!(response.data.len(), 2);
+        assert_eq!(response.data[1].index, 1);
+        assert_eq!(response.data[1].embedding, vec![0.3, 0.4]);
+        assert_eq!(response.usage.total_tokens, 2);
+    }
+
+    #[tokio::test]
+    async fn test_generate_returns_every_choice() {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let choices: Vec<_> = (0..3)
+            .map(|index| {
+                serde_json::json!({
+                    "index": index,
+                    "message": {"role": "assistant", "content": format!("Reply {index}")},
+                    "finish_reason": "stop"
+                })
+            })
+            .collect();
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(serde_json::json!({"n": 3})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "chatcmpl-n",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "gpt-3.5-turbo",
+                "choices": choices,
+                "usage": {"prompt_tokens": 4, "completion_tokens": 6, "total_tokens": 10}
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut settings = (*create_test_settings()).clone();
+        settings.inference.base_url = server.uri();
+        settings.inference.http = Some(HttpConfigSchema {
+            timeout_secs: 5,
+            ..Default::default()
+        });
+        let provider = OpenAIProvider::new(Arc::new(settings)).unwrap();
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            n: Some(3),
+            ..Default::default()
+        };
+
+        let response = provider.generate(&request, "gpt-3.5-turbo").await.unwrap();
+
+        assert_eq!(response.choices.len(), 3);
+        for (i, choice) in response.choices.iter().enumerate() {
+            assert_eq!(choice.index, i as u32);
+            let content = choice.message.as_ref().and_then(|m| m.content.as_deref());
+            assert_eq!(content, Some(format!("Reply {i}").as_str()));
+        }
+        assert_eq!(response.usage.unwrap().completion_tokens, Some(6));
+    }
+
 }
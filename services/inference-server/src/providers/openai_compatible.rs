@@ -0,0 +1,678 @@
+use super::lmstudio::LM_STUDIO_EXTENSIONS;
+use super::{
+    BoxFuture, Bound, ExtensionKind, ExtensionSpec, HttpProviderClient, InferenceProvider,
+    InferenceRequest, InferenceResponse, ProviderError, ProviderStream, attach_provider_data,
+    standard_completion_response,
+};
+use crate::config::{HttpConfigSchema, Settings};
+use crate::models::{CompletionRequest, CompletionResponse};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderValue};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+/// vLLM sampling and guided decoding parameters. The `vllm` provider accepts
+/// all of them; allowlisting one for `openai-compatible` keeps its checks.
+const VLLM_EXTENSIONS: &[ExtensionSpec] = &[
+    ExtensionSpec::new(
+        "top_k",
+        ExtensionKind::Integer,
+        "Top-k sampling (-1 considers all tokens)",
+    )
+    .min(Bound::inclusive(-1.0)),
+    ExtensionSpec::new("repetition_penalty", ExtensionKind::Number, "Repetition penalty")
+        .min(Bound::exclusive(0.0)),
+    ExtensionSpec::new("length_penalty", ExtensionKind::Number, "Beam search length penalty"),
+    ExtensionSpec::new("best_of", ExtensionKind::Integer, "Candidates to generate per choice")
+        .min(Bound::inclusive(1.0)),
+    ExtensionSpec::new("ignore_eos", ExtensionKind::Boolean, "Keep generating past EOS"),
+    ExtensionSpec::new(
+        "skip_special_tokens",
+        ExtensionKind::Boolean,
+        "Drop special tokens from the output",
+    ),
+    ExtensionSpec::new("use_beam_search", ExtensionKind::Boolean, "Use beam search"),
+    ExtensionSpec::new(
+        "guided_json",
+        ExtensionKind::Object,
+        "JSON schema the output must match",
+    ),
+    ExtensionSpec::new("guided_regex", ExtensionKind::String, "Regex the output must match"),
+    ExtensionSpec::new(
+        "guided_choice",
+        ExtensionKind::Array,
+        "Strings the output must be one of",
+    ),
+    ExtensionSpec::new(
+        "prompt_logprobs",
+        ExtensionKind::Integer,
+        "Log probabilities to return per prompt token",
+    )
+    .min(Bound::inclusive(0.0)),
+];
+
+/// Top-level vLLM response fields returned in `provider_extensions`
+const VLLM_RESPONSE_FIELDS: &[&str] = &["prompt_logprobs", "prompt_token_ids"];
+
+/// Provider for servers that speak the OpenAI chat API with their own paths,
+/// auth and extension parameters (vLLM, llama.cpp server, LocalAI, ...).
+/// Also serves the `vllm` provider, with vLLM's paths, extensions and extra
+/// response fields built in.
+pub struct GenericOpenAIProvider {
+    http: HttpProviderClient,
+    name: &'static str,
+    chat_path: String,
+    models_path: String,
+    extensions: &'static [ExtensionSpec],
+    response_fields: &'static [&'static str],
+}
+
+impl GenericOpenAIProvider {
+    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
+        let (name, chat_path, models_path, api_key, extensions, response_fields) =
+            match &settings.inference.provider {
+                crate::config::InferenceProvider::OpenAICompatible {
+                    chat_path,
+                    models_path,
+                    api_key,
+                    extensions_allowlist,
+                } => (
+                    "openai-compatible",
+                    chat_path.clone(),
+                    models_path.clone(),
+                    api_key,
+                    allowed_extensions(extensions_allowlist),
+                    &[][..],
+                ),
+                crate::config::InferenceProvider::Vllm { api_key } => (
+                    "vllm",
+                    "v1/chat/completions".to_string(),
+                    "v1/models".to_string(),
+                    api_key,
+                    VLLM_EXTENSIONS,
+                    VLLM_RESPONSE_FIELDS,
+                ),
+                _ => {
+                    return Err(ProviderError::Configuration(
+                        "Invalid provider configuration for GenericOpenAIProvider".to_string(),
+                    ));
+                }
+            };
+
+        let headers = match api_key {
+            Some(api_key) => {
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    AUTHORIZATION,
+                    HeaderValue::from_str(&format!("Bearer {api_key}")).map_err(|e| {
+                        ProviderError::Configuration(format!("Invalid API key format: {e}"))
+                    })?,
+                );
+                Some(headers)
+            }
+            None => None,
+        };
+
+        let http = HttpProviderClient::new(
+            &settings.inference.base_url,
+            settings.inference.http.as_ref(),
+            headers,
+        )?
+        .with_max_logged_body_chars(settings.logging.max_logged_body_chars)
+        .with_extra_request_fields(settings.inference.extra_request_fields.clone())
+        .with_invalid_utf8(settings.inference.stream_invalid_utf8);
+
+        Ok(Self {
+            http,
+            name,
+            chat_path,
+            models_path,
+            extensions,
+            response_fields,
+        })
+    }
+
+    /// The configured extra fields present at the top level of `body`
+    fn extra_response_fields(
+        &self,
+        body: &serde_json::Value,
+    ) -> HashMap<String, serde_json::Value> {
+        self.response_fields
+            .iter()
+            .filter_map(|field| Some((field.to_string(), body.get(*field)?.clone())))
+            .collect()
+    }
+
+    /// Build the request body, forwarding only the parameters the client set
+    fn build_request_body(
+        &self,
+        request: &InferenceRequest,
+        extensions: Option<&HashMap<String, serde_json::Value>>,
+    ) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "model": request.model,
+            "messages": request.messages,
+        });
+
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = serde_json::json!(max_tokens);
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = serde_json::json!(temperature);
+        }
+        if let Some(top_p) = request.top_p {
+            body["top_p"] = serde_json::json!(top_p);
+        }
+        if let Some(freq_penalty) = request.frequency_penalty {
+            body["frequency_penalty"] = serde_json::json!(freq_penalty);
+        }
+        if let Some(pres_penalty) = request.presence_penalty {
+            body["presence_penalty"] = serde_json::json!(pres_penalty);
+        }
+        if let Some(ref stop) = request.stop_sequences {
+            body["stop"] = serde_json::json!(stop);
+        }
+        if let Some(seed) = request.seed {
+            body["seed"] = serde_json::json!(seed);
+        }
+        if let Some(ref user) = request.user {
+            body["user"] = serde_json::json!(user);
+        }
+        if let Some(n) = request.n {
+            body["n"] = serde_json::json!(n);
+        }
+        if let Some(ref response_format) = request.response_format {
+            body["response_format"] = serde_json::json!(response_format);
+        }
+        if let Some(ref logit_bias) = request.logit_bias {
+            body["logit_bias"] = serde_json::json!(logit_bias);
+        }
+        if let Some(logprobs) = request.logprobs {
+            body["logprobs"] = serde_json::json!(logprobs);
+        }
+        if let Some(top_logprobs) = request.top_logprobs {
+            body["top_logprobs"] = serde_json::json!(top_logprobs);
+        }
+
+        if let Some(exts) = extensions {
+            for (key, value) in exts {
+                body[key] = value.clone();
+            }
+        }
+
+        self.http.merge_extra_request_fields(&mut body);
+        body
+    }
+
+    /// Validate extensions and build the upstream body for `request`
+    fn prepare(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<(InferenceRequest, serde_json::Value), ProviderError> {
+        if let Some(ref exts) = request.extensions {
+            self.validate_extensions(exts)?;
+        }
+        let inference_req = self.build_inference_request(request, model)?;
+        let body = self.build_request_body(&inference_req, request.extensions.as_ref());
+        Ok((inference_req, body))
+    }
+}
+
+/// Specs for the allowlisted extensions. Known parameters keep their type and
+/// range; anything else is passed through for the server to check.
+fn allowed_extensions(allowlist: &[String]) -> &'static [ExtensionSpec] {
+    let specs: Vec<ExtensionSpec> = allowlist
+        .iter()
+        .map(|name| {
+            LM_STUDIO_EXTENSIONS
+                .iter()
+                .chain(VLLM_EXTENSIONS)
+                .find(|spec| spec.name == name)
+                .cloned()
+                .unwrap_or_else(|| {
+                    ExtensionSpec::new(
+                        name.clone().leak(),
+                        ExtensionKind::Any,
+                        "Passed through to the server",
+                    )
+                })
+        })
+        .collect();
+    // Built once per provider at startup; specs are `'static` like the
+    // hardcoded tables of the other providers
+    specs.leak()
+}
+
+impl InferenceProvider for GenericOpenAIProvider {
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request_body = self.build_request_body(request, None);
+        let hedgeable = self.http.should_hedge(request);
+
+        Box::pin(async move {
+            let (response_body, hedge) = self
+                .http
+                .post_json_hedged(&self.chat_path, &request_body, hedgeable)
+                .await?;
+            let mut provider_data = self.extra_response_fields(&response_body);
+            if let Some(hedge) = hedge {
+                provider_data.insert("hedge".to_string(), hedge.to_provider_data());
+            }
+            let response: CompletionResponse =
+                serde_json::from_value(response_body).map_err(|e| {
+                    ProviderError::InvalidResponse(format!("Failed to parse response: {e}"))
+                })?;
+            let usage = response.usage.as_ref();
+            let (prompt_tokens, completion_tokens, total_tokens) = (
+                usage.and_then(|u| u.prompt_tokens),
+                usage.and_then(|u| u.completion_tokens),
+                usage.and_then(|u| u.total_tokens),
+            );
+            let choice = response.choices.into_iter().next().ok_or_else(|| {
+                ProviderError::InvalidResponse("No choices in response".to_string())
+            })?;
+            let message = choice.message.unwrap_or_default();
+
+            Ok(InferenceResponse {
+                text: message.content.unwrap_or_default(),
+                model_used: response.model,
+                total_tokens,
+                prompt_tokens,
+                completion_tokens,
+                finish_reason: choice.finish_reason,
+                latency_ms: None,
+                provider_request_id: Some(response.id),
+                system_fingerprint: response.system_fingerprint,
+                tool_calls: message.tool_calls,
+                logprobs: choice.logprobs,
+                provider_data: (!provider_data.is_empty()).then_some(provider_data),
+            })
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        standard_completion_response(response, original_request, self.name())
+    }
+
+    fn generate(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<CompletionResponse, ProviderError>> {
+        let (inference_req, request_body) = match self.prepare(request, model) {
+            Ok(prepared) => prepared,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        let hedgeable = self.http.should_hedge(&inference_req);
+        let request_clone = request.clone();
+
+        Box::pin(async move {
+            debug!(
+                "Sending request to {}: {}",
+                self.chat_path,
+                self.http.loggable_body(&request_body)
+            );
+            let (response_body, hedge) = self
+                .http
+                .post_json_hedged(&self.chat_path, &request_body, hedgeable)
+                .await?;
+            debug!("OpenAI-compatible response: {}", self.http.loggable_body(&response_body));
+
+            let extra_fields = self.extra_response_fields(&response_body);
+            let mut completion_response: CompletionResponse =
+                serde_json::from_value(response_body).map_err(|e| {
+                    ProviderError::InvalidResponse(format!("Failed to parse response: {e}"))
+                })?;
+            for (field, value) in extra_fields {
+                attach_provider_data(
+                    &mut completion_response,
+                    &request_clone,
+                    self.name(),
+                    &field,
+                    value,
+                );
+            }
+            if let Some(hedge) = hedge {
+                attach_provider_data(
+                    &mut completion_response,
+                    &request_clone,
+                    self.name(),
+                    "hedge",
+                    hedge.to_provider_data(),
+                );
+            }
+            Ok(completion_response)
+        })
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let (_, mut body) = self.prepare(request, model)?;
+        if request.stream == Some(true) {
+            body["stream"] = serde_json::json!(true);
+        }
+        Ok(body)
+    }
+
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let mut request_body = match self.prepare(request, model) {
+            Ok((_, body)) => body,
+            Err(e) => return Box::pin(async move { Err(e) }),
+        };
+        request_body["stream"] = serde_json::json!(true);
+
+        Box::pin(async move {
+            debug!(
+                "Sending streaming request to {}: {}",
+                self.chat_path,
+                self.http.loggable_body(&request_body)
+            );
+            self.http.post_sse(&self.chat_path, &request_body).await
+        })
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        Some(self.http.http_config())
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            let response = self.http.get(&self.models_path).await?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Health check failed".to_string(),
+                })
+            }
+        })
+    }
+
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct ModelsResponse {
+                data: Vec<ModelInfo>,
+            }
+            #[derive(Deserialize)]
+            struct ModelInfo {
+                id: String,
+            }
+
+            let response = self.http.get(&self.models_path).await?;
+            if !response.status().is_success() {
+                return Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Failed to list models".to_string(),
+                });
+            }
+            let models_response: ModelsResponse = response.json().await.map_err(|e| {
+                ProviderError::InvalidResponse(format!("Invalid models response: {e}"))
+            })?;
+            Ok(models_response.data.into_iter().map(|m| m.id).collect())
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn extension_specs(&self) -> &'static [ExtensionSpec] {
+        self.extensions
+    }
+
+    fn supports_json_mode(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Message, Role};
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn create_test_settings(
+        base_url: &str,
+        api_key: Option<&str>,
+        extensions_allowlist: &[&str],
+    ) -> Arc<Settings> {
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::OpenAICompatible {
+                chat_path: "openai/v1/chat/completions".to_string(),
+                models_path: "openai/v1/models".to_string(),
+                api_key: api_key.map(str::to_string),
+                extensions_allowlist: extensions_allowlist
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            },
+            base_url,
+            "qwen2.5-7b",
+        ))
+    }
+
+    fn vllm_settings(base_url: &str) -> Arc<Settings> {
+        let mut settings = (*create_test_settings(base_url, None, &[])).clone();
+        settings.inference.provider = crate::config::InferenceProvider::Vllm { api_key: None };
+        Arc::new(settings)
+    }
+
+    fn chat_request(extensions: &[(&str, serde_json::Value)]) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            extensions: (!extensions.is_empty()).then(|| {
+                extensions
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_config_defaults_to_v1_paths() {
+        let settings: Settings = serde_yml::from_str(
+            "server: {}\ninference:\n  provider: openai-compatible\n  base_url: http://localhost:8000\nlogging: {}\n",
+        )
+        .unwrap();
+
+        let crate::config::InferenceProvider::OpenAICompatible {
+            chat_path,
+            models_path,
+            api_key,
+            extensions_allowlist,
+        } = settings.inference.provider
+        else {
+            panic!("expected openai-compatible provider");
+        };
+        assert_eq!(chat_path, "v1/chat/completions");
+        assert_eq!(models_path, "v1/models");
+        assert_eq!(api_key, None);
+        assert!(extensions_allowlist.is_empty());
+    }
+
+    #[test]
+    fn test_allowlist_drives_supported_extensions() {
+        let provider = GenericOpenAIProvider::new(create_test_settings(
+            "http://localhost:8000",
+            None,
+            &["top_k", "repetition_penalty", "guided_json"],
+        ))
+        .unwrap();
+
+        assert_eq!(
+            provider.supported_extensions(),
+            ["top_k", "repetition_penalty", "guided_json"]
+        );
+        let validate = |name: &str, value: serde_json::Value| {
+            provider.validate_extensions(&HashMap::from([(name.to_string(), value)]))
+        };
+        // Known parameters keep their checks, unknown ones accept any value
+        assert!(validate("top_k", json!(0)).is_err());
+        assert!(validate("repetition_penalty", json!(1.1)).is_ok());
+        assert!(validate("guided_json", json!({"type": "object"})).is_ok());
+        assert!(validate("min_p", json!(0.1)).is_err());
+    }
+
+    #[test]
+    fn test_empty_allowlist_rejects_extensions() {
+        let provider =
+            GenericOpenAIProvider::new(create_test_settings("http://localhost:8000", None, &[]))
+                .unwrap();
+
+        let error = provider
+            .preview_request_body(&chat_request(&[("top_k", json!(40))]), "qwen2.5-7b")
+            .unwrap_err();
+        assert!(matches!(error, ProviderError::InvalidExtension { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_generate_uses_configured_path_and_auth() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/openai/v1/chat/completions"))
+            .and(header("authorization", "Bearer secret"))
+            .and(body_partial_json(json!({"model": "qwen2.5-7b", "top_k": 40})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "cmpl-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "qwen2.5-7b",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let provider = GenericOpenAIProvider::new(create_test_settings(
+            &server.uri(),
+            Some("secret"),
+            &["top_k"],
+        ))
+        .unwrap();
+
+        let response = provider
+            .generate(&chat_request(&[("top_k", json!(40))]), "qwen2.5-7b")
+            .await
+            .unwrap();
+
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hi"));
+    }
+
+    #[tokio::test]
+    async fn test_list_models_uses_configured_path() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/openai/v1/models"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "object": "list",
+                "data": [{"id": "qwen2.5-7b", "object": "model"}]
+            })))
+            .mount(&server)
+            .await;
+        let provider =
+            GenericOpenAIProvider::new(create_test_settings(&server.uri(), None, &[])).unwrap();
+
+        assert_eq!(provider.list_models().await.unwrap(), ["qwen2.5-7b"]);
+        provider.health_check().await.unwrap();
+    }
+
+    #[test]
+    fn test_vllm_rejects_out_of_range_best_of() {
+        let provider = GenericOpenAIProvider::new(vllm_settings("http://localhost:8000")).unwrap();
+
+        let error = provider
+            .preview_request_body(&chat_request(&[("best_of", json!(0))]), "qwen2.5-7b")
+            .unwrap_err();
+        match error {
+            ProviderError::InvalidExtension { param, reason } => {
+                assert_eq!(param, "best_of");
+                assert_eq!(reason, "must be >= 1");
+            }
+            e => panic!("expected InvalidExtension, got {e:?}"),
+        }
+
+        let body = provider
+            .preview_request_body(
+                &chat_request(&[
+                    ("best_of", json!(3)),
+                    ("guided_choice", json!(["yes", "no"])),
+                ]),
+                "qwen2.5-7b",
+            )
+            .unwrap();
+        assert_eq!(body["best_of"], 3);
+        assert_eq!(body["guided_choice"], json!(["yes", "no"]));
+        assert!(
+            provider
+                .preview_request_body(
+                    &chat_request(&[("guided_json", json!("schema"))]),
+                    "qwen2.5-7b"
+                )
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_vllm_prompt_logprobs_in_extended_mode() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/chat/completions"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "cmpl-1",
+                "object": "chat.completion",
+                "created": 1,
+                "model": "qwen2.5-7b",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "stop"
+                }],
+                "prompt_logprobs": [null, {"9906": {"logprob": -1.5}}]
+            })))
+            .mount(&server)
+            .await;
+        let provider = GenericOpenAIProvider::new(vllm_settings(&server.uri())).unwrap();
+        let mut request = chat_request(&[("prompt_logprobs", json!(1))]);
+
+        let response = provider.generate(&request, "qwen2.5-7b").await.unwrap();
+        assert!(response.provider_extensions.is_none());
+
+        request.response_mode = Some(crate::models::ResponseMode::Extended);
+        let response = provider.generate(&request, "qwen2.5-7b").await.unwrap();
+        let extensions = response.provider_extensions.unwrap();
+        assert_eq!(extensions.provider, "vllm");
+        assert_eq!(
+            extensions.data["prompt_logprobs"],
+            json!([null, {"9906": {"logprob": -1.5}}])
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use super::circuit_breaker::CircuitHealth;
+use super::failover::{FailoverBackend, FailoverProvider};
+use super::{
+    BoxFuture, ExtensionSpec, InferenceProvider, InferenceRequest, InferenceResponse,
+    ProviderError, ProviderModels, ProviderStream, standard_completion_response,
+};
+use crate::admission::matches_pattern;
+use crate::config::ModelRoute;
+use crate::model_list;
+use crate::models::{CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// Sends each request to one of several named providers, chosen by the
+/// first route whose pattern matches the model
+pub struct RoutingProvider {
+    backends: HashMap<String, Arc<dyn InferenceProvider>>,
+    routes: Vec<ModelRoute>,
+    /// What serves each route: its backend, or a failover chain when it has fallbacks
+    targets: Vec<Arc<dyn InferenceProvider>>,
+}
+
+impl RoutingProvider {
+    /// `fallback_models` maps backend names to the model they are asked for
+    /// when a route fails over to them
+    pub fn new(
+        backends: HashMap<String, Arc<dyn InferenceProvider>>,
+        routes: Vec<ModelRoute>,
+        fallback_models: &HashMap<String, String>,
+    ) -> Result<Self, ProviderError> {
+        let mut targets = Vec::with_capacity(routes.len());
+        for route in &routes {
+            let chain = std::iter::once(&route.backend)
+                .chain(&route.fallbacks)
+                .map(|name| match backends.get(name) {
+                    Some(provider) => Ok(FailoverBackend {
+                        name: name.clone(),
+                        provider: provider.clone(),
+                        model: fallback_models.get(name).cloned(),
+                    }),
+                    None => Err(ProviderError::Configuration(format!(
+                        "Route for '{}' names unknown backend '{name}'",
+                        route.model
+                    ))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            targets.push(match <[_; 1]>::try_from(chain) {
+                Ok([backend]) => backend.provider,
+                Err(chain) => Arc::new(FailoverProvider::new(chain)?),
+            });
+        }
+        Ok(Self {
+            backends,
+            routes,
+            targets,
+        })
+    }
+
+    /// What serves the first route matching `model`
+    fn backend(&self, model: &str) -> Option<&Arc<dyn InferenceProvider>> {
+        self.routes
+            .iter()
+            .position(|route| matches_pattern(&route.model, model))
+            .map(|index| &self.targets[index])
+    }
+
+    /// Backends in name order, so listings don't depend on hash order
+    fn sorted_backends(&self) -> Vec<(&str, &Arc<dyn InferenceProvider>)> {
+        let mut backends: Vec<_> = self
+            .backends
+            .iter()
+            .map(|(name, backend)| (name.as_str(), backend))
+            .collect();
+        backends.sort_by_key(|(name, _)| *name);
+        backends
+    }
+
+    /// Error for a model no route matches, listing every backend's models
+    async fn unroutable(&self, model: &str) -> ProviderError {
+        ProviderError::ModelNotAvailable {
+            requested: model.to_string(),
+            available: self.list_models().await.unwrap_or_default(),
+        }
+    }
+}
+
+impl InferenceProvider for RoutingProvider {
+    fn build_inference_request(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<InferenceRequest, ProviderError> {
+        match self.backend(model) {
+            Some(backend) => backend.build_inference_request(request, model),
+            None => Err(ProviderError::ModelNotAvailable {
+                requested: model.to_string(),
+                available: Vec::new(),
+            }),
+        }
+    }
+
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request = request.clone();
+        Box::pin(async move {
+            match self.backend(&request.model) {
+                Some(backend) => backend.execute(&request).await,
+                None => Err(self.unroutable(&request.model).await),
+            }
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        match self.backend(&response.model_used) {
+            Some(backend) => backend.build_completion_response(response, original_request),
+            None => standard_completion_response(response, original_request, self.name()),
+        }
+    }
+
+    fn generate(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<CompletionResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            match self.backend(&model) {
+                Some(backend) => backend.generate(&request, &model).await,
+                None => Err(self.unroutable(&model).await),
+            }
+        })
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        match self.backend(model) {
+            Some(backend) => backend.preview_request_body(request, model),
+            None => Err(ProviderError::ModelNotAvailable {
+                requested: model.to_string(),
+                available: Vec::new(),
+            }),
+        }
+    }
+
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<ProviderStream, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            match self.backend(&model) {
+                Some(backend) => backend.stream(&request, &model).await,
+                None => Err(self.unroutable(&model).await),
+            }
+        })
+    }
+
+    fn embed(
+        &self,
+        request: &EmbeddingRequest,
+        model: &str,
+    ) -> BoxFuture<'_, Result<EmbeddingResponse, ProviderError>> {
+        let request = request.clone();
+        let model = model.to_string();
+        Box::pin(async move {
+            match self.backend(&model) {
+                Some(backend) => backend.embed(&request, &model).await,
+                None => Err(self.unroutable(&model).await),
+            }
+        })
+    }
+
+    fn streaming_preference(&self, model: &str) -> BoxFuture<'_, Option<bool>> {
+        let model = model.to_string();
+        Box::pin(async move {
+            match self.backend(&model) {
+                Some(backend) => backend.streaming_preference(&model).await,
+                None => None,
+            }
+        })
+    }
+
+    fn route(
+        &self,
+        model: &str,
+    ) -> BoxFuture<'_, Result<Option<Arc<dyn InferenceProvider>>, ProviderError>> {
+        let model = model.to_string();
+        Box::pin(async move {
+            match self.backend(&model) {
+                Some(backend) => Ok(Some(backend.clone())),
+                None => Err(self.unroutable(&model).await),
+            }
+        })
+    }
+
+    fn name(&self) -> &str {
+        "router"
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.backends.values().any(|b| b.supports_streaming())
+    }
+
+    /// Models of all backends, deduplicated. A backend that can't list its
+    /// models is skipped unless none can.
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        Box::pin(async move {
+            let listed = self.list_models_by_provider().await?;
+            Ok(model_list::aggregate(listed)
+                .into_iter()
+                .map(|model| model.id)
+                .collect())
+        })
+    }
+
+    /// Each reachable backend's models under its provider name
+    fn list_models_by_provider(&self) -> BoxFuture<'_, Result<ProviderModels, ProviderError>> {
+        Box::pin(async move {
+            let mut listed = Vec::new();
+            let mut first_error = None;
+            for (name, backend) in self.sorted_backends() {
+                match backend.list_models().await {
+                    Ok(models) => listed.push((backend.name().to_string(), models)),
+                    Err(e) => {
+                        warn!(backend = name, error = %e, "Failed to list backend models");
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            if let (true, Some(e)) = (listed.is_empty(), first_error) {
+                return Err(e);
+            }
+            Ok(listed)
+        })
+    }
+
+    /// Each backend's extensions under its provider name, once per provider
+    fn extension_specs_by_provider(&self) -> Vec<(String, &'static [ExtensionSpec])> {
+        let mut listed: Vec<(String, &'static [ExtensionSpec])> = Vec::new();
+        for (_, backend) in self.sorted_backends() {
+            for (provider, specs) in backend.extension_specs_by_provider() {
+                if !listed.iter().any(|(listed, _)| *listed == provider) {
+                    listed.push((provider, specs));
+                }
+            }
+        }
+        listed
+    }
+
+    /// Healthy when every backend is
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            for (_, backend) in self.sorted_backends() {
+                backend.health_check().await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Each backend's circuit, under the backend's name
+    fn circuit_health(&self) -> Vec<CircuitHealth> {
+        self.sorted_backends()
+            .into_iter()
+            .flat_map(|(name, backend)| {
+                backend.circuit_health().into_iter().map(|mut circuit| {
+                    circuit.provider = name.to_string();
+                    circuit
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::models::{Message, Role};
+    use crate::providers::mock::MockProvider;
+    use tempfile::TempDir;
+
+    /// Mock backend serving one `mock-<scenario>` model per entry of `scenarios`
+    fn mock_backend(dir: &TempDir, scenarios: &[&str]) -> Arc<dyn InferenceProvider> {
+        for scenario in scenarios {
+            std::fs::write(
+                dir.path().join(format!("{scenario}.yaml")),
+                format!("responses:\n  - text: \"from {scenario}\"\nsettings:\n  mode: first\n"),
+            )
+            .unwrap();
+        }
+        let settings: Settings = serde_yml::from_str(&format!(
+            "server: {{}}\ninference:\n  provider: mock\n  base_url: http://localhost\n  responses_dir: {:?}\nlogging: {{}}\n",
+            dir.path()
+        ))
+        .unwrap();
+        Arc::new(MockProvider::new(Arc::new(settings)).unwrap())
+    }
+
+    fn route(model: &str, backend: &str) -> ModelRoute {
+        ModelRoute {
+            model: model.to_string(),
+            backend: backend.to_string(),
+            fallbacks: Vec::new(),
+        }
+    }
+
+    fn router(dirs: &(TempDir, TempDir)) -> RoutingProvider {
+        RoutingProvider::new(
+            HashMap::from([
+                ("left".to_string(), mock_backend(&dirs.0, &["left", "shared"])),
+                ("right".to_string(), mock_backend(&dirs.1, &["right", "shared"])),
+            ]),
+            vec![
+                route("mock-left", "left"),
+                route("mock-shared", "right"),
+                route("mock-*", "left"),
+            ],
+            &HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    fn dirs() -> (TempDir, TempDir) {
+        (TempDir::new().unwrap(), TempDir::new().unwrap())
+    }
+
+    async fn reply(router: &RoutingProvider, model: &str) -> Result<String, ProviderError> {
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            ..Default::default()
+        };
+        let response = router.generate(&request, model).await?;
+        Ok(response.choices[0]
+            .message
+            .as_ref()
+            .and_then(|m| m.content.clone())
+            .unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_first_matching_route_wins() {
+        let dirs = dirs();
+        let router = router(&dirs);
+
+        assert_eq!(reply(&router, "mock-left").await.unwrap(), "from left");
+        assert_eq!(reply(&router, "mock-shared").await.unwrap(), "from shared");
+        assert!(Arc::ptr_eq(
+            &router.route("mock-shared").await.unwrap().unwrap(),
+            &router.backends["right"]
+        ));
+        assert!(Arc::ptr_eq(
+            &router.route("mock-anything").await.unwrap().unwrap(),
+            &router.backends["left"]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_list_models_merges_backends() {
+        let dirs = dirs();
+        let router = router(&dirs);
+
+        assert_eq!(
+            router.list_models().await.unwrap(),
+            ["mock-left", "mock-right", "mock-shared"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_models_by_provider_names_each_backend() {
+        let dirs = dirs();
+        let router = router(&dirs);
+
+        let listed = router.list_models_by_provider().await.unwrap();
+
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].0, "mock");
+        assert_eq!(listed[0].1, ["mock-left", "mock-shared"]);
+        assert_eq!(listed[1].1, ["mock-right", "mock-shared"]);
+    }
+
+    #[test]
+    fn test_extension_specs_are_listed_once_per_provider() {
+        let dirs = dirs();
+        let router = router(&dirs);
+
+        let listed = router.extension_specs_by_provider();
+
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0, "mock");
+        assert_eq!(listed[0].1.len(), router.backends["left"].extension_specs().len());
+    }
+
+    #[tokio::test]
+    async fn test_unmatched_model_lists_all_models() {
+        let dirs = dirs();
+        let router = router(&dirs);
+
+        match reply(&router, "gpt-4o").await.unwrap_err() {
+            ProviderError::ModelNotAvailable {
+                requested,
+                available,
+            } => {
+                assert_eq!(requested, "gpt-4o");
+                assert_eq!(available, ["mock-left", "mock-right", "mock-shared"]);
+            }
+            e => panic!("expected ModelNotAvailable, got {e:?}"),
+        }
+        assert!(router.route("gpt-4o").await.is_err());
+    }
+
+    #[test]
+    fn test_unknown_backend_is_rejected() {
+        let result =
+            RoutingProvider::new(HashMap::new(), vec![route("*", "missing")], &HashMap::new());
+        assert!(matches!(result, Err(ProviderError::Configuration(_))));
+
+        let dir = TempDir::new().unwrap();
+        let mut fallback = route("*", "local");
+        fallback.fallbacks = vec!["missing".to_string()];
+        let result = RoutingProvider::new(
+            HashMap::from([("local".to_string(), mock_backend(&dir, &["test"]))]),
+            vec![fallback],
+            &HashMap::new(),
+        );
+        assert!(matches!(result, Err(ProviderError::Configuration(_))));
+    }
+}
@@ -0,0 +1,454 @@
+use super::{
+    BoxFuture, HttpProviderClient, InferenceProvider, InferenceRequest, InferenceResponse,
+    ProviderError, standard_completion_response,
+};
+use crate::config::{HttpConfigSchema, Settings};
+use crate::models::{CompletionRequest, CompletionResponse, FinishReason, Message};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::debug;
+
+/// Input tensor carrying the rendered prompt
+const TEXT_INPUT: &str = "text_input";
+/// Output tensor carrying the generated text
+const TEXT_OUTPUT: &str = "text_output";
+
+/// Triton Inference Server over its HTTP/REST (KServe v2) protocol.
+///
+/// The model is expected to take a `text_input` BYTES tensor and answer with a
+/// `text_output` BYTES tensor, as the TensorRT-LLM and vLLM backends' ensembles do.
+/// Chat messages are rendered into a single prompt; sampling parameters are sent
+/// as optional input tensors.
+pub struct TritonProvider {
+    http: HttpProviderClient,
+    /// Version segment of the infer URL; empty lets Triton pick by its version policy
+    model_version: String,
+}
+
+/// One named tensor of an infer request or response
+#[derive(Debug, Deserialize)]
+struct Tensor {
+    name: String,
+    #[serde(default)]
+    data: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InferResponse {
+    model_name: String,
+    #[serde(default)]
+    model_version: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
+    outputs: Vec<Tensor>,
+}
+
+impl TritonProvider {
+    pub fn new(settings: Arc<Settings>) -> Result<Self, ProviderError> {
+        let model_version = match &settings.inference.provider {
+            crate::config::InferenceProvider::Triton { model_version } => model_version.clone(),
+            _ => {
+                return Err(ProviderError::Configuration(
+                    "Invalid provider configuration for TritonProvider".to_string(),
+                ));
+            }
+        };
+
+        let http = HttpProviderClient::new(
+            &settings.inference.base_url,
+            settings.inference.http.as_ref(),
+            None,
+        )?
+        .with_max_logged_body_chars(settings.logging.max_logged_body_chars);
+
+        Ok(Self {
+            http,
+            model_version,
+        })
+    }
+
+    /// Path of the infer endpoint for `model`
+    fn infer_path(&self, model: &str) -> String {
+        if self.model_version.is_empty() {
+            format!("v2/models/{model}/infer")
+        } else {
+            format!("v2/models/{model}/versions/{}/infer", self.model_version)
+        }
+    }
+
+    /// Build the v2 infer body: the prompt plus any sampling parameters the request set
+    fn build_request_body(&self, request: &InferenceRequest) -> serde_json::Value {
+        let mut inputs = vec![tensor(
+            TEXT_INPUT,
+            "BYTES",
+            vec![render_prompt(&request.messages).into()],
+        )];
+
+        if let Some(max_tokens) = request.max_tokens {
+            inputs.push(tensor("max_tokens", "INT32", vec![max_tokens.into()]));
+        }
+        if let Some(temperature) = request.temperature {
+            inputs.push(tensor("temperature", "FP32", vec![temperature.into()]));
+        }
+        if let Some(top_p) = request.top_p {
+            inputs.push(tensor("top_p", "FP32", vec![top_p.into()]));
+        }
+        if let Some(seed) = request.seed {
+            inputs.push(tensor("random_seed", "UINT64", vec![seed.into()]));
+        }
+        if let Some(ref stop) = request.stop_sequences {
+            let words = stop.iter().map(|word| word.as_str().into()).collect();
+            inputs.push(tensor("stop_words", "BYTES", words));
+        }
+
+        serde_json::json!({
+            "inputs": inputs,
+            "outputs": [{"name": TEXT_OUTPUT}],
+        })
+    }
+
+    /// Read the generated text out of the `text_output` tensor
+    fn parse_response_body(
+        &self,
+        response: serde_json::Value,
+        prompt_tokens: u32,
+    ) -> Result<InferenceResponse, ProviderError> {
+        let response: InferResponse = serde_json::from_value(response).map_err(|e| {
+            ProviderError::InvalidResponse(format!("Failed to parse infer response: {e}"))
+        })?;
+
+        let text = response
+            .outputs
+            .iter()
+            .find(|output| output.name == TEXT_OUTPUT)
+            .and_then(|output| output.data.first())
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| {
+                ProviderError::InvalidResponse(format!(
+                    "Infer response has no {TEXT_OUTPUT} string tensor"
+                ))
+            })?
+            .to_string();
+
+        let mut provider_data = HashMap::new();
+        if let Some(version) = response.model_version {
+            provider_data.insert("model_version".to_string(), serde_json::json!(version));
+        }
+
+        Ok(InferenceResponse {
+            text,
+            model_used: response.model_name,
+            finish_reason: Some(FinishReason::Stop),
+            // Triton reports no usage; the prompt count is an estimate
+            total_tokens: None,
+            prompt_tokens: Some(prompt_tokens),
+            completion_tokens: None,
+            latency_ms: None,
+            provider_request_id: response.id,
+            system_fingerprint: None,
+            tool_calls: None,
+            logprobs: None,
+            provider_data: if provider_data.is_empty() {
+                None
+            } else {
+                Some(provider_data)
+            },
+        })
+    }
+}
+
+fn tensor(name: &str, datatype: &str, data: Vec<serde_json::Value>) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "datatype": datatype,
+        "shape": [data.len()],
+        "data": data,
+    })
+}
+
+/// Render chat messages as `role: content` turns, ending with an open
+/// assistant turn for the model to complete
+fn render_prompt(messages: &[Message]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        let Some(ref content) = message.content else {
+            continue;
+        };
+        prompt.push_str(message.role.as_str());
+        prompt.push_str(": ");
+        prompt.push_str(content);
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("assistant:");
+    prompt
+}
+
+impl InferenceProvider for TritonProvider {
+    fn execute(
+        &self,
+        request: &InferenceRequest,
+    ) -> BoxFuture<'_, Result<InferenceResponse, ProviderError>> {
+        let request_body = self.build_request_body(request);
+        let path = self.infer_path(&request.model);
+        let prompt_tokens = super::estimate_prompt_tokens(&request.messages);
+
+        Box::pin(async move {
+            debug!(
+                "Sending request to Triton: {}",
+                self.http.loggable_body(&request_body)
+            );
+            let response_body = self.http.post_json(&path, &request_body).await?;
+            debug!(
+                "Triton response: {}",
+                self.http.loggable_body(&response_body)
+            );
+            self.parse_response_body(response_body, prompt_tokens)
+        })
+    }
+
+    fn build_completion_response(
+        &self,
+        response: &InferenceResponse,
+        original_request: &CompletionRequest,
+    ) -> CompletionResponse {
+        standard_completion_response(response, original_request, self.name())
+    }
+
+    fn name(&self) -> &str {
+        "triton"
+    }
+
+    fn http_config(&self) -> Option<&HttpConfigSchema> {
+        Some(self.http.http_config())
+    }
+
+    fn preview_request_body(
+        &self,
+        request: &CompletionRequest,
+        model: &str,
+    ) -> Result<serde_json::Value, ProviderError> {
+        let inference_req = self.build_inference_request(request, model)?;
+        Ok(self.build_request_body(&inference_req))
+    }
+
+    fn health_check(&self) -> BoxFuture<'_, Result<(), ProviderError>> {
+        Box::pin(async move {
+            let response = self.http.get("v2/health/ready").await?;
+            if response.status().is_success() {
+                Ok(())
+            } else {
+                Err(ProviderError::RequestFailed {
+                    status: response.status().as_u16(),
+                    message: "Health check failed".to_string(),
+                })
+            }
+        })
+    }
+
+    /// Models in the repository index that are loaded and ready
+    fn list_models(&self) -> BoxFuture<'_, Result<Vec<String>, ProviderError>> {
+        Box::pin(async move {
+            #[derive(Deserialize)]
+            struct IndexEntry {
+                name: String,
+                #[serde(default)]
+                state: Option<String>,
+            }
+
+            let response = self
+                .http
+                .post_json("v2/repository/index", &serde_json::json!({"ready": true}))
+                .await?;
+            let entries: Vec<IndexEntry> = serde_json::from_value(response).map_err(|e| {
+                ProviderError::InvalidResponse(format!("Invalid repository index: {e}"))
+            })?;
+
+            let mut models: Vec<_> = entries
+                .into_iter()
+                .filter(|entry| entry.state.as_deref().is_none_or(|state| state == "READY"))
+                .map(|entry| entry.name)
+                .collect();
+            models.dedup();
+            Ok(models)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Role;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn create_test_settings(base_url: &str, model_version: &str) -> Arc<Settings> {
+        Arc::new(Settings::for_tests(
+            crate::config::InferenceProvider::Triton {
+                model_version: model_version.to_string(),
+            },
+            base_url,
+            "ensemble",
+        ))
+    }
+
+    fn chat_request() -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![
+                Message::new(Role::System, "Be brief"),
+                Message::new(Role::User, "Hello"),
+            ],
+            max_tokens: Some(32),
+            temperature: Some(0.5),
+            stop: Some(crate::models::StringOrArray::String("\n\n".to_string())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_build_request_body() {
+        let provider =
+            TritonProvider::new(create_test_settings("http://localhost:8000", "1")).unwrap();
+        let request = provider
+            .build_inference_request(&chat_request(), "ensemble")
+            .unwrap();
+
+        let body = provider.build_request_body(&request);
+
+        let inputs = body["inputs"].as_array().unwrap();
+        assert_eq!(inputs[0]["name"], "text_input");
+        assert_eq!(inputs[0]["datatype"], "BYTES");
+        assert_eq!(inputs[0]["shape"], serde_json::json!([1]));
+        assert_eq!(
+            inputs[0]["data"][0],
+            "system: Be brief\n\nuser: Hello\n\nassistant:"
+        );
+        let names: Vec<_> = inputs
+            .iter()
+            .map(|input| input["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["text_input", "max_tokens", "temperature", "stop_words"]
+        );
+        assert_eq!(inputs[1]["data"][0], 32);
+        assert_eq!(body["outputs"][0]["name"], "text_output");
+        assert_eq!(
+            provider.infer_path("ensemble"),
+            "v2/models/ensemble/versions/1/infer"
+        );
+    }
+
+    #[test]
+    fn test_unversioned_infer_path() {
+        let provider =
+            TritonProvider::new(create_test_settings("http://localhost:8000", "")).unwrap();
+
+        assert_eq!(provider.infer_path("ensemble"), "v2/models/ensemble/infer");
+    }
+
+    #[test]
+    fn test_parse_infer_response() {
+        let provider =
+            TritonProvider::new(create_test_settings("http://localhost:8000", "1")).unwrap();
+        let sample = serde_json::json!({
+            "model_name": "ensemble",
+            "model_version": "1",
+            "id": "42",
+            "outputs": [
+                {"name": "sequence_length", "datatype": "INT32", "shape": [1], "data": [7]},
+                {"name": "text_output", "datatype": "BYTES", "shape": [1], "data": ["Paris"]}
+            ]
+        });
+
+        let response = provider.parse_response_body(sample, 9).unwrap();
+
+        assert_eq!(response.text, "Paris");
+        assert_eq!(response.model_used, "ensemble");
+        assert_eq!(response.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(response.prompt_tokens, Some(9));
+        assert_eq!(response.provider_request_id.as_deref(), Some("42"));
+        let data = response.provider_data.unwrap();
+        assert_eq!(data["model_version"], "1");
+    }
+
+    #[tokio::test]
+    async fn test_generate_returns_openai_completion() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/models/ensemble/versions/1/infer"))
+            .and(body_partial_json(
+                serde_json::json!({"outputs": [{"name": "text_output"}]}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "model_name": "ensemble",
+                "model_version": "1",
+                "id": "req-7",
+                "outputs": [{
+                    "name": "text_output",
+                    "datatype": "BYTES",
+                    "shape": [1],
+                    "data": ["Hi there"]
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let provider = TritonProvider::new(create_test_settings(&server.uri(), "1")).unwrap();
+
+        let response = provider
+            .generate(&chat_request(), "ensemble")
+            .await
+            .unwrap();
+
+        assert_eq!(response.object, "chat.completion");
+        assert_eq!(response.model, "ensemble");
+        let message = response.choices[0].message.as_ref().unwrap();
+        assert_eq!(message.content.as_deref(), Some("Hi there"));
+        assert_eq!(response.choices[0].finish_reason, Some(FinishReason::Stop));
+        assert!(response.usage.unwrap().prompt_tokens.unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_missing_text_output_is_invalid_response() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "model_name": "ensemble",
+                "outputs": [{"name": "logits", "datatype": "FP32", "shape": [1], "data": [0.5]}]
+            })))
+            .mount(&server)
+            .await;
+        let provider = TritonProvider::new(create_test_settings(&server.uri(), "1")).unwrap();
+
+        let result = provider.generate(&chat_request(), "ensemble").await;
+
+        assert!(matches!(result, Err(ProviderError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_and_model_index() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/health/ready"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2/repository/index"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"name": "ensemble", "version": "1", "state": "READY"},
+                {"name": "preprocessing", "version": "1", "state": "READY"},
+                {"name": "draft", "version": "1", "state": "UNAVAILABLE"}
+            ])))
+            .mount(&server)
+            .await;
+        let provider = TritonProvider::new(create_test_settings(&server.uri(), "1")).unwrap();
+
+        provider.health_check().await.unwrap();
+        assert_eq!(
+            provider.list_models().await.unwrap(),
+            ["ensemble", "preprocessing"]
+        );
+    }
+}
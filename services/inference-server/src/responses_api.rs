@@ -0,0 +1,572 @@
+//! The OpenAI Responses API shape, served at `POST /v1/responses`.
+//!
+//! A Responses request is translated into a chat completion request and runs
+//! through the usual pipeline, so providers never see the difference. The
+//! completion is then reshaped into a `response` object whose `output` holds
+//! the assistant message and any function calls. Only non-streaming requests
+//! with text and function-call items are supported.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    CompletionRequest, CompletionResponse, FinishReason, Function, FunctionCall, Message,
+    ProviderExtensions, ResponseMode, Role, Tool, ToolCall, ToolChoice, ToolFunction, Warning,
+};
+use crate::validations::ValidationError;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResponsesRequest {
+    pub model: Option<String>,
+    pub input: Input,
+    /// System prompt placed ahead of the input
+    pub instructions: Option<String>,
+    pub max_output_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub stream: Option<bool>,
+    pub tools: Option<Vec<ResponsesTool>>,
+    pub tool_choice: Option<ResponsesToolChoice>,
+    pub user: Option<String>,
+    // Server features, passed through as on /v1/chat/completions
+    pub response_mode: Option<ResponseMode>,
+    pub extensions: Option<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Input {
+    /// A single user message
+    Text(String),
+    Items(Vec<InputItem>),
+}
+
+/// One entry of `input`. Messages may leave out `type`; function calls and
+/// their outputs carry `call_id` to pair them up.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InputItem {
+    #[serde(rename = "type")]
+    pub item_type: Option<String>,
+    pub role: Option<String>,
+    pub content: Option<InputContent>,
+    pub call_id: Option<String>,
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+    pub output: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum InputContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ContentPart {
+    #[serde(rename = "type")]
+    pub part_type: String,
+    pub text: Option<String>,
+}
+
+/// Function tools are declared flat, without the chat format's `function` wrapper
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResponsesTool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub parameters: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ResponsesToolChoice {
+    /// "none", "auto" or "required"
+    Mode(String),
+    Function {
+        #[serde(rename = "type")]
+        choice_type: String,
+        name: String,
+    },
+}
+
+fn unsupported(param: impl Into<String>, reason: impl Into<String>) -> ValidationError {
+    ValidationError::UnsupportedInput {
+        param: param.into(),
+        reason: reason.into(),
+    }
+}
+
+/// Translate a Responses request into the chat completion the pipeline runs
+pub fn to_completion_request(
+    request: ResponsesRequest,
+) -> Result<CompletionRequest, ValidationError> {
+    if request.stream == Some(true) {
+        return Err(unsupported(
+            "stream",
+            "streaming is not supported on /v1/responses",
+        ));
+    }
+
+    let mut messages = Vec::new();
+    if let Some(instructions) = request.instructions {
+        messages.push(Message::new(Role::System, &instructions));
+    }
+    match request.input {
+        Input::Text(text) => messages.push(Message::new(Role::User, &text)),
+        Input::Items(items) => {
+            for (index, item) in items.into_iter().enumerate() {
+                push_item(&mut messages, item, index)?;
+            }
+        }
+    }
+
+    let tools = request
+        .tools
+        .map(|tools| {
+            tools
+                .into_iter()
+                .map(to_tool)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?;
+    let tool_choice = request.tool_choice.map(|choice| match choice {
+        ResponsesToolChoice::Mode(mode) => ToolChoice::String(mode),
+        ResponsesToolChoice::Function { choice_type, name } => ToolChoice::Object {
+            choice_type,
+            function: ToolFunction { name },
+        },
+    });
+
+    Ok(CompletionRequest {
+        messages,
+        model: request.model,
+        max_tokens: request.max_output_tokens,
+        temperature: request.temperature,
+        top_p: request.top_p,
+        tools,
+        tool_choice,
+        user: request.user,
+        response_mode: request.response_mode,
+        extensions: request.extensions,
+        ..Default::default()
+    })
+}
+
+/// Append the chat message for `item`. Consecutive function calls become the
+/// tool calls of one assistant message.
+fn push_item(
+    messages: &mut Vec<Message>,
+    item: InputItem,
+    index: usize,
+) -> Result<(), ValidationError> {
+    match item.item_type.as_deref().unwrap_or("message") {
+        "message" => {
+            let role = match item.role.as_deref() {
+                Some("user") => Role::User,
+                Some("assistant") => Role::Assistant,
+                Some("system" | "developer") => Role::System,
+                other => {
+                    return Err(unsupported(
+                        format!("input[{index}].role"),
+                        format!("role {other:?} is not supported"),
+                    ));
+                }
+            };
+            let text = match item.content {
+                Some(InputContent::Text(text)) => text,
+                Some(InputContent::Parts(parts)) => content_text(parts, index)?,
+                None => String::new(),
+            };
+            messages.push(Message::new(role, &text));
+        }
+        "function_call" => {
+            let call = ToolCall {
+                id: item.call_id.unwrap_or_else(|| format!("call_{index}")),
+                tool_type: "function".to_string(),
+                function: FunctionCall {
+                    name: item.name.unwrap_or_default(),
+                    arguments: item.arguments.unwrap_or_default(),
+                },
+            };
+            match messages.last_mut() {
+                Some(last) if last.role == Role::Assistant && last.tool_calls.is_some() => {
+                    last.tool_calls.get_or_insert_default().push(call);
+                }
+                _ => {
+                    let mut message = Message::new(Role::Assistant, "");
+                    message.content = None;
+                    message.tool_calls = Some(vec![call]);
+                    messages.push(message);
+                }
+            }
+        }
+        "function_call_output" => {
+            let call_id = item.call_id.unwrap_or_default();
+            let output = item.output.unwrap_or_default();
+            messages.push(Message::tool_response(&call_id, &output));
+        }
+        other => {
+            return Err(unsupported(
+                format!("input[{index}].type"),
+                format!("input items of type '{other}' are not supported"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Join the text parts of a message; other part types (images, files) are rejected
+fn content_text(parts: Vec<ContentPart>, index: usize) -> Result<String, ValidationError> {
+    let mut text = String::new();
+    for (part_index, part) in parts.into_iter().enumerate() {
+        match part.part_type.as_str() {
+            "input_text" | "output_text" | "text" => text.push_str(&part.text.unwrap_or_default()),
+            other => {
+                return Err(unsupported(
+                    format!("input[{index}].content[{part_index}]"),
+                    format!("content of type '{other}' is not supported"),
+                ));
+            }
+        }
+    }
+    Ok(text)
+}
+
+fn to_tool(tool: ResponsesTool) -> Result<Tool, ValidationError> {
+    match (tool.tool_type.as_str(), tool.name) {
+        ("function", Some(name)) => Ok(Tool {
+            tool_type: "function".to_string(),
+            function: Function {
+                name,
+                description: tool.description,
+                parameters: tool.parameters,
+            },
+        }),
+        ("function", None) => Err(unsupported("tools", "function tools need a name")),
+        (other, _) => Err(unsupported(
+            "tools",
+            format!("tools of type '{other}' are not supported"),
+        )),
+    }
+}
+
+/// A completion in the Responses API shape
+#[derive(Serialize, Debug)]
+pub struct ResponseObject {
+    pub id: String,
+    pub object: &'static str,
+    pub created_at: u64,
+    /// `completed`, or `incomplete` when the output was cut off
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incomplete_details: Option<IncompleteDetails>,
+    pub model: String,
+    pub output: Vec<OutputItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResponseUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider_extensions: Option<ProviderExtensions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<Warning>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IncompleteDetails {
+    pub reason: &'static str,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    Message {
+        id: String,
+        status: &'static str,
+        role: &'static str,
+        content: Vec<OutputContent>,
+    },
+    FunctionCall {
+        id: String,
+        call_id: String,
+        name: String,
+        arguments: String,
+        status: &'static str,
+    },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    OutputText {
+        text: String,
+        annotations: Vec<serde_json::Value>,
+    },
+    Refusal {
+        refusal: String,
+    },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ResponseUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// Reshape a completion into a Responses object. The Responses API has no `n`,
+/// so only the first choice is used.
+pub fn from_completion(response: &CompletionResponse) -> ResponseObject {
+    let suffix = response
+        .id
+        .strip_prefix("chatcmpl-")
+        .unwrap_or(&response.id);
+    let choice = response.choices.first();
+    let message = choice.and_then(|c| c.message.as_ref());
+
+    let mut output = Vec::new();
+    if let Some(message) = message {
+        let mut content = Vec::new();
+        if let Some(text) = message.content.as_ref().filter(|text| !text.is_empty()) {
+            content.push(OutputContent::OutputText {
+                text: text.clone(),
+                annotations: Vec::new(),
+            });
+        }
+        if let Some(ref refusal) = message.refusal {
+            content.push(OutputContent::Refusal {
+                refusal: refusal.clone(),
+            });
+        }
+        if !content.is_empty() {
+            output.push(OutputItem::Message {
+                id: format!("msg_{suffix}"),
+                status: "completed",
+                role: "assistant",
+                content,
+            });
+        }
+        for call in message.tool_calls.iter().flatten() {
+            output.push(OutputItem::FunctionCall {
+                id: format!("fc_{}", call.id),
+                call_id: call.id.clone(),
+                name: call.function.name.clone(),
+                arguments: call.function.arguments.clone(),
+                status: "completed",
+            });
+        }
+    }
+
+    let incomplete_details = match choice.and_then(|c| c.finish_reason.as_ref()) {
+        Some(FinishReason::Length) => Some(IncompleteDetails {
+            reason: "max_output_tokens",
+        }),
+        Some(FinishReason::ContentFilter) => Some(IncompleteDetails {
+            reason: "content_filter",
+        }),
+        _ => None,
+    };
+    let usage = response.usage.as_ref().map(|usage| {
+        let input_tokens = usage.prompt_tokens.unwrap_or(0);
+        let output_tokens = usage.completion_tokens.unwrap_or(0);
+        ResponseUsage {
+            input_tokens,
+            output_tokens,
+            total_tokens: usage.total_tokens.unwrap_or(input_tokens + output_tokens),
+        }
+    });
+
+    ResponseObject {
+        id: format!("resp_{suffix}"),
+        object: "response",
+        created_at: response.created,
+        status: if incomplete_details.is_some() {
+            "incomplete"
+        } else {
+            "completed"
+        },
+        incomplete_details,
+        model: response.model.clone(),
+        output,
+        usage,
+        provider_extensions: response.provider_extensions.clone(),
+        warnings: response.warnings.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Choice, Usage};
+
+    fn parse(body: serde_json::Value) -> ResponsesRequest {
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn test_text_input_becomes_user_message() {
+        let request = to_completion_request(parse(serde_json::json!({
+            "model": "mock-test",
+            "instructions": "Be brief.",
+            "input": "Hello",
+            "max_output_tokens": 50
+        })))
+        .unwrap();
+
+        let roles: Vec<_> = request.messages.iter().map(|m| m.role.clone()).collect();
+        assert_eq!(roles, [Role::System, Role::User]);
+        assert_eq!(request.messages[0].content.as_deref(), Some("Be brief."));
+        assert_eq!(request.messages[1].content.as_deref(), Some("Hello"));
+        assert_eq!(request.model.as_deref(), Some("mock-test"));
+        assert_eq!(request.max_tokens, Some(50));
+    }
+
+    #[test]
+    fn test_input_items_become_messages() {
+        let request = to_completion_request(parse(serde_json::json!({
+            "input": [
+                {"role": "developer", "content": "Use tools."},
+                {"type": "message", "role": "user", "content": [
+                    {"type": "input_text", "text": "Weather in "},
+                    {"type": "input_text", "text": "Paris?"}
+                ]},
+                {"type": "function_call", "call_id": "call_a", "name": "weather", "arguments": "{}"},
+                {"type": "function_call", "call_id": "call_b", "name": "time", "arguments": "{}"},
+                {"type": "function_call_output", "call_id": "call_a", "output": "Sunny"}
+            ],
+            "tools": [{"type": "function", "name": "weather", "parameters": {"type": "object"}}],
+            "tool_choice": {"type": "function", "name": "weather"}
+        })))
+        .unwrap();
+
+        let messages = &request.messages;
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[1].content.as_deref(), Some("Weather in Paris?"));
+        assert_eq!(messages[2].role, Role::Assistant);
+        let calls: Vec<_> = messages[2]
+            .tool_calls
+            .iter()
+            .flatten()
+            .map(|call| call.id.as_str())
+            .collect();
+        assert_eq!(calls, ["call_a", "call_b"]);
+        assert_eq!(messages[3].role, Role::Tool);
+        assert_eq!(messages[3].tool_call_id.as_deref(), Some("call_a"));
+        assert_eq!(request.tools.unwrap()[0].function.name, "weather");
+        assert!(matches!(
+            request.tool_choice,
+            Some(ToolChoice::Object { ref function, .. }) if function.name == "weather"
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_input_is_rejected() {
+        for (body, param) in [
+            (serde_json::json!({"input": "Hi", "stream": true}), "stream"),
+            (
+                serde_json::json!({"input": [{"role": "user", "content": [
+                    {"type": "input_image", "image_url": "https://example.com/a.png"}
+                ]}]}),
+                "input[0].content[0]",
+            ),
+            (
+                serde_json::json!({"input": [{"type": "reasoning"}]}),
+                "input[0].type",
+            ),
+            (
+                serde_json::json!({"input": "Hi", "tools": [{"type": "web_search"}]}),
+                "tools",
+            ),
+        ] {
+            match to_completion_request(parse(body)) {
+                Err(ValidationError::UnsupportedInput { param: p, .. }) => assert_eq!(p, param),
+                other => panic!("expected UnsupportedInput for {param}, got {other:?}"),
+            }
+        }
+    }
+
+    fn completion(message: Message, finish_reason: FinishReason) -> CompletionResponse {
+        CompletionResponse {
+            id: "chatcmpl-abc".to_string(),
+            object: "chat.completion".to_string(),
+            created: 1_700_000_000,
+            model: "mock-test".to_string(),
+            choices: vec![Choice {
+                index: 0,
+                message: Some(message),
+                delta: None,
+                finish_reason: Some(finish_reason),
+                logprobs: None,
+            }],
+            usage: Some(Usage {
+                prompt_tokens: Some(5),
+                completion_tokens: Some(4),
+                total_tokens: Some(9),
+            }),
+            system_fingerprint: None,
+            provider_extensions: None,
+            warnings: None,
+        }
+    }
+
+    #[test]
+    fn test_completion_is_reshaped_into_output() {
+        let response = from_completion(&completion(
+            Message::new(Role::Assistant, "The quick brown fox"),
+            FinishReason::Stop,
+        ));
+        let value = serde_json::to_value(&response).unwrap();
+
+        assert_eq!(value["id"], "resp_abc");
+        assert_eq!(value["object"], "response");
+        assert_eq!(value["status"], "completed");
+        assert_eq!(value["output"][0]["type"], "message");
+        assert_eq!(value["output"][0]["id"], "msg_abc");
+        assert_eq!(value["output"][0]["content"][0]["type"], "output_text");
+        assert_eq!(
+            value["output"][0]["content"][0]["text"],
+            "The quick brown fox"
+        );
+        assert_eq!(
+            value["usage"],
+            serde_json::json!({"input_tokens": 5, "output_tokens": 4, "total_tokens": 9})
+        );
+    }
+
+    #[test]
+    fn test_tool_calls_become_function_call_items() {
+        let mut message = Message::new(Role::Assistant, "");
+        message.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            tool_type: "function".to_string(),
+            function: FunctionCall {
+                name: "weather".to_string(),
+                arguments: r#"{"city":"Paris"}"#.to_string(),
+            },
+        }]);
+        let response = from_completion(&completion(message, FinishReason::ToolCalls));
+
+        assert_eq!(response.output.len(), 1);
+        let value = serde_json::to_value(&response.output[0]).unwrap();
+        assert_eq!(value["type"], "function_call");
+        assert_eq!(value["call_id"], "call_1");
+        assert_eq!(value["name"], "weather");
+        assert_eq!(value["arguments"], r#"{"city":"Paris"}"#);
+    }
+
+    #[test]
+    fn test_truncated_completion_is_incomplete() {
+        let response = from_completion(&completion(
+            Message::new(Role::Assistant, "The quick"),
+            FinishReason::Length,
+        ));
+
+        assert_eq!(response.status, "incomplete");
+        assert_eq!(
+            response.incomplete_details.unwrap().reason,
+            "max_output_tokens"
+        );
+    }
+}
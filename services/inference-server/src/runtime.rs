@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::config::{Settings, StreamErrorFormat};
+use crate::deprecations::{self, Deprecation};
+
+/// `owned_by` for models of providers without an `owned_by` entry
+const DEFAULT_OWNED_BY: &str = "local";
+
+/// Wire-format choices resolved once from `runtime` settings and shared by
+/// the handlers and the completion pipeline
+#[derive(Debug, Clone)]
+pub struct RuntimeOptions {
+    /// Send `data: [DONE]` after the last chunk
+    pub done_marker: bool,
+    /// Keep-alive comment interval for idle streams; none disables them
+    pub keep_alive: Option<Duration>,
+    /// Empty-delta chunk interval before a stream's first token; none disables them
+    pub first_token_keep_alive: Option<Duration>,
+    pub stream_errors: StreamErrorFormat,
+    pub supports_tools: bool,
+    /// Buffer streams for clients that only accept JSON
+    pub negotiate_accept: bool,
+    /// Deprecations to look for; empty when notices are off
+    pub deprecations: Vec<&'static Deprecation>,
+    owned_by: HashMap<String, String>,
+}
+
+impl RuntimeOptions {
+    pub fn new(settings: &Settings) -> Self {
+        let runtime = &settings.runtime;
+        Self {
+            done_marker: runtime.stream_done_marker,
+            keep_alive: (runtime.stream_keep_alive_secs > 0)
+                .then(|| Duration::from_secs(runtime.stream_keep_alive_secs)),
+            first_token_keep_alive: (runtime.first_token_keep_alive_secs > 0)
+                .then(|| Duration::from_secs(runtime.first_token_keep_alive_secs)),
+            stream_errors: runtime.stream_errors,
+            supports_tools: runtime.supports_tools,
+            negotiate_accept: runtime.negotiate_accept,
+            deprecations: deprecations::active(runtime),
+            owned_by: runtime.owned_by.clone(),
+        }
+    }
+
+    /// `owned_by` reported for the models of `provider`
+    pub fn owned_by(&self, provider: &str) -> &str {
+        self.owned_by
+            .get(provider)
+            .map_or(DEFAULT_OWNED_BY, String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(runtime: &str) -> Settings {
+        serde_yml::from_str(&format!(
+            "server: {{}}\ninference:\n  provider: lmstudio\n  base_url: http://localhost:1234\nlogging: {{}}\n{runtime}"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_defaults_match_openai_wire_format() {
+        let options = RuntimeOptions::new(&settings(""));
+
+        assert!(options.done_marker);
+        assert_eq!(options.keep_alive, Some(Duration::from_secs(15)));
+        assert_eq!(options.first_token_keep_alive, None);
+        assert_eq!(options.stream_errors, StreamErrorFormat::Data);
+        assert!(!options.supports_tools);
+        assert!(options.negotiate_accept);
+        assert_eq!(options.owned_by("lmstudio"), "local");
+    }
+
+    #[test]
+    fn test_runtime_settings_override_defaults() {
+        let options = RuntimeOptions::new(&settings(
+            "runtime:\n  stream_done_marker: false\n  stream_keep_alive_secs: 0\n  stream_errors: event\n  supports_tools: true\n  negotiate_accept: false\n  owned_by:\n    openai: openai\n",
+        ));
+
+        assert!(!options.done_marker);
+        assert_eq!(options.keep_alive, None);
+        assert_eq!(options.stream_errors, StreamErrorFormat::Event);
+        assert!(options.supports_tools);
+        assert!(!options.negotiate_accept);
+        assert_eq!(options.owned_by("openai"), "openai");
+        assert_eq!(options.owned_by("mock"), "local");
+    }
+}
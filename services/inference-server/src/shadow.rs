@@ -0,0 +1,207 @@
+//! Shadow traffic for comparing a candidate backend against production.
+//!
+//! A sample of completions is sent again to the shadow provider in the
+//! background, and how its response differs from the primary one is logged.
+//! The client only ever sees the primary response, and shadow failures are
+//! only logged.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use rand::Rng;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::models::{CompletionRequest, CompletionResponse, FinishReason};
+use crate::providers::InferenceProvider;
+use crate::telemetry::truncate_for_log;
+
+pub struct Shadow {
+    provider: Arc<dyn InferenceProvider>,
+    sample_rate: f64,
+    max_logged_chars: Option<usize>,
+}
+
+impl Shadow {
+    pub fn new(
+        provider: Arc<dyn InferenceProvider>,
+        sample_rate: f64,
+        max_logged_chars: Option<usize>,
+    ) -> Self {
+        Self {
+            provider,
+            sample_rate,
+            max_logged_chars,
+        }
+    }
+
+    /// Whether this request is one of the sampled share
+    pub fn sampled(&self) -> bool {
+        rand::rng().random_bool(self.sample_rate.clamp(0.0, 1.0))
+    }
+
+    /// Send `request` to the shadow provider in the background and log how
+    /// its response compares to `primary`
+    pub fn mirror(
+        &self,
+        request: CompletionRequest,
+        model: String,
+        primary: &CompletionResponse,
+    ) -> JoinHandle<()> {
+        let provider = self.provider.clone();
+        let primary = Summary::of(primary);
+        let max_chars = self.max_logged_chars;
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let result = provider.generate(&request, &model).await;
+            let latency_ms = started.elapsed().as_millis() as u64;
+            let shadow = provider.name();
+            match result {
+                Ok(response) => {
+                    let differences = primary.differences(&Summary::of(&response), max_chars);
+                    if differences.is_empty() {
+                        info!(shadow, model, latency_ms, "Shadow response matches primary");
+                    } else {
+                        info!(
+                            shadow,
+                            model,
+                            latency_ms,
+                            differences = ?differences,
+                            "Shadow response differs from primary"
+                        );
+                    }
+                }
+                Err(e) => {
+                    warn!(shadow, model, latency_ms, error = %e, "Shadow request failed");
+                }
+            }
+        })
+    }
+}
+
+/// The parts of a response that are compared
+#[derive(Debug, PartialEq)]
+struct Summary {
+    choices: usize,
+    content: Option<String>,
+    finish_reason: Option<FinishReason>,
+    completion_tokens: Option<u32>,
+}
+
+impl Summary {
+    fn of(response: &CompletionResponse) -> Self {
+        let choice = response.choices.first();
+        Self {
+            choices: response.choices.len(),
+            content: choice
+                .and_then(|c| c.message.as_ref())
+                .and_then(|m| m.content.clone()),
+            finish_reason: choice.and_then(|c| c.finish_reason.clone()),
+            completion_tokens: response.usage.as_ref().and_then(|u| u.completion_tokens),
+        }
+    }
+
+    /// Fields that differ in `shadow`, as `field: primary -> shadow` lines
+    fn differences(&self, shadow: &Summary, max_chars: Option<usize>) -> Vec<String> {
+        let mut differences = Vec::new();
+        let mut compare = |field: &str, primary: String, shadow: String| {
+            if primary != shadow {
+                differences.push(format!(
+                    "{field}: {} -> {}",
+                    truncate_for_log(&primary, max_chars),
+                    truncate_for_log(&shadow, max_chars)
+                ));
+            }
+        };
+        compare("choices", self.choices.to_string(), shadow.choices.to_string());
+        compare(
+            "content",
+            format!("{:?}", self.content),
+            format!("{:?}", shadow.content),
+        );
+        compare(
+            "finish_reason",
+            format!("{:?}", self.finish_reason),
+            format!("{:?}", shadow.finish_reason),
+        );
+        compare(
+            "completion_tokens",
+            format!("{:?}", self.completion_tokens),
+            format!("{:?}", shadow.completion_tokens),
+        );
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::models::{Message, Role};
+    use crate::providers::mock::MockProvider;
+    use crate::telemetry::CaptureWriter;
+    use tempfile::TempDir;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Mock provider answering `mock-test` with `text`
+    fn mock_provider(dir: &TempDir, text: &str) -> Arc<dyn InferenceProvider> {
+        std::fs::write(
+            dir.path().join("test.yaml"),
+            format!(
+                "responses:\n  - text: \"{text}\"\n    completion_tokens: 3\nsettings:\n  mode: first\n"
+            ),
+        )
+        .unwrap();
+        let settings: Settings = serde_yml::from_str(&format!(
+            "server: {{}}\ninference:\n  provider: mock\n  base_url: http://localhost\n  responses_dir: {:?}\nlogging: {{}}\n",
+            dir.path()
+        ))
+        .unwrap();
+        Arc::new(MockProvider::new(Arc::new(settings)).unwrap())
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "Hello")],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mirror_logs_differences_from_primary() {
+        let (primary_dir, shadow_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+        let primary = mock_provider(&primary_dir, "Primary reply")
+            .generate(&request(), "mock-test")
+            .await
+            .unwrap();
+        let shadow = Shadow::new(mock_provider(&shadow_dir, "Shadow reply"), 1.0, None);
+
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || make_writer.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+        shadow
+            .mirror(request(), "mock-test".to_string(), &primary)
+            .await
+            .unwrap();
+
+        let logs = writer.contents();
+        assert!(logs.contains("Shadow response differs from primary"));
+        assert!(logs.contains("shadow=\"mock\""));
+        assert!(logs.contains(r#"content: Some(\"Primary reply\") -> Some(\"Shadow reply\")"#));
+        assert!(!logs.contains("completion_tokens:"));
+    }
+
+    #[test]
+    fn test_sample_rate_bounds() {
+        let dir = TempDir::new().unwrap();
+        let provider = mock_provider(&dir, "reply");
+
+        assert!(Shadow::new(provider.clone(), 1.0, None).sampled());
+        assert!(!Shadow::new(provider, 0.0, None).sampled());
+    }
+}
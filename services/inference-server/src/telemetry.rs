@@ -1,11 +1,25 @@
 use crate::config::{LogFormat, LogOutput, LoggingConfig, RotationPolicy};
 use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
 use opentelemetry_sdk::logs::SdkLoggerProvider;
+use std::borrow::Cow;
 use std::fs;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Event, Metadata, Subscriber};
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::fmt;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Span field carrying a per-request log level override (e.g. "debug")
+pub const LOG_LEVEL_OVERRIDE_FIELD: &str = "log_level_override";
+
+/// Marker appended to bodies cut short by `max_logged_body_chars`
+pub const TRUNCATION_SUFFIX: &str = "...[truncated]";
+
 pub fn init_logging(config: &LoggingConfig) -> (SdkLoggerProvider, Option<WorkerGuard>) {
     // Simple stdout exporter for now
     let exporter = opentelemetry_stdout::LogExporter::default();
@@ -21,71 +35,11 @@ pub fn init_logging(config: &LoggingConfig) -> (SdkLoggerProvider, Option<Worker
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.level));
 
     let subscriber = tracing_subscriber::registry()
-        .with(env_filter)
+        .with(RequestLevelFilter::new(env_filter, config.allow_level_override))
         .with(telemetry_layer);
 
-    // Apply format and writer in one go
-    let guard = match config.output {
-        LogOutput::Stdout => {
-            match config.format {
-                LogFormat::Pretty => subscriber.with(fmt::layer().pretty()).init(),
-                LogFormat::Json => subscriber.with(fmt::layer().json()).init(),
-                LogFormat::Compact => subscriber.with(fmt::layer().compact()).init(),
-            }
-            None
-        }
-        LogOutput::File => {
-            if let Some(file_config) = &config.file {
-                let (writer, guard) = create_file_writer(file_config);
-
-                match config.format {
-                    LogFormat::Pretty => subscriber
-                        .with(fmt::layer().pretty().with_writer(writer))
-                        .init(),
-                    LogFormat::Json => subscriber
-                        .with(fmt::layer().json().with_writer(writer))
-                        .init(),
-                    LogFormat::Compact => subscriber
-                        .with(fmt::layer().compact().with_writer(writer))
-                        .init(),
-                }
-                Some(guard)
-            } else {
-                eprintln!("File output requested but no file config provided");
-                subscriber.with(fmt::layer()).init();
-                None
-            }
-        }
-        LogOutput::Both => {
-            if let Some(file_config) = &config.file {
-                let (file_writer, guard) = create_file_writer(file_config);
-
-                match config.format {
-                    LogFormat::Pretty => subscriber
-                        .with(fmt::layer().pretty().with_writer(file_writer))
-                        .with(fmt::layer().pretty())
-                        .init(),
-                    LogFormat::Json => subscriber
-                        .with(fmt::layer().json().with_writer(file_writer))
-                        .with(fmt::layer().json())
-                        .init(),
-                    LogFormat::Compact => subscriber
-                        .with(fmt::layer().compact().with_writer(file_writer))
-                        .with(fmt::layer().compact())
-                        .init(),
-                }
-                Some(guard)
-            } else {
-                eprintln!("'Both' output requested but no file config provided, using stdout only");
-                match config.format {
-                    LogFormat::Pretty => subscriber.with(fmt::layer().pretty()).init(),
-                    LogFormat::Json => subscriber.with(fmt::layer().json()).init(),
-                    LogFormat::Compact => subscriber.with(fmt::layer().compact()).init(),
-                }
-                None
-            }
-        }
-    };
+    let (layers, guard) = output_layers(config);
+    subscriber.with(layers).init();
 
     tracing::info!(
         "Logging initialized: level={}, format={:?}",
@@ -95,6 +49,51 @@ pub fn init_logging(config: &LoggingConfig) -> (SdkLoggerProvider, Option<Worker
     (logger_provider, guard)
 }
 
+type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Formatting layers for `config.output`, plus the guard that keeps the file
+/// writer flushing. `Both` writes every event to stdout and the file.
+fn output_layers<S>(config: &LoggingConfig) -> (Vec<BoxedLayer<S>>, Option<WorkerGuard>)
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let stdout = || format_layer(&config.format, std::io::stdout);
+    let Some(file_config) = &config.file else {
+        match config.output {
+            LogOutput::Stdout => {}
+            LogOutput::File => eprintln!("File output requested but no file config provided"),
+            LogOutput::Both => eprintln!(
+                "'Both' output requested but no file config provided, using stdout only"
+            ),
+        }
+        return (vec![stdout()], None);
+    };
+
+    match config.output {
+        LogOutput::Stdout => (vec![stdout()], None),
+        LogOutput::File => {
+            let (writer, guard) = create_file_writer(file_config);
+            (vec![format_layer(&config.format, writer)], Some(guard))
+        }
+        LogOutput::Both => {
+            let (writer, guard) = create_file_writer(file_config);
+            (vec![format_layer(&config.format, writer), stdout()], Some(guard))
+        }
+    }
+}
+
+fn format_layer<S, W>(format: &LogFormat, writer: W) -> BoxedLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => fmt::layer().pretty().with_writer(writer).boxed(),
+        LogFormat::Json => fmt::layer().json().with_writer(writer).boxed(),
+        LogFormat::Compact => fmt::layer().compact().with_writer(writer).boxed(),
+    }
+}
+
 fn create_file_writer(
     file_config: &crate::config::FileLoggingConfig,
 ) -> (NonBlocking, WorkerGuard) {
@@ -121,13 +120,251 @@ fn create_file_writer(
     tracing_appender::non_blocking(appender)
 }
 
+/// Global filter that wraps the configured `EnvFilter` and additionally enables
+/// events inside spans carrying a `log_level_override` field, so a single request
+/// can be logged more verbosely without changing the server-wide level.
+pub struct RequestLevelFilter {
+    inner: EnvFilter,
+    allow_override: bool,
+}
+
+/// Level override stored in the extensions of the span that requested it
+struct LevelOverride(LevelFilter);
+
+impl RequestLevelFilter {
+    pub fn new(inner: EnvFilter, allow_override: bool) -> Self {
+        Self {
+            inner,
+            allow_override,
+        }
+    }
+
+    fn is_override_span(&self, metadata: &Metadata<'_>) -> bool {
+        self.allow_override
+            && metadata.is_span()
+            && metadata.fields().field(LOG_LEVEL_OVERRIDE_FIELD).is_some()
+    }
+
+    /// Check whether any span in the current scope raises the level enough for `metadata`
+    fn override_permits<S>(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let Some(current) = ctx.lookup_current() else {
+            return false;
+        };
+        current.scope().any(|span| {
+            span.extensions()
+                .get::<LevelOverride>()
+                .is_some_and(|level| level.0 >= *metadata.level())
+        })
+    }
+}
+
+impl<S> Layer<S> for RequestLevelFilter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let interest = Layer::<S>::register_callsite(&self.inner, metadata);
+        // Callsites the EnvFilter rejects must be re-evaluated per event when overrides are allowed
+        if self.allow_override && interest.is_never() {
+            Interest::sometimes()
+        } else {
+            interest
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        if Layer::<S>::enabled(&self.inner, metadata, ctx.clone()) || self.is_override_span(metadata)
+        {
+            return true;
+        }
+        self.allow_override && self.override_permits(metadata, &ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        if self.allow_override {
+            Some(LevelFilter::TRACE)
+        } else {
+            Layer::<S>::max_level_hint(&self.inner)
+        }
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx.clone());
+
+        if !self.is_override_span(attrs.metadata()) {
+            return;
+        }
+        let mut visitor = LevelOverrideVisitor(None);
+        attrs.record(&mut visitor);
+        if let (Some(level), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(LevelOverride(level));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(id, values, ctx);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.inner.on_event(event, ctx);
+    }
+}
+
+/// Extracts the `log_level_override` field value from span attributes
+struct LevelOverrideVisitor(Option<LevelFilter>);
+
+impl Visit for LevelOverrideVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == LOG_LEVEL_OVERRIDE_FIELD {
+            self.0 = value.parse().ok();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == LOG_LEVEL_OVERRIDE_FIELD {
+            self.0 = format!("{value:?}").trim_matches('"').parse().ok();
+        }
+    }
+}
+
+/// Truncate a request/response body for logging to at most `max_chars` characters
+pub fn truncate_for_log(body: &str, max_chars: Option<usize>) -> Cow<'_, str> {
+    match max_chars.and_then(|max| body.char_indices().nth(max)) {
+        Some((cut, _)) => Cow::Owned(format!("{}{}", &body[..cut], TRUNCATION_SUFFIX)),
+        None => Cow::Borrowed(body),
+    }
+}
+
 pub fn shutdown_logging(logger_provider: SdkLoggerProvider) {
     if let Err(err) = logger_provider.shutdown() {
         eprintln!("Failed to shutdown logger provider: {err}");
     }
 }
 
-// TODO: init_metrics
 // TODO: init_tracing
 // TODO: top level init for all telemetry
 // TODO: top level shutdown for all telemetry
+
+/// Writer that captures formatted log output for assertions
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct CaptureWriter(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl std::io::Write for CaptureWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CaptureWriter {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::{debug, info_span};
+
+    fn capture_logs(allow_override: bool, f: impl FnOnce()) -> String {
+        let writer = CaptureWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(RequestLevelFilter::new(EnvFilter::new("info"), allow_override))
+            .with(
+                fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(move || make_writer.clone()),
+            );
+        tracing::subscriber::with_default(subscriber, f);
+        writer.contents()
+    }
+
+    #[test]
+    fn test_level_override_applies_only_inside_request_span() {
+        let logs = capture_logs(true, || {
+            debug!("before request");
+            info_span!("request", log_level_override = "debug").in_scope(|| {
+                debug!("inside overridden request");
+            });
+            info_span!("request").in_scope(|| {
+                debug!("inside normal request");
+            });
+            debug!("after request");
+        });
+
+        assert!(logs.contains("inside overridden request"));
+        assert!(!logs.contains("inside normal request"));
+        assert!(!logs.contains("before request"));
+        assert!(!logs.contains("after request"));
+    }
+
+    #[test]
+    fn test_truncate_for_log() {
+        assert_eq!(truncate_for_log("short body", Some(20)), "short body");
+        assert_eq!(truncate_for_log("exactly10!", Some(10)), "exactly10!");
+        assert_eq!(truncate_for_log("a very long prompt body", Some(6)), "a very...[truncated]");
+        // Multi-byte characters are counted as characters, not bytes
+        assert_eq!(truncate_for_log("héllo wörld", Some(4)), "héll...[truncated]");
+        assert_eq!(truncate_for_log("unbounded", None), "unbounded");
+    }
+
+    #[test]
+    fn test_level_override_ignored_when_disabled() {
+        let logs = capture_logs(false, || {
+            info_span!("request", log_level_override = "debug").in_scope(|| {
+                debug!("inside overridden request");
+            });
+        });
+
+        assert!(!logs.contains("inside overridden request"));
+    }
+
+    #[test]
+    fn test_both_output_writes_to_log_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config: LoggingConfig = serde_yml::from_str(&format!(
+            "format: json\noutput: both\nfile:\n  directory: {:?}\n  prefix: both.log\n",
+            dir.path()
+        ))
+        .unwrap();
+
+        let (layers, guard) = output_layers(&config);
+        assert_eq!(layers.len(), 2);
+        let subscriber = tracing_subscriber::registry().with(layers);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("written to both outputs");
+        });
+        // Dropping the guard flushes the non-blocking file writer
+        drop(guard);
+
+        let logged: String = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        assert!(logged.contains("written to both outputs"));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::models::{CompletionRequest, OpenAIError, OpenAIErrorResponse};
+use crate::i18n::MessageKey;
+use crate::models::{
+    CompletionRequest, EmbeddingRequest, FormatType, OpenAIError, OpenAIErrorResponse, Role,
+    StringOrArray,
+};
 use axum::{Json, http::StatusCode, response::IntoResponse, response::Response};
 use std::collections::HashSet;
 
@@ -6,7 +10,7 @@ use std::collections::HashSet;
 pub enum ValidationError {
     EmptyMessages,
     NoContent, // All messages have null content and no tool calls
-    InvalidMaxTokens(u32),
+    InvalidMaxTokens { value: u32, max: u32 },
     InvalidTemperature(f32),
     InvalidTopP(f32),
     InvalidFrequencyPenalty(f32),
@@ -16,6 +20,16 @@ pub enum ValidationError {
     ModelNotInAllowedList { model: String, allowed: Vec<String> },
     StreamingNotSupported,
     InvalidLogitBias { token_id: String, reason: String },
+    InvalidMessageSequence { index: usize, role: Role },
+    InvalidToolSchema { name: String },
+    TooManyStopSequences { provider: String, count: usize, max: usize },
+    AudioNotSupported,
+    InvalidResponseFormat { format_type: String, supported: Vec<String> },
+    InvalidPagination { param: &'static str, reason: String },
+    /// Part of a `/v1/responses` request that has no chat completion equivalent
+    UnsupportedInput { param: String, reason: String },
+    /// An embeddings request with no text to embed
+    EmptyInput,
 }
 
 impl ValidationError {
@@ -38,8 +52,8 @@ impl ValidationError {
                 param: Some("messages".to_string()),
                 code: None,
             },
-            ValidationError::InvalidMaxTokens(max_tokens) => OpenAIError {
-                message: format!("Max tokens must be between 1 and 128000, got {}", max_tokens),
+            ValidationError::InvalidMaxTokens { value, max } => OpenAIError {
+                message: format!("Max tokens must be between 1 and {max}, got {value}"),
                 error_type: "invalid_request_error".to_string(),
                 param: Some("max_tokens".to_string()),
                 code: None,
@@ -102,6 +116,183 @@ impl ValidationError {
                 param: Some("logit_bias".to_string()),
                 code: None,
             },
+            ValidationError::InvalidMessageSequence { index, role } => OpenAIError {
+                message: format!(
+                    "Message at index {} repeats role '{}' from the previous turn; user and assistant turns must alternate",
+                    index,
+                    role.as_str()
+                ),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("messages".to_string()),
+                code: None,
+            },
+            ValidationError::InvalidToolSchema { name } => OpenAIError {
+                message: format!(
+                    "Parameters of tool '{}' must be a JSON Schema object with a valid 'type'",
+                    name
+                ),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("tools".to_string()),
+                code: None,
+            },
+            ValidationError::TooManyStopSequences {
+                provider,
+                count,
+                max,
+            } => OpenAIError {
+                message: format!(
+                    "Provider '{}' allows at most {} stop sequences, got {}",
+                    provider, max, count
+                ),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("stop".to_string()),
+                code: None,
+            },
+            ValidationError::AudioNotSupported => OpenAIError {
+                message: "Audio output is not supported by the current provider".to_string(),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("modalities".to_string()),
+                code: Some("unsupported_parameter".to_string()),
+            },
+            ValidationError::InvalidResponseFormat {
+                format_type,
+                supported,
+            } => OpenAIError {
+                message: format!(
+                    "Response format '{}' is not supported. Supported formats: {}",
+                    format_type,
+                    supported.join(", ")
+                ),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("response_format".to_string()),
+                code: Some("unsupported_parameter".to_string()),
+            },
+            ValidationError::InvalidPagination { param, reason } => OpenAIError {
+                message: format!("Invalid '{}': {}", param, reason),
+                error_type: "invalid_request_error".to_string(),
+                param: Some(param.to_string()),
+                code: None,
+            },
+            ValidationError::UnsupportedInput { param, reason } => OpenAIError {
+                message: format!("Unsupported '{}': {}", param, reason),
+                error_type: "invalid_request_error".to_string(),
+                param: Some(param.clone()),
+                code: Some("unsupported_parameter".to_string()),
+            },
+            ValidationError::EmptyInput => OpenAIError {
+                message: "Input cannot be empty".to_string(),
+                error_type: "invalid_request_error".to_string(),
+                param: Some("input".to_string()),
+                code: None,
+            },
+        }
+    }
+}
+
+impl ValidationError {
+    /// Variant name, used as the `rule` label of `validation_rejections_total`
+    pub fn variant_name(&self) -> &'static str {
+        match self {
+            ValidationError::EmptyMessages => "EmptyMessages",
+            ValidationError::NoContent => "NoContent",
+            ValidationError::InvalidMaxTokens { .. } => "InvalidMaxTokens",
+            ValidationError::InvalidTemperature(_) => "InvalidTemperature",
+            ValidationError::InvalidTopP(_) => "InvalidTopP",
+            ValidationError::InvalidFrequencyPenalty(_) => "InvalidFrequencyPenalty",
+            ValidationError::InvalidPresencePenalty(_) => "InvalidPresencePenalty",
+            ValidationError::InvalidTopLogprobs(_) => "InvalidTopLogprobs",
+            ValidationError::InvalidN(_) => "InvalidN",
+            ValidationError::ModelNotInAllowedList { .. } => "ModelNotInAllowedList",
+            ValidationError::StreamingNotSupported => "StreamingNotSupported",
+            ValidationError::InvalidLogitBias { .. } => "InvalidLogitBias",
+            ValidationError::InvalidMessageSequence { .. } => "InvalidMessageSequence",
+            ValidationError::InvalidToolSchema { .. } => "InvalidToolSchema",
+            ValidationError::TooManyStopSequences { .. } => "TooManyStopSequences",
+            ValidationError::AudioNotSupported => "AudioNotSupported",
+            ValidationError::InvalidResponseFormat { .. } => "InvalidResponseFormat",
+            ValidationError::InvalidPagination { .. } => "InvalidPagination",
+            ValidationError::UnsupportedInput { .. } => "UnsupportedInput",
+            ValidationError::EmptyInput => "EmptyInput",
+        }
+    }
+
+    /// Catalog key and arguments used to localize the message
+    pub fn message_key(&self) -> MessageKey {
+        match self {
+            ValidationError::EmptyMessages => MessageKey::new("validation.empty_messages"),
+            ValidationError::NoContent => MessageKey::new("validation.no_content"),
+            ValidationError::InvalidMaxTokens { value, max } => {
+                MessageKey::new("validation.invalid_max_tokens")
+                    .arg("value", value)
+                    .arg("max", max)
+            }
+            ValidationError::InvalidTemperature(value) => {
+                MessageKey::new("validation.invalid_temperature").arg("value", value)
+            }
+            ValidationError::InvalidTopP(value) => {
+                MessageKey::new("validation.invalid_top_p").arg("value", value)
+            }
+            ValidationError::InvalidFrequencyPenalty(value) => {
+                MessageKey::new("validation.invalid_frequency_penalty").arg("value", value)
+            }
+            ValidationError::InvalidPresencePenalty(value) => {
+                MessageKey::new("validation.invalid_presence_penalty").arg("value", value)
+            }
+            ValidationError::InvalidTopLogprobs(value) => {
+                MessageKey::new("validation.invalid_top_logprobs").arg("value", value)
+            }
+            ValidationError::InvalidN(value) => {
+                MessageKey::new("validation.invalid_n").arg("value", value)
+            }
+            ValidationError::ModelNotInAllowedList { model, allowed } => {
+                MessageKey::new("validation.model_not_allowed")
+                    .arg("model", model)
+                    .arg("allowed", allowed.join(", "))
+            }
+            ValidationError::StreamingNotSupported => {
+                MessageKey::new("validation.streaming_not_supported")
+            }
+            ValidationError::InvalidLogitBias { token_id, reason } => {
+                MessageKey::new("validation.invalid_logit_bias")
+                    .arg("token_id", token_id)
+                    .arg("reason", reason)
+            }
+            ValidationError::InvalidMessageSequence { index, role } => {
+                MessageKey::new("validation.invalid_message_sequence")
+                    .arg("index", index)
+                    .arg("role", role.as_str())
+            }
+            ValidationError::InvalidToolSchema { name } => {
+                MessageKey::new("validation.invalid_tool_schema").arg("name", name)
+            }
+            ValidationError::TooManyStopSequences {
+                provider,
+                count,
+                max,
+            } => {
+                MessageKey::new("validation.too_many_stop_sequences")
+                    .arg("provider", provider)
+                    .arg("count", count)
+                    .arg("max", max)
+            }
+            ValidationError::AudioNotSupported => MessageKey::new("validation.audio_not_supported"),
+            ValidationError::InvalidResponseFormat {
+                format_type,
+                supported,
+            } => MessageKey::new("validation.invalid_response_format")
+                .arg("format_type", format_type)
+                .arg("supported", supported.join(", ")),
+            ValidationError::InvalidPagination { param, reason } => {
+                MessageKey::new("validation.invalid_pagination")
+                    .arg("param", param)
+                    .arg("reason", reason)
+            }
+            ValidationError::UnsupportedInput { param, reason } => {
+                MessageKey::new("validation.unsupported_input")
+                    .arg("param", param)
+                    .arg("reason", reason)
+            }
+            ValidationError::EmptyInput => MessageKey::new("validation.empty_input"),
         }
     }
 }
@@ -125,7 +316,15 @@ impl IntoResponse for ValidationError {
     }
 }
 
-pub fn validate_completion_request(request: &CompletionRequest) -> Result<(), ValidationError> {
+/// Upper bound for `max_tokens` when `inference.max_tokens_limit` is unset
+const DEFAULT_MAX_TOKENS_LIMIT: u32 = 128_000;
+
+/// `max_tokens_limit` replaces `DEFAULT_MAX_TOKENS_LIMIT` as the largest
+/// accepted `max_tokens` when set
+pub fn validate_completion_request(
+    request: &CompletionRequest,
+    max_tokens_limit: Option<u32>,
+) -> Result<(), ValidationError> {
     if request.messages.is_empty() {
         return Err(ValidationError::EmptyMessages);
     }
@@ -141,10 +340,11 @@ pub fn validate_completion_request(request: &CompletionRequest) -> Result<(), Va
     }
 
     // Validate max_tokens if present
-    if let Some(max_tokens) = request.max_tokens
-        && (max_tokens == 0 || max_tokens > 128000)
+    let max = max_tokens_limit.unwrap_or(DEFAULT_MAX_TOKENS_LIMIT);
+    if let Some(value) = request.max_tokens
+        && (value == 0 || value > max)
     {
-        return Err(ValidationError::InvalidMaxTokens(max_tokens));
+        return Err(ValidationError::InvalidMaxTokens { value, max });
     }
 
     // Validate temperature
@@ -189,8 +389,6 @@ pub fn validate_completion_request(request: &CompletionRequest) -> Result<(), Va
         return Err(ValidationError::InvalidN(n));
     }
 
-    // Note: response_format.format_type is now validated at deserialization by the FormatType enum
-
     // Validate logit_bias
     if let Some(ref logit_bias) = request.logit_bias {
         for (token_id, bias_value) in logit_bias {
@@ -219,6 +417,101 @@ pub fn validate_completion_request(request: &CompletionRequest) -> Result<(), Va
         }
     }
 
+    // Catch malformed function schemas here rather than as an opaque upstream 400
+    if let Some(ref tools) = request.tools {
+        for tool in tools {
+            if let Some(ref parameters) = tool.function.parameters
+                && !is_valid_json_schema(parameters)
+            {
+                return Err(ValidationError::InvalidToolSchema {
+                    name: tool.function.name.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const JSON_SCHEMA_TYPES: [&str; 7] =
+    ["object", "array", "string", "number", "integer", "boolean", "null"];
+
+/// Structural check of a JSON Schema: an object with a recognizable `type`
+/// (a name or list of names), object-valued `properties`, and string `required` entries.
+/// Nested property schemas are checked the same way, except `type` may be omitted.
+fn is_valid_json_schema(schema: &serde_json::Value) -> bool {
+    schema.get("type").is_some() && is_valid_subschema(schema)
+}
+
+fn is_valid_subschema(schema: &serde_json::Value) -> bool {
+    let Some(schema) = schema.as_object() else {
+        return false;
+    };
+
+    let is_known_type = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .is_some_and(|name| JSON_SCHEMA_TYPES.contains(&name))
+    };
+    let type_ok = match schema.get("type") {
+        None => true,
+        Some(serde_json::Value::Array(types)) => {
+            !types.is_empty() && types.iter().all(is_known_type)
+        }
+        Some(value) => is_known_type(value),
+    };
+
+    let properties_ok = match schema.get("properties") {
+        None => true,
+        Some(properties) => properties
+            .as_object()
+            .is_some_and(|properties| properties.values().all(is_valid_subschema)),
+    };
+
+    let required_ok = match schema.get("required") {
+        None => true,
+        Some(required) => required
+            .as_array()
+            .is_some_and(|names| names.iter().all(serde_json::Value::is_string)),
+    };
+
+    let items_ok = schema.get("items").is_none_or(is_valid_subschema);
+
+    type_ok && properties_ok && required_ok && items_ok
+}
+
+/// Reject conversations where two consecutive user/assistant messages share a role.
+/// System messages are exempt and don't break up a run. Tool and function results
+/// end the turn, so the assistant may answer them after its own tool call.
+pub fn validate_message_sequence(request: &CompletionRequest) -> Result<(), ValidationError> {
+    let mut previous: Option<&Role> = None;
+
+    for (index, message) in request.messages.iter().enumerate() {
+        match message.role {
+            Role::System => continue,
+            Role::Tool | Role::Function => {
+                previous = None;
+                continue;
+            }
+            _ => {}
+        }
+        if previous == Some(&message.role) {
+            return Err(ValidationError::InvalidMessageSequence {
+                index,
+                role: message.role.clone(),
+            });
+        }
+        previous = Some(&message.role);
+    }
+
+    Ok(())
+}
+
+pub fn validate_embedding_request(request: &EmbeddingRequest) -> Result<(), ValidationError> {
+    let inputs = request.inputs();
+    if inputs.is_empty() || inputs.iter().any(|input| input.is_empty()) {
+        return Err(ValidationError::EmptyInput);
+    }
     Ok(())
 }
 
@@ -241,18 +534,72 @@ pub fn validate_provider_capabilities(
     request: &CompletionRequest,
     supports_streaming: bool,
     _supports_tools: bool,
+    supports_audio: bool,
+    max_stop_sequences: usize,
+    provider: &str,
 ) -> Result<(), ValidationError> {
     // Check streaming support
     if request.stream == Some(true) && !supports_streaming {
         return Err(ValidationError::StreamingNotSupported);
     }
 
+    let wants_audio = request
+        .modalities
+        .as_ref()
+        .is_some_and(|modalities| modalities.iter().any(|m| m == "audio"));
+    if wants_audio && !supports_audio {
+        return Err(ValidationError::AudioNotSupported);
+    }
+
+    // A single string counts as one sequence
+    let stop_count = match request.stop {
+        Some(StringOrArray::Array(ref sequences)) => sequences.len(),
+        Some(StringOrArray::String(_)) => 1,
+        None => 0,
+    };
+    if stop_count > max_stop_sequences {
+        return Err(ValidationError::TooManyStopSequences {
+            provider: provider.to_string(),
+            count: stop_count,
+            max: max_stop_sequences,
+        });
+    }
+
     // Note: Tool/function calling validation removed - not currently implemented
     // Can be re-added when provider tool support is implemented
 
     Ok(())
 }
 
+/// Check `response_format` against the types the provider can honor, narrowed
+/// by the configured allowlist. Providers without JSON mode only take `text`.
+pub fn validate_response_format(
+    request: &CompletionRequest,
+    supports_json_mode: bool,
+    allowed: Option<&[FormatType]>,
+) -> Result<(), ValidationError> {
+    let Some(ref response_format) = request.response_format else {
+        return Ok(());
+    };
+
+    let supported: Vec<&FormatType> = FormatType::KNOWN
+        .iter()
+        .filter(|format| supports_json_mode || **format == FormatType::Text)
+        .filter(|format| allowed.is_none_or(|allowed| allowed.contains(format)))
+        .collect();
+    if supported.contains(&&response_format.format_type) {
+        return Ok(());
+    }
+
+    Err(ValidationError::InvalidResponseFormat {
+        format_type: response_format.format_type.as_str().to_string(),
+        supported: supported
+            .iter()
+            .map(|format| format.as_str().to_string())
+            .collect(),
+    })
+}
+
 pub fn determine_model<'a>(
     requested_model: Option<&'a str>,
     default_model: &'a str,
@@ -272,7 +619,7 @@ pub fn determine_model<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{Message, Role};
+    use crate::models::{Function, FunctionCall, Message, Tool, ToolCall};
 
     #[test]
     fn test_validate_empty_messages() {
@@ -284,7 +631,7 @@ mod tests {
             ..Default::default()
         };
 
-        let result = validate_completion_request(&request);
+        let result = validate_completion_request(&request, None);
         assert!(matches!(result, Err(ValidationError::EmptyMessages)));
     }
 
@@ -301,7 +648,7 @@ mod tests {
             ..Default::default()
         };
 
-        let result = validate_completion_request(&request);
+        let result = validate_completion_request(&request, None);
         assert!(result.is_ok());
     }
 
@@ -317,10 +664,34 @@ mod tests {
             ..Default::default()
         };
 
-        let result = validate_completion_request(&request);
+        let result = validate_completion_request(&request, None);
         assert!(matches!(result, Err(ValidationError::NoContent)));
     }
 
+    #[test]
+    fn test_validate_max_tokens_against_configured_limit() {
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "test")],
+            max_tokens: Some(4096),
+            ..Default::default()
+        };
+
+        assert!(validate_completion_request(&request, None).is_ok());
+        assert!(validate_completion_request(&request, Some(4096)).is_ok());
+        let error = validate_completion_request(&request, Some(2048)).unwrap_err();
+        assert!(matches!(
+            error,
+            ValidationError::InvalidMaxTokens {
+                value: 4096,
+                max: 2048
+            }
+        ));
+        assert_eq!(
+            error.to_openai_error().message,
+            "Max tokens must be between 1 and 2048, got 4096"
+        );
+    }
+
     #[test]
     fn test_validate_frequency_penalty_bounds() {
         let request = CompletionRequest {
@@ -329,7 +700,7 @@ mod tests {
             ..Default::default()
         };
 
-        let result = validate_completion_request(&request);
+        let result = validate_completion_request(&request, None);
         assert!(matches!(
             result,
             Err(ValidationError::InvalidFrequencyPenalty(_))
@@ -344,11 +715,78 @@ mod tests {
             ..Default::default()
         };
 
-        let result = validate_provider_capabilities(&request, false, false);
+        let result = validate_provider_capabilities(&request, false, false, false, 4, "mock");
         assert!(matches!(
             result,
             Err(ValidationError::StreamingNotSupported)
         ));
+
+        let request = CompletionRequest {
+            messages: vec![Message::new(Role::User, "test")],
+            modalities: Some(vec!["text".to_string(), "audio".to_string()]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            validate_provider_capabilities(&request, true, false, false, 4, "mock"),
+            Err(ValidationError::AudioNotSupported)
+        ));
+        assert!(validate_provider_capabilities(&request, true, false, true, 4, "mock").is_ok());
+    }
+
+    fn with_response_format(json: serde_json::Value) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "test")],
+            response_format: Some(serde_json::from_value(json).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate_response_format_rejects_unknown_type() {
+        let request = with_response_format(serde_json::json!({"type": "xml"}));
+
+        let error = validate_response_format(&request, true, None).unwrap_err();
+        match error {
+            ValidationError::InvalidResponseFormat {
+                ref format_type,
+                ref supported,
+            } => {
+                assert_eq!(format_type, "xml");
+                assert_eq!(supported, &["text", "json_object", "json_schema"]);
+            }
+            ref other => panic!("expected InvalidResponseFormat, got {:?}", other),
+        }
+        let openai_error = error.to_openai_error();
+        assert_eq!(openai_error.param, Some("response_format".to_string()));
+        assert_eq!(openai_error.code, Some("unsupported_parameter".to_string()));
+    }
+
+    #[test]
+    fn test_validate_response_format_accepts_supported_types() {
+        let request = with_response_format(serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {"name": "answer", "schema": {"type": "object"}}
+        }));
+        assert!(validate_response_format(&request, true, None).is_ok());
+        let text = with_response_format(serde_json::json!({"type": "text"}));
+        assert!(validate_response_format(&text, false, None).is_ok());
+        assert!(validate_response_format(&CompletionRequest::default(), false, None).is_ok());
+    }
+
+    #[test]
+    fn test_validate_response_format_without_json_mode_or_outside_allowlist() {
+        let request = with_response_format(serde_json::json!({"type": "json_object"}));
+        assert!(matches!(
+            validate_response_format(&request, false, None),
+            Err(ValidationError::InvalidResponseFormat { ref supported, .. }) if supported == &["text"]
+        ));
+
+        let allowed = [FormatType::Text, FormatType::JsonSchema];
+        assert!(matches!(
+            validate_response_format(&request, true, Some(&allowed)),
+            Err(ValidationError::InvalidResponseFormat { ref supported, .. })
+                if supported == &["text", "json_schema"]
+        ));
     }
 
     // OpenAI error format tests
@@ -400,7 +838,10 @@ mod tests {
 
     #[test]
     fn test_invalid_max_tokens_openai_error() {
-        let error = ValidationError::InvalidMaxTokens(200000);
+        let error = ValidationError::InvalidMaxTokens {
+            value: 200000,
+            max: DEFAULT_MAX_TOKENS_LIMIT,
+        };
         let openai_error = error.to_openai_error();
 
         assert_eq!(openai_error.error_type, "invalid_request_error");
@@ -442,6 +883,125 @@ mod tests {
         assert!(openai_error.message.contains("Value out of range"));
     }
 
+    #[test]
+    fn test_alternating_roles_accepted() {
+        let request = CompletionRequest {
+            messages: vec![
+                Message::new(Role::System, "You are helpful"),
+                Message::new(Role::User, "Hi"),
+                Message::new(Role::Assistant, "Hello!"),
+                Message::new(Role::User, "How are you?"),
+            ],
+            ..Default::default()
+        };
+
+        assert!(validate_message_sequence(&request).is_ok());
+    }
+
+    #[test]
+    fn test_assistant_reply_after_tool_output_accepted() {
+        let mut tool_call = Message::new(Role::Assistant, "");
+        tool_call.content = None;
+        tool_call.tool_calls = Some(vec![ToolCall {
+            id: "call_1".to_string(),
+            tool_type: "function".to_string(),
+            function: FunctionCall {
+                name: "get_weather".to_string(),
+                arguments: "{}".to_string(),
+            },
+        }]);
+        let request = CompletionRequest {
+            messages: vec![
+                Message::new(Role::User, "What's the weather?"),
+                tool_call,
+                Message::tool_response("call_1", "{\"temp\": 18}"),
+                Message::new(Role::Assistant, "It's 18 degrees."),
+            ],
+            ..Default::default()
+        };
+
+        assert!(validate_message_sequence(&request).is_ok());
+    }
+
+    #[test]
+    fn test_doubled_user_turn_rejected() {
+        let request = CompletionRequest {
+            messages: vec![
+                Message::new(Role::User, "Hi"),
+                Message::new(Role::System, "Be brief"),
+                Message::new(Role::User, "Are you there?"),
+            ],
+            ..Default::default()
+        };
+
+        let result = validate_message_sequence(&request);
+        assert!(matches!(
+            result,
+            Err(ValidationError::InvalidMessageSequence { index: 2, role: Role::User })
+        ));
+        let openai_error = result.unwrap_err().to_openai_error();
+        assert_eq!(openai_error.param, Some("messages".to_string()));
+        assert!(openai_error.message.contains("'user'"));
+    }
+
+    fn tool_with_parameters(parameters: serde_json::Value) -> CompletionRequest {
+        CompletionRequest {
+            messages: vec![Message::new(Role::User, "What's the weather?")],
+            tools: Some(vec![Tool {
+                tool_type: "function".to_string(),
+                function: Function {
+                    name: "get_weather".to_string(),
+                    description: None,
+                    parameters: Some(parameters),
+                },
+            }]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_tool_schema_accepted() {
+        let request = tool_with_parameters(serde_json::json!({
+            "type": "object",
+            "properties": {
+                "location": {"type": "string"},
+                "days": {"type": ["integer", "null"]},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["location"]
+        }));
+
+        assert!(validate_completion_request(&request, None).is_ok());
+    }
+
+    #[test]
+    fn test_malformed_tool_schema_rejected() {
+        for parameters in [
+            serde_json::json!("object"),
+            serde_json::json!({"properties": {}}),
+            serde_json::json!({"type": "dictionary"}),
+            serde_json::json!({"type": "object", "properties": {"location": "string"}}),
+            serde_json::json!({"type": "object", "required": "location"}),
+        ] {
+            let result =
+                validate_completion_request(&tool_with_parameters(parameters.clone()), None);
+            assert!(
+                matches!(
+                    result,
+                    Err(ValidationError::InvalidToolSchema { ref name }) if name == "get_weather"
+                ),
+                "expected rejection for {parameters}"
+            );
+        }
+
+        let openai_error = ValidationError::InvalidToolSchema {
+            name: "get_weather".to_string(),
+        }
+        .to_openai_error();
+        assert_eq!(openai_error.param, Some("tools".to_string()));
+        assert!(openai_error.message.contains("'get_weather'"));
+    }
+
     #[test]
     fn test_validation_error_status_code() {
         let error = ValidationError::InvalidTemperature(3.0);